@@ -0,0 +1,17 @@
+//! Parse arbitrary stylesheets, and check that whatever the parser keeps is written out as CSS
+//! that parses again without errors.
+//!
+//! cargo +nightly fuzz run css
+
+#![no_main]
+
+use browser_engine::css;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let stylesheet = css::parse(source.to_string());
+    let serialized = stylesheet.to_string();
+    let (reparsed, errors) = css::parse_with_errors(serialized.clone());
+    assert!(errors.is_empty(), "{:?} in {:?}", errors, serialized);
+    assert_eq!(reparsed.to_string(), serialized);
+});
@@ -0,0 +1,14 @@
+//! Parse arbitrary bytes as a document, in whatever encoding it declares, and parse what the
+//! parser produces again once it is written out as HTML.
+//!
+//! cargo +nightly fuzz run html
+
+#![no_main]
+
+use browser_engine::html;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let root = html::parse_reader(data).unwrap();
+    html::parse(root.to_string());
+});
@@ -1,11 +1,112 @@
+use std::fmt;
+
+use crate::encoding::{self, Encoding};
+
+#[derive(Debug)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
+    /// The declarations of `@page` rules, which style the pages of printed output.
+    pub page: Vec<Declaration>,
+}
+
+/// Stylesheets are written back out as CSS, one rule to a line, which parses to the same rules.
+impl fmt::Display for Stylesheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "{}", rule)?;
+        }
+        if !self.page.is_empty() {
+            write!(f, "@page")?;
+            write_declarations(f, &self.page)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, selector) in self.selectors.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", selector)?;
+        }
+        write_declarations(f, &self.declarations)
+    }
+}
+
+/// An identifier, written with escapes for any characters that can't otherwise appear in it.
+///
+/// http://drafts.csswg.org/cssom/#serialize-an-identifier
+struct Identifier<'a>(&'a str);
+
+impl fmt::Display for Identifier<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, c) in self.0.chars().enumerate() {
+            // A digit can't start an identifier, or follow a `-` at the start, as that would
+            // be read as a number.
+            let starts_number =
+                c.is_ascii_digit() && (index == 0 || (index == 1 && self.0.starts_with('-')));
+            if (c.is_control() && c.is_ascii()) || starts_number {
+                write!(f, "\\{:x} ", c as u32)?;
+            } else if valid_identifier_char(c) {
+                write!(f, "{}", c)?;
+            } else {
+                write!(f, "\\{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A string, written in double quotes with escapes for quotes, backslashes and control
+/// characters.
+///
+/// http://drafts.csswg.org/cssom/#serialize-a-string
+struct QuotedString<'a>(&'a str);
+
+impl fmt::Display for QuotedString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' | '\\' => write!(f, "\\{}", c)?,
+                c if c.is_control() && c.is_ascii() => write!(f, "\\{:x} ", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        write!(f, "\"")
+    }
+}
+
+fn write_declarations(f: &mut fmt::Formatter, declarations: &[Declaration]) -> fmt::Result {
+    write!(f, " {{")?;
+    for declaration in declarations {
+        write!(f, " {}", declaration)?;
+    }
+    write!(f, " }}")
 }
 
 #[derive(Debug)]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    /// Where the rule starts in the stylesheet's source.
+    pub location: SourceLocation,
+}
+
+/// A position in the source of a stylesheet, counting lines and columns from 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 #[derive(Debug)]
@@ -19,10 +120,50 @@ impl Selector {
     pub fn specificity(&self) -> Specificity {
         let Selector::Simple(ref simple) = *self;
         let a = simple.id.iter().count();
-        let b = simple.class.len();
-        let c = simple.tag_name.iter().count();
+        let b = simple.class.len() + simple.attributes.len();
+        let c = simple.tag_name.iter().count() + simple.pseudo_element.iter().count();
         (a, b, c)
     }
+
+    /// The pseudo-element this selector styles, or `None` if it styles elements themselves.
+    pub fn pseudo_element(&self) -> Option<PseudoElement> {
+        let Selector::Simple(ref simple) = *self;
+        simple.pseudo_element
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Selector::Simple(ref simple) = *self;
+        if simple.tag_name.is_none()
+            && simple.id.is_none()
+            && simple.class.is_empty()
+            && simple.attributes.is_empty()
+            && simple.pseudo_element.is_none()
+        {
+            return write!(f, "*");
+        }
+        if let Some(ref tag_name) = simple.tag_name {
+            write!(f, "{}", Identifier(tag_name))?;
+        }
+        if let Some(ref id) = simple.id {
+            write!(f, "#{}", Identifier(id))?;
+        }
+        for class in &simple.class {
+            write!(f, ".{}", Identifier(class))?;
+        }
+        for attribute in &simple.attributes {
+            write!(f, "[{}", Identifier(&attribute.name))?;
+            if let Some(ref value) = attribute.value {
+                write!(f, "={}", QuotedString(value))?;
+            }
+            write!(f, "]")?;
+        }
+        if let Some(pseudo_element) = simple.pseudo_element {
+            write!(f, "::{}", pseudo_element.name())?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -30,6 +171,48 @@ pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
     pub class: Vec<String>,
+    pub attributes: Vec<AttributeSelector>,
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+/// A condition on an attribute: `[name]` for any element that has it, or `[name=value]` for
+/// one where it has exactly that value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSelector {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// A box that is generated by CSS rather than by an element, such as `::before`, but is styled
+/// as if it were the element's child.
+///
+/// http://www.w3.org/TR/css-pseudo-4/#treelike
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PseudoElement {
+    Before,
+    After,
+    /// The bullet or number of a list item.
+    Marker,
+}
+
+impl PseudoElement {
+    /// The pseudo-element with the name given after `::`, ignoring case.
+    pub fn for_name(name: &str) -> Option<PseudoElement> {
+        match &*name.to_ascii_lowercase() {
+            "before" => Some(PseudoElement::Before),
+            "after" => Some(PseudoElement::After),
+            "marker" => Some(PseudoElement::Marker),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PseudoElement::Before => "before",
+            PseudoElement::After => "after",
+            PseudoElement::Marker => "marker",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -38,220 +221,932 @@ pub struct Declaration {
     pub value: Value,
 }
 
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {};", Identifier(&self.name), self.value)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Keyword(String),
     Length(f32, Unit),
+    Number(f32),
     ColorValue(Color),
+    /// A functional notation such as `repeat(3, 1fr)`, with its comma-separated arguments.
+    /// Parentheses inside `calc()` are kept as a function with an empty name.
+    Function(String, Vec<Value>),
+    /// A `url()`, such as the location of a background image.
+    Url(String),
+    /// A quoted string, such as the text generated by `content`.
+    String(String),
+    /// Several space-separated values, such as `100px 1fr`. A `/` separator is kept as the
+    /// keyword `/`.
+    List(Vec<Value>),
+}
+
+impl Value {
+    /// Return the size of a length in px, or zero for non-lengths. Percentages, including those
+    /// inside `calc()`, have no size here.
+    pub fn to_px(&self) -> f32 {
+        self.resolve_length(None).unwrap_or(0.0)
+    }
+
+    /// Return the size in px of a length, a percentage of `percent_basis`, or a `calc()`
+    /// expression combining them, such as `calc(100% - 2 * 10px)`. Returns `None` for other
+    /// values, and for percentages when there is no basis.
+    pub fn resolve_length(&self, percent_basis: Option<f32>) -> Option<f32> {
+        match *self {
+            Value::Number(0.0) => Some(0.0),
+            Value::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("calc") => {
+                match arguments.as_slice() {
+                    [expression] => match evaluate_calc(expression, percent_basis)? {
+                        Calc::Length(size) => Some(size),
+                        Calc::Number(_) => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => match calc_operand(self, percent_basis)? {
+                Calc::Length(size) => Some(size),
+                Calc::Number(_) => None,
+            },
+        }
+    }
+}
+
+/// The value of a `calc()` expression or one of its operands: a length in px, or a plain
+/// number that lengths can be multiplied or divided by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Calc {
+    Length(f32),
+    Number(f32),
+}
+
+/// Evaluate a `calc()` expression, a sum of products, with `*` and `/` binding more tightly
+/// than `+` and `-`. Returns `None` if the expression is malformed or adds a length to a
+/// number.
+///
+/// http://www.w3.org/TR/css-values-3/#calc-notation
+fn evaluate_calc(expression: &Value, percent_basis: Option<f32>) -> Option<Calc> {
+    let terms = match *expression {
+        Value::List(ref terms) => terms.as_slice(),
+        ref term => std::slice::from_ref(term),
+    };
+    let mut products = terms.split(|term| is_operator(term, &["+", "-"]));
+    let mut sum = evaluate_product(products.next()?, percent_basis)?;
+    let operators = terms.iter().filter(|term| is_operator(term, &["+", "-"]));
+    for (operator, product) in operators.zip(products) {
+        let product = evaluate_product(product, percent_basis)?;
+        let sign = if is_operator(operator, &["-"]) {
+            -1.0
+        } else {
+            1.0
+        };
+        sum = match (sum, product) {
+            (Calc::Length(a), Calc::Length(b)) => Calc::Length(a + sign * b),
+            (Calc::Number(a), Calc::Number(b)) => Calc::Number(a + sign * b),
+            _ => return None,
+        };
+    }
+    Some(sum)
+}
+
+fn evaluate_product(terms: &[Value], percent_basis: Option<f32>) -> Option<Calc> {
+    let (first, rest) = terms.split_first()?;
+    let mut product = calc_operand(first, percent_basis)?;
+    for pair in rest.chunks(2) {
+        let [operator, operand] = pair else {
+            return None;
+        };
+        let operand = calc_operand(operand, percent_basis)?;
+        product = match (product, operand) {
+            (Calc::Number(a), Calc::Number(b)) if is_operator(operator, &["*"]) => {
+                Calc::Number(a * b)
+            }
+            (Calc::Length(a), Calc::Number(b)) | (Calc::Number(b), Calc::Length(a))
+                if is_operator(operator, &["*"]) =>
+            {
+                Calc::Length(a * b)
+            }
+            (Calc::Number(a), Calc::Number(b)) if is_operator(operator, &["/"]) && b != 0.0 => {
+                Calc::Number(a / b)
+            }
+            (Calc::Length(a), Calc::Number(b)) if is_operator(operator, &["/"]) && b != 0.0 => {
+                Calc::Length(a / b)
+            }
+            _ => return None,
+        };
+    }
+    Some(product)
+}
+
+/// A number, length or percentage, or a nested `calc()` or parenthesized expression.
+fn calc_operand(value: &Value, percent_basis: Option<f32>) -> Option<Calc> {
+    match *value {
+        Value::Length(size, Unit::Px) => Some(Calc::Length(size)),
+        Value::Length(percent, Unit::Percent) => {
+            percent_basis.map(|basis| Calc::Length(basis * percent / 100.0))
+        }
+        Value::Number(number) => Some(Calc::Number(number)),
+        Value::Function(ref name, ref arguments)
+            if name.is_empty() || name.eq_ignore_ascii_case("calc") =>
+        {
+            match arguments.as_slice() {
+                [expression] => evaluate_calc(expression, percent_basis),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_operator(value: &Value, operators: &[&str]) -> bool {
+    matches!(*value, Value::Keyword(ref keyword) if operators.contains(&keyword.as_str()))
+}
+
+/// Values are written back out as CSS, as they would be parsed.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            // The operators of `calc()` are kept as keywords, but aren't identifiers.
+            Value::Keyword(ref keyword) if matches!(&**keyword, "/" | "+" | "*") => {
+                write!(f, "{}", keyword)
+            }
+            Value::Keyword(ref keyword) => write!(f, "{}", Identifier(keyword)),
+            Value::Length(value, ref unit) => {
+                let unit = match unit {
+                    Unit::Px => "px",
+                    Unit::Percent => "%",
+                    Unit::Fr => "fr",
+                    Unit::Deg => "deg",
+                };
+                write!(f, "{}{}", value, unit)
+            }
+            Value::Number(value) => write!(f, "{}", value),
+            Value::ColorValue(ref color) => {
+                write!(f, "#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+            }
+            Value::Function(ref name, ref arguments) => {
+                write!(f, "{}(", Identifier(name))?;
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Value::Url(ref url) => write!(f, "url({})", QuotedString(url)),
+            Value::String(ref string) => write!(f, "{}", QuotedString(string)),
+            Value::List(ref values) => {
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Unit {
     Px,
+    Percent,
+    /// A fraction of the free space in a grid container.
+    Fr,
+    /// An angle in degrees, such as the direction of a gradient.
+    Deg,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Color {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/// A syntax error in a stylesheet. The parser recovers from errors by skipping the declaration
+/// or rule that contains them, as browsers do.
+///
+/// http://www.w3.org/TR/CSS2/syndata.html#parsing-errors
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub location: SourceLocation,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
 }
 
+type ParseResult<T> = Result<T, ParseError>;
+
+/// How deeply functions and parentheses may be nested in a value, so that a stylesheet can't
+/// exhaust the stack.
+const MAX_NESTING: usize = 32;
+
+/// Parse a stylesheet, skipping anything that can't be parsed.
 pub fn parse(source: String) -> Stylesheet {
-    let mut parser = Parser {
-        pos: 0,
-        input: source,
+    parse_with_errors(source).0
+}
+
+/// Parse a stylesheet, and return the errors in the declarations and rules that were skipped.
+pub fn parse_with_errors(source: String) -> (Stylesheet, Vec<ParseError>) {
+    let mut parser = Parser::new(source);
+    let mut stylesheet = Stylesheet {
+        rules: Vec::new(),
+        page: Vec::new(),
     };
-    Stylesheet {
-        rules: parser.parse_rules(),
+    let mut errors = Vec::new();
+    parser.parse_rules(&mut stylesheet, &mut errors);
+    (stylesheet, errors)
+}
+
+/// Decode the bytes of a stylesheet, in the encoding given by its byte order mark or its
+/// `@charset` rule, or else in `fallback`, such as the encoding of the document that linked
+/// to it.
+///
+/// http://www.w3.org/TR/css-syntax-3/#determine-the-fallback-encoding
+pub fn decode(bytes: &[u8], fallback: Encoding) -> String {
+    if let Some((encoding, bom_length)) = encoding::from_bom(bytes) {
+        return encoding::decode(&bytes[bom_length..], encoding);
+    }
+    // The rule must be written exactly like this, as the bytes are read before being decoded.
+    let declared = bytes
+        .strip_prefix(b"@charset \"")
+        .and_then(|rest| rest.get(..rest.windows(2).position(|pair| pair == b"\";")?))
+        .and_then(|label| Encoding::for_label(&String::from_utf8_lossy(label)));
+    let encoding = declared.map_or(fallback, Encoding::declared);
+    encoding::decode(bytes, encoding)
+}
+
+/// Parse a single selector, such as `div.note`. Returns `None` if `source` is anything else.
+pub fn parse_selector(source: String) -> Option<Selector> {
+    let mut parser = Parser::new(source.trim().to_string());
+    let selector = parser.parse_simple_selector().ok()?;
+    if parser.eof() && !parser.input.is_empty() {
+        Some(Selector::Simple(selector))
+    } else {
+        None
     }
 }
 
 struct Parser {
     pos: usize,
     input: String,
+    /// The number of functions and parentheses that enclose the current position.
+    nesting: usize,
 }
 
 impl Parser {
-    fn next_char(&self) -> char {
-        self.input[self.pos..].chars().next().unwrap()
+    fn new(input: String) -> Parser {
+        Parser {
+            pos: 0,
+            input,
+            nesting: 0,
+        }
+    }
+
+    /// Return the next character, or `None` at the end of the input.
+    fn next_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
     }
 
     fn eof(&self) -> bool {
         self.pos >= self.input.len()
     }
 
-    /// If the exact string `s` is found at the current position, consume it.
-    /// Otherwise, panic.
-    fn expect_char(&mut self, c: char) {
-        if self.consume_char() != c {
-            panic!("Expected {:?} at byte {} but it was not found", c, self.pos);
+    /// Consume the character `c`, or fail if the input continues with anything else.
+    fn expect_char(&mut self, c: char) -> ParseResult<()> {
+        if self.next_char() == Some(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("place of {:?}", c)))
         }
     }
 
-    fn consume_char(&mut self) -> char {
-        let c = self.next_char();
+    fn consume_char(&mut self) -> Option<char> {
+        let c = self.next_char()?;
         self.pos += c.len_utf8();
-        c
+        Some(c)
     }
 
     fn consume_while(&mut self, test: impl Fn(char) -> bool) -> String {
         let mut result = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
+        while let Some(c) = self.next_char().filter(|&c| test(c)) {
+            self.pos += c.len_utf8();
+            result.push(c);
         }
         result
     }
 
     fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+        self.consume_while(is_whitespace);
+    }
+
+    /// Whether the input continues with a backslash that escapes the next character.
+    fn starts_with_escape(&self) -> bool {
+        let mut chars = self.input[self.pos..].chars();
+        chars.next() == Some('\\') && !matches!(chars.next(), Some('\n' | '\r' | '\x0C'))
+    }
+
+    /// Consume an escape, such as `\.` for `.` or `\31 ` for `1`, and return the character it
+    /// stands for. A code point that isn't a valid character, or the end of the input, is read
+    /// as U+FFFD.
+    ///
+    /// http://www.w3.org/TR/css-syntax-3/#consume-escaped-code-point
+    fn consume_escape(&mut self) -> char {
+        self.consume_char();
+        let hex = &self.input[self.pos..];
+        let digits = hex
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(hex.len())
+            .min(6);
+        if digits == 0 {
+            return match self.consume_char() {
+                Some('\0') | None => char::REPLACEMENT_CHARACTER,
+                Some(c) => c,
+            };
+        }
+        let code = u32::from_str_radix(&hex[..digits], 16).unwrap();
+        self.pos += digits;
+        // One whitespace character after the digits ends the escape, and a CRLF counts as one.
+        if self.input[self.pos..].starts_with("\r\n") {
+            self.pos += 2;
+        } else if self.next_char().is_some_and(is_whitespace) {
+            self.consume_char();
+        }
+        match char::from_u32(code) {
+            Some('\0') | None => char::REPLACEMENT_CHARACTER,
+            Some(c) => c,
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError {
+            message,
+            location: self.location(),
+        }
     }
 
-    /// Parse a list of rule sets, separated by optional whitespace.
-    fn parse_rules(&mut self) -> Vec<Rule> {
-        let mut rules = Vec::new();
+    /// An error for the character at the current position, found in the `context` named.
+    fn unexpected(&self, context: &str) -> ParseError {
+        match self.next_char() {
+            Some(c) => self.error(format!("unexpected {:?} in {}", c, context)),
+            None => self.error(format!("unexpected end of input in {}", context)),
+        }
+    }
+
+    /// Parse a list of rule sets and at-rules, separated by optional whitespace. A rule that
+    /// can't be parsed is skipped, up to the end of its block.
+    fn parse_rules(&mut self, stylesheet: &mut Stylesheet, errors: &mut Vec<ParseError>) {
         loop {
             self.consume_whitespace();
             if self.eof() {
                 break;
             }
-            rules.push(self.parse_rule());
+            let start = self.pos;
+            let at_rule = self.next_char() == Some('@');
+            let result = if at_rule {
+                self.parse_at_rule(stylesheet, errors)
+            } else {
+                self.parse_rule(errors)
+                    .map(|rule| stylesheet.rules.push(rule))
+            };
+            if let Err(error) = result {
+                errors.push(error);
+                self.pos = start;
+                self.skip_rule(at_rule);
+            }
         }
-        rules
+    }
+
+    /// Parse an at-rule. Only `@page` is supported, and rules for particular pages, such as
+    /// `@page :first`, are ignored.
+    fn parse_at_rule(
+        &mut self,
+        stylesheet: &mut Stylesheet,
+        errors: &mut Vec<ParseError>,
+    ) -> ParseResult<()> {
+        let location = self.location();
+        self.expect_char('@')?;
+        let name = self.parse_identifier();
+        if name.eq_ignore_ascii_case("charset") {
+            // The encoding was already found by `decode`.
+            self.consume_while(|c| c != ';');
+            self.consume_char();
+            return Ok(());
+        }
+        if !name.eq_ignore_ascii_case("page") {
+            let message = format!("unsupported at-rule @{}", name);
+            return Err(ParseError { message, location });
+        }
+        let page_selector = self.consume_while(|c| c != '{');
+        let declarations = self.parse_declarations(errors)?;
+        if page_selector.trim().is_empty() {
+            stylesheet.page.extend(declarations);
+        }
+        Ok(())
     }
 
     // Parse a rule set: `<selectors> { <declarations> }`.
-    fn parse_rule(&mut self) -> Rule {
-        Rule {
-            selectors: self.parse_selectors(),
-            declarations: self.parse_declarations(),
+    fn parse_rule(&mut self, errors: &mut Vec<ParseError>) -> ParseResult<Rule> {
+        let location = self.location();
+        Ok(Rule {
+            selectors: self.parse_selectors()?,
+            declarations: self.parse_declarations(errors)?,
+            location,
+        })
+    }
+
+    /// The line and column of the current position.
+    fn location(&self) -> SourceLocation {
+        let before = &self.input[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        SourceLocation {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
         }
     }
 
-    fn parse_selectors(&mut self) -> Vec<Selector> {
+    fn parse_selectors(&mut self) -> ParseResult<Vec<Selector>> {
         let mut selectors = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector()));
+            let start = self.pos;
+            let selector = self.parse_simple_selector()?;
+            if self.pos == start {
+                return Err(self.unexpected("selector"));
+            }
+            selectors.push(Selector::Simple(selector));
             self.consume_whitespace();
             match self.next_char() {
-                ',' => {
+                Some(',') => {
                     self.consume_char();
                     self.consume_whitespace();
                 }
-                '{' => break,
-                c => panic!("Unexpected character {} in selector list", c),
+                Some('{') => break,
+                _ => return Err(self.unexpected("selector list")),
             }
         }
-        selectors.sort_by(|a, b| b.specificity().cmp(&a.specificity()));
-        selectors
+        selectors.sort_by_key(|s| std::cmp::Reverse(s.specificity()));
+        Ok(selectors)
     }
 
-    // Parse one simple selector, e.g.: `type#id.class1.class2.class3`
-    fn parse_simple_selector(&mut self) -> SimpleSelector {
+    // Parse one simple selector, e.g.: `type#id.class1.class2.class3[attr=value]::before`
+    fn parse_simple_selector(&mut self) -> ParseResult<SimpleSelector> {
         let mut selector = SimpleSelector {
             tag_name: None,
             id: None,
             class: Vec::new(),
+            attributes: Vec::new(),
+            pseudo_element: None,
         };
-        while !self.eof() {
-            match self.next_char() {
+        while let Some(c) = self.next_char() {
+            match c {
                 '#' => {
                     self.consume_char();
-                    selector.id = Some(self.parse_identifier());
+                    selector.id = Some(self.parse_name()?);
                 }
                 '.' => {
                     self.consume_char();
-                    selector.class.push(self.parse_identifier());
+                    selector.class.push(self.parse_name()?);
                 }
                 '*' => {
                     // universal selector
                     self.consume_char();
                 }
-                c if valid_identifier_char(c) => {
+                '[' => selector.attributes.push(self.parse_attribute_selector()?),
+                // A pseudo-element must come last.
+                ':' => {
+                    selector.pseudo_element = Some(self.parse_pseudo_element()?);
+                    break;
+                }
+                c if valid_identifier_char(c) || self.starts_with_escape() => {
                     selector.tag_name = Some(self.parse_identifier());
                 }
                 _ => break,
             }
         }
-        return selector;
+        Ok(selector)
+    }
+
+    /// Parse an attribute selector: `[name]` or `[name=value]`, where the value is an
+    /// identifier or a string.
+    fn parse_attribute_selector(&mut self) -> ParseResult<AttributeSelector> {
+        self.expect_char('[')?;
+        self.consume_whitespace();
+        let name = self.parse_name()?;
+        self.consume_whitespace();
+        let value = match self.next_char() {
+            Some('=') => {
+                self.consume_char();
+                self.consume_whitespace();
+                let value = match self.next_char() {
+                    Some(quote @ ('"' | '\'')) => self.parse_string(quote)?,
+                    _ => self.parse_name()?,
+                };
+                self.consume_whitespace();
+                Some(value)
+            }
+            _ => None,
+        };
+        self.expect_char(']')?;
+        Ok(AttributeSelector { name, value })
     }
 
-    fn parse_declarations(&mut self) -> Vec<Declaration> {
-        self.expect_char('{');
+    /// Parse a pseudo-element such as `::before`, or `:before` or `:after` in the syntax of
+    /// CSS 2, which used a single colon.
+    ///
+    /// http://www.w3.org/TR/selectors-3/#pseudo-elements
+    fn parse_pseudo_element(&mut self) -> ParseResult<PseudoElement> {
+        let location = self.location();
+        self.expect_char(':')?;
+        let legacy = self.next_char() != Some(':');
+        if !legacy {
+            self.consume_char();
+        }
+        let name = self.parse_name()?;
+        match PseudoElement::for_name(&name) {
+            Some(pseudo_element @ (PseudoElement::Before | PseudoElement::After)) => {
+                Ok(pseudo_element)
+            }
+            Some(pseudo_element) if !legacy => Ok(pseudo_element),
+            _ if legacy => Err(ParseError {
+                message: format!("unsupported pseudo-class :{}", name),
+                location,
+            }),
+            _ => Err(ParseError {
+                message: format!("unsupported pseudo-element ::{}", name),
+                location,
+            }),
+        }
+    }
+
+    /// Parse a block of declarations. Declarations that can't be parsed are skipped, and the
+    /// block may be left open at the end of the stylesheet.
+    fn parse_declarations(
+        &mut self,
+        errors: &mut Vec<ParseError>,
+    ) -> ParseResult<Vec<Declaration>> {
+        self.expect_char('{')?;
         let mut declarations = Vec::new();
         loop {
             self.consume_whitespace();
-            if self.next_char() == '}' {
-                self.consume_char();
-                break;
+            match self.next_char() {
+                None => break,
+                Some('}') => {
+                    self.consume_char();
+                    break;
+                }
+                Some(';') => {
+                    self.consume_char();
+                }
+                Some(_) => {
+                    let start = self.pos;
+                    match self.parse_declaration() {
+                        Ok(declaration) => declarations.push(declaration),
+                        Err(error) => {
+                            errors.push(error);
+                            self.pos = start;
+                            self.skip_declaration();
+                        }
+                    }
+                }
             }
-            declarations.push(self.parse_declaration());
         }
-        declarations
+        Ok(declarations)
     }
 
-    fn parse_declaration(&mut self) -> Declaration {
-        let name = self.parse_identifier();
+    /// Parse a declaration, ending with a `;` unless it is the last in its block.
+    fn parse_declaration(&mut self) -> ParseResult<Declaration> {
+        let name = self.parse_name()?;
         self.consume_whitespace();
-        self.expect_char(':');
+        self.expect_char(':')?;
         self.consume_whitespace();
-        let value = self.parse_value();
+        let value = self.parse_value()?;
         self.consume_whitespace();
-        self.expect_char(';');
+        match self.next_char() {
+            Some(';') => {
+                self.consume_char();
+            }
+            Some('}') | None => {}
+            Some(_) => return Err(self.unexpected("declaration")),
+        }
 
-        Declaration { name, value }
+        Ok(Declaration { name, value })
     }
 
+    /// Parse an identifier, which may contain escapes.
     fn parse_identifier(&mut self) -> String {
-        self.consume_while(valid_identifier_char)
+        let mut identifier = String::new();
+        loop {
+            identifier.push_str(&self.consume_while(valid_identifier_char));
+            if !self.starts_with_escape() {
+                return identifier;
+            }
+            identifier.push(self.consume_escape());
+        }
     }
 
-    fn parse_value(&mut self) -> Value {
+    /// Parse an identifier that must not be empty, such as a class name or property name.
+    fn parse_name(&mut self) -> ParseResult<String> {
+        let name = self.parse_identifier();
+        if name.is_empty() {
+            return Err(self.unexpected("place of a name"));
+        }
+        Ok(name)
+    }
+
+    /// Parse a space-separated list of component values, up to the end of the declaration or
+    /// function argument.
+    fn parse_value(&mut self) -> ParseResult<Value> {
+        let mut values = Vec::new();
+        loop {
+            self.consume_whitespace();
+            match self.next_char() {
+                None | Some(';' | '}' | ',' | ')') => break,
+                Some(_) => values.push(self.parse_component_value()?),
+            }
+        }
+        if values.len() == 1 {
+            Ok(values.pop().unwrap())
+        } else {
+            Ok(Value::List(values))
+        }
+    }
+
+    fn parse_component_value(&mut self) -> ParseResult<Value> {
         match self.next_char() {
-            '0'..='9' => self.parse_length(),
-            '#' => self.parse_color(),
-            _ => Value::Keyword(self.parse_identifier()),
+            Some('0'..='9' | '.') => self.parse_length(),
+            Some('-') if self.starts_with_number() => self.parse_length(),
+            Some('#') => self.parse_color(),
+            Some(quote @ ('"' | '\'')) => Ok(Value::String(self.parse_string(quote)?)),
+            // Separators, and the operators of `calc()`.
+            Some(c @ ('/' | '+' | '*')) => {
+                self.consume_char();
+                Ok(Value::Keyword(c.to_string()))
+            }
+            // A parenthesized part of a `calc()` expression, kept as a function with no name.
+            Some('(') => self.parse_function(String::new()),
+            _ => {
+                let name = self.parse_identifier();
+                if name.is_empty() {
+                    return Err(self.unexpected("value"));
+                }
+                if self.next_char() == Some('(') {
+                    if name.eq_ignore_ascii_case("url") {
+                        return self.parse_url();
+                    }
+                    self.parse_function(name)
+                } else {
+                    Ok(Value::Keyword(name))
+                }
+            }
         }
     }
 
-    fn parse_length(&mut self) -> Value {
-        Value::Length(self.parse_float(), self.parse_unit())
+    /// Parse the arguments of a function such as `minmax(100px, 1fr)`.
+    fn parse_function(&mut self, name: String) -> ParseResult<Value> {
+        if self.nesting >= MAX_NESTING {
+            return Err(self.error("functions are nested too deeply".to_string()));
+        }
+        self.expect_char('(')?;
+        self.nesting += 1;
+        let arguments = self.parse_arguments(&name);
+        self.nesting -= 1;
+        Ok(Value::Function(name, arguments?))
     }
 
-    fn parse_float(&mut self) -> f32 {
-        self.consume_while(|c| matches!(c, '0'..='9' | '.'))
-            .parse()
-            .unwrap()
+    fn parse_arguments(&mut self, name: &str) -> ParseResult<Vec<Value>> {
+        let mut arguments = Vec::new();
+        loop {
+            arguments.push(self.parse_value()?);
+            match self.next_char() {
+                Some(',') => {}
+                Some(')') => break,
+                _ => return Err(self.unexpected(&format!("arguments of {}()", name))),
+            }
+            self.consume_char();
+        }
+        self.consume_char();
+        Ok(arguments)
+    }
+
+    /// Parse the location in `url(...)`, which may be quoted.
+    fn parse_url(&mut self) -> ParseResult<Value> {
+        self.expect_char('(')?;
+        self.consume_whitespace();
+        let url = match self.next_char() {
+            Some(quote @ ('"' | '\'')) => self.parse_string(quote)?,
+            // An unquoted url can't contain quotes or brackets, so they end it, and make it
+            // invalid if anything but whitespace and `)` follows.
+            _ => {
+                let mut url = String::new();
+                loop {
+                    url.push_str(&self.consume_while(|c| {
+                        !is_whitespace(c) && !matches!(c, ')' | '(' | '"' | '\'' | '\\')
+                    }));
+                    if !self.starts_with_escape() {
+                        break url;
+                    }
+                    url.push(self.consume_escape());
+                }
+            }
+        };
+        self.consume_whitespace();
+        self.expect_char(')')?;
+        Ok(Value::Url(url))
+    }
+
+    /// Parse a string in `quote`s. Escapes in it are decoded, and an escaped newline is
+    /// skipped, so that a string can continue on the next line.
+    fn parse_string(&mut self, quote: char) -> ParseResult<String> {
+        self.expect_char(quote)?;
+        let mut string = String::new();
+        loop {
+            string.push_str(&self.consume_while(|c| c != quote && c != '\\' && c != '\n'));
+            match self.next_char() {
+                Some('\\') if self.starts_with_escape() => string.push(self.consume_escape()),
+                Some('\\') => {
+                    self.consume_char();
+                    if self.input[self.pos..].starts_with("\r\n") {
+                        self.pos += 1;
+                    }
+                    self.consume_char();
+                }
+                _ => {
+                    self.expect_char(quote)?;
+                    return Ok(string);
+                }
+            }
+        }
+    }
+
+    fn parse_length(&mut self) -> ParseResult<Value> {
+        let value = self.parse_float()?;
+        if self.next_char() == Some('%') {
+            self.consume_char();
+            Ok(Value::Length(value, Unit::Percent))
+        } else if !self.next_char().is_some_and(valid_identifier_char) && !self.starts_with_escape()
+        {
+            Ok(Value::Number(value))
+        } else {
+            // Absolute units are converted to px, at 96px to the inch.
+            let location = self.location();
+            let unit = self.parse_identifier();
+            Ok(match &*unit.to_ascii_lowercase() {
+                "px" => Value::Length(value, Unit::Px),
+                "in" => Value::Length(value * 96.0, Unit::Px),
+                "cm" => Value::Length(value * 96.0 / 2.54, Unit::Px),
+                "mm" => Value::Length(value * 96.0 / 25.4, Unit::Px),
+                "pt" => Value::Length(value * 96.0 / 72.0, Unit::Px),
+                "pc" => Value::Length(value * 16.0, Unit::Px),
+                "fr" => Value::Length(value, Unit::Fr),
+                "deg" => Value::Length(value, Unit::Deg),
+                _ => {
+                    let message = format!("unrecognized unit {:?}", unit);
+                    return Err(ParseError { message, location });
+                }
+            })
+        }
+    }
+
+    /// Whether the input continues with a signed number such as `-4px`, as opposed to an
+    /// identifier that happens to start with `-`.
+    fn starts_with_number(&self) -> bool {
+        let mut chars = self.input[self.pos..].chars();
+        if chars.next() == Some('-') {
+            matches!(chars.next(), Some('0'..='9' | '.'))
+        } else {
+            false
+        }
     }
 
-    fn parse_unit(&mut self) -> Unit {
-        match &*self.parse_identifier().to_ascii_lowercase() {
-            "px" => Unit::Px,
-            _ => panic!("unrecognized unit"),
+    fn parse_float(&mut self) -> ParseResult<f32> {
+        let location = self.location();
+        let sign = if self.next_char() == Some('-') {
+            self.consume_char();
+            -1.0
+        } else {
+            1.0
+        };
+        let digits = self.consume_while(|c| matches!(c, '0'..='9' | '.'));
+        match digits.parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(sign * value),
+            _ => Err(ParseError {
+                message: format!("invalid number {:?}", digits),
+                location,
+            }),
         }
     }
 
-    fn parse_color(&mut self) -> Value {
-        self.expect_char('#');
-        Value::ColorValue(Color {
-            r: self.parse_hex_pair(),
-            g: self.parse_hex_pair(),
-            b: self.parse_hex_pair(),
+    fn parse_color(&mut self) -> ParseResult<Value> {
+        let location = self.location();
+        self.expect_char('#')?;
+        let digits = self.consume_while(|c| c.is_ascii_hexdigit());
+        if digits.len() != 6 {
+            let message = format!("invalid color #{}", digits);
+            return Err(ParseError { message, location });
+        }
+        let channel = |index: usize| u8::from_str_radix(&digits[index..index + 2], 16).unwrap();
+        Ok(Value::ColorValue(Color {
+            r: channel(0),
+            g: channel(2),
+            b: channel(4),
             a: 255,
-        })
+        }))
     }
 
-    fn parse_hex_pair(&mut self) -> u8 {
-        let s = &self.input[self.pos..self.pos + 2];
-        self.pos += 2;
-        u8::from_str_radix(s, 16).unwrap()
+    /// Skip the rest of a declaration that couldn't be parsed: up to and including the next
+    /// `;`, or up to the `}` that ends its block.
+    fn skip_declaration(&mut self) {
+        loop {
+            match self.next_char() {
+                None | Some('}') => break,
+                Some(';') => {
+                    self.consume_char();
+                    break;
+                }
+                Some(_) => self.skip_component(),
+            }
+        }
+    }
+
+    /// Skip a rule that couldn't be parsed, up to the end of its block, or for an at-rule
+    /// without a block, up to the end of the statement.
+    fn skip_rule(&mut self, at_rule: bool) {
+        loop {
+            match self.next_char() {
+                None => break,
+                Some(';') if at_rule => {
+                    self.consume_char();
+                    break;
+                }
+                Some('{') => {
+                    self.skip_component();
+                    break;
+                }
+                Some(_) => self.skip_component(),
+            }
+        }
+    }
+
+    /// Skip one character, or a whole block in brackets or a string, including any blocks
+    /// nested inside it.
+    fn skip_component(&mut self) {
+        let mut closing = Vec::new();
+        loop {
+            let Some(c) = self.consume_char() else {
+                return;
+            };
+            match c {
+                '(' => closing.push(')'),
+                '[' => closing.push(']'),
+                '{' => closing.push('}'),
+                '"' | '\'' => loop {
+                    self.consume_while(|next| next != c && next != '\\');
+                    match self.consume_char() {
+                        Some('\\') => {
+                            self.consume_char();
+                        }
+                        _ => break,
+                    }
+                },
+                '\\' => {
+                    self.consume_char();
+                }
+                c if closing.last() == Some(&c) => {
+                    closing.pop();
+                }
+                _ => {}
+            }
+            if closing.is_empty() {
+                return;
+            }
+        }
     }
 }
 
+/// Whether `c` may appear in an identifier without being escaped: letters, digits, `-`, `_`,
+/// and any character outside ASCII.
+///
+/// http://www.w3.org/TR/css-syntax-3/#ident-code-point
 fn valid_identifier_char(c: char) -> bool {
-    // TODO: Include U+00A0 and higher.
-    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_')
+    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_') || !c.is_ascii()
+}
+
+/// Whitespace in CSS, which unlike `char::is_whitespace` doesn't include any characters outside
+/// ASCII.
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')
 }
 
 #[cfg(test)]
@@ -260,20 +1155,14 @@ mod tests {
 
     #[test]
     fn test_parse_identifier() {
-        let mut parser = Parser {
-            pos: 0,
-            input: "example".to_string(),
-        };
+        let mut parser = Parser::new("example".to_string());
         assert_eq!(parser.parse_identifier(), "example");
     }
 
     #[test]
     fn test_parse_simple_selector() {
-        let mut parser = Parser {
-            pos: 0,
-            input: "div#main.container".to_string(),
-        };
-        let selector = parser.parse_simple_selector();
+        let mut parser = Parser::new("div#main.container".to_string());
+        let selector = parser.parse_simple_selector().unwrap();
         assert_eq!(selector.tag_name, Some("div".to_string()));
         assert_eq!(selector.id, Some("main".to_string()));
         assert_eq!(selector.class, vec!["container".to_string()]);
@@ -281,11 +1170,8 @@ mod tests {
 
     #[test]
     fn test_parse_length() {
-        let mut parser = Parser {
-            pos: 0,
-            input: "15px".to_string(),
-        };
-        if let Value::Length(value, unit) = parser.parse_length() {
+        let mut parser = Parser::new("15px".to_string());
+        if let Value::Length(value, unit) = parser.parse_length().unwrap() {
             assert_eq!(value, 15.0);
             assert!(matches!(unit, Unit::Px));
         } else {
@@ -294,12 +1180,93 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_color() {
-        let mut parser = Parser {
-            pos: 0,
-            input: "#ffcc00".to_string(),
+    fn test_parse_negative_length() {
+        let mut parser = Parser::new("-2.5px".to_string());
+        assert_eq!(parser.parse_value().unwrap(), Value::Length(-2.5, Unit::Px));
+    }
+
+    #[test]
+    fn test_parse_number() {
+        let mut parser = Parser::new("-1;".to_string());
+        assert_eq!(parser.parse_value().unwrap(), Value::Number(-1.0));
+    }
+
+    #[test]
+    fn test_parse_value_list_and_functions() {
+        let mut parser = Parser::new("50% repeat(2, minmax(10px, 1fr)) / span 2;".to_string());
+        assert_eq!(
+            parser.parse_value().unwrap(),
+            Value::List(vec![
+                Value::Length(50.0, Unit::Percent),
+                Value::Function(
+                    "repeat".to_string(),
+                    vec![
+                        Value::Number(2.0),
+                        Value::Function(
+                            "minmax".to_string(),
+                            vec![Value::Length(10.0, Unit::Px), Value::Length(1.0, Unit::Fr)],
+                        ),
+                    ],
+                ),
+                Value::Keyword("/".to_string()),
+                Value::Keyword("span".to_string()),
+                Value::Number(2.0),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_and_resolve_calc() {
+        let value = |source: &str| {
+            let mut parser = Parser::new(source.to_string());
+            parser.parse_value().unwrap()
         };
-        if let Value::ColorValue(color) = parser.parse_color() {
+        let width = value("calc(100% - 2 * (10px + 5px))");
+        assert_eq!(width.to_string(), "calc(100% - 2 * (10px + 5px))");
+        assert_eq!(width.resolve_length(Some(200.0)), Some(170.0));
+        assert_eq!(width.resolve_length(None), None);
+        assert_eq!(width.to_px(), 0.0);
+
+        assert_eq!(value("calc(30px / 2 + 1px)").to_px(), 16.0);
+        assert_eq!(value("calc(50%)").resolve_length(Some(80.0)), Some(40.0));
+        assert_eq!(value("25%").resolve_length(Some(80.0)), Some(20.0));
+        // Lengths can't be added to numbers, multiplied together, or divided by zero.
+        assert_eq!(value("calc(10px + 2)").resolve_length(None), None);
+        assert_eq!(value("calc(10px * 2px)").resolve_length(None), None);
+        assert_eq!(value("calc(10px / 0)").resolve_length(None), None);
+        assert_eq!(value("calc(2 * 3)").resolve_length(None), None);
+    }
+
+    #[test]
+    fn test_parse_url_and_angle() {
+        let mut parser = Parser::new(
+            "url(images/a.png) url( \"b c.ppm\" ) linear-gradient(45deg, #000000);".to_string(),
+        );
+        assert_eq!(
+            parser.parse_value().unwrap(),
+            Value::List(vec![
+                Value::Url("images/a.png".to_string()),
+                Value::Url("b c.ppm".to_string()),
+                Value::Function(
+                    "linear-gradient".to_string(),
+                    vec![
+                        Value::Length(45.0, Unit::Deg),
+                        Value::ColorValue(Color {
+                            r: 0,
+                            g: 0,
+                            b: 0,
+                            a: 255
+                        }),
+                    ],
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_color() {
+        let mut parser = Parser::new("#ffcc00".to_string());
+        if let Value::ColorValue(color) = parser.parse_color().unwrap() {
             assert_eq!(color.r, 0xff);
             assert_eq!(color.g, 0xcc);
             assert_eq!(color.b, 0x00);
@@ -311,11 +1278,8 @@ mod tests {
 
     #[test]
     fn test_parse_declaration() {
-        let mut parser = Parser {
-            pos: 0,
-            input: "color: red;".to_string(),
-        };
-        let decl = parser.parse_declaration();
+        let mut parser = Parser::new("color: red;".to_string());
+        let decl = parser.parse_declaration().unwrap();
         assert_eq!(decl.name, "color");
         if let Value::Keyword(value) = decl.value {
             assert_eq!(value, "red");
@@ -324,16 +1288,223 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_absolute_units() {
+        let mut parser = Parser::new("1in 2.54cm 72pt;".to_string());
+        assert_eq!(
+            parser.parse_value().unwrap(),
+            Value::List(vec![Value::Length(96.0, Unit::Px); 3])
+        );
+    }
+
+    #[test]
+    fn test_parse_page_rules() {
+        let stylesheet = parse(
+            "@page { size: 100px 200px; margin: 10px; } \
+             p { color: red; } \
+             @page :first { margin: 0px; }"
+                .to_string(),
+        );
+        assert_eq!(stylesheet.rules.len(), 1);
+        let names: Vec<&str> = stylesheet.page.iter().map(|d| &*d.name).collect();
+        assert_eq!(names, vec!["size", "margin"]);
+    }
+
+    #[test]
+    fn test_rule_locations() {
+        let stylesheet = parse("a { color: red; }\n\n  p,\nb { color: blue; }".to_string());
+        let locations: Vec<String> = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.location.to_string())
+            .collect();
+        assert_eq!(locations, vec!["1:1", "3:3"]);
+    }
+
+    #[test]
+    fn test_display_values_and_selectors() {
+        let mut parser =
+            Parser::new("1px 50% linear-gradient(45deg, #ff0000) url(\"a.png\") 1.5;".to_string());
+        assert_eq!(
+            parser.parse_value().unwrap().to_string(),
+            "1px 50% linear-gradient(45deg, #ff0000) url(\"a.png\") 1.5"
+        );
+        let selector = parse_selector(" div#main.a.b ".to_string()).unwrap();
+        assert_eq!(selector.to_string(), "div#main.a.b");
+        assert!(parse_selector("div > p".to_string()).is_none());
+        assert!(parse_selector("".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let (stylesheet, errors) = parse_with_errors(
+            "div { color: #ff0000; width: 10qq; margin: f(1, {2}); height: 5px }\n\
+             @media print { p { color: #000000; } }\n\
+             @import url(a.css);\n\
+             p > a { color: #000000; }\n\
+             span { display: inline; background: #12; padding: 1px"
+                .to_string(),
+        );
+        assert_eq!(
+            stylesheet.to_string(),
+            "div { color: #ff0000; height: 5px; }\nspan { display: inline; padding: 1px; }\n"
+        );
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "1:32: unrecognized unit \"qq\"",
+                "1:49: unexpected '{' in value",
+                "2:1: unsupported at-rule @media",
+                "3:1: unsupported at-rule @import",
+                "4:3: unexpected '>' in selector list",
+                "5:37: invalid color #12",
+            ]
+        );
+        // Nothing is too malformed to parse.
+        for source in [
+            "",
+            "{",
+            "}",
+            "a{b:c(",
+            "#",
+            "a { b: 1e; c: --; }",
+            "@",
+            "a,{}",
+        ] {
+            parse(source.to_string());
+        }
+    }
+
     #[test]
     fn test_parse_rules() {
-        let mut parser = Parser {
-            pos: 0,
-            input: "div { color: blue; width: 100px; }".to_string(),
-        };
+        let parser = Parser::new("div { color: blue; width: 100px; }".to_string());
         let stylesheet = parse(parser.input.clone());
         assert_eq!(stylesheet.rules.len(), 1);
         let rule = &stylesheet.rules[0];
         assert_eq!(rule.selectors.len(), 1);
         assert_eq!(rule.declarations.len(), 2);
     }
+
+    #[test]
+    fn test_unicode_and_escapes() {
+        let source = "ünïcode.\\31 0\\.5, #a\\ b { --größe: 10px; font-family: \\\"Q\\\"; \
+                      background: url(\"a\\\"b\\\n.png\") url(c\\).png) }"
+            .to_string();
+        let (stylesheet, errors) = parse_with_errors(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let rule = &stylesheet.rules[0];
+        let Selector::Simple(ref first) = rule.selectors[1];
+        assert_eq!(first.tag_name.as_deref(), Some("ünïcode"));
+        assert_eq!(first.class, ["10.5"]);
+        let Selector::Simple(ref second) = rule.selectors[0];
+        assert_eq!(second.id.as_deref(), Some("a b"));
+        assert_eq!(rule.declarations[0].name, "--größe");
+        assert_eq!(
+            rule.declarations[1].value,
+            Value::Keyword("\"Q\"".to_string())
+        );
+        assert_eq!(
+            rule.declarations[2].value,
+            Value::List(vec![
+                Value::Url("a\"b.png".to_string()),
+                Value::Url("c).png".to_string()),
+            ])
+        );
+        // Escapes are written out where they are needed.
+        assert_eq!(
+            rule.to_string(),
+            "#a\\ b, ünïcode.\\31 0\\.5 { --größe: 10px; font-family: \\\"Q\\\"; \
+             background: url(\"a\\\"b.png\") url(\"c).png\"); }"
+        );
+
+        // Code points that aren't characters are replaced.
+        let mut parser = Parser::new("a\\0 b\\110000\\\0\\".to_string());
+        assert_eq!(
+            parser.parse_identifier(),
+            "a\u{fffd}b\u{fffd}\u{fffd}\u{fffd}"
+        );
+        // Whitespace outside ASCII is part of an identifier.
+        let mut parser = Parser::new("a\u{3000}b c".to_string());
+        assert_eq!(parser.parse_identifier(), "a\u{3000}b");
+    }
+
+    #[test]
+    fn test_attribute_selectors() {
+        let stylesheet = parse(
+            "input[type=checkbox], [ data-x = 'a b' ], a[href] { color: #000000; }".to_string(),
+        );
+        let rule = &stylesheet.rules[0];
+        let specificities: Vec<_> = rule.selectors.iter().map(Selector::specificity).collect();
+        assert_eq!(specificities, [(0, 1, 1), (0, 1, 1), (0, 1, 0)]);
+        assert_eq!(
+            rule.to_string(),
+            "input[type=\"checkbox\"], a[href], [data-x=\"a b\"] { color: #000000; }"
+        );
+    }
+
+    #[test]
+    fn test_pseudo_elements_and_strings() {
+        let (stylesheet, errors) = parse_with_errors(
+            "li::MARKER, ::before { content: 'n\\'' counter(item) \"\\a\"; }\n\
+             p::first-line { color: #000000; }\n\
+             a::after b { color: #000000; }\n\
+             a:After, q:before { content: attr(title); }\n\
+             a:hover, li:marker { color: #000000; }"
+                .to_string(),
+        );
+        let messages: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "2:2: unsupported pseudo-element ::first-line",
+                "3:10: unexpected 'b' in selector list",
+                "5:2: unsupported pseudo-class :hover"
+            ]
+        );
+        let rule = &stylesheet.rules[0];
+        let selectors: Vec<_> = rule
+            .selectors
+            .iter()
+            .map(|selector| (selector.pseudo_element(), selector.specificity()))
+            .collect();
+        assert_eq!(
+            selectors,
+            [
+                (Some(PseudoElement::Marker), (0, 0, 2)),
+                (Some(PseudoElement::Before), (0, 0, 1)),
+            ]
+        );
+        assert_eq!(
+            rule.declarations[0].value,
+            Value::List(vec![
+                Value::String("n'".to_string()),
+                Value::Function(
+                    "counter".to_string(),
+                    vec![Value::Keyword("item".to_string())]
+                ),
+                Value::String("\n".to_string()),
+            ])
+        );
+        assert_eq!(
+            rule.to_string(),
+            "li::marker, ::before { content: \"n'\" counter(item) \"\\a \"; }"
+        ); // The single-colon syntax of CSS 2 is kept for `:before` and `:after`.
+        assert_eq!(
+            stylesheet.rules[1].to_string(),
+            "a::after, q::before { content: attr(title); }"
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let latin1 = b"@charset \"iso-8859-1\";\n.caf\xe9 { color: #000000 }";
+        let stylesheet = parse(decode(latin1, Encoding::Utf8));
+        assert_eq!(stylesheet.to_string(), ".café { color: #000000; }\n");
+        assert_eq!(decode(b"a { }", Encoding::Windows1252), "a { }");
+        assert_eq!(
+            decode(b"\xef\xbb\xbf.\xc3\xa9", Encoding::Windows1252),
+            ".é"
+        );
+    }
 }
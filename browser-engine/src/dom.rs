@@ -1,18 +1,21 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-#[derive(PartialEq, Debug)]
+use crate::html::VOID_ELEMENTS;
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Node {
     pub children: Vec<Node>,
     pub node_type: NodeType,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum NodeType {
     Text(String),
     Element(ElementData),
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ElementData {
     pub tag_name: String,
     pub attrs: AttrMap,
@@ -29,6 +32,80 @@ impl ElementData {
             None => HashSet::new(),
         }
     }
+
+    /// Add a class to the element's `class` attribute, unless it already has it.
+    pub fn add_class(&mut self, class: &str) {
+        if self.classes().contains(class) {
+            return;
+        }
+        let classlist = self.attrs.entry("class".to_string()).or_default();
+        if !classlist.is_empty() {
+            classlist.push(' ');
+        }
+        classlist.push_str(class);
+    }
+
+    /// Remove a class from the element's `class` attribute.
+    pub fn remove_class(&mut self, class: &str) {
+        if let Some(classlist) = self.attrs.get_mut("class") {
+            let classes: Vec<&str> = classlist
+                .split(' ')
+                .filter(|&other| !other.is_empty() && other != class)
+                .collect();
+            *classlist = classes.join(" ");
+        }
+    }
+
+    /// Remove a class if the element has it, or else add it. Returns whether it was added.
+    pub fn toggle_class(&mut self, class: &str) -> bool {
+        let add = !self.classes().contains(class);
+        if add {
+            self.add_class(class);
+        } else {
+            self.remove_class(class);
+        }
+        add
+    }
+}
+
+/// An element is shown as its start tag, with its attributes in alphabetical order.
+impl fmt::Display for ElementData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut attrs: Vec<_> = self.attrs.iter().collect();
+        attrs.sort();
+        write!(f, "<{}", self.tag_name)?;
+        for (name, value) in attrs {
+            write!(f, " {}={:?}", name, value)?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// A node is written out as HTML: an element with its attributes in alphabetical order, then
+/// its children and its end tag. Attribute values are quoted with `'` if they contain `"`.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let element = match self.node_type {
+            NodeType::Text(ref text) => return write!(f, "{}", text),
+            NodeType::Element(ref element) => element,
+        };
+        let mut attrs: Vec<_> = element.attrs.iter().collect();
+        attrs.sort();
+        write!(f, "<{}", element.tag_name)?;
+        for (name, value) in attrs {
+            let quote = if value.contains('"') { '\'' } else { '"' };
+            write!(f, " {}={}{}{}", name, quote, value, quote)?;
+        }
+        write!(f, ">")?;
+        let void = VOID_ELEMENTS.contains(&&*element.tag_name.to_ascii_lowercase());
+        if void && self.children.is_empty() {
+            return Ok(());
+        }
+        for child in &self.children {
+            write!(f, "{}", child)?;
+        }
+        write!(f, "</{}>", element.tag_name)
+    }
 }
 
 pub type AttrMap = HashMap<String, String>;
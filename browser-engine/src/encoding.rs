@@ -0,0 +1,215 @@
+//! Character encodings of documents and stylesheets: UTF-8, UTF-16 in either byte order, and
+//! windows-1252, which is what documents labelled as Latin-1 (ISO-8859-1) or ASCII really use.
+//!
+//! http://encoding.spec.whatwg.org/
+
+/// A supported character encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+/// The characters of bytes 0x80 to 0x9F in windows-1252. Bytes that the encoding doesn't
+/// define are mapped to the C1 control characters with the same values.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+impl Encoding {
+    /// The encoding named by a label such as `utf-8` or `latin1`, as given in a
+    /// `<meta charset>` or `@charset`, ignoring case and surrounding whitespace.
+    ///
+    /// http://encoding.spec.whatwg.org/#names-and-labels
+    pub fn for_label(label: &str) -> Option<Encoding> {
+        let label = label
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_ascii_lowercase();
+        match &*label {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Encoding::Utf8),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Encoding::Utf16Le),
+            "unicodefffe" | "utf-16be" => Some(Encoding::Utf16Be),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// The encoding to use when a document in ASCII-compatible bytes, such as a `<meta>`
+    /// element, declares this one. A document can't be in UTF-16 if its declaration could be
+    /// read that way, so it is taken to be UTF-8.
+    pub fn declared(self) -> Encoding {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16Be => Encoding::Utf8,
+            encoding => encoding,
+        }
+    }
+
+    /// A decoder for text in this encoding that arrives in chunks.
+    pub fn decoder(self) -> Decoder {
+        Decoder {
+            encoding: self,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Find the byte order mark at the start of some text, and return the encoding it indicates
+/// and its length.
+pub fn from_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        _ => None,
+    }
+}
+
+/// Decode the whole of some text. Bytes that aren't valid in the encoding are replaced with
+/// U+FFFD.
+pub fn decode(bytes: &[u8], encoding: Encoding) -> String {
+    encoding.decoder().decode(bytes, true)
+}
+
+/// Decodes text that arrives in chunks, which may split a character.
+pub struct Decoder {
+    encoding: Encoding,
+    /// Bytes at the end of the last chunk that begin a character.
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Decode the next chunk. A character that is cut off at the end of the chunk is decoded
+    /// with the next one, unless this is the `last` chunk, when it is replaced with U+FFFD.
+    pub fn decode(&mut self, chunk: &[u8], last: bool) -> String {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(chunk);
+        let (mut text, rest) = match self.encoding {
+            Encoding::Utf8 => decode_utf8(&bytes),
+            Encoding::Utf16Le => decode_utf16(&bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(&bytes, u16::from_be_bytes),
+            Encoding::Windows1252 => (bytes.iter().map(|&b| windows_1252(b)).collect(), 0),
+        };
+        if last && rest > 0 {
+            text.push(char::REPLACEMENT_CHARACTER);
+        } else {
+            self.pending = bytes[bytes.len() - rest..].to_vec();
+        }
+        text
+    }
+}
+
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[byte as usize - 0x80],
+        _ => byte as char,
+    }
+}
+
+/// Decode UTF-8, and return the text and the number of bytes at the end that are the start of
+/// a character.
+fn decode_utf8(bytes: &[u8]) -> (String, usize) {
+    let mut text = String::new();
+    let mut rest = bytes;
+    while let Err(error) = std::str::from_utf8(rest) {
+        let (valid, invalid) = rest.split_at(error.valid_up_to());
+        text.push_str(std::str::from_utf8(valid).unwrap());
+        match error.error_len() {
+            Some(length) => {
+                text.push(char::REPLACEMENT_CHARACTER);
+                rest = &invalid[length..];
+            }
+            None => return (text, invalid.len()),
+        }
+    }
+    text.push_str(std::str::from_utf8(rest).unwrap());
+    (text, 0)
+}
+
+/// Decode UTF-16 with the given byte order, and return the text and the number of bytes at the
+/// end that are the start of a character.
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> (String, usize) {
+    let mut units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| unit([pair[0], pair[1]]))
+        .collect();
+    let mut rest = bytes.len() % 2;
+    // A high surrogate at the end may be followed by its low surrogate in the next chunk.
+    if let Some(0xD800..=0xDBFF) = units.last() {
+        units.pop();
+        rest += 2;
+    }
+    let text = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    (text, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(Encoding::for_label(" UTF-8"), Some(Encoding::Utf8));
+        assert_eq!(
+            Encoding::for_label("ISO-8859-1"),
+            Some(Encoding::Windows1252)
+        );
+        assert_eq!(Encoding::for_label("utf-16"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::for_label("shift_jis"), None);
+        assert_eq!(Encoding::Utf16Be.declared(), Encoding::Utf8);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"caf\xe9 \x80", Encoding::Windows1252), "café €");
+        assert_eq!(decode(b"a\xffb", Encoding::Utf8), "a\u{fffd}b");
+        let utf16: Vec<u8> = "é😀".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(&utf16, Encoding::Utf16Be), "é😀");
+        assert_eq!(decode(&utf16[..5], Encoding::Utf16Be), "é\u{fffd}");
+        assert_eq!(from_bom(b"\xff\xfea\0"), Some((Encoding::Utf16Le, 2)));
+    }
+
+    #[test]
+    fn test_decode_in_chunks() {
+        let text = "a é 😀 z";
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            let bytes: Vec<u8> = match encoding {
+                Encoding::Utf8 => text.as_bytes().to_vec(),
+                Encoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+                _ => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            };
+            let mut decoder = encoding.decoder();
+            let mut decoded = String::new();
+            for byte in &bytes {
+                decoded.push_str(&decoder.decode(&[*byte], false));
+            }
+            decoded.push_str(&decoder.decode(&[], true));
+            assert_eq!(decoded, text, "{}", encoding.name());
+        }
+    }
+}
@@ -1,27 +1,296 @@
+//! An HTML parser that, like a browser, accepts any input. Missing end tags are implied, stray
+//! end tags are ignored, and a `<` that doesn't start a tag is text.
+//!
+//! Documents can be parsed as they arrive, a chunk at a time, and the tree parsed so far can be
+//! taken at any point, so that rendering can start before the whole document is loaded.
+//!
+//! Documents given as bytes may be in any of the encodings in `encoding`. The encoding is
+//! found from a byte order mark, or else from a `<meta charset>` near the start.
+
 use std::collections::HashMap;
+use std::io;
 
 use crate::dom;
+use crate::encoding::{self, Decoder, Encoding};
 
-struct Parser {
-    pos: usize,
+/// Elements that never have contents, so they have no end tag.
+///
+/// http://html.spec.whatwg.org/multipage/syntax.html#void-elements
+pub(crate) const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// How deeply elements may be nested. Elements any deeper are given no children, and what
+/// would have been their contents become their siblings, so that a document can't exhaust the
+/// stack.
+const MAX_DEPTH: usize = 512;
+
+/// How much `parse_reader` reads at a time.
+const CHUNK_SIZE: usize = 8192;
+
+/// How far into a document to look for a `<meta>` element declaring its encoding.
+///
+/// http://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+const PRESCAN_LENGTH: usize = 1024;
+
+/// An incremental HTML parser. Input is given to it in chunks with `feed` or `feed_bytes`, and
+/// each chunk is parsed as far as it can be: up to the end of the last complete tag, and the
+/// text after it.
+///
+/// ```
+/// use browser_engine::html::Parser;
+///
+/// let mut parser = Parser::new();
+/// parser.feed("<div><p>Hello");
+/// assert_eq!(parser.document().to_string(), "<div><p>Hello</p></div>");
+/// parser.feed(", world</p><img sr");
+/// assert_eq!(parser.document().to_string(), "<div><p>Hello, world</p></div>");
+/// parser.feed("c=\"a.png\"></div>");
+/// assert_eq!(parser.finish().to_string(), "<div><p>Hello, world</p><img src=\"a.png\"></div>");
+/// ```
+#[derive(Default)]
+pub struct Parser {
+    /// Input that hasn't been parsed yet, because it might be the start of a tag that continues
+    /// in the next chunk.
     input: String,
+    /// Decodes the bytes given to `feed_bytes`, once their encoding is known.
+    decoder: Option<Decoder>,
+    /// The start of the document's bytes, kept until there are enough to find their encoding.
+    undecoded: Vec<u8>,
+    /// The encoding to use unless the document has a byte order mark.
+    encoding: Option<Encoding>,
+    /// The elements that are open, with the children parsed so far. Below them is the
+    /// document, whose children are the top-level nodes.
+    open: Vec<dom::Node>,
+    /// Whether a newline at the start of the next token is left out, because it follows the
+    /// start tag of a `<pre>` or `<textarea>`.
+    skip_newline: bool,
+}
+
+/// A piece of markup, or a run of text.
+enum Token {
+    Text(String),
+    StartTag {
+        name: String,
+        attrs: dom::AttrMap,
+        self_closing: bool,
+    },
+    EndTag(String),
+    /// A comment, or a declaration such as `<!DOCTYPE html>`, which are skipped.
+    Comment,
 }
 
 impl Parser {
-    fn next_char(&self) -> char {
-        self.input[self.pos..].chars().next().unwrap()
+    pub fn new() -> Parser {
+        Parser {
+            open: vec![dom::elem(String::new(), HashMap::new(), Vec::new())],
+            ..Default::default()
+        }
     }
 
-    fn starts_with(&self, s: &str) -> bool {
-        self.input[self.pos..].starts_with(s)
+    /// A parser for a document whose encoding is already known, for instance from the
+    /// `Content-Type` it was served with. A byte order mark still takes precedence.
+    pub fn with_encoding(encoding: Encoding) -> Parser {
+        Parser {
+            encoding: Some(encoding),
+            ..Parser::new()
+        }
     }
 
-    fn expect(&mut self, s: &str) {
-        if self.starts_with(s) {
-            self.pos += s.len();
-        } else {
-            panic!("Expected {:?} at byte {} but it was not found", s, self.pos);
+    /// The encoding of the bytes given to `feed_bytes`, once it has been found.
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.decoder.as_ref().map(Decoder::encoding)
+    }
+
+    /// Parse a whole document.
+    pub fn parse(source: String) -> dom::Node {
+        let mut parser = Parser::new();
+        parser.feed(&source);
+        parser.finish()
+    }
+
+    /// Parse the next chunk of the document.
+    pub fn feed(&mut self, chunk: &str) {
+        self.input.push_str(chunk);
+        self.parse_input(false);
+    }
+
+    /// Parse the next chunk of a document given as bytes. A character may be split between
+    /// chunks, and bytes that aren't valid in the document's encoding are replaced with U+FFFD.
+    ///
+    /// Unless the encoding was given, nothing is parsed until there is enough of the document
+    /// to look for a `<meta charset>` in.
+    pub fn feed_bytes(&mut self, chunk: &[u8]) {
+        self.decode(chunk, false);
+    }
+
+    fn decode(&mut self, chunk: &[u8], last: bool) {
+        let text = match self.decoder {
+            Some(ref mut decoder) => decoder.decode(chunk, last),
+            None => {
+                self.undecoded.extend_from_slice(chunk);
+                let needed = if self.encoding.is_some() {
+                    3
+                } else {
+                    PRESCAN_LENGTH
+                };
+                if self.undecoded.len() < needed && !last {
+                    return;
+                }
+                let bytes = std::mem::take(&mut self.undecoded);
+                let (encoding, bom_length) = encoding::from_bom(&bytes).unwrap_or_else(|| {
+                    let encoding = self.encoding.or_else(|| prescan(&bytes));
+                    (encoding.unwrap_or(Encoding::Utf8), 0)
+                });
+                let decoder = self.decoder.insert(encoding.decoder());
+                decoder.decode(&bytes[bom_length..], last)
+            }
+        };
+        self.feed(&text);
+    }
+
+    /// The tree parsed so far, as if the input ended here.
+    pub fn document(&self) -> dom::Node {
+        let mut open = self.open.clone();
+        close_all(&mut open)
+    }
+
+    /// Parse the rest of the input, and return the whole tree. The root is the document's
+    /// only top-level node, or else an `html` element holding them all.
+    pub fn finish(mut self) -> dom::Node {
+        self.decode(&[], true);
+        self.parse_input(true);
+        close_all(&mut self.open)
+    }
+
+    /// Parse as much of the input as can be parsed. At the end of the input, tags that are
+    /// cut off are parsed as they are.
+    fn parse_input(&mut self, at_end: bool) {
+        let mut pos = 0;
+        while pos < self.input.len() {
+            let mut scanner = Scanner {
+                input: &self.input[pos..],
+                pos: 0,
+                at_end,
+            };
+            let Some(token) = scanner.next_token() else {
+                break;
+            };
+            pos += scanner.pos;
+            self.add(token);
         }
+        self.input.drain(..pos);
+    }
+
+    /// Add a token to the tree.
+    fn add(&mut self, token: Token) {
+        let skip_newline = std::mem::take(&mut self.skip_newline);
+        match token {
+            Token::Text(text) => {
+                let at_top_level = self.open.len() == 1;
+                let parent = self.open.last_mut().unwrap();
+                match parent.children.last_mut() {
+                    // Text on either side of a comment or stray end tag is one node.
+                    Some(dom::Node {
+                        node_type: dom::NodeType::Text(ref mut previous),
+                        ..
+                    }) => previous.push_str(&text),
+                    _ => {
+                        let text = if at_top_level {
+                            // Whitespace around the top-level nodes isn't part of the document.
+                            text.trim_start()
+                        } else if skip_newline {
+                            // The newline may be a CR LF pair or a lone CR.
+                            text.strip_prefix("\r\n")
+                                .or_else(|| text.strip_prefix('\r'))
+                                .or_else(|| text.strip_prefix('\n'))
+                                .unwrap_or(&text)
+                        } else {
+                            &text
+                        };
+                        if !text.is_empty() {
+                            parent.children.push(dom::text(text.to_string()));
+                        }
+                    }
+                }
+            }
+            Token::StartTag {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                let void = VOID_ELEMENTS.contains(&&*name.to_ascii_lowercase());
+                let element = dom::elem(name, attrs, Vec::new());
+                // The document at the bottom of the stack isn't counted.
+                if self_closing || void || self.open.len() > MAX_DEPTH {
+                    self.open.last_mut().unwrap().children.push(element);
+                } else {
+                    self.skip_newline = skips_first_newline(&element);
+                    self.open.push(element);
+                }
+            }
+            Token::EndTag(name) => {
+                // The end tag closes the innermost open element with its name, and any inside
+                // that. If there is none, it is ignored.
+                if let Some(index) = self.open.iter().skip(1).rposition(|open| {
+                    matches!(open.node_type, dom::NodeType::Element(ref element)
+                        if element.tag_name.eq_ignore_ascii_case(&name))
+                }) {
+                    while self.open.len() > index + 1 {
+                        let element = self.open.pop().unwrap();
+                        self.open.last_mut().unwrap().children.push(element);
+                    }
+                }
+            }
+            Token::Comment => {}
+        }
+    }
+}
+
+/// Whether a newline straight after the start tag of an element is left out of its text, as it
+/// is for `<pre>` and `<textarea>`, so that their content can start on the line after the tag.
+///
+/// http://html.spec.whatwg.org/multipage/syntax.html#element-restrictions
+fn skips_first_newline(element: &dom::Node) -> bool {
+    match element.node_type {
+        dom::NodeType::Element(ref element) => ["pre", "listing", "textarea"]
+            .iter()
+            .any(|name| element.tag_name.eq_ignore_ascii_case(name)),
+        dom::NodeType::Text(_) => false,
+    }
+}
+
+/// Close all of the `open` elements, and return the root of the document.
+fn close_all(open: &mut Vec<dom::Node>) -> dom::Node {
+    while open.len() > 1 {
+        let element = open.pop().unwrap();
+        open.last_mut().unwrap().children.push(element);
+    }
+    let mut nodes = open.pop().unwrap().children;
+    if nodes.len() == 1 {
+        nodes.remove(0)
+    } else {
+        dom::elem("html".to_string(), HashMap::new(), nodes)
+    }
+}
+
+/// Reads tokens from the unparsed input. A token that may continue past the end of the input
+/// is only read `at_end`.
+struct Scanner<'a> {
+    input: &'a str,
+    pos: usize,
+    at_end: bool,
+}
+
+impl Scanner<'_> {
+    /// Return the next character, or `None` at the end of the input.
+    fn next_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.input[self.pos..].starts_with(s)
     }
 
     fn eof(&self) -> bool {
@@ -29,17 +298,18 @@ impl Parser {
     }
 
     // Return the current character, and advance self.pos to the next character.
-    fn consume_char(&mut self) -> char {
-        let c = self.next_char();
+    fn consume_char(&mut self) -> Option<char> {
+        let c = self.next_char()?;
         self.pos += c.len_utf8();
-        c
+        Some(c)
     }
 
     // Consume characters until `test` returns false.
     fn consume_while(&mut self, test: impl Fn(char) -> bool) -> String {
         let mut result = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
+        while let Some(c) = self.next_char().filter(|&c| test(c)) {
+            self.pos += c.len_utf8();
+            result.push(c);
         }
         result
     }
@@ -48,95 +318,233 @@ impl Parser {
         self.consume_while(char::is_whitespace);
     }
 
+    /// Parse a tag or attribute name. Names may contain anything but whitespace, and the
+    /// characters that end them or start values: `/`, `>`, `=`, `<` and quotes.
     fn parse_name(&mut self) -> String {
-        self.consume_while(|c| matches!(c, 'a'..='z' |'A'..='Z'| '0'..='9'))
+        self.consume_while(|c| {
+            !c.is_whitespace() && !matches!(c, '/' | '>' | '=' | '<' | '"' | '\'')
+        })
     }
 
-    // Parse a single node.
-    fn parse_node(&mut self) -> dom::Node {
-        if self.starts_with("<") {
-            self.parse_element()
-        } else {
-            self.parse_text()
+    /// Read the next token, or return `None` if it might continue past the end of the input.
+    fn next_token(&mut self) -> Option<Token> {
+        match self.starts_markup() {
+            None => None,
+            Some(false) => self.parse_text(),
+            Some(true) if self.starts_with("</") => self.parse_end_tag(),
+            Some(true) if self.starts_with("<!") || self.starts_with("<?") => self.skip_comment(),
+            Some(true) => self.parse_start_tag(),
         }
     }
 
-    // Parse a text node.
-    fn parse_text(&mut self) -> dom::Node {
-        dom::text(self.consume_while(|c| c != '<'))
+    /// Whether the input continues with a start tag, an end tag, or a comment or other
+    /// declaration, or `None` if that depends on input yet to come.
+    fn starts_markup(&self) -> Option<bool> {
+        let mut chars = self.input[self.pos..].chars();
+        if chars.next() != Some('<') {
+            return Some(false);
+        }
+        let starts_tag = |c: Option<char>| match c {
+            Some(c) => Some(c.is_ascii_alphabetic()),
+            None if self.at_end => Some(false),
+            None => None,
+        };
+        match chars.next() {
+            Some('!' | '?') => Some(true),
+            Some('/') => starts_tag(chars.next()),
+            c => starts_tag(c),
+        }
     }
 
-    fn parse_element(&mut self) -> dom::Node {
-        // opening tag
-        self.expect("<");
-        let tag_name = self.parse_name();
-        let attrs = self.parse_attributes();
-        self.expect(">");
-
-        // contents
-        let children = self.parse_nodes();
+    /// Read text up to the next markup. If the input ends first, the text so far is read, and
+    /// the rest of it will be read as another token that continues the same text node.
+    fn parse_text(&mut self) -> Option<Token> {
+        let mut text = String::new();
+        loop {
+            text.push_str(&self.consume_while(|c| c != '<'));
+            if self.eof() || self.starts_markup() != Some(false) {
+                break;
+            }
+            // A `<` that doesn't start a tag.
+            self.consume_char();
+            text.push('<');
+        }
+        (!text.is_empty()).then_some(Token::Text(text))
+    }
 
-        self.expect("</");
-        self.expect(&tag_name);
-        self.expect(">");
+    /// Skip a comment, or a declaration such as `<!DOCTYPE html>`.
+    fn skip_comment(&mut self) -> Option<Token> {
+        let rest = &self.input[self.pos..];
+        // Until the input reaches `<!--`, it isn't known how the comment ends.
+        if !self.at_end && rest.len() < 4 && "<!--".starts_with(rest) {
+            return None;
+        }
+        let end = if rest.starts_with("<!--") { "-->" } else { ">" };
+        match rest[2..].find(end) {
+            Some(index) => self.pos += 2 + index + end.len(),
+            None if self.at_end => self.pos = self.input.len(),
+            None => return None,
+        }
+        Some(Token::Comment)
+    }
 
-        dom::elem(tag_name, attrs, children)
+    fn parse_start_tag(&mut self) -> Option<Token> {
+        self.consume_char();
+        let name = self.parse_name();
+        let attrs = self.parse_attributes();
+        if self.eof() && !self.at_end {
+            return None;
+        }
+        // A void element may be written as a self-closing tag.
+        let self_closing = self.starts_with("/>");
+        if self_closing {
+            self.pos += 2;
+        } else {
+            self.consume_char();
+        }
+        Some(Token::StartTag {
+            name,
+            attrs,
+            self_closing,
+        })
     }
 
-    fn parse_attr(&mut self) -> (String, String) {
+    fn parse_end_tag(&mut self) -> Option<Token> {
+        self.pos += 2;
         let name = self.parse_name();
-        self.expect("=");
-        let value = self.parse_attr_value();
-        (name, value)
+        self.consume_while(|c| c != '>');
+        if self.eof() && !self.at_end {
+            return None;
+        }
+        self.consume_char();
+        Some(Token::EndTag(name))
     }
 
     fn parse_attr_value(&mut self) -> String {
-        let open_quote = self.consume_char();
-        assert!(open_quote == '"' || open_quote == '\'');
-        let value = self.consume_while(|c| c != open_quote);
-        let close_quote = self.consume_char();
-        assert_eq!(open_quote, close_quote);
-        value
+        match self.next_char() {
+            Some(quote @ ('"' | '\'')) => {
+                self.consume_char();
+                let value = self.consume_while(|c| c != quote);
+                self.consume_char();
+                value
+            }
+            _ => self.consume_while(|c| !c.is_whitespace() && c != '>'),
+        }
     }
 
+    /// Parse the attributes of a start tag. An attribute without a value, such as `checked`,
+    /// has the empty string as its value, and only the first of several attributes with the
+    /// same name is kept.
     fn parse_attributes(&mut self) -> dom::AttrMap {
         let mut attributes = HashMap::new();
         loop {
             self.consume_whitespace();
-            if self.next_char() == '>' {
+            if self.eof() || self.starts_with(">") || self.starts_with("/>") {
                 break;
             }
-            let (name, value) = self.parse_attr();
-            attributes.insert(name, value);
+            let name = self.parse_name();
+            if name.is_empty() {
+                // Skip a character that can't start an attribute, such as a stray quote.
+                self.consume_char();
+                continue;
+            }
+            self.consume_whitespace();
+            let value = if self.starts_with("=") {
+                self.consume_char();
+                self.consume_whitespace();
+                self.parse_attr_value()
+            } else {
+                String::new()
+            };
+            attributes.entry(name).or_insert(value);
         }
         attributes
     }
+}
 
-    fn parse_nodes(&mut self) -> Vec<dom::Node> {
-        let mut nodes = Vec::new();
-        loop {
-            self.consume_whitespace();
-            if self.eof() || self.starts_with("</") {
-                break;
+/// Find the encoding declared by a `<meta>` element at the start of a document, such as
+/// `<meta charset="iso-8859-1">` or
+/// `<meta http-equiv="Content-Type" content="text/html; charset=iso-8859-1">`.
+fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    // Markup is in ASCII, which reads the same in any encoding that can declare itself.
+    let source = encoding::decode(
+        &bytes[..bytes.len().min(PRESCAN_LENGTH)],
+        Encoding::Windows1252,
+    );
+    let mut scanner = Scanner {
+        input: &source,
+        pos: 0,
+        at_end: true,
+    };
+    while let Some(token) = scanner.next_token() {
+        let Token::StartTag { name, attrs, .. } = token else {
+            continue;
+        };
+        if !name.eq_ignore_ascii_case("meta") {
+            continue;
+        }
+        let attr = |wanted: &str| {
+            attrs
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+                .map(|(_, value)| value.as_str())
+        };
+        let label = match attr("charset") {
+            Some(label) => Some(label),
+            None if attr("http-equiv").is_some_and(|v| v.eq_ignore_ascii_case("content-type")) => {
+                attr("content").and_then(charset_parameter)
             }
-            nodes.push(self.parse_node());
+            None => None,
+        };
+        if let Some(encoding) = label.and_then(Encoding::for_label) {
+            return Some(encoding.declared());
         }
-        nodes
     }
+    None
+}
 
-    pub fn parse(source: String) -> dom::Node {
-        let mut nodes = Parser {
-            pos: 0,
-            input: source,
-        }
-        .parse_nodes();
+/// The value of the `charset` parameter of a content type such as `text/html; charset=utf-8`.
+fn charset_parameter(content_type: &str) -> Option<&str> {
+    let start = content_type.to_ascii_lowercase().find("charset")? + "charset".len();
+    let rest = content_type[start..].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    match rest.chars().next()? {
+        quote @ ('"' | '\'') => rest[1..].split(quote).next(),
+        _ => rest.split([';', ' ', '\t', '\n']).next(),
+    }
+}
 
-        if nodes.len() == 1 {
-            nodes.remove(0)
-        } else {
-            dom::elem("html".to_string(), HashMap::new(), nodes)
+/// Parse an HTML document and return the root element. Any input is accepted.
+pub fn parse(source: String) -> dom::Node {
+    Parser::parse(source)
+}
+
+/// Parse a fragment of a document, such as `<li>One</li> <li>Two</li>`, as the contents of an
+/// element, and return its nodes.
+pub fn parse_fragment(source: &str) -> Vec<dom::Node> {
+    let mut parser = Parser::new();
+    // The fragment is parsed inside an element without a name, which no end tag can close.
+    parser
+        .open
+        .push(dom::elem(String::new(), HashMap::new(), Vec::new()));
+    parser.feed(source);
+    parser.finish().children
+}
+
+/// Parse an HTML document from a reader, a chunk at a time, without reading it all into memory
+/// first.
+pub fn parse_reader(mut reader: impl io::Read) -> io::Result<dom::Node> {
+    let mut parser = Parser::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(length) => parser.feed_bytes(&chunk[..length]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
         }
     }
+    Ok(parser.finish())
 }
 
 #[cfg(test)]
@@ -145,7 +553,8 @@ mod tests {
 
     use crate::{
         dom::{elem, text},
-        html::Parser,
+        encoding::Encoding,
+        html::{Parser, PRESCAN_LENGTH},
     };
 
     #[test]
@@ -186,6 +595,26 @@ mod tests {
         assert_eq!(result, elem("img".to_string(), attributes, vec![]));
     }
 
+    #[test]
+    fn test_parse_void_elements() {
+        let input = "<p><img src=\"a.png\"><br/>Text</p>".to_string();
+        let result = Parser::parse(input);
+        let mut attributes = HashMap::new();
+        attributes.insert("src".to_string(), "a.png".to_string());
+        assert_eq!(
+            result,
+            elem(
+                "p".to_string(),
+                HashMap::new(),
+                vec![
+                    elem("img".to_string(), attributes, vec![]),
+                    elem("br".to_string(), HashMap::new(), vec![]),
+                    text("Text".to_string()),
+                ]
+            )
+        );
+    }
+
     #[test]
     fn test_parse_nested_elements() {
         let input = "<div><p>Hello</p><span>World</span></div>".to_string();
@@ -210,4 +639,168 @@ mod tests {
             )
         );
     }
+    #[test]
+    fn test_white_space_is_kept() {
+        let input =
+            "\n<div>\n  <p>a <b>b</b> c</p>\n  <pre>\n\n x</pre><textarea>\nt</textarea>\n</div>\n";
+        let result = Parser::parse(input.to_string());
+        // Only the white space around the top-level node, and the newline straight after a
+        // `<pre>` or `<textarea>` start tag, are left out.
+        assert_eq!(
+            result.to_string(),
+            "<div>\n  <p>a <b>b</b> c</p>\n  <pre>\n x</pre><textarea>t</textarea>\n</div>"
+        );
+        let mut parser = Parser::new();
+        parser.feed("<pre>");
+        parser.feed("\n");
+        parser.feed("\nx</pre>");
+        assert_eq!(parser.finish().to_string(), "<pre>\nx</pre>");
+        for (input, expected) in [
+            ("<pre>\r\n\r\nx</pre>", "<pre>\r\nx</pre>"),
+            ("<pre>\r\rx</pre>", "<pre>\rx</pre>"),
+            ("<textarea>\r\nt</textarea>", "<textarea>t</textarea>"),
+        ] {
+            assert_eq!(Parser::parse(input.to_string()).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let nodes = super::parse_fragment(" <li>One</li> two</ul>");
+        let nodes: Vec<_> = nodes.iter().map(ToString::to_string).collect();
+        assert_eq!(nodes, [" ", "<li>One</li>", " two"]);
+        assert!(super::parse_fragment("").is_empty());
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let input = "<!DOCTYPE html><div id=main hidden class='a \"b\"' id=\"x\">\
+                     <p>1 < 2<!-- note --> and 3</span> > 2<br></div>"
+            .to_string();
+        let root = Parser::parse(input);
+        assert_eq!(
+            root.to_string(),
+            "<div class='a \"b\"' hidden=\"\" id=\"main\"><p>1 < 2 and 3 > 2<br></p></div>"
+        );
+        // Nothing is too malformed to parse.
+        for input in [
+            "",
+            "<",
+            "</",
+            "<a",
+            "<a b='",
+            "</a>",
+            "<!--",
+            "<a></b></a>",
+            "<<a>>",
+        ] {
+            Parser::parse(input.to_string());
+        }
+        let deep = "<div>".repeat(100_000);
+        Parser::parse(deep);
+    }
+
+    #[test]
+    fn test_parse_in_chunks() {
+        let input = "<div id=\"a\"><!-- ü --><p>Héllo <em>wörld</em></p>1 < 2<br/></div>";
+        let expected = Parser::parse(input.to_string());
+        // However the input is split, even within a tag or character, the result is the same.
+        for size in 1..8 {
+            let mut parser = Parser::new();
+            for chunk in input.as_bytes().chunks(size) {
+                parser.feed_bytes(chunk);
+            }
+            assert_eq!(parser.finish(), expected, "chunks of {} bytes", size);
+        }
+
+        // The tree parsed so far can be taken at any point.
+        let mut parser = Parser::new();
+        parser.feed("<div><p>Hello");
+        assert_eq!(parser.document().to_string(), "<div><p>Hello</p></div>");
+        parser.feed(", world</p><im");
+        assert_eq!(
+            parser.document().to_string(),
+            "<div><p>Hello, world</p></div>"
+        );
+        parser.feed("g src=\"a.png\"><");
+        assert_eq!(
+            parser.document().to_string(),
+            "<div><p>Hello, world</p><img src=\"a.png\"></div>"
+        );
+        parser.feed(" 3");
+        assert_eq!(
+            parser.finish().to_string(),
+            "<div><p>Hello, world</p><img src=\"a.png\">< 3</div>"
+        );
+
+        // Invalid UTF-8, and a character cut off at the end, are replaced.
+        let mut parser = Parser::new();
+        parser.feed_bytes(b"<p>a\xffb\xc3");
+        assert_eq!(
+            parser.finish(),
+            elem(
+                "p".to_string(),
+                HashMap::new(),
+                vec![text("a\u{fffd}b\u{fffd}".to_string())]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let input = format!("<ul>{}</ul>", "<li>item</li>".repeat(2000));
+        let root = super::parse_reader(input.as_bytes()).unwrap();
+        assert_eq!(root.children.len(), 2000);
+        assert_eq!(root, Parser::parse(input));
+    }
+
+    #[test]
+    fn test_names() {
+        let root =
+            Parser::parse("<my-element data-id=\"7\" xml:lang=en _x>ü</my-element>".to_string());
+        let mut attrs = HashMap::new();
+        attrs.insert("data-id".to_string(), "7".to_string());
+        attrs.insert("xml:lang".to_string(), "en".to_string());
+        attrs.insert("_x".to_string(), String::new());
+        assert_eq!(
+            root,
+            elem("my-element".to_string(), attrs, vec![text("ü".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_encodings() {
+        let parse = |bytes: &[u8]| {
+            let mut parser = Parser::new();
+            parser.feed_bytes(bytes);
+            let encoding = parser.encoding();
+            let root = parser.finish();
+            (root.children.last().unwrap().to_string(), encoding)
+        };
+        let latin1 = b"<html><meta charset=\"ISO-8859-1\"><p>caf\xe9</p></html>";
+        assert_eq!(parse(latin1), ("<p>café</p>".to_string(), None));
+        let declared = b"<html><meta http-equiv=Content-Type \
+                         content='text/html; charset=windows-1252'><p>\x93hi\x94</p></html>";
+        assert_eq!(parse(declared).0, "<p>\u{201c}hi\u{201d}</p>");
+        // Without a declaration, the document is taken to be UTF-8.
+        assert_eq!(
+            parse("<html><p>café</p></html>".as_bytes()).0,
+            "<p>café</p>"
+        );
+
+        // A byte order mark overrides any declaration.
+        let mut utf16 = vec![0xFF, 0xFE];
+        let source = "<html><meta charset=latin1><p>日本</p></html>";
+        utf16.extend(source.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(parse(&utf16).0, "<p>日本</p>");
+
+        // Once enough of the document has arrived, its encoding is known.
+        let mut parser = Parser::new();
+        parser.feed_bytes(latin1);
+        parser.feed_bytes(&[b' '; PRESCAN_LENGTH]);
+        assert_eq!(parser.encoding(), Some(Encoding::Windows1252));
+        let mut parser = Parser::with_encoding(Encoding::Windows1252);
+        parser.feed_bytes(b"<p>\xe9");
+        assert_eq!(parser.document().to_string(), "<p>é</p>");
+    }
 }
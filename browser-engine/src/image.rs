@@ -0,0 +1,298 @@
+//! Decoding of raster images, used for backgrounds and `<img>` elements.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::css::Color;
+
+mod bmp;
+mod inflate;
+mod png;
+mod ppm;
+
+/// The largest number of pixels an image may have, so that a corrupt header can't make us
+/// allocate unbounded memory.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// A decoded image, stored in rows from the top left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> &Color {
+        &self.pixels[y * self.width + x]
+    }
+
+    /// The pixel at a point given as fractions of the image's width and height, clamped to
+    /// the image.
+    pub fn sample(&self, u: f32, v: f32) -> &Color {
+        let x = (u * self.width as f32) as usize;
+        let y = (v * self.height as f32) as usize;
+        self.pixel(
+            x.min(self.width.saturating_sub(1)),
+            y.min(self.height.saturating_sub(1)),
+        )
+    }
+}
+
+/// Load an image from a file.
+pub fn load(path: &Path) -> io::Result<Image> {
+    decode(&fs::read(path)?)
+}
+
+/// Decode an image, detecting its format from its first bytes.
+pub fn decode(data: &[u8]) -> io::Result<Image> {
+    if data.starts_with(png::SIGNATURE) {
+        png::decode(data)
+    } else if data.starts_with(b"P3") || data.starts_with(b"P6") {
+        ppm::decode(data)
+    } else if data.starts_with(bmp::SIGNATURE) {
+        bmp::decode(data)
+    } else {
+        Err(invalid_data("unrecognized image format"))
+    }
+}
+
+/// Encode an image as a PNG.
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    png::encode(image)
+}
+
+/// Images referred to by URLs relative to a document. Each image is loaded once; images that
+/// fail to load are remembered as missing.
+pub struct ImageCache {
+    base: PathBuf,
+    images: HashMap<String, Option<Rc<Image>>>,
+}
+
+impl ImageCache {
+    /// Create a cache that resolves relative URLs against `base`, the document's directory.
+    pub fn new(base: &Path) -> ImageCache {
+        ImageCache {
+            base: base.to_path_buf(),
+            images: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, url: &str) -> Option<Rc<Image>> {
+        if let Some(image) = self.images.get(url) {
+            return image.clone();
+        }
+        let image = load(&self.resolve(url)).ok().map(Rc::new);
+        self.images.insert(url.to_string(), image.clone());
+        image
+    }
+
+    /// The path of the local file a URL refers to.
+    fn resolve(&self, url: &str) -> PathBuf {
+        let path = Path::new(url.strip_prefix("file://").unwrap_or(url));
+        self.base.join(path)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn check_size(width: usize, height: usize) -> io::Result<()> {
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(()),
+        _ => Err(invalid_data("image is too large")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    #[test]
+    fn test_decode_ppm() {
+        let plain = decode(b"P3\n# a comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        assert_eq!((plain.width, plain.height), (2, 1));
+        assert_eq!(plain.pixels, vec![rgb(255, 0, 0), rgb(0, 0, 255)]);
+
+        let raw = decode(b"P6 1 2 15\n\x0f\x00\x00\x00\x0f\x00").unwrap();
+        assert_eq!(raw.pixels, vec![rgb(255, 0, 0), rgb(0, 255, 0)]);
+
+        assert!(decode(b"P6 2 2 255\n\x00").is_err());
+    }
+
+    /// Build a PNG from its IHDR fields and uncompressed image data. The CRCs are left as zero,
+    /// since they aren't checked.
+    fn png(
+        width: u32,
+        height: u32,
+        depth: u8,
+        color_type: u8,
+        extra: &[(&[u8; 4], &[u8])],
+        rows: &[u8],
+    ) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+        // A zlib stream with a single stored block.
+        let mut zlib = vec![0x78, 0x01, 0x01];
+        zlib.extend_from_slice(&(rows.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(rows.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(rows);
+
+        let mut data = png::SIGNATURE.to_vec();
+        let mut chunks: Vec<(&[u8; 4], &[u8])> = vec![(b"IHDR", &header)];
+        chunks.extend_from_slice(extra);
+        chunks.push((b"IDAT", &zlib));
+        chunks.push((b"IEND", &[]));
+        for (kind, body) in chunks {
+            data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            data.extend_from_slice(kind);
+            data.extend_from_slice(body);
+            data.extend_from_slice(&[0; 4]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_png_filters() {
+        // Two RGBA rows: the first with the Sub filter, the second with the Up filter.
+        let rows = [
+            1, 10, 20, 30, 255, 5, 5, 5, 0, //
+            2, 1, 1, 1, 0, 1, 1, 1, 0,
+        ];
+        let image = decode(&png(2, 2, 8, 6, &[], &rows)).unwrap();
+        assert_eq!(image.pixels[0], rgb(10, 20, 30));
+        assert_eq!(image.pixels[1], rgb(15, 25, 35));
+        assert_eq!(image.pixels[3], rgb(16, 26, 36));
+    }
+
+    #[test]
+    fn test_decode_png_palette_and_transparency() {
+        // A 4x1 image with two bits per palette index: 0, 1, 1, 0.
+        let image = decode(&png(
+            4,
+            1,
+            2,
+            3,
+            &[(b"PLTE", &[255, 0, 0, 0, 0, 255]), (b"tRNS", &[128])],
+            &[0, 0b0001_0100],
+        ))
+        .unwrap();
+        assert_eq!(
+            image.pixels[0],
+            Color {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 128
+            }
+        );
+        assert_eq!(image.pixels[1], rgb(0, 0, 255));
+        assert_eq!(image.pixels[3].a, 128);
+
+        // One-bit grayscale.
+        let gray = decode(&png(2, 1, 1, 0, &[], &[0, 0b0100_0000])).unwrap();
+        assert_eq!(gray.pixels, vec![rgb(0, 0, 0), rgb(255, 255, 255)]);
+    }
+
+    /// Build a BMP with a 40-byte header, followed by `extra` (masks or a palette) and the
+    /// pixel rows.
+    fn bmp(
+        width: i32,
+        height: i32,
+        bit_count: u16,
+        compression: u32,
+        extra: &[u8],
+        rows: &[u8],
+    ) -> Vec<u8> {
+        let offset = 54 + extra.len() as u32;
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&(offset + rows.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&bit_count.to_le_bytes());
+        data.extend_from_slice(&compression.to_le_bytes());
+        data.extend_from_slice(&[0; 20]);
+        data.extend_from_slice(extra);
+        data.extend_from_slice(rows);
+        data
+    }
+
+    #[test]
+    fn test_decode_bmp() {
+        // A 24-bit image with two rows stored bottom-up, each padded to 8 bytes.
+        let image = decode(&bmp(
+            2,
+            2,
+            24,
+            0,
+            &[],
+            &[0, 0, 255, 0, 255, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0],
+        ))
+        .unwrap();
+        assert_eq!(
+            image.pixels,
+            vec![rgb(0, 0, 255), rgb(0, 0, 0), rgb(255, 0, 0), rgb(0, 255, 0)]
+        );
+
+        // A top-down image with a 1-bit palette.
+        let palette = [0, 0, 0, 0, 255, 255, 255, 0];
+        let image = decode(&bmp(3, -1, 1, 0, &palette, &[0b0100_0000, 0, 0, 0])).unwrap();
+        assert_eq!(
+            image.pixels,
+            vec![rgb(0, 0, 0), rgb(255, 255, 255), rgb(0, 0, 0)]
+        );
+
+        // 16-bit pixels with 5-6-5 bitfields.
+        let masks = [0x00, 0xf8, 0, 0, 0xe0, 0x07, 0, 0, 0x1f, 0, 0, 0];
+        let image = decode(&bmp(1, 1, 16, 3, &masks, &[0x1f, 0xf8, 0, 0])).unwrap();
+        assert_eq!(image.pixels, vec![rgb(255, 0, 255)]);
+
+        assert!(decode(&bmp(2, 2, 24, 0, &[], &[0; 8])).is_err());
+        assert!(decode(&bmp(1, 1, 8, 1, &[], &[0; 4])).is_err());
+    }
+
+    #[test]
+    fn test_encode_png_round_trip() {
+        let image = Image {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                rgb(255, 0, 0),
+                rgb(0, 255, 0),
+                Color {
+                    r: 0,
+                    g: 0,
+                    b: 255,
+                    a: 128,
+                },
+                rgb(1, 2, 3),
+            ],
+        };
+        let data = encode_png(&image);
+        assert_eq!(decode(&data).unwrap(), image);
+        // The CRC of the IEND chunk, which has no data.
+        assert_eq!(data[data.len() - 4..], [0xae, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn test_decode_png_errors() {
+        assert!(decode(&png(2, 2, 8, 2, &[], &[0, 1, 2])).is_err());
+        assert!(decode(&png(1, 1, 3, 2, &[], &[0, 0, 0, 0])).is_err());
+        assert!(decode(&png::SIGNATURE[..6]).is_err());
+        assert!(decode(b"GIF89a").is_err());
+    }
+}
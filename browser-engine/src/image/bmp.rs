@@ -0,0 +1,178 @@
+//! BMP decoding, for uncompressed and bitfield-encoded images of every bit depth.
+//!
+//! http://learn.microsoft.com/en-us/windows/win32/gdi/bitmap-storage
+
+use std::io;
+
+use super::{check_size, invalid_data, Image};
+use crate::css::Color;
+
+pub const SIGNATURE: &[u8] = b"BM";
+
+/// The `biCompression` values for uncompressed pixels, and for pixels whose channels are given
+/// by bit masks.
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+/// The bit masks of the red, green, blue and alpha channels of a 16 or 32 bit pixel. A zero
+/// alpha mask means the image is opaque.
+struct Masks {
+    red: u32,
+    green: u32,
+    blue: u32,
+    alpha: u32,
+}
+
+pub fn decode(data: &[u8]) -> io::Result<Image> {
+    let pixel_offset = read_u32(data, 10)? as usize;
+    let header_size = read_u32(data, 14)? as usize;
+
+    // The original OS/2 header has 16-bit sizes; later headers all extend the Windows one.
+    let (width, height, bit_count, compression) = match header_size {
+        12 => (
+            read_u16(data, 18)? as i32,
+            read_u16(data, 20)? as i32,
+            read_u16(data, 24)?,
+            BI_RGB,
+        ),
+        40.. => (
+            read_u32(data, 18)? as i32,
+            read_u32(data, 22)? as i32,
+            read_u16(data, 28)?,
+            read_u32(data, 30)?,
+        ),
+        _ => return Err(invalid_data("invalid BMP header")),
+    };
+    // Rows are stored from the bottom up, unless the height is negative.
+    let top_down = height < 0;
+    let (width, height) = (width.max(0) as usize, height.unsigned_abs() as usize);
+    check_size(width, height)?;
+
+    let masks = match (compression, bit_count) {
+        (BI_RGB, 1 | 4 | 8) => None,
+        (BI_RGB, 16) => Some(Masks {
+            red: 0x7c00,
+            green: 0x03e0,
+            blue: 0x001f,
+            alpha: 0,
+        }),
+        (BI_RGB, 24 | 32) => Some(Masks {
+            red: 0xff0000,
+            green: 0x00ff00,
+            blue: 0x0000ff,
+            alpha: 0,
+        }),
+        // The masks follow a 40-byte header, or are part of a larger one.
+        (BI_BITFIELDS, 16 | 32) => Some(Masks {
+            red: read_u32(data, 54)?,
+            green: read_u32(data, 58)?,
+            blue: read_u32(data, 62)?,
+            alpha: if header_size >= 56 {
+                read_u32(data, 66)?
+            } else {
+                0
+            },
+        }),
+        (BI_RGB | BI_BITFIELDS, _) => return Err(invalid_data("invalid BMP bit depth")),
+        _ => return Err(invalid_data("unsupported BMP compression")),
+    };
+
+    // Images with up to 8 bits per pixel index a palette of BGR colors, which follows the
+    // header.
+    let palette = if masks.is_none() {
+        let entry_size = if header_size == 12 { 3 } else { 4 };
+        let colors_used = if header_size >= 40 {
+            read_u32(data, 46)? as usize
+        } else {
+            0
+        };
+        let count = match colors_used {
+            0 => 1 << bit_count,
+            count => count.min(1 << bit_count),
+        };
+        let start = 14 + header_size;
+        let entries = data
+            .get(start..start + count * entry_size)
+            .ok_or_else(|| invalid_data("truncated BMP palette"))?;
+        entries
+            .chunks_exact(entry_size)
+            .map(|bgr| Color {
+                r: bgr[2],
+                g: bgr[1],
+                b: bgr[0],
+                a: 255,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Each row is padded to a multiple of four bytes.
+    let stride = (width * bit_count as usize).div_ceil(32) * 4;
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row_index = if top_down { y } else { height - 1 - y };
+        let start = pixel_offset + row_index * stride;
+        let row = data
+            .get(start..start + stride)
+            .ok_or_else(|| invalid_data("truncated BMP image data"))?;
+        for x in 0..width {
+            let color = match masks {
+                None => {
+                    let bits = bit_count as usize;
+                    let bit = x * bits;
+                    let index = (row[bit / 8] >> (8 - bits - bit % 8)) as usize & ((1 << bits) - 1);
+                    palette
+                        .get(index)
+                        .cloned()
+                        .ok_or_else(|| invalid_data("BMP palette index out of range"))?
+                }
+                Some(ref masks) => {
+                    let bytes = bit_count as usize / 8;
+                    let value = row[x * bytes..(x + 1) * bytes]
+                        .iter()
+                        .rev()
+                        .fold(0, |value, &byte| value << 8 | byte as u32);
+                    Color {
+                        r: channel(value, masks.red),
+                        g: channel(value, masks.green),
+                        b: channel(value, masks.blue),
+                        a: if masks.alpha == 0 {
+                            255
+                        } else {
+                            channel(value, masks.alpha)
+                        },
+                    }
+                }
+            };
+            pixels.push(color);
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// The value of the channel selected by `mask`, scaled to 8 bits.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    (((value & mask) >> shift) as u64 * 255 / max) as u8
+}
+
+fn read_u16(data: &[u8], pos: usize) -> io::Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| invalid_data("truncated BMP"))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> io::Result<u32> {
+    data.get(pos..pos + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid_data("truncated BMP"))
+}
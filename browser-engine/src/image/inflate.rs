@@ -0,0 +1,334 @@
+//! Decompression of zlib streams, as used by PNG, and the uncompressed streams written when
+//! encoding PNGs.
+//!
+//! http://www.ietf.org/rfc/rfc1950.txt (zlib) and http://www.ietf.org/rfc/rfc1951.txt (DEFLATE)
+
+use std::io;
+
+use super::invalid_data;
+
+/// The base lengths of length codes 257..285, and the number of extra bits each takes.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distances of distance codes 0..29, and the number of extra bits each takes.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompress a zlib stream. The checksum is not verified.
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    if data.len() < 2
+        || data[0] & 0x0f != 8
+        || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+    {
+        return Err(invalid_data("invalid zlib header"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(invalid_data("zlib preset dictionaries are not supported"));
+    }
+    inflate(&data[2..])
+}
+
+/// Wrap data in a zlib stream of stored blocks, without compressing it.
+pub fn store(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        stream.push(blocks.peek().is_none() as u8);
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// The checksum at the end of a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+/// Decompress raw DEFLATE data.
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut input = BitReader {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut output = Vec::new();
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let len = input.bytes(2)?;
+                let nlen = input.bytes(2)?;
+                if len != !nlen & 0xffff {
+                    return Err(invalid_data("corrupt stored block length"));
+                }
+                let end = input.pos + len as usize;
+                let block = data
+                    .get(input.pos..end)
+                    .ok_or_else(|| invalid_data("truncated stored block"))?;
+                output.extend_from_slice(block);
+                input.pos = end;
+            }
+            1 => {
+                let mut lengths = [0; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut input, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut input)?;
+                inflate_block(&mut input, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(invalid_data("invalid DEFLATE block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+/// Decode the symbols of one compressed block up to its end-of-block code.
+fn inflate_block(
+    input: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(input)?;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(invalid_data("invalid length code"));
+                }
+                let length =
+                    LENGTH_BASE[index] as usize + input.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(input)?;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(invalid_data("invalid distance code"));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + input.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(invalid_data("distance too far back"));
+                }
+                // The copy may overlap the bytes it produces, so it goes one byte at a time.
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Read the code lengths of a dynamic block, and build its literal/length and distance codes.
+fn read_dynamic_codes(input: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let code_length_count = input.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_length_lengths[index] = input.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_lengths.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid_data("repeated code length with no previous length"))?;
+                (previous, 3 + input.bits(2)?)
+            }
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid_data("too many code lengths"));
+    }
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((
+        Huffman::new(literal_lengths),
+        Huffman::new(distance_lengths),
+    ))
+}
+
+/// Reads bits least significant first, as DEFLATE packs them.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid_data("unexpected end of compressed data"))?;
+            value |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skip to the start of the next byte.
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+
+    /// Read a little-endian integer from whole bytes.
+    fn bytes(&mut self, count: u32) -> io::Result<u32> {
+        self.bits(count * 8)
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    /// The number of codes of each length.
+    counts: [u16; 16],
+    /// The symbols, ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length > 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, input: &mut BitReader) -> io::Result<usize> {
+        // Codes of each length are consecutive, so the code is found by counting how many
+        // codes of each length come before it.
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in &self.counts[1..] {
+            code |= input.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid Huffman code"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_block() {
+        let data = [0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(decompress(&data).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_fixed_and_dynamic_blocks() {
+        // zlib.compress(b"abcabcabcabc")
+        let fixed = [
+            0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00, 0x1d, 0xe0, 0x04, 0x99,
+        ];
+        assert_eq!(decompress(&fixed).unwrap(), b"abcabcabcabc");
+
+        // 300 letters from a linear congruential generator, compressed with zlib at level 9.
+        let mut seed: u64 = 12345;
+        let text: Vec<u8> = (0..300)
+            .map(|_| {
+                seed = (seed * 1103515245 + 12345) % (1 << 31);
+                b"eeeeeeeetttaaoinshrdlu"[(seed >> 16) as usize % 22]
+            })
+            .collect();
+        let dynamic = [
+            0x78, 0xda, 0x1d, 0x8f, 0x4b, 0x0a, 0x00, 0x31, 0x08, 0x43, 0xcf, 0x2a, 0x18, 0xb0,
+            0x50, 0x14, 0x34, 0xbd, 0xff, 0xc4, 0xe9, 0xa2, 0x4d, 0xfd, 0xbd, 0xd8, 0x00, 0x88,
+            0x3d, 0x07, 0x66, 0xd2, 0x8c, 0x03, 0xfa, 0xfd, 0x83, 0x2c, 0xf6, 0x6b, 0xbe, 0x27,
+            0xad, 0x4b, 0xd1, 0x26, 0x91, 0x80, 0xa9, 0x4e, 0x69, 0xa9, 0x07, 0x36, 0x78, 0x12,
+            0xa6, 0x07, 0xd9, 0x28, 0x1c, 0x06, 0xe9, 0x2a, 0xad, 0x9d, 0x4a, 0x85, 0x8d, 0x87,
+            0xfe, 0xa4, 0x06, 0x28, 0x56, 0x2c, 0xec, 0x52, 0x34, 0x9b, 0x5c, 0xf4, 0xd8, 0x15,
+            0x82, 0x46, 0x5e, 0x8a, 0x52, 0x1a, 0x3f, 0x34, 0xdf, 0x7e, 0xee, 0x0f, 0x8e, 0xfc,
+            0xb9, 0x9e, 0x10, 0xea, 0x5a, 0x52, 0xdd, 0xaa, 0xb2, 0xe9, 0x87, 0xb6, 0x4b, 0x99,
+            0x70, 0x1c, 0x1b, 0xd4, 0xeb, 0x9d, 0x04, 0xe4, 0x15, 0x56, 0x84, 0x00, 0x03, 0xad,
+            0x66, 0x25, 0x73, 0xb7, 0x8a, 0x8d, 0x61, 0x82, 0x9b, 0x1e, 0x86, 0xe9, 0x92, 0xb4,
+            0xf5, 0xe9, 0xf1, 0xbb, 0xf2, 0x11, 0x77, 0x57, 0x9c, 0x35, 0xac, 0xb5, 0xb1, 0x2b,
+            0xe3, 0x03, 0x7d, 0x38, 0x7c, 0x7c,
+        ];
+        assert_eq!(dynamic[2] >> 1 & 3, 2, "expected a dynamic block");
+        assert_eq!(decompress(&dynamic).unwrap(), text);
+    }
+
+    #[test]
+    fn test_truncated_input_is_an_error() {
+        assert!(decompress(&[0x78, 0x9c, 0x4b, 0x4c]).is_err());
+        assert!(decompress(&[0x78]).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! PNG decoding and encoding.
+//!
+//! http://www.w3.org/TR/PNG/
+
+use std::io;
+
+use super::{check_size, inflate, invalid_data, Image};
+use crate::css::Color;
+
+pub const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const TRANSPARENT: Color = Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 0,
+};
+
+/// The header of a PNG image, from its IHDR chunk.
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// The number of bytes in one row of `width` pixels, without the filter type byte.
+    fn row_bytes(&self, width: usize) -> usize {
+        (width * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// The distance in bytes between corresponding bytes of adjacent pixels, for filtering.
+    fn filter_distance(&self) -> usize {
+        (self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+}
+
+/// The origin and spacing of the pixels in each of the seven passes of an Adam7-interlaced
+/// image: (x, y, dx, dy).
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+pub fn decode(data: &[u8]) -> io::Result<Image> {
+    let mut header = None;
+    let mut palette: Vec<Color> = Vec::new();
+    let mut transparency: Vec<u8> = Vec::new();
+    let mut compressed = Vec::new();
+
+    let mut pos = SIGNATURE.len();
+    loop {
+        let length = read_u32(data, pos)? as usize;
+        let kind = data
+            .get(pos + 4..pos + 8)
+            .ok_or_else(|| invalid_data("truncated PNG chunk"))?;
+        let body = data
+            .get(pos + 8..pos + 8 + length)
+            .ok_or_else(|| invalid_data("truncated PNG chunk"))?;
+        // Skip the chunk's type, data and CRC.
+        pos += 12 + length;
+        match kind {
+            b"IHDR" => header = Some(read_header(body)?),
+            b"PLTE" => {
+                palette = body
+                    .chunks_exact(3)
+                    .map(|rgb| Color {
+                        r: rgb[0],
+                        g: rgb[1],
+                        b: rgb[2],
+                        a: 255,
+                    })
+                    .collect()
+            }
+            b"tRNS" => transparency = body.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| invalid_data("PNG has no IHDR chunk"))?;
+    for (index, alpha) in transparency.iter().enumerate() {
+        if let Some(color) = palette.get_mut(index) {
+            color.a = *alpha;
+        }
+    }
+    let data = inflate::decompress(&compressed)?;
+    let mut image = Image {
+        width: header.width,
+        height: header.height,
+        pixels: vec![TRANSPARENT; header.width * header.height],
+    };
+
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced {
+        &ADAM7
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+    let mut offset = 0;
+    for &(x0, y0, dx, dy) in passes {
+        let width = (header.width + dx - 1 - x0) / dx;
+        let height = (header.height + dy - 1 - y0) / dy;
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let row_bytes = header.row_bytes(width);
+        let size = (row_bytes + 1) * height;
+        let pass = data
+            .get(offset..offset + size)
+            .ok_or_else(|| invalid_data("truncated PNG image data"))?;
+        offset += size;
+        let rows = unfilter(pass, row_bytes, header.filter_distance())?;
+        for (y, row) in rows.chunks_exact(row_bytes).enumerate() {
+            for x in 0..width {
+                let color = pixel(&header, row, x, &palette, &transparency)?;
+                image.pixels[(y0 + y * dy) * header.width + x0 + x * dx] = color;
+            }
+        }
+    }
+    Ok(image)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> io::Result<u32> {
+    data.get(pos..pos + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid_data("truncated PNG"))
+}
+
+fn read_header(body: &[u8]) -> io::Result<Header> {
+    if body.len() < 13 {
+        return Err(invalid_data("truncated IHDR chunk"));
+    }
+    let header = Header {
+        width: read_u32(body, 0)? as usize,
+        height: read_u32(body, 4)? as usize,
+        bit_depth: body[8],
+        color_type: body[9],
+        interlaced: body[12] == 1,
+    };
+    let valid_depths: &[u8] = match header.color_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        2 | 4 | 6 => &[8, 16],
+        _ => return Err(invalid_data("invalid PNG color type")),
+    };
+    if !valid_depths.contains(&header.bit_depth) {
+        return Err(invalid_data("invalid PNG bit depth"));
+    }
+    check_size(header.width, header.height)?;
+    Ok(header)
+}
+
+/// Reverse the filter applied to each row, returning the rows without their filter type bytes.
+///
+/// http://www.w3.org/TR/PNG/#9Filters
+fn unfilter(data: &[u8], row_bytes: usize, distance: usize) -> io::Result<Vec<u8>> {
+    let mut rows = vec![0; data.len() / (row_bytes + 1) * row_bytes];
+    for (y, filtered) in data.chunks_exact(row_bytes + 1).enumerate() {
+        let start = y * row_bytes;
+        for i in 0..row_bytes {
+            let a = if i >= distance {
+                rows[start + i - distance]
+            } else {
+                0
+            };
+            let b = if y > 0 {
+                rows[start + i - row_bytes]
+            } else {
+                0
+            };
+            let c = if y > 0 && i >= distance {
+                rows[start + i - row_bytes - distance]
+            } else {
+                0
+            };
+            let predictor = match filtered[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(invalid_data("invalid PNG filter type")),
+            };
+            rows[start + i] = filtered[i + 1].wrapping_add(predictor);
+        }
+    }
+    Ok(rows)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The color of the pixel at `x` in an unfiltered row.
+fn pixel(
+    header: &Header,
+    row: &[u8],
+    x: usize,
+    palette: &[Color],
+    transparency: &[u8],
+) -> io::Result<Color> {
+    let depth = header.bit_depth as usize;
+    let channels = header.channels();
+    // The value of one channel, and the same value scaled to 8 bits.
+    let sample = |channel: usize| -> (u16, u8) {
+        let index = x * channels + channel;
+        match depth {
+            16 => {
+                let value = u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]);
+                (value, (value >> 8) as u8)
+            }
+            8 => (row[index] as u16, row[index]),
+            _ => {
+                let bit = index * depth;
+                let max = (1 << depth) - 1;
+                let value = (row[bit / 8] >> (8 - depth - bit % 8)) as u16 & max;
+                (value, (value * 255 / max) as u8)
+            }
+        }
+    };
+    // A single color may be marked as transparent by the tRNS chunk.
+    let transparent = |values: &[u16]| {
+        transparency.len() == values.len() * 2
+            && values.iter().enumerate().all(|(i, &value)| {
+                value == u16::from_be_bytes([transparency[i * 2], transparency[i * 2 + 1]])
+            })
+    };
+    Ok(match header.color_type {
+        0 => {
+            let (value, gray) = sample(0);
+            Color {
+                r: gray,
+                g: gray,
+                b: gray,
+                a: if transparent(&[value]) { 0 } else { 255 },
+            }
+        }
+        2 => {
+            let (r, g, b) = (sample(0), sample(1), sample(2));
+            Color {
+                r: r.1,
+                g: g.1,
+                b: b.1,
+                a: if transparent(&[r.0, g.0, b.0]) {
+                    0
+                } else {
+                    255
+                },
+            }
+        }
+        3 => palette
+            .get(sample(0).0 as usize)
+            .cloned()
+            .ok_or_else(|| invalid_data("PNG palette index out of range"))?,
+        4 => {
+            let (gray, alpha) = (sample(0).1, sample(1).1);
+            Color {
+                r: gray,
+                g: gray,
+                b: gray,
+                a: alpha,
+            }
+        }
+        _ => Color {
+            r: sample(0).1,
+            g: sample(1).1,
+            b: sample(2).1,
+            a: sample(3).1,
+        },
+    })
+}
+
+/// Encode an image as an 8-bit RGBA PNG. The image data is stored without compression.
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    // Each row starts with filter type 0, for no filtering.
+    let mut rows = Vec::with_capacity((image.width * 4 + 1) * image.height);
+    for row in image.pixels.chunks(image.width.max(1)) {
+        rows.push(0);
+        for color in row {
+            rows.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    let mut data = SIGNATURE.to_vec();
+    write_chunk(&mut data, b"IHDR", &header);
+    write_chunk(&mut data, b"IDAT", &inflate::store(&rows));
+    write_chunk(&mut data, b"IEND", &[]);
+    data
+}
+
+fn write_chunk(data: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    let start = data.len();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    let crc = crc32(&data[start..]);
+    data.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 of a chunk's type and data.
+///
+/// http://www.w3.org/TR/PNG/#D-CRCAppendix
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,89 @@
+//! PPM decoding, in both the plain (`P3`) and raw (`P6`) formats.
+//!
+//! http://netpbm.sourceforge.net/doc/ppm.html
+
+use std::io;
+
+use super::{check_size, invalid_data, Image};
+use crate::css::Color;
+
+pub fn decode(data: &[u8]) -> io::Result<Image> {
+    let mut parser = Parser { data, pos: 2 };
+    let plain = data.starts_with(b"P3");
+    let width = parser.parse_number()? as usize;
+    let height = parser.parse_number()? as usize;
+    let max = parser.parse_number()?;
+    if max == 0 || max > 65535 {
+        return Err(invalid_data("invalid PPM maximum value"));
+    }
+    check_size(width, height)?;
+
+    // A single whitespace character separates the header from raw samples.
+    parser.pos += 1;
+    let mut samples = Vec::with_capacity(width * height * 3);
+    for _ in 0..width * height * 3 {
+        let value = if plain {
+            parser.parse_number()?
+        } else if max < 256 {
+            parser.parse_bytes(1)?
+        } else {
+            parser.parse_bytes(2)?
+        };
+        samples.push((value.min(max) * 255 / max) as u8);
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels: samples
+            .chunks_exact(3)
+            .map(|rgb| Color {
+                r: rgb[0],
+                g: rgb[1],
+                b: rgb[2],
+                a: 255,
+            })
+            .collect(),
+    })
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// Parse a decimal number, skipping the whitespace and comments before it.
+    fn parse_number(&mut self) -> io::Result<u32> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while !matches!(self.data.get(self.pos), Some(b'\n') | None) {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        while matches!(self.data.get(self.pos), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| invalid_data("invalid number in PPM"))
+    }
+
+    /// Parse a big-endian binary sample.
+    fn parse_bytes(&mut self, count: usize) -> io::Result<u32> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + count)
+            .ok_or_else(|| invalid_data("truncated PPM"))?;
+        self.pos += count;
+        Ok(bytes
+            .iter()
+            .fold(0, |value, &byte| value << 8 | byte as u32))
+    }
+}
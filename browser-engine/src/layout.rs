@@ -1,68 +1,1091 @@
-use crate::style::StyledNode;
+use std::rc::Rc;
 
-struct Dimensions {
+use crate::css::PseudoElement;
+use crate::css::Unit::Px;
+use crate::css::Value::{self, Keyword, Length};
+use crate::dom::{Node, NodeType};
+use crate::image::{Image, ImageCache};
+use crate::style::{BoxSizing, Clear, Display, Float, Overflow, Position, StyledNode};
+
+pub use self::BoxType::{
+    AnonymousBlock, BlockNode, FlexNode, GridNode, InlineNode, LineBox, ReplacedNode,
+    TableCellNode, TableNode, TableRowNode, TextRun,
+};
+
+pub use self::controls::{Control, SELECT_ARROW_WIDTH};
+pub use self::inline::{font_size, line_height, text_width};
+pub use self::lists::Marker;
+
+use self::floats::FloatContext;
+
+mod controls;
+mod dump;
+mod flex;
+mod floats;
+mod grid;
+mod inline;
+mod lists;
+mod pagination;
+mod positioned;
+mod replaced;
+mod table;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Dimensions {
     // position of the content area relative to the document origin:
-    content: Rect,
+    pub content: Rect,
 
     // Surrounding edges:
-    padding: EdgeSize,
-    border: EdgeSize,
-    margin: EdgeSize,
+    pub padding: EdgeSize,
+    pub border: EdgeSize,
+    pub margin: EdgeSize,
 }
 
-struct Rect {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
 }
 
-struct EdgeSize {
-    left: f32,
-    right: f32,
-    top: f32,
-    bottom: f32,
+#[derive(Clone, Copy, Default, Debug)]
+pub struct EdgeSize {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
 }
 
-struct LayoutBox<'a> {
-    dimensions: Dimensions,
-    box_type: BoxType<'a>,
-    children: Vec<LayoutBox<'a>>,
+/// The set of adjoining vertical margins that collapse into a single margin.
+///
+/// http://www.w3.org/TR/CSS2/box.html#collapsing-margins
+#[derive(Clone, Copy, Default, Debug)]
+struct CollapsibleMargin {
+    positive: f32,
+    negative: f32,
 }
 
-enum BoxType<'a> {
+impl CollapsibleMargin {
+    fn new(margin: f32) -> CollapsibleMargin {
+        CollapsibleMargin::default().adjoin(CollapsibleMargin {
+            positive: margin,
+            negative: margin,
+        })
+    }
+
+    fn adjoin(self, other: CollapsibleMargin) -> CollapsibleMargin {
+        CollapsibleMargin {
+            positive: self.positive.max(other.positive),
+            negative: self.negative.min(other.negative),
+        }
+    }
+
+    /// The width of the collapsed margin: the largest positive margin plus the most negative one.
+    fn resolve(self) -> f32 {
+        self.positive + self.negative
+    }
+}
+
+/// The sizes that percentages of a box's lengths resolve against: the content width and height
+/// of its containing block. A size is `None` when it isn't known, as when the height of the
+/// containing block depends on its content, and percentages of it then behave as `auto`.
+///
+/// http://www.w3.org/TR/CSS2/visudet.html#the-height-property
+#[derive(Clone, Copy, Default, Debug)]
+struct PercentBasis {
+    width: Option<f32>,
+    height: Option<f32>,
+}
+
+/// The alignment of a flex or grid item within its flex line or grid area, along one axis.
+#[derive(Clone, Copy, PartialEq)]
+enum Align {
+    Start,
+    End,
+    Center,
+    Stretch,
+}
+
+pub struct LayoutBox<'a> {
+    pub dimensions: Dimensions,
+    pub box_type: BoxType<'a>,
+    pub children: Vec<LayoutBox<'a>>,
+    /// Set by the box's container before the box is laid out.
+    percent_basis: PercentBasis,
+}
+
+pub enum BoxType<'a> {
     BlockNode(&'a StyledNode<'a>),
+    /// A block-level flex container, whose children are laid out as flex items.
+    FlexNode(&'a StyledNode<'a>),
+    /// A block-level grid container, whose children are placed in its grid as grid items.
+    GridNode(&'a StyledNode<'a>),
+    /// A block-level table, whose children are its rows. Rows without an element of their own
+    /// are anonymous blocks.
+    TableNode(&'a StyledNode<'a>),
+    TableRowNode(&'a StyledNode<'a>),
+    TableCellNode(&'a StyledNode<'a>),
     InlineNode(&'a StyledNode<'a>),
-    AnonymousBlock,
+    /// A block holding inline content, or a row holding table cells, which has no element of
+    /// its own. It has the style of the box it is in, whose text properties apply to its lines.
+    AnonymousBlock(&'a StyledNode<'a>),
+    /// One line of inline content, created when an anonymous block is laid out.
+    LineBox,
+    /// A run of words on a single line, styled by the text node they came from.
+    TextRun(&'a StyledNode<'a>, String),
+    /// An element whose content is an image or a form control, rather than its children, or
+    /// the marker of a list item. It is inline-level or block-level depending on its `display`.
+    ReplacedNode(&'a StyledNode<'a>, Replaced),
+}
+
+/// The content of a replaced element.
+#[derive(Clone, Debug)]
+pub enum Replaced {
+    Image(Rc<Image>),
+    Control(Control),
+    Marker(Marker),
 }
 
-impl LayoutBox {
+/// Transform a style tree into a layout tree. The images of replaced elements are loaded
+/// through `images`. A root element with `display: none` gives an empty anonymous block of
+/// zero size.
+pub fn layout_tree<'a>(
+    node: &'a StyledNode<'a>,
+    mut containing_block: Dimensions,
+    images: &mut ImageCache,
+) -> LayoutBox<'a> {
+    if node.display() == Display::None {
+        return LayoutBox::new(AnonymousBlock(node));
+    }
+
+    // The initial containing block has the dimensions of the viewport.
+    let viewport = containing_block;
+
+    // The layout algorithm expects the container height to start at 0.
+    containing_block.content.height = 0.0;
+
+    // The root element's margins never collapse with its children.
+    let mut root_box = build_layout_tree(node, images);
+    root_box.percent_basis = PercentBasis {
+        width: Some(viewport.content.width),
+        height: Some(viewport.content.height),
+    };
+    let mut floats = FloatContext::default();
+    match root_box.box_type {
+        BlockNode(_) | FlexNode(_) | GridNode(_) | TableNode(_) | ReplacedNode(..) => {
+            root_box.layout_block(containing_block, &mut floats, true)
+        }
+        _ => root_box.layout(containing_block, &mut floats),
+    }
+    let positioned = root_box.is_positioned();
+    root_box.layout_viewport_descendants(viewport, positioned);
+    root_box
+}
+
+impl<'a> LayoutBox<'a> {
     fn new(box_type: BoxType) -> LayoutBox {
         LayoutBox {
             dimensions: Default::default(),
             box_type,
             children: Vec::new(),
+            percent_basis: PercentBasis::default(),
+        }
+    }
+
+    fn get_style_node(&self) -> &'a StyledNode<'a> {
+        match self.box_type {
+            BlockNode(node)
+            | FlexNode(node)
+            | GridNode(node)
+            | TableNode(node)
+            | TableRowNode(node)
+            | TableCellNode(node)
+            | InlineNode(node)
+            | TextRun(node, _)
+            | ReplacedNode(node, _) => node,
+            AnonymousBlock(_) | LineBox => panic!("Anonymous block box has no style node"),
+        }
+    }
+
+    /// The `position` of this box. Anonymous boxes are never positioned.
+    pub fn position(&self) -> Position {
+        match self.box_type {
+            BlockNode(style)
+            | FlexNode(style)
+            | GridNode(style)
+            | TableNode(style)
+            | TableRowNode(style)
+            | TableCellNode(style)
+            | InlineNode(style)
+            | ReplacedNode(style, _) => style.position(),
+            AnonymousBlock(_) | LineBox | TextRun(..) => Position::Static,
+        }
+    }
+
+    pub fn is_positioned(&self) -> bool {
+        self.position() != Position::Static
+    }
+
+    /// Whether this box is taken out of normal flow by absolute or fixed positioning.
+    fn is_absolutely_positioned(&self) -> bool {
+        matches!(self.position(), Position::Absolute | Position::Fixed)
+    }
+
+    /// The `float` of this box. Only block-level boxes float.
+    pub fn float(&self) -> Float {
+        match self.box_type {
+            BlockNode(style)
+            | FlexNode(style)
+            | GridNode(style)
+            | TableNode(style)
+            | ReplacedNode(style, _) => style.float(),
+            _ => Float::None,
+        }
+    }
+
+    fn clear(&self) -> Clear {
+        match self.box_type {
+            BlockNode(style)
+            | FlexNode(style)
+            | GridNode(style)
+            | TableNode(style)
+            | ReplacedNode(style, _) => style.clear(),
+            _ => Clear::None,
+        }
+    }
+
+    /// Whether the contents of this box are clipped to its padding box, horizontally and
+    /// vertically, by `overflow`. It only applies to block containers.
+    pub fn clips_overflow(&self) -> (bool, bool) {
+        match self.box_type {
+            BlockNode(style) | FlexNode(style) | GridNode(style) | TableCellNode(style) => (
+                style.overflow_x() != Overflow::Visible,
+                style.overflow_y() != Overflow::Visible,
+            ),
+            _ => (false, false),
+        }
+    }
+
+    /// The area covered by this box and its descendants, except those parts clipped by
+    /// `overflow`. This is the area of the document that can be scrolled into view.
+    pub fn scrollable_overflow(&self) -> Rect {
+        let d = &self.dimensions;
+        let mut area = d.border_box();
+        let (clip_x, clip_y) = self.clips_overflow();
+        let padding_box = d.padding_box();
+        for child in &self.children {
+            let mut child_area = child.scrollable_overflow();
+            if clip_x {
+                child_area = child_area.intersection(Rect {
+                    y: child_area.y,
+                    height: child_area.height,
+                    ..padding_box
+                });
+            }
+            if clip_y {
+                child_area = child_area.intersection(Rect {
+                    x: child_area.x,
+                    width: child_area.width,
+                    ..padding_box
+                });
+            }
+            if child_area.width > 0.0 && child_area.height > 0.0 {
+                area = area.union(child_area);
+            }
+        }
+        area
+    }
+
+    /// The deepest DOM node whose box contains the point (x, y): the text node of a run of text,
+    /// or else the element whose border box contains it. Positioned boxes, which are painted
+    /// above the others, are tested first, and later siblings before earlier ones. Descendants
+    /// of a box that clips its overflow are only hit inside its padding box.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<&'a Node> {
+        let padding_box = self.dimensions.padding_box();
+        let (clip_x, clip_y) = self.clips_overflow();
+        let in_clip = (!clip_x || (x >= padding_box.x && x < padding_box.x + padding_box.width))
+            && (!clip_y || (y >= padding_box.y && y < padding_box.y + padding_box.height));
+        if in_clip {
+            let (positioned, in_flow): (Vec<&LayoutBox>, Vec<&LayoutBox>) = self
+                .children
+                .iter()
+                .partition(|child| child.is_positioned());
+            let children = positioned
+                .into_iter()
+                .rev()
+                .chain(in_flow.into_iter().rev());
+            for child in children {
+                if let Some(node) = child.hit_test(x, y) {
+                    return Some(node);
+                }
+            }
+        }
+        match self.box_type {
+            AnonymousBlock(_) | LineBox => None,
+            _ if self.dimensions.border_box().contains(x, y) => Some(self.get_style_node().node),
+            _ => None,
+        }
+    }
+
+    /// Whether this box is taken out of normal flow by positioning or floating, or is a list
+    /// item's outside marker.
+    fn is_out_of_flow(&self) -> bool {
+        self.is_absolutely_positioned() || self.float() != Float::None || self.is_outside_marker()
+    }
+
+    /// Whether this box establishes a new block formatting context, which contains its floats
+    /// and keeps its margins apart from its children's.
+    ///
+    /// http://www.w3.org/TR/CSS2/visuren.html#block-formatting
+    fn establishes_formatting_context(&self) -> bool {
+        match self.box_type {
+            BlockNode(style) => self.is_out_of_flow() || style.overflow_x() != Overflow::Visible,
+            // Flex and grid containers establish flex and grid formatting contexts, which behave
+            // the same way.
+            FlexNode(_) | GridNode(_) => true,
+            // Tables and table cells establish block formatting contexts, and block-level
+            // replaced elements avoid floats in the same way.
+            TableNode(_) | TableCellNode(_) | ReplacedNode(..) => true,
+            _ => false,
+        }
+    }
+
+    fn layout(&mut self, containing_block: Dimensions, floats: &mut FloatContext) {
+        match self.box_type {
+            BlockNode(_) | FlexNode(_) | GridNode(_) | TableNode(_) | ReplacedNode(..) => {
+                let formatting_context_root = self.establishes_formatting_context();
+                self.layout_block(containing_block, floats, formatting_context_root)
+            }
+            AnonymousBlock(_) => self.layout_anonymous_block(containing_block, floats, false),
+            // Rows and cells are laid out by their table.
+            TableRowNode(_) | TableCellNode(_) | InlineNode(_) | LineBox | TextRun(..) => {}
+        }
+    }
+
+    /// Lay out a block-level box. `formatting_context_root` is set for boxes whose margins must
+    /// not collapse with those of their children, and which keep their floats to themselves.
+    fn layout_block(
+        &mut self,
+        containing_block: Dimensions,
+        floats: &mut FloatContext,
+        formatting_context_root: bool,
+    ) {
+        // Child width can depend on parent width, so we need to calculate this box's width
+        // before laying out its children.
+        self.calculate_block_width(containing_block);
+
+        // Determine where the box is located within its container.
+        self.calculate_block_position(containing_block);
+
+        // Recursively lay out the children of this box.
+        if formatting_context_root {
+            let mut floats = FloatContext::default();
+            self.layout_children(&mut floats, true);
+            self.contain_floats(&floats);
+        } else {
+            self.layout_children(floats, false);
+        }
+
+        // Parent height can depend on child height, so `calculate_height` must be called
+        // *after* the children are laid out.
+        self.calculate_block_height();
+
+        self.apply_relative_position();
+    }
+
+    /// Lay out the children of a block, flex, grid or table container within its content area,
+    /// setting its content height.
+    fn layout_children(&mut self, floats: &mut FloatContext, formatting_context_root: bool) {
+        let percent_basis = PercentBasis {
+            width: Some(self.dimensions.content.width),
+            height: self.definite_height(),
+        };
+        for child in &mut self.children {
+            child.percent_basis = percent_basis;
+        }
+        match self.box_type {
+            FlexNode(_) => self.layout_flex_children(),
+            GridNode(_) => self.layout_grid_children(),
+            TableNode(_) => self.layout_table_children(),
+            _ => self.layout_block_children(floats, formatting_context_root),
+        }
+    }
+
+    /// Lay out a box that establishes an independent formatting context with the given content
+    /// width, placing its content box at the origin. The caller moves it into place afterwards.
+    /// Its edges must already be set by `resolve_edges`.
+    fn layout_contents(&mut self, width: f32) {
+        if let AnonymousBlock(_) = self.box_type {
+            let mut containing_block = Dimensions::default();
+            containing_block.content.width = width;
+            self.layout_anonymous_block(containing_block, &mut FloatContext::default(), true);
+            return;
+        }
+        let d = &mut self.dimensions;
+        d.content.width = width;
+        d.content.x = 0.0;
+        d.content.y = 0.0;
+        let mut floats = FloatContext::default();
+        self.layout_children(&mut floats, true);
+        self.contain_floats(&floats);
+        self.calculate_block_height();
+    }
+
+    /// Set the padding, border and margin sizes from the computed style. Auto margins are
+    /// treated as zero, and percentages are of the width of the containing block. Anonymous
+    /// boxes have no edges.
+    fn resolve_edges(&mut self) {
+        let style = match self.box_type {
+            AnonymousBlock(_) | LineBox => {
+                let d = &mut self.dimensions;
+                d.padding = EdgeSize::default();
+                d.border = EdgeSize::default();
+                d.margin = EdgeSize::default();
+                return;
+            }
+            _ => self.get_style_node(),
+        };
+        let edge = |name, shorthand| self.edge(name, shorthand);
+        let padding = EdgeSize {
+            left: edge("padding-left", "padding"),
+            right: edge("padding-right", "padding"),
+            top: edge("padding-top", "padding"),
+            bottom: edge("padding-bottom", "padding"),
+        };
+        let margin = EdgeSize {
+            left: edge("margin-left", "margin"),
+            right: edge("margin-right", "margin"),
+            top: edge("margin-top", "margin"),
+            bottom: edge("margin-bottom", "margin"),
+        };
+        let d = &mut self.dimensions;
+        d.padding = padding;
+        d.border.left = style.border_width("left");
+        d.border.right = style.border_width("right");
+        d.border.top = style.border_width("top");
+        d.border.bottom = style.border_width("bottom");
+        d.margin = margin;
+    }
+
+    /// The shrink-to-fit content width of a box inside a containing block of width `available`:
+    /// its specified width, or else its preferred width limited to the available space, then
+    /// clamped by its min and max widths. Its edges must already be set by `resolve_edges`.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#shrink-to-fit-float
+    fn shrink_to_fit_width(&self, available: f32) -> f32 {
+        let edges = self.horizontal_edges();
+        let basis = Some(available);
+        let d = &self.dimensions;
+        let available = available - d.margin.left - d.margin.right - edges;
+        let mut width = self
+            .content_size_in("width", edges, basis)
+            .unwrap_or_else(|| (self.max_content_width() - edges).min(available).max(0.0));
+        if let Some(max_width) = self.content_size_in("max-width", edges, basis) {
+            width = width.min(max_width);
+        }
+        if let Some(min_width) = self.content_size_in("min-width", edges, basis) {
+            width = width.max(min_width);
+        }
+        width
+    }
+
+    /// Calculate the width of a block-level non-replaced element in normal flow.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#blockwidth
+    ///
+    /// Sets the horizontal margin/padding/border dimensions, and the `width`.
+    fn calculate_block_width(&mut self, containing_block: Dimensions) {
+        let style = self.get_style_node();
+        let zero = Length(0.0, Px);
+        let basis = Some(containing_block.content.width);
+        let horizontal = |name, shorthand| {
+            let value = style.lookup(name, shorthand, &zero);
+            value.resolve_length(basis).unwrap_or(0.0)
+        };
+
+        let d = &mut self.dimensions;
+        d.padding.left = horizontal("padding-left", "padding");
+        d.padding.right = horizontal("padding-right", "padding");
+        d.border.left = style.border_width("left");
+        d.border.right = style.border_width("right");
+        let edges = d.padding.left + d.padding.right + d.border.left + d.border.right;
+
+        // The width of a table also depends on its columns.
+        let width = match self.box_type {
+            TableNode(_) => {
+                let margins =
+                    horizontal("margin-left", "margin") + horizontal("margin-right", "margin");
+                Some(self.table_width(containing_block.content.width - margins - edges))
+            }
+            _ => self.content_size_in("width", edges, basis),
+        };
+
+        // http://www.w3.org/TR/CSS2/visudet.html#min-max-widths
+        let mut used = self.solve_block_width(containing_block, width);
+        if let Some(max_width) = self.content_size_in("max-width", edges, basis) {
+            if used.1 > max_width {
+                used = self.solve_block_width(containing_block, Some(max_width));
+            }
+        }
+        if let Some(min_width) = self.content_size_in("min-width", edges, basis) {
+            if used.1 < min_width {
+                used = self.solve_block_width(containing_block, Some(min_width));
+            }
+        }
+
+        let (margin_left, width, margin_right) = used;
+        let d = &mut self.dimensions;
+        d.margin.left = margin_left;
+        d.content.width = width;
+        d.margin.right = margin_right;
+    }
+
+    /// Solve the horizontal constraint equation for a given content `width` (`None` means
+    /// `auto`), returning the used left margin, width and right margin.
+    fn solve_block_width(
+        &self,
+        containing_block: Dimensions,
+        width: Option<f32>,
+    ) -> (f32, f32, f32) {
+        let style = self.get_style_node();
+        let auto = Keyword("auto".to_string());
+        let zero = Length(0.0, Px);
+        let d = &self.dimensions;
+
+        let basis = Some(containing_block.content.width);
+        let px = |value: &Value| value.resolve_length(basis).unwrap_or(0.0);
+
+        let mut margin_left = style.lookup("margin-left", "margin", &zero);
+        let mut margin_right = style.lookup("margin-right", "margin", &zero);
+        let margin_left_auto = margin_left == auto;
+        let margin_right_auto = margin_right == auto;
+
+        let total = px(&margin_left)
+            + px(&margin_right)
+            + d.border.left
+            + d.border.right
+            + d.padding.left
+            + d.padding.right
+            + width.unwrap_or(0.0);
+
+        // If width is not auto and the total is wider than the container, treat auto margins as 0.
+        if width.is_some() && total > containing_block.content.width {
+            if margin_left_auto {
+                margin_left = zero.clone();
+            }
+            if margin_right_auto {
+                margin_right = zero.clone();
+            }
         }
+
+        // Adjust used values so that the above sum equals `containing_block.width`.
+        // Each arm of the `match` should increase the total width by exactly `underflow`,
+        // and afterward all values should be absolute lengths in px.
+        let underflow = containing_block.content.width - total;
+        let (margin_left, width, margin_right) =
+            match (width, margin_left == auto, margin_right == auto) {
+                // If the values are overconstrained, calculate margin_right.
+                (Some(width), false, false) => {
+                    (px(&margin_left), width, px(&margin_right) + underflow)
+                }
+
+                // If exactly one size is auto, its used value follows from the equality.
+                (Some(width), false, true) => (px(&margin_left), width, underflow),
+                (Some(width), true, false) => (underflow, width, px(&margin_right)),
+
+                // If margin-left and margin-right are both auto, their used values are equal.
+                (Some(width), true, true) => (underflow / 2.0, width, underflow / 2.0),
+
+                // If width is set to auto, any other auto values become 0.
+                (None, _, _) => {
+                    let margin_left = px(&margin_left);
+                    let margin_right = px(&margin_right);
+                    if underflow >= 0.0 {
+                        // Expand width to fill the underflow.
+                        (margin_left, underflow, margin_right)
+                    } else {
+                        // Width can't be negative. Adjust the right margin instead.
+                        (margin_left, 0.0, margin_right + underflow)
+                    }
+                }
+            };
+        (margin_left, width, margin_right)
     }
 
-    fn layout(&mut self, containing_block: Dimensions) {
+    /// Return the content-box size for a length property such as `width` or `min-height`, or
+    /// `None` if it is not a length. `edges` is the sum of padding and border along the same
+    /// axis, which `box-sizing: border-box` lengths include. Percentages are of the containing
+    /// block's size along the same axis.
+    ///
+    /// The `width` and `height` of a replaced element are never `None`: they default to the
+    /// size of its image.
+    fn content_size(&self, name: &str, edges: f32) -> Option<f32> {
+        let percent_basis = if name.ends_with("width") {
+            self.percent_basis.width
+        } else {
+            self.percent_basis.height
+        };
+        self.content_size_in(name, edges, percent_basis)
+    }
+
+    /// Like `content_size`, but with percentages, including those inside `calc()`, resolved
+    /// against `percent_basis`. Intrinsic sizes are found with no basis, treating percentages
+    /// as `auto`.
+    fn content_size_in(&self, name: &str, edges: f32, percent_basis: Option<f32>) -> Option<f32> {
         match self.box_type {
-            BoxType::BlockNode(_) => self.layout_block(containing_block),
-            BoxType::InlineNode(_) => {}
-            BoxType::AnonymousBlock => {}
+            AnonymousBlock(_) | LineBox => None,
+            ReplacedNode(..) if name == "width" => Some(self.replaced_size().0),
+            ReplacedNode(..) if name == "height" => Some(self.replaced_size().1),
+            _ => self
+                .get_style_node()
+                .value(name)?
+                .resolve_length(percent_basis)
+                .map(|size| self.content_box_size(size, edges)),
+        }
+    }
+
+    /// Clamp a content-box size by this box's min and max sizes along one axis.
+    fn clamp_size(&self, size: f32, horizontal: bool) -> f32 {
+        let (min, max) = if horizontal {
+            ("min-width", "max-width")
+        } else {
+            ("min-height", "max-height")
+        };
+        let edges = padding_and_border(self.dimensions, horizontal);
+        let mut size = size;
+        if let Some(max_size) = self.content_size(max, edges) {
+            size = size.min(max_size);
+        }
+        if let Some(min_size) = self.content_size(min, edges) {
+            size = size.max(min_size);
+        }
+        size.max(0.0)
+    }
+
+    /// Convert a size given for this box's `box-sizing` into a content-box size.
+    fn content_box_size(&self, size: f32, edges: f32) -> f32 {
+        match self.get_style_node().box_sizing() {
+            BoxSizing::ContentBox => size,
+            BoxSizing::BorderBox => (size - edges).max(0.0),
         }
     }
 
-    fn get_inline_container(&mut self) -> &mut LayoutBox {
+    /// Finish calculating the block's edge sizes, and position it within its containing block.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#normal-block
+    ///
+    /// Sets the vertical margin/padding/border dimensions, and the `x`, `y` values.
+    fn calculate_block_position(&mut self, containing_block: Dimensions) {
+        let style = self.get_style_node();
+        let d = &mut self.dimensions;
+
+        // margin, border, and padding have initial value 0. Vertical percentages are of the
+        // containing block's width too.
+        let zero = Length(0.0, Px);
+        let basis = Some(containing_block.content.width);
+        let vertical = |name, shorthand| {
+            let value = style.lookup(name, shorthand, &zero);
+            value.resolve_length(basis).unwrap_or(0.0)
+        };
+
+        // If margin-top or margin-bottom is `auto`, the used value is zero.
+        d.margin.top = vertical("margin-top", "margin");
+        d.margin.bottom = vertical("margin-bottom", "margin");
+
+        d.border.top = style.border_width("top");
+        d.border.bottom = style.border_width("bottom");
+
+        d.padding.top = vertical("padding-top", "padding");
+        d.padding.bottom = vertical("padding-bottom", "padding");
+
+        d.content.x = containing_block.content.x + d.margin.left + d.border.left + d.padding.left;
+
+        // Position the box below all the previous boxes in the container.
+        d.content.y = containing_block.content.height
+            + containing_block.content.y
+            + d.margin.top
+            + d.border.top
+            + d.padding.top;
+    }
+
+    /// Lay out the block's children within its content area, collapsing adjoining vertical
+    /// margins.
+    ///
+    /// Sets `self.dimensions.height` to the total content height.
+    fn layout_block_children(&mut self, floats: &mut FloatContext, formatting_context_root: bool) {
+        let collapse_top = !formatting_context_root && self.top_margin_collapses_with_children();
+        let collapse_bottom =
+            !formatting_context_root && self.bottom_margin_collapses_with_children();
+
+        // Margins adjoining the top of our content were already added to our own top margin
+        // when our parent positioned us, so they don't move the children again.
+        let mut absorbed = collapse_top;
+        let mut pending = CollapsibleMargin::default();
+        let mut cursor = 0.0;
+
+        // The first line of a block is indented if it is in an anonymous block of its own.
+        let first_in_flow = self
+            .children
+            .iter()
+            .position(|child| !child.is_out_of_flow());
+
+        let d = &mut self.dimensions;
+        for (index, child) in self.children.iter_mut().enumerate() {
+            if child.is_outside_marker() {
+                continue;
+            }
+            if child.is_absolutely_positioned() {
+                // Record the static position; the box is laid out once its containing block is
+                // known.
+                let offset = if absorbed { 0.0 } else { pending.resolve() };
+                child.dimensions.content.x = d.content.x;
+                child.dimensions.content.y = d.content.y + cursor + offset;
+                continue;
+            }
+            if child.float() != Float::None {
+                let offset = if absorbed { 0.0 } else { pending.resolve() };
+                child.layout_float(*d, d.content.y + cursor + offset, floats);
+                continue;
+            }
+
+            pending = pending.adjoin(child.collapsed_top_margin());
+            let mut offset = if absorbed { 0.0 } else { pending.resolve() };
+
+            // Clearance moves the child's border edge below the floats it clears.
+            // http://www.w3.org/TR/CSS2/visuren.html#flow-control
+            if let Some(clear_y) = floats.clearance(child.clear()) {
+                let border_top = d.content.y + cursor + offset;
+                if clear_y > border_top {
+                    offset += clear_y - border_top;
+                    absorbed = false;
+                }
+            }
+
+            // Position the child so that its top border edge lands below the collapsed margin.
+            let mut containing_block = *d;
+            containing_block.content.height = cursor + offset - child.margin_top();
+
+            // The border box of a formatting context root must not overlap any floats, so it is
+            // moved down or narrowed to fit beside them.
+            if child.establishes_formatting_context() {
+                let left = containing_block.content.x;
+                let right = left + containing_block.content.width;
+                let (y, left, right) = floats.find_space(
+                    d.content.y + cursor + offset,
+                    child.min_margin_box_width(),
+                    0.0,
+                    left,
+                    right,
+                );
+                offset = y - d.content.y - cursor;
+                containing_block.content.height = cursor + offset - child.margin_top();
+                containing_block.content.x = left;
+                containing_block.content.width = right - left;
+            }
+            match child.box_type {
+                AnonymousBlock(_) if Some(index) == first_in_flow => {
+                    child.layout_anonymous_block(containing_block, floats, true)
+                }
+                _ => child.layout(containing_block, floats),
+            }
+
+            if child.collapses_through() {
+                pending = pending.adjoin(child.collapsed_bottom_margin());
+            } else {
+                // Track the height so each child is laid out below the previous content.
+                cursor += offset + child.dimensions.border_box().height;
+                pending = child.collapsed_bottom_margin();
+                absorbed = false;
+            }
+        }
+
+        // The last margin either collapses through our bottom edge, or separates the last
+        // child from it.
+        if !collapse_bottom && !absorbed {
+            cursor += pending.resolve();
+        }
+        d.content.height = cursor;
+        self.layout_outside_markers();
+    }
+
+    /// The computed `margin-top` of this box in px.
+    fn margin_top(&self) -> f32 {
+        self.edge("margin-top", "margin")
+    }
+
+    /// The computed size of one edge (margin, border or padding) in px, or zero for anonymous
+    /// boxes. Percentages are of the width of the containing block.
+    fn edge(&self, name: &str, fallback_name: &str) -> f32 {
+        match self.box_type {
+            BlockNode(style)
+            | FlexNode(style)
+            | GridNode(style)
+            | TableNode(style)
+            | TableRowNode(style)
+            | TableCellNode(style)
+            | InlineNode(style)
+            | ReplacedNode(style, _) => style
+                .lookup(name, fallback_name, &Length(0.0, Px))
+                .resolve_length(self.percent_basis.width)
+                .unwrap_or(0.0),
+            AnonymousBlock(_) | LineBox | TextRun(..) => 0.0,
+        }
+    }
+
+    /// The used border width of one side, or zero for anonymous boxes.
+    fn border(&self, side: &str) -> f32 {
+        match self.box_type {
+            AnonymousBlock(_) | LineBox | TextRun(..) => 0.0,
+            _ => self.get_style_node().border_width(side),
+        }
+    }
+
+    /// Whether this box's top margin adjoins the top margin of its first in-flow child.
+    fn top_margin_collapses_with_children(&self) -> bool {
+        match self.box_type {
+            BlockNode(_) => {
+                self.border("top") == 0.0
+                    && self.edge("padding-top", "padding") == 0.0
+                    && !self.establishes_formatting_context()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this box's bottom margin adjoins the bottom margin of its last in-flow child.
+    fn bottom_margin_collapses_with_children(&self) -> bool {
+        match self.box_type {
+            BlockNode(_) => {
+                self.border("bottom") == 0.0
+                    && self.edge("padding-bottom", "padding") == 0.0
+                    && self.content_size("height", 0.0).is_none()
+                    && self
+                        .content_size("min-height", 0.0)
+                        .is_none_or(|height| height == 0.0)
+                    && !self.establishes_formatting_context()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this box is empty, so that its top and bottom margins collapse together.
+    fn collapses_through(&self) -> bool {
         match self.box_type {
-            InlineNode(_) | AnonymousBlock => self,
             BlockNode(_) => {
+                self.top_margin_collapses_with_children()
+                    && self.border("bottom") == 0.0
+                    && self.edge("padding-bottom", "padding") == 0.0
+                    && self
+                        .content_size("min-height", 0.0)
+                        .is_none_or(|height| height == 0.0)
+                    && self
+                        .content_size("height", 0.0)
+                        .is_none_or(|height| height == 0.0)
+                    && self
+                        .children
+                        .iter()
+                        .all(|child| child.is_out_of_flow() || child.collapses_through())
+            }
+            // Anonymous blocks only exist to hold inline content.
+            AnonymousBlock(_) => self.children.is_empty(),
+            FlexNode(_) | GridNode(_) | TableNode(_) | TableRowNode(_) | TableCellNode(_)
+            | InlineNode(_) | LineBox | TextRun(..) | ReplacedNode(..) => false,
+        }
+    }
+
+    /// This box's top margin, collapsed with any descendant margins that adjoin it.
+    fn collapsed_top_margin(&self) -> CollapsibleMargin {
+        let mut margin = CollapsibleMargin::new(self.margin_top());
+        if self.top_margin_collapses_with_children() {
+            for child in self.in_flow_children() {
+                margin = margin.adjoin(child.collapsed_top_margin());
+                if !child.collapses_through() {
+                    break;
+                }
+                margin = margin.adjoin(child.collapsed_bottom_margin());
+            }
+        }
+        margin
+    }
+
+    /// This box's bottom margin, collapsed with any descendant margins that adjoin it.
+    fn collapsed_bottom_margin(&self) -> CollapsibleMargin {
+        let mut margin = CollapsibleMargin::new(self.edge("margin-bottom", "margin"));
+        if self.bottom_margin_collapses_with_children() || self.collapses_through() {
+            for child in self.in_flow_children().rev() {
+                margin = margin.adjoin(child.collapsed_bottom_margin());
+                if !child.collapses_through() {
+                    break;
+                }
+                margin = margin.adjoin(child.collapsed_top_margin());
+            }
+        }
+        margin
+    }
+
+    /// Height of a block-level non-replaced element in normal flow with overflow visible.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#min-max-heights
+    fn calculate_block_height(&mut self) {
+        let d = &self.dimensions;
+        let edges = d.padding.top + d.padding.bottom + d.border.top + d.border.bottom;
+
+        // If the height is set to an explicit length, use that exact length.
+        // Otherwise, just keep the value set by `layout_block_children`.
+        let mut height = self
+            .content_size("height", edges)
+            .unwrap_or(self.dimensions.content.height);
+        if let Some(max_height) = self.content_size("max-height", edges) {
+            height = height.min(max_height);
+        }
+        if let Some(min_height) = self.content_size("min-height", edges) {
+            height = height.max(min_height);
+        }
+        self.dimensions.content.height = height;
+    }
+
+    /// The content height of this box if it is known before its contents are laid out, which
+    /// percentages of the heights of its children resolve against. Anonymous blocks pass on
+    /// the height of their own containing block.
+    fn definite_height(&self) -> Option<f32> {
+        match self.box_type {
+            AnonymousBlock(_) => self.percent_basis.height,
+            LineBox | TextRun(..) => None,
+            _ => {
+                let edges = padding_and_border(self.dimensions, false);
+                let height = self.content_size("height", edges)?;
+                Some(self.clamp_size(height, false))
+            }
+        }
+    }
+
+    /// Grow the auto height of a formatting context root to contain its floats.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#root-height
+    fn contain_floats(&mut self, floats: &FloatContext) {
+        if let Some(bottom) = floats.bottom() {
+            let d = &mut self.dimensions;
+            d.content.height = d.content.height.max(bottom - d.content.y);
+        }
+    }
+
+    /// The width this box would take if given unlimited space, including its padding and
+    /// borders but not its margins. Used for shrink-to-fit widths.
+    fn max_content_width(&self) -> f32 {
+        let edges = self.horizontal_edges();
+        match self.box_type {
+            BlockNode(_) | FlexNode(_) | GridNode(_) | TableNode(_) | TableRowNode(_)
+            | TableCellNode(_) | ReplacedNode(..) => {}
+            AnonymousBlock(_) | InlineNode(_) | LineBox | TextRun(..) => {
+                return self.inline_max_content_width()
+            }
+        }
+        if let Some(width) = self.content_size_in("width", edges, None) {
+            return width + edges;
+        }
+        match self.box_type {
+            FlexNode(_) => return self.flex_max_content_width() + edges,
+            GridNode(_) => return self.grid_content_width(false) + edges,
+            TableNode(_) => return self.table_content_width(false) + edges,
+            _ => {}
+        }
+        let content = self
+            .in_flow_children()
+            .map(|child| {
+                child.max_content_width()
+                    + child.edge("margin-left", "margin")
+                    + child.edge("margin-right", "margin")
+            })
+            .fold(0.0, f32::max);
+        content + edges
+    }
+
+    /// The narrowest width this box can take without its content overflowing, including its
+    /// padding and borders but not its margins.
+    fn min_content_width(&self) -> f32 {
+        let edges = self.horizontal_edges();
+        match self.box_type {
+            BlockNode(_) | FlexNode(_) | GridNode(_) | TableNode(_) | TableRowNode(_)
+            | TableCellNode(_) | ReplacedNode(..) => {}
+            AnonymousBlock(_) | InlineNode(_) | LineBox | TextRun(..) => {
+                return self.inline_min_content_width()
+            }
+        }
+        if let Some(width) = self.content_size_in("width", edges, None) {
+            return width + edges;
+        }
+        match self.box_type {
+            GridNode(_) => return self.grid_content_width(true) + edges,
+            TableNode(_) => return self.table_content_width(true) + edges,
+            _ => {}
+        }
+        let content = self
+            .children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned() && !child.is_outside_marker())
+            .map(|child| {
+                child.min_content_width()
+                    + child.edge("margin-left", "margin")
+                    + child.edge("margin-right", "margin")
+            })
+            .fold(0.0, f32::max);
+        content + edges
+    }
+
+    /// The narrowest margin box this box can be given: its specified width if it has one,
+    /// otherwise just its horizontal edges.
+    fn min_margin_box_width(&self) -> f32 {
+        let edges = self.horizontal_edges();
+        let margins = self.edge("margin-left", "margin") + self.edge("margin-right", "margin");
+        match self.box_type {
+            BlockNode(_) | FlexNode(_) | GridNode(_) | TableNode(_) | ReplacedNode(..) => {
+                self.content_size("width", edges).unwrap_or(0.0) + edges + margins
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The sum of the left and right padding and border widths.
+    fn horizontal_edges(&self) -> f32 {
+        self.edge("padding-left", "padding")
+            + self.edge("padding-right", "padding")
+            + self.border("left")
+            + self.border("right")
+    }
+
+    fn in_flow_children(&self) -> impl DoubleEndedIterator<Item = &LayoutBox<'a>> {
+        self.children.iter().filter(|child| !child.is_out_of_flow())
+    }
+
+    /// This box's padding box, wrapped up to serve as a containing block for its descendants.
+    fn padding_box_dimensions(&self) -> Dimensions {
+        Dimensions {
+            content: self.dimensions.padding_box(),
+            ..Default::default()
+        }
+    }
+
+    /// Move this box and all of its descendants.
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.dimensions.content.x += dx;
+        self.dimensions.content.y += dy;
+        for child in &mut self.children {
+            child.translate(dx, dy);
+        }
+    }
+
+    fn get_inline_container(&mut self) -> &mut LayoutBox<'a> {
+        match self.box_type {
+            InlineNode(_) | AnonymousBlock(_) | LineBox | TextRun(..) | ReplacedNode(..) => self,
+            BlockNode(style) | FlexNode(style) | GridNode(style) | TableNode(style)
+            | TableRowNode(style) | TableCellNode(style) => {
                 match self.children.last() {
                     Some(&LayoutBox {
-                        box_type: AnonymousBlock,
+                        box_type: AnonymousBlock(_),
                         ..
                     }) => {}
-                    _ => self.children.push(LayoutBox::new(AnonymousBlock)),
+                    _ => self.children.push(LayoutBox::new(AnonymousBlock(style))),
                 }
                 self.children.last_mut().unwrap()
             }
@@ -70,24 +1093,1547 @@ impl LayoutBox {
     }
 }
 
-fn build_layout_tree<'a>(style_node: &'a StyledNode<'a>) -> LayoutBox<'a> {
+impl Rect {
+    pub fn expanded_by(self, edge: EdgeSize) -> Rect {
+        Rect {
+            x: self.x - edge.left,
+            y: self.y - edge.top,
+            width: self.width + edge.left + edge.right,
+            height: self.height + edge.top + edge.bottom,
+        }
+    }
+
+    /// Whether the point (x, y) is inside this rectangle. Points on its right and bottom edges
+    /// are outside it.
+    pub fn contains(self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    /// The area covered by both rectangles, which is empty if they don't overlap.
+    pub fn intersection(self, other: Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Rect {
+            x,
+            y,
+            width: ((self.x + self.width).min(other.x + other.width) - x).max(0.0),
+            height: ((self.y + self.height).min(other.y + other.height) - y).max(0.0),
+        }
+    }
+
+    /// The smallest rectangle that contains both rectangles.
+    pub fn union(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+impl Dimensions {
+    /// The area covered by the content area plus its padding.
+    pub fn padding_box(self) -> Rect {
+        self.content.expanded_by(self.padding)
+    }
+
+    /// The area covered by the content area plus padding and borders.
+    pub fn border_box(self) -> Rect {
+        self.padding_box().expanded_by(self.border)
+    }
+
+    /// The area covered by the content area plus padding, borders, and margin.
+    pub fn margin_box(self) -> Rect {
+        self.border_box().expanded_by(self.margin)
+    }
+}
+
+/// The px size of a length value or `calc()` expression, or `None` for keywords such as `auto`
+/// and for percentages. A unitless zero is also a valid length.
+fn length(value: Option<Value>) -> Option<f32> {
+    value?.resolve_length(None)
+}
+
+/// The total border and padding along one axis.
+fn padding_and_border(d: Dimensions, horizontal: bool) -> f32 {
+    let border_box = d.border_box();
+    if horizontal {
+        border_box.width - d.content.width
+    } else {
+        border_box.height - d.content.height
+    }
+}
+
+/// The total margin along one axis.
+fn margins(d: Dimensions, horizontal: bool) -> f32 {
+    if horizontal {
+        d.margin.left + d.margin.right
+    } else {
+        d.margin.top + d.margin.bottom
+    }
+}
+
+/// The column and row gaps of a flex or grid container. The `gap` shorthand gives the row gap
+/// and then the column gap, which defaults to the row gap. Percentages resolve against the
+/// container's content size along each gap's axis.
+///
+/// http://www.w3.org/TR/css-align-3/#gap-shorthand
+fn gaps(style: &StyledNode, basis: PercentBasis) -> (f32, f32) {
+    let shorthand = match style.value("gap") {
+        Some(Value::List(values)) => values,
+        value => value.into_iter().collect(),
+    };
+    let gap = |name, index: usize, basis: Option<f32>| {
+        style
+            .value(name)
+            .or_else(|| shorthand.get(index).or(shorthand.first()).cloned())
+            .and_then(|value| value.resolve_length(basis))
+            .unwrap_or(0.0)
+    };
+    (
+        gap("column-gap", 1, basis.width),
+        gap("row-gap", 0, basis.height),
+    )
+}
+
+fn keyword(style: &StyledNode, name: &str) -> Option<String> {
+    match style.value(name) {
+        Some(Keyword(keyword)) => Some(keyword),
+        _ => None,
+    }
+}
+
+/// Parse an alignment keyword such as the value of `align-self`. `auto` and unknown values give
+/// `None`.
+fn align(keyword: Option<&str>) -> Option<Align> {
+    match keyword? {
+        "flex-start" | "start" | "self-start" | "left" | "baseline" => Some(Align::Start),
+        "flex-end" | "end" | "self-end" | "right" => Some(Align::End),
+        "center" => Some(Align::Center),
+        "stretch" | "normal" => Some(Align::Stretch),
+        _ => None,
+    }
+}
+
+fn build_layout_tree<'a>(style_node: &'a StyledNode<'a>, images: &mut ImageCache) -> LayoutBox<'a> {
+    if let Some(content) = replaced_content(style_node, images) {
+        return LayoutBox::new(ReplacedNode(style_node, content));
+    }
     // create the root box
-    let mut root = LayoutBox::new(match style_node.display() {
-        Block => BlockNode(style_node),
-        Inline => InlineNode(style_node),
-        DisplayNone => panic!("Root node has display: none."),
-    });
+    build_box(
+        style_node,
+        match style_node.display() {
+            Display::Block | Display::ListItem => BlockNode(style_node),
+            Display::Flex => FlexNode(style_node),
+            Display::Grid => GridNode(style_node),
+            Display::Table => return build_table(style_node, images),
+            // Internal table boxes outside of a table are treated as blocks.
+            Display::TableRowGroup | Display::TableRow | Display::TableCell => {
+                BlockNode(style_node)
+            }
+            Display::Inline => InlineNode(style_node),
+            // Boxes are only built for elements that are rendered.
+            Display::None => unreachable!("no box is built for display: none"),
+        },
+        images,
+    )
+}
+
+/// The content of a replaced element: the image shown by an `<img>`, unless it has none or it
+/// fails to load, or a form control. Returns `None` for other elements, and for pseudo-elements.
+fn replaced_content(style_node: &StyledNode, images: &mut ImageCache) -> Option<Replaced> {
+    if style_node.pseudo_element.is_some() {
+        return None;
+    }
+    match style_node.node.node_type {
+        NodeType::Element(ref element) if element.tag_name.eq_ignore_ascii_case("img") => {
+            images.get(element.attrs.get("src")?).map(Replaced::Image)
+        }
+        _ => Control::for_node(style_node.node).map(Replaced::Control),
+    }
+}
+
+fn build_box<'a>(
+    style_node: &'a StyledNode<'a>,
+    box_type: BoxType<'a>,
+    images: &mut ImageCache,
+) -> LayoutBox<'a> {
+    let mut root = LayoutBox::new(box_type);
+    let flex_or_grid_container = matches!(root.box_type, FlexNode(_) | GridNode(_));
+
+    // The text of a `::before` or `::after` pseudo-element is its only content.
+    if let (Some(PseudoElement::Before | PseudoElement::After), Some(text)) =
+        (style_node.pseudo_element, &style_node.content)
+    {
+        root.get_inline_container()
+            .children
+            .push(LayoutBox::new(TextRun(style_node, text.clone())));
+    }
 
     // create the descendant boxes.
     for child in &style_node.children {
+        if child.pseudo_element == Some(PseudoElement::Marker) {
+            root.add_marker(child);
+            continue;
+        }
         match child.display() {
-            Block => root.children.push(build_layout_tree(child)),
-            Inline => root
+            Display::Block
+            | Display::ListItem
+            | Display::Flex
+            | Display::Grid
+            | Display::Table
+            | Display::TableRowGroup
+            | Display::TableRow
+            | Display::TableCell => root.children.push(build_layout_tree(child, images)),
+            // Each child element of a flex or grid container is blockified into an item, and
+            // runs of text are wrapped in anonymous items. Whitespace between items is not
+            // rendered.
+            // http://www.w3.org/TR/css-flexbox-1/#flex-items
+            Display::Inline if flex_or_grid_container => match child.node.node_type {
+                NodeType::Element(_) => root.children.push(match replaced_content(child, images) {
+                    Some(content) => LayoutBox::new(ReplacedNode(child, content)),
+                    None => build_box(child, BlockNode(child), images),
+                }),
+                NodeType::Text(ref text) if text.trim().is_empty() => {}
+                NodeType::Text(_) => root
+                    .get_inline_container()
+                    .children
+                    .push(build_layout_tree(child, images)),
+            },
+            Display::Inline => root
                 .get_inline_container()
                 .children
-                .push(build_layout_tree(child)),
-            DisplayNone => {} // Skip nodes with `display: none;`
+                .push(build_layout_tree(child, images)),
+            Display::None => {} // Skip nodes with `display: none;`
         }
     }
+    // White space that collapses away between blocks generates no boxes.
+    root.children
+        .retain(|child| !child.is_collapsible_white_space());
     root
 }
+
+/// Build the box of a table. Rows in row groups become children of the table itself, and
+/// other children are treated as cells and wrapped in anonymous rows. Text outside of cells is
+/// not rendered.
+///
+/// http://www.w3.org/TR/CSS2/tables.html#anonymous-boxes
+fn build_table<'a>(style_node: &'a StyledNode<'a>, images: &mut ImageCache) -> LayoutBox<'a> {
+    let mut table = LayoutBox::new(TableNode(style_node));
+    build_table_rows(&mut table, style_node, images);
+    table
+}
+
+fn build_table_rows<'a>(
+    table: &mut LayoutBox<'a>,
+    style_node: &'a StyledNode<'a>,
+    images: &mut ImageCache,
+) {
+    for child in &style_node.children {
+        match (child.display(), &child.node.node_type) {
+            (Display::None, _) | (_, NodeType::Text(_)) => {}
+            (Display::TableRowGroup, _) => build_table_rows(table, child, images),
+            (Display::TableRow, _) => {
+                let mut row = LayoutBox::new(TableRowNode(child));
+                for cell in &child.children {
+                    if let (Display::None, _) | (_, NodeType::Text(_)) =
+                        (cell.display(), &cell.node.node_type)
+                    {
+                        continue;
+                    }
+                    row.children
+                        .push(build_box(cell, TableCellNode(cell), images));
+                }
+                table.children.push(row);
+            }
+            _ => {
+                if !matches!(
+                    table.children.last(),
+                    Some(LayoutBox {
+                        box_type: AnonymousBlock(_),
+                        ..
+                    })
+                ) {
+                    table
+                        .children
+                        .push(LayoutBox::new(AnonymousBlock(style_node)));
+                }
+                let row = table.children.last_mut().unwrap();
+                row.children
+                    .push(build_box(child, TableCellNode(child), images));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{css, html, style::style_tree};
+    use std::path::Path;
+
+    fn viewport(width: f32) -> Dimensions {
+        let mut viewport: Dimensions = Default::default();
+        viewport.content.width = width;
+        viewport.content.height = 600.0;
+        viewport
+    }
+
+    fn with_layout(source: &str, stylesheet: &str, test: impl FnOnce(&LayoutBox)) {
+        with_layout_in(source, stylesheet, Path::new("."), test)
+    }
+
+    /// Lay out a document in the directory `base`, which its images are loaded from.
+    fn with_layout_in(source: &str, stylesheet: &str, base: &Path, test: impl FnOnce(&LayoutBox)) {
+        let root = html::parse(source.to_string());
+        let stylesheet = css::parse(stylesheet.to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let mut images = ImageCache::new(base);
+        test(&layout_tree(&styled, viewport(800.0), &mut images));
+    }
+
+    #[test]
+    fn test_auto_width_fills_container() {
+        with_layout(
+            "<div></div>",
+            "div { display: block; margin: 10px; padding: 5px; }",
+            |root| {
+                let d = root.dimensions;
+                assert_eq!(d.content.width, 770.0);
+                assert_eq!(d.content.x, 15.0);
+                assert_eq!(d.margin_box().width, 800.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_border_box_width_includes_padding_and_border() {
+        with_layout(
+            "<div></div>",
+            "div { display: block; box-sizing: border-box; width: 200px; padding: 20px; \
+             border-width: 5px; }",
+            |root| {
+                let d = root.dimensions;
+                assert_eq!(d.content.width, 150.0);
+                assert_eq!(d.border_box().width, 200.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_max_width_container_is_centered() {
+        with_layout(
+            "<div></div>",
+            "div { display: block; box-sizing: border-box; max-width: 600px; margin: auto; \
+             padding: 10px; }",
+            |root| {
+                let d = root.dimensions;
+                assert_eq!(d.border_box().width, 600.0);
+                assert_eq!(d.margin.left, 100.0);
+                assert_eq!(d.margin.right, 100.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_calc_and_percentage_widths() {
+        with_layout(
+            "<div><p></p></div>",
+            "div { display: block; --side: 50px; width: calc(100% - 2 * var(--side)); } \
+             p { display: block; width: 50%; margin-left: calc(25% + 10px); }",
+            |root| {
+                assert_eq!(root.dimensions.content.width, 700.0);
+                let p = root.children[0].dimensions;
+                assert_eq!(p.content.width, 350.0);
+                assert_eq!(p.margin.left, 185.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_calc_and_percentage_heights_and_vertical_edges() {
+        with_layout(
+            "<div><p class=\"a\"></p><section><p class=\"b\"></p></section></div>",
+            "div { display: block; height: 200px; } section { display: block; } \
+             .a { display: block; height: calc(50% - 10px); margin-top: calc(10% + 5px); \
+             padding: 5%; } \
+             .b { display: block; height: 50%; min-height: calc(10% + 1px); }",
+            |root| {
+                // Vertical margins and padding are percentages of the containing block's width.
+                let a = root.children[0].dimensions;
+                assert_eq!(a.content.height, 90.0);
+                assert_eq!(a.margin.top, 85.0);
+                assert_eq!((a.padding.top, a.padding.left), (40.0, 40.0));
+                assert_eq!(a.content.y, 125.0);
+                // The height of the section depends on its content, so a percentage of it is
+                // treated as auto.
+                let b = root.children[1].children[0].dimensions;
+                assert_eq!(b.content.height, 0.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_hidden_root_has_no_boxes() {
+        with_layout("<div><p></p></div>", "div { display: none; }", |root| {
+            assert!(matches!(root.box_type, AnonymousBlock(_)));
+            assert!(root.children.is_empty());
+            assert_eq!(root.dimensions.margin_box(), Rect::default());
+        });
+    }
+
+    #[test]
+    fn test_min_width_wins_over_max_width() {
+        with_layout(
+            "<div></div>",
+            "div { display: block; width: 100px; min-width: 300px; max-width: 200px; }",
+            |root| assert_eq!(root.dimensions.content.width, 300.0),
+        );
+    }
+
+    #[test]
+    fn test_explicit_height_and_limits() {
+        with_layout(
+            "<div><p></p></div>",
+            "div { display: block; min-height: 50px; } \
+             p { display: block; box-sizing: border-box; height: 30px; padding: 5px; \
+             max-height: 20px; }",
+            |root| {
+                assert_eq!(root.dimensions.content.height, 50.0);
+                let p = &root.children[0].dimensions;
+                assert_eq!(p.content.height, 10.0);
+                assert_eq!(p.border_box().height, 20.0);
+            },
+        );
+    }
+
+    // The expected positions in the margin collapsing tests below match what browsers produce
+    // for the same markup.
+
+    #[test]
+    fn test_sibling_margins_collapse() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+            "div { display: block; } p { display: block; height: 10px; } \
+             .a { margin-bottom: 20px; } .b { margin-top: 30px; }",
+            |root| {
+                assert_eq!(root.children[1].dimensions.content.y, 40.0);
+                assert_eq!(root.dimensions.content.height, 50.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_negative_margins_collapse() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p></div>",
+            "div { display: block; } p { display: block; height: 10px; } \
+             .a { margin-bottom: 20px; } .b { margin-top: -5px; margin-bottom: -10px; } \
+             .c { margin-top: -20px; }",
+            |root| {
+                assert_eq!(root.children[1].dimensions.content.y, 25.0);
+                assert_eq!(root.children[2].dimensions.content.y, 15.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_parent_and_first_child_margins_collapse() {
+        with_layout(
+            "<body><div><p></p></div></body>",
+            "body { display: block; } div { display: block; margin-top: 10px; } \
+             p { display: block; margin-top: 25px; height: 10px; }",
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(div.dimensions.border_box().y, 25.0);
+                assert_eq!(div.children[0].dimensions.content.y, 25.0);
+                assert_eq!(div.dimensions.content.height, 10.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_border_prevents_collapse() {
+        with_layout(
+            "<body><div><p></p></div></body>",
+            "body { display: block; } div { display: block; margin-top: 10px; \
+             border-width: 1px; } p { display: block; margin: 25px; height: 10px; }",
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(div.dimensions.border_box().y, 10.0);
+                assert_eq!(div.children[0].dimensions.content.y, 36.0);
+                assert_eq!(div.dimensions.content.height, 60.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_parent_and_last_child_margins_collapse() {
+        with_layout(
+            "<body><div><p></p></div><p></p></body>",
+            "body { display: block; } div { display: block; margin-bottom: 5px; } \
+             p { display: block; margin-bottom: 20px; height: 10px; }",
+            |root| {
+                assert_eq!(root.children[0].dimensions.content.height, 10.0);
+                assert_eq!(root.children[1].dimensions.content.y, 30.0);
+                assert_eq!(root.dimensions.content.height, 60.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_empty_block_margins_collapse_through() {
+        with_layout(
+            "<body><p class=\"a\"></p><div></div><p class=\"b\"></p></body>",
+            "body { display: block; } p { display: block; height: 10px; } \
+             div { display: block; margin-top: 30px; margin-bottom: 5px; } \
+             .a { margin-bottom: 10px; } .b { margin-top: 15px; }",
+            |root| {
+                assert_eq!(root.children[1].dimensions.content.height, 0.0);
+                assert_eq!(root.children[2].dimensions.content.y, 40.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_relative_offset_moves_box_and_descendants() {
+        with_layout(
+            "<body><div><p></p></div><p></p></body>",
+            "body { display: block; } p { display: block; height: 10px; } \
+             div { display: block; position: relative; top: 5px; left: -10px; }",
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(div.dimensions.content.x, -10.0);
+                assert_eq!(div.children[0].dimensions.content.y, 5.0);
+                // The following sibling is laid out as if the box had not moved.
+                assert_eq!(root.children[1].dimensions.content.y, 10.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_absolute_box_is_out_of_flow() {
+        with_layout(
+            "<body><div class=\"card\"><p></p><span class=\"badge\"></span></div></body>",
+            "body { display: block; padding: 10px; } p { display: block; height: 100px; } \
+             .card { display: block; position: relative; width: 300px; padding: 5px; } \
+             .badge { position: absolute; top: 0; right: 0; width: 20px; height: 20px; }",
+            |root| {
+                let card = &root.children[0];
+                assert_eq!(card.dimensions.content.height, 100.0);
+                let badge = &card.children[1].dimensions;
+                assert_eq!(badge.content.x, 10.0 + 310.0 - 20.0);
+                assert_eq!(badge.content.y, 10.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_absolute_box_stretches_between_offsets() {
+        with_layout(
+            "<body><div></div></body>",
+            "body { display: block; } \
+             div { position: absolute; left: 100px; right: 100px; top: 20px; bottom: 30px; \
+             padding: 10px; }",
+            |root| {
+                let d = root.children[0].dimensions;
+                assert_eq!(d.content.width, 580.0);
+                assert_eq!(d.content.x, 110.0);
+                assert_eq!(d.border_box().height, 600.0 - 50.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_absolute_box_uses_static_position_and_shrinks_to_fit() {
+        with_layout(
+            "<body><p></p><div><p class=\"wide\"></p></div></body>",
+            "body { display: block; margin: 8px; } p { display: block; height: 10px; } \
+             .wide { width: 120px; margin-left: 4px; } div { position: absolute; }",
+            |root| {
+                let d = root.children[1].dimensions;
+                assert_eq!(d.content.x, 8.0);
+                assert_eq!(d.content.y, 18.0);
+                assert_eq!(d.content.width, 124.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_absolute_percentages_are_of_containing_block() {
+        with_layout(
+            "<body><div class=\"frame\"><p class=\"cover\"></p><p class=\"pin\"></p></div>\
+             </body>",
+            "body { display: block; } \
+             .frame { display: block; position: relative; width: 400px; height: 200px; \
+             padding: 10px; } \
+             .cover { position: absolute; left: 0; top: 0; width: 100%; height: 50%; } \
+             .pin { position: absolute; left: 50%; top: 10%; width: calc(25% - 10px); \
+             height: 20px; margin-left: 5%; }",
+            |root| {
+                // The containing block is the frame's padding box, 420px by 220px.
+                let frame = &root.children[0];
+                let cover = frame.children[0].dimensions.content;
+                assert_eq!((cover.x, cover.y), (0.0, 0.0));
+                assert_eq!((cover.width, cover.height), (420.0, 110.0));
+                let pin = frame.children[1].dimensions;
+                assert_eq!(pin.content.width, 95.0);
+                assert_eq!(pin.margin.left, 21.0);
+                assert_eq!((pin.content.x, pin.content.y), (210.0 + 21.0, 22.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_relative_percentage_offsets() {
+        with_layout(
+            "<div><p class=\"fixed\"></p><section><p class=\"auto\"></p></section></div>",
+            "div { display: block; width: 400px; height: 100px; } \
+             section { display: block; } \
+             p { display: block; position: relative; height: 10px; left: 25%; top: 50%; } \
+             .auto { bottom: 10%; }",
+            |root| {
+                let fixed = root.children[0].dimensions.content;
+                assert_eq!((fixed.x, fixed.y), (100.0, 50.0));
+                // The section's height depends on its content, so vertical percentages of it
+                // are auto.
+                let auto = root.children[1].children[0].dimensions.content;
+                assert_eq!((auto.x, auto.y), (100.0, 10.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_fixed_box_is_positioned_against_viewport() {
+        with_layout(
+            "<body><div><p></p></div></body>",
+            "body { display: block; } \
+             div { display: block; position: relative; top: 50px; margin: 40px; } \
+             p { position: fixed; bottom: 0; left: 0; width: 800px; height: 50px; }",
+            |root| {
+                let p = root.children[0].children[0].dimensions;
+                assert_eq!(p.content.x, 0.0);
+                assert_eq!(p.content.y, 550.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_text_is_broken_into_lines() {
+        with_layout(
+            "<div>aaaa bbbb cccc dddd eeee</div>",
+            "div { display: block; width: 60px; font-size: 10px; }",
+            |root| {
+                let lines = &root.children[0].children;
+                assert_eq!(lines.len(), 3);
+                assert!(
+                    matches!(&lines[1].children[0].box_type, TextRun(_, text) if text == "cccc dddd")
+                );
+                assert_eq!(lines[2].dimensions.content.y, 24.0);
+                assert_eq!(root.dimensions.content.height, 36.0);
+            },
+        );
+    }
+
+    /// The text and x position of the runs on each line of an anonymous block.
+    fn line_runs(block: &LayoutBox) -> Vec<Vec<(String, f32)>> {
+        block
+            .children
+            .iter()
+            .map(|line| {
+                line.children
+                    .iter()
+                    .filter_map(|run| match run.box_type {
+                        TextRun(_, ref text) => Some((text.clone(), run.dimensions.content.x)),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn runs(runs: &[&[(&str, f32)]]) -> Vec<Vec<(String, f32)>> {
+        runs.iter()
+            .map(|line| {
+                line.iter()
+                    .map(|&(text, x)| (text.to_string(), x))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_white_space_collapses_or_is_preserved() {
+        with_layout(
+            "<div>\n  <p> one  <b> two</b>three\n four </p>\n  <pre>\n  a\tb\n\n c</pre>\n\
+             <p class=\"nowrap\">x y z</p><p class=\"pre-wrap\">ab  cd  ef</p><p>a<br> b</p></div>",
+            "div, p { display: block; } div { width: 60px; font-size: 10px; }\n\
+             b { color: #ff0000; } .nowrap { white-space: nowrap; width: 12px; }\n\
+             .pre-wrap { white-space: pre-wrap; width: 36px; }",
+            |root| {
+                // The white space between the blocks generates no boxes.
+                assert_eq!(root.children.len(), 5);
+                let block = |index: usize| &root.children[index].children[0];
+
+                // Runs of white space collapse, even across inline boxes, and are removed at the
+                // ends of lines. Nothing separates words without white space between them.
+                assert_eq!(
+                    line_runs(block(0)),
+                    runs(&[
+                        &[("one", 0.0)],
+                        &[("two", 0.0), ("three", 18.0)],
+                        &[("four", 0.0)]
+                    ])
+                );
+                // Preformatted text keeps its spaces and newlines, and tabs move to the next tab
+                // stop. The newline after the start tag isn't part of the text.
+                assert_eq!(
+                    line_runs(block(1)),
+                    runs(&[&[("  a     b", 0.0)], &[], &[(" c", 0.0)]])
+                );
+                assert_eq!(root.children[1].dimensions.content.height, 36.0);
+                // Text that can't wrap overflows its block.
+                assert_eq!(line_runs(block(2)), runs(&[&[("x y z", 0.0)]]));
+                // Preserved spaces at the end of a wrapped line hang past its end.
+                assert_eq!(
+                    line_runs(block(3)),
+                    runs(&[&[("ab  cd  ", 0.0)], &[("ef", 0.0)]])
+                );
+                assert_eq!(line_runs(block(4)), runs(&[&[("a", 0.0)], &[("b", 0.0)]]));
+            },
+        );
+    }
+
+    #[test]
+    fn test_text_align_indent_and_spacing() {
+        with_layout(
+            "<div><p class=\"right\">ab cd</p><p class=\"center\">ab</p>\
+             <p class=\"justify\">aa b cc dd</p><p class=\"indent\">aa bb cc</p></div>",
+            "div, p { display: block; } div { width: 60px; font-size: 10px; }\n\
+             .right { text-align: right; } .center { text-align: center; }\n\
+             .justify { text-align: justify; width: 48px; }\n\
+             .indent { text-indent: 50%; line-height: 2; letter-spacing: 1px; }",
+            |root| {
+                let block = |index: usize| &root.children[index].children[0];
+                assert_eq!(line_runs(block(0)), runs(&[&[("ab cd", 30.0)]]));
+                assert_eq!(line_runs(block(1)), runs(&[&[("ab", 24.0)]]));
+                // The spaces of a justified line are stretched to fill it, but its last line is
+                // aligned to the left.
+                assert_eq!(
+                    line_runs(block(2)),
+                    runs(&[
+                        &[
+                            ("aa", 0.0),
+                            (" ", 12.0),
+                            ("b", 21.0),
+                            (" ", 27.0),
+                            ("cc", 36.0)
+                        ],
+                        &[("dd", 0.0)]
+                    ])
+                );
+                // Only the first line is indented. Letter spacing widens every character.
+                assert_eq!(
+                    line_runs(block(3)),
+                    runs(&[&[("aa", 30.0)], &[("bb cc", 0.0)]])
+                );
+                assert_eq!(
+                    block(3).children[1].children[0].dimensions.content.width,
+                    35.0
+                );
+                assert_eq!(root.children[3].dimensions.content.height, 40.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_lines_are_shortened_beside_floats() {
+        with_layout(
+            "<div><p></p>aaaa bbbb cccc dddd eeee</div>",
+            "div { display: block; width: 100px; font-size: 10px; } \
+             p { float: left; width: 40px; height: 20px; }",
+            |root| {
+                let lines = &root.children[1].children;
+                assert_eq!(lines.len(), 3);
+                assert_eq!(lines[0].dimensions.content.x, 40.0);
+                assert_eq!(lines[0].dimensions.content.width, 60.0);
+                assert_eq!(lines[1].dimensions.content.x, 40.0);
+                assert_eq!(lines[1].dimensions.content.y, 12.0);
+                assert_eq!(lines[2].dimensions.content.x, 0.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_floats_stack_and_wrap_below() {
+        with_layout(
+            "<body><div><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p></div></body>",
+            "body, div { display: block; } p { float: left; width: 300px; } \
+             .a { height: 20px; } .b { height: 40px; } .c { height: 10px; }",
+            |root| {
+                let div = &root.children[0];
+                let b = div.children[1].dimensions.content;
+                let c = div.children[2].dimensions.content;
+                assert_eq!((b.x, b.y), (300.0, 0.0));
+                assert_eq!((c.x, c.y), (0.0, 40.0));
+                // Floats don't contribute to the height of a normal block, but the root element
+                // grows to contain them.
+                assert_eq!(div.dimensions.content.height, 0.0);
+                assert_eq!(root.dimensions.content.height, 50.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_right_float_and_clearance() {
+        with_layout(
+            "<body><div><p class=\"r\"></p></div><p class=\"c\"></p></body>",
+            "body { display: block; } div { display: block; } \
+             .r { float: right; width: 50px; height: 30px; margin-right: 10px; } \
+             .c { display: block; clear: right; height: 10px; }",
+            |root| {
+                let r = root.children[0].children[0].dimensions;
+                assert_eq!(r.content.x, 740.0);
+                assert_eq!(root.children[1].dimensions.content.y, 30.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_formatting_context_root_avoids_floats() {
+        with_layout(
+            "<div><p></p><section></section><article></article></div>",
+            "div { display: block; } p { float: left; width: 100px; height: 50px; } \
+             section { display: block; overflow: hidden; height: 10px; } \
+             article { display: block; height: 10px; }",
+            |root| {
+                let section = root.children[1].dimensions.content;
+                assert_eq!((section.x, section.width), (100.0, 700.0));
+                let article = root.children[2].dimensions.content;
+                assert_eq!((article.x, article.width), (0.0, 800.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_formatting_context_root_contains_floats() {
+        with_layout(
+            "<body><div><p>abc def</p></div></body>",
+            "body { display: block; } div { display: block; overflow: hidden; } \
+             p { float: left; font-size: 10px; }",
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(div.dimensions.content.height, 12.0);
+                assert_eq!(div.children[0].dimensions.content.width, 42.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_children_are_stacked_vertically() {
+        with_layout(
+            "<div><p></p><p></p></div>",
+            "div { display: block; } p { display: block; height: 40px; max-height: none; }",
+            |root| {
+                assert_eq!(root.children[1].dimensions.content.y, 40.0);
+                assert_eq!(root.dimensions.content.height, 80.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_items_grow_into_free_space() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+            "div { display: flex; } p { width: 100px; height: 10px; } \
+             .a { flex-grow: 1; } .b { flex-grow: 3; }",
+            |root| {
+                let a = root.children[0].dimensions.content;
+                let b = root.children[1].dimensions.content;
+                assert_eq!((a.x, a.width), (0.0, 250.0));
+                assert_eq!((b.x, b.width), (250.0, 550.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_items_shrink_by_weighted_basis() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+            "div { display: flex; width: 500px; } .a { flex-basis: 600px; } \
+             .b { width: 400px; min-width: 300px; }",
+            |root| {
+                assert_eq!(root.children[0].dimensions.content.width, 200.0);
+                assert_eq!(root.children[1].dimensions.content.width, 300.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_justify_content_with_gap() {
+        with_layout(
+            "<div><p></p><p></p><p></p></div>",
+            "div { display: flex; justify-content: space-between; gap: 50px; } \
+             p { width: 100px; }",
+            |root| {
+                let xs: Vec<f32> = root
+                    .children
+                    .iter()
+                    .map(|item| item.dimensions.content.x)
+                    .collect();
+                assert_eq!(xs, vec![0.0, 350.0, 700.0]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_row_and_column_gaps() {
+        let positions = |root: &LayoutBox| -> Vec<(f32, f32)> {
+            root.children
+                .iter()
+                .map(|item| (item.dimensions.content.x, item.dimensions.content.y))
+                .collect()
+        };
+        let html = "<div><p></p><p></p><p></p><p></p></div>";
+        with_layout(
+            html,
+            "div { display: flex; flex-wrap: wrap; width: 200px; gap: 10px 20px; } \
+             p { width: 50px; height: 30px; }",
+            |root| {
+                let expected = vec![(0.0, 0.0), (70.0, 0.0), (140.0, 0.0), (0.0, 40.0)];
+                assert_eq!(positions(root), expected);
+            },
+        );
+        // A percentage column gap resolves against the container's width, and a percentage row
+        // gap of a container with an auto height is zero.
+        with_layout(
+            html,
+            "div { display: flex; flex-wrap: wrap; width: 200px; gap: 50% 10%; } \
+             p { width: 50px; height: 30px; }",
+            |root| {
+                let expected = vec![(0.0, 0.0), (70.0, 0.0), (140.0, 0.0), (0.0, 30.0)];
+                assert_eq!(positions(root), expected);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_align_items() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+            "div { display: flex; height: 100px; align-items: center; } p { width: 10px; } \
+             .a { height: 20px; } .b { align-self: stretch; margin-top: 10px; }",
+            |root| {
+                assert_eq!(root.children[0].dimensions.content.y, 40.0);
+                let b = root.children[1].dimensions.content;
+                assert_eq!((b.y, b.height), (10.0, 90.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_wrap_breaks_lines() {
+        with_layout(
+            "<div><p></p><p></p><p></p></div>",
+            "div { display: flex; flex-wrap: wrap; row-gap: 10px; } \
+             p { width: 300px; height: 20px; }",
+            |root| {
+                let third = root.children[2].dimensions.content;
+                assert_eq!((third.x, third.y), (0.0, 30.0));
+                assert_eq!(root.dimensions.content.height, 50.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_column_with_definite_height() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+            "div { display: flex; flex-direction: column; height: 300px; } \
+             .a { flex: 1; } .b { height: 100px; }",
+            |root| {
+                let a = root.children[0].dimensions.content;
+                let b = root.children[1].dimensions.content;
+                assert_eq!((a.y, a.height, a.width), (0.0, 200.0, 800.0));
+                assert_eq!((b.y, b.height), (200.0, 100.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_shorthand_with_three_values() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p></div>",
+            "div { display: flex; width: 400px; } .a { flex: 1 1 0px; } \
+             .b { flex: 2 1 0px; } .c { flex: 0 0 100px; }",
+            |root| {
+                let widths: Vec<f32> = root
+                    .children
+                    .iter()
+                    .map(|child| child.dimensions.content.width)
+                    .collect();
+                assert_eq!(widths, [100.0, 200.0, 100.0]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_shorthand_with_one_or_two_values() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p><p class=\"d\"></p>\
+             </div>",
+            "div { display: flex; width: 600px; } p { width: 300px; } \
+             .a { flex: 2 50px; } .b { flex: 100px 1 0; } .c { flex: 1 0; } .d { flex: 50px; }",
+            |root| {
+                let widths: Vec<f32> = root
+                    .children
+                    .iter()
+                    .map(|child| child.dimensions.content.width)
+                    .collect();
+                assert_eq!(widths, [210.0, 180.0, 80.0, 130.0]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_percentage_basis_and_width() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p></div>",
+            "div { display: flex; width: 400px; } .a { flex: 0 0 50%; } \
+             .b { flex-basis: calc(25% - 10px); flex-grow: 0; } .c { flex: none; width: 25%; }",
+            |root| {
+                let widths: Vec<f32> = root
+                    .children
+                    .iter()
+                    .map(|child| child.dimensions.content.width)
+                    .collect();
+                assert_eq!(widths, [200.0, 90.0, 100.0]);
+            },
+        );
+        // A percentage of an indefinite height is treated as `content`.
+        with_layout(
+            "<div><section class=\"definite\"><p></p></section><section><p></p></section></div>",
+            "div { display: block; } section { display: flex; flex-direction: column; } \
+             .definite { height: 300px; } p { flex: 0 0 50%; }",
+            |root| {
+                let definite = &root.children[0].children[0];
+                assert_eq!(definite.dimensions.content.height, 150.0);
+                let indefinite = &root.children[1].children[0];
+                assert_eq!(indefinite.dimensions.content.height, 0.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_flex_row_reverse_and_auto_margins() {
+        with_layout(
+            "<div><p></p><p></p></div>",
+            "div { display: flex; flex-direction: row-reverse; } p { width: 100px; }",
+            |root| {
+                assert_eq!(root.children[0].dimensions.content.x, 700.0);
+                assert_eq!(root.children[1].dimensions.content.x, 600.0);
+            },
+        );
+        with_layout(
+            "<div><p></p><p class=\"end\"></p></div>",
+            "div { display: flex; justify-content: center; } p { width: 100px; } \
+             .end { margin-left: auto; }",
+            |root| {
+                assert_eq!(root.children[0].dimensions.content.x, 0.0);
+                assert_eq!(root.children[1].dimensions.content.x, 700.0);
+            },
+        );
+        with_layout(
+            "<div><p></p></div>",
+            "div { display: flex; } p { width: 100px; margin: 0 auto; }",
+            |root| assert_eq!(root.children[0].dimensions.content.x, 350.0),
+        );
+    }
+
+    #[test]
+    fn test_flex_text_becomes_anonymous_item() {
+        with_layout(
+            "<div>hello<span>x</span></div>",
+            "div { display: flex; font-size: 10px; }",
+            |root| {
+                assert_eq!(root.children.len(), 2);
+                assert_eq!(root.children[0].dimensions.content.width, 30.0);
+                let span = root.children[1].dimensions.content;
+                assert_eq!((span.x, span.width), (30.0, 6.0));
+                assert_eq!(root.dimensions.content.height, 12.0);
+            },
+        );
+    }
+
+    fn x_positions(root: &LayoutBox) -> Vec<f32> {
+        root.children
+            .iter()
+            .map(|item| item.dimensions.content.x)
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_fr_columns_share_free_space() {
+        with_layout(
+            "<div><p></p><p></p><p></p></div>",
+            "div { display: grid; grid-template-columns: 200px 1fr 3fr; column-gap: 20px; }",
+            |root| {
+                assert_eq!(x_positions(root), vec![0.0, 220.0, 380.0]);
+                assert_eq!(root.children[2].dimensions.content.width, 420.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_grid_repeat_percent_and_minmax() {
+        with_layout(
+            "<div><p></p><p></p><p></p><p></p></div>",
+            "div { display: grid; grid-template-columns: repeat(2, 25%) minmax(100px, 1fr); } \
+             p { height: 10px; }",
+            |root| {
+                assert_eq!(x_positions(root), vec![0.0, 200.0, 400.0, 0.0]);
+                assert_eq!(root.children[2].dimensions.content.width, 400.0);
+                assert_eq!(root.children[3].dimensions.content.y, 10.0);
+            },
+        );
+        with_layout(
+            "<div><p></p><p></p><p></p><p></p><p></p></div>",
+            "div { display: grid; grid-template-columns: repeat(auto-fill, 200px); gap: 10px; }",
+            |root| assert_eq!(x_positions(root), vec![0.0, 210.0, 420.0, 0.0, 210.0]),
+        );
+    }
+
+    #[test]
+    fn test_grid_row_and_column_gaps() {
+        let positions = |root: &LayoutBox| -> Vec<(f32, f32)> {
+            root.children
+                .iter()
+                .map(|item| (item.dimensions.content.x, item.dimensions.content.y))
+                .collect()
+        };
+        with_layout(
+            "<div><p></p><p></p><p></p></div>",
+            "div { display: grid; grid-template-columns: 150px 150px; gap: 10px 20px; } \
+             p { height: 30px; }",
+            |root| {
+                let expected = vec![(0.0, 0.0), (170.0, 0.0), (0.0, 40.0)];
+                assert_eq!(positions(root), expected);
+            },
+        );
+        // Percentage gaps resolve against the container's content width and definite height.
+        with_layout(
+            "<div><p></p><p></p><p></p></div>",
+            "div { display: grid; grid-template-columns: 150px 150px; height: 200px; \
+             grid-template-rows: 30px 30px; gap: 10% 5%; }",
+            |root| {
+                let expected = vec![(0.0, 0.0), (190.0, 0.0), (0.0, 50.0)];
+                assert_eq!(positions(root), expected);
+            },
+        );
+    }
+
+    #[test]
+    fn test_grid_auto_placement_and_line_placement() {
+        with_layout(
+            "<div><p class=\"a\"></p><p></p><p class=\"c\"></p></div>",
+            "div { display: grid; grid-template-columns: repeat(3, 100px); } \
+             p { height: 20px; } .a { grid-column: 2 / span 2; } .c { grid-column: 1 / -1; }",
+            |root| {
+                let a = root.children[0].dimensions.content;
+                assert_eq!((a.x, a.y, a.width), (100.0, 0.0, 200.0));
+                let b = root.children[1].dimensions.content;
+                assert_eq!((b.x, b.y), (0.0, 20.0));
+                let c = root.children[2].dimensions.content;
+                assert_eq!((c.x, c.y, c.width), (0.0, 40.0, 300.0));
+                assert_eq!(root.dimensions.content.height, 60.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_grid_explicit_rows_and_item_alignment() {
+        with_layout(
+            "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+            "div { display: grid; grid-template-columns: 100px 100px; \
+             grid-template-rows: 50px auto; justify-items: center; } \
+             .a { grid-row: 2; grid-column-start: 2; width: 50px; height: 30px; } \
+             .b { grid-row-start: 1; grid-column-start: 1; align-self: end; height: 10px; }",
+            |root| {
+                let a = root.children[0].dimensions.content;
+                assert_eq!((a.x, a.y), (125.0, 50.0));
+                let b = root.children[1].dimensions.content;
+                assert_eq!((b.x, b.y, b.width), (50.0, 40.0, 0.0));
+                assert_eq!(root.dimensions.content.height, 80.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_grid_item_percentages_are_of_grid_area() {
+        with_layout(
+            "<div><p></p></div>",
+            "div { display: grid; grid-template-columns: 200px 100px; \
+             grid-template-rows: 80px; } \
+             p { width: calc(50% + 10px); height: 50%; padding-left: 10%; align-self: start; }",
+            |root| {
+                let p = root.children[0].dimensions;
+                assert_eq!((p.content.width, p.content.height), (110.0, 40.0));
+                assert_eq!((p.padding.left, p.content.x), (20.0, 20.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_grid_auto_flow_column_and_auto_tracks() {
+        with_layout(
+            "<div><p></p><p></p><p></p></div>",
+            "div { display: grid; grid-template-rows: 10px 10px; grid-auto-flow: column; \
+             grid-auto-columns: 50px; }",
+            |root| {
+                let third = root.children[2].dimensions.content;
+                assert_eq!((third.x, third.y, third.width), (50.0, 0.0, 50.0));
+                assert_eq!(root.children[1].dimensions.content.y, 10.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_grid_auto_columns_fit_content() {
+        with_layout(
+            "<body><div>abcd<span>ab</span></div></body>",
+            "body { display: block; } \
+             div { display: grid; grid-template-columns: auto auto; float: left; \
+             font-size: 10px; column-gap: 4px; }",
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(div.dimensions.content.width, 40.0);
+                assert_eq!(x_positions(div), vec![0.0, 28.0]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_table_auto_layout_shrinks_to_content() {
+        with_layout(
+            "<table><tr><td>aa</td><td>aaaa</td></tr></table>",
+            "table { border-spacing: 0; } td { padding: 0; font-size: 10px; }",
+            |root| {
+                assert_eq!(root.dimensions.content.width, 36.0);
+                let row = &root.children[0];
+                assert_eq!(x_positions(row), vec![0.0, 12.0]);
+                assert_eq!(root.dimensions.content.height, 12.0);
+            },
+        );
+        with_layout(
+            "<table><tr><td>aa bb</td><td>aaaa</td></tr></table>",
+            "table { border-spacing: 0; width: 72px; } td { padding: 0; font-size: 10px; }",
+            |root| {
+                let row = &root.children[0];
+                assert_eq!(x_positions(row), vec![0.0, 40.0]);
+                assert_eq!(row.children[1].dimensions.content.width, 32.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_table_fixed_layout_uses_first_row() {
+        with_layout(
+            "<table><tr><td class=\"first\"></td><td></td></tr>\
+             <tr><td>aaaaaaaaaaaaaaaaaaaaaaaa</td></tr></table>",
+            "table { table-layout: fixed; width: 300px; border-spacing: 10px; } \
+             td { padding: 0; } td.first { width: 100px; }",
+            |root| {
+                assert_eq!(root.dimensions.content.width, 300.0);
+                let row = &root.children[0];
+                assert_eq!(x_positions(row), vec![10.0, 120.0]);
+                assert_eq!(row.children[1].dimensions.content.width, 170.0);
+                assert_eq!(root.children[1].children[0].dimensions.content.width, 100.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_table_colspan_and_rowspan() {
+        with_layout(
+            "<table><tbody>\
+             <tr><td class=\"tall\" rowspan=\"2\"></td><td></td><td></td></tr>\
+             <tr><td colspan=\"2\"></td></tr>\
+             </tbody></table>",
+            "table { border-spacing: 0; width: 300px; } td { padding: 0; height: 10px; } \
+             td.tall { width: 100px; }",
+            |root| {
+                assert_eq!(root.children.len(), 2);
+                let tall = root.children[0].children[0].dimensions.content;
+                assert_eq!((tall.x, tall.height), (0.0, 20.0));
+                let wide = root.children[1].children[0].dimensions.content;
+                assert_eq!((wide.x, wide.y, wide.width), (100.0, 10.0, 200.0));
+                assert_eq!(root.dimensions.content.height, 20.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_table_cell_vertical_align_middle() {
+        with_layout(
+            "<table><tr><td><p></p></td><td class=\"tall\"></td></tr></table>",
+            "table { border-spacing: 0; } td { padding: 0; vertical-align: middle; } \
+             p { display: block; height: 10px; } td.tall { height: 30px; }",
+            |root| {
+                let cell = &root.children[0].children[0];
+                assert_eq!(cell.dimensions.content.height, 30.0);
+                assert_eq!(cell.children[0].dimensions.content.y, 10.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        with_layout(
+            "<div class=\"a\">Hi \"you\"</div>",
+            "div { display: block; padding: 2px; width: 100px; }",
+            |root| {
+                assert_eq!(
+                    root.dump().lines().collect::<Vec<_>>(),
+                    vec![
+                        "block <div class=\"a\"> content=2,2 100x19.2 padding=0,0 104x23.2 \
+                         border=0,0 104x23.2 margin=0,0 800x23.2",
+                        "  anonymous content=2,2 100x19.2 padding=2,2 100x19.2 \
+                         border=2,2 100x19.2 margin=2,2 100x19.2",
+                        "    line content=2,2 100x19.2 padding=2,2 100x19.2 \
+                         border=2,2 100x19.2 margin=2,2 100x19.2",
+                        "      text \"Hi \\\"you\\\"\" content=2,2 76.8x19.2 \
+                         padding=2,2 76.8x19.2 border=2,2 76.8x19.2 margin=2,2 76.8x19.2",
+                    ]
+                );
+                let json = root.dump_json();
+                assert!(json.starts_with(
+                    "{\"type\":\"block\",\"element\":{\"tag\":\"div\",\"attributes\":{\"class\":\"a\"}},\
+                     \"content\":{\"x\":2,\"y\":2,\"width\":100,\"height\":19.2},"
+                ));
+                assert!(json
+                    .contains("{\"type\":\"text\",\"element\":null,\"text\":\"Hi \\\"you\\\"\","));
+                assert!(json.ends_with("\"children\":[]}]}]}]}"));
+            },
+        );
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let source = "<div id=\"outer\"><p id=\"inner\">text</p><span id=\"abs\"></span></div>";
+        let css = "div, p { display: block; } \
+                   #outer { height: 100px; padding: 10px; overflow: hidden; } \
+                   #abs { position: absolute; top: 0px; left: 0px; width: 30px; height: 30px; } \
+                   #inner { height: 150px; }";
+        with_layout(source, css, |root| {
+            let id = |x, y| match root.hit_test(x, y)?.node_type {
+                NodeType::Element(ref element) => element.id().cloned(),
+                NodeType::Text(ref text) => Some(text.clone()),
+            };
+            assert_eq!(id(5.0, 5.0), Some("abs".to_string()));
+            assert_eq!(id(40.0, 12.0), Some("text".to_string()));
+            assert_eq!(id(200.0, 50.0), Some("inner".to_string()));
+            assert_eq!(id(200.0, 5.0), Some("outer".to_string()));
+            // Content clipped by the outer box can't be hit.
+            assert_eq!(id(200.0, 125.0), None);
+        });
+    }
+
+    #[test]
+    fn test_page_breaks() {
+        let source = "<div><p class=\"a\"></p><p class=\"b\"><i></i><i></i></p><p></p></div>";
+        let css = "div, p, i { display: block; } p { height: 60px; } i { height: 30px; } ";
+        // Pages end at the last break that fits, which may be inside a box.
+        with_layout(source, css, |root| {
+            assert_eq!(root.page_breaks(100.0), vec![0.0, 90.0])
+        });
+        with_layout(
+            source,
+            &format!("{} .b {{ page-break-inside: avoid; }}", css),
+            |root| assert_eq!(root.page_breaks(100.0), vec![0.0, 60.0, 120.0]),
+        );
+        // A forced break on the first child of a box applies before the box.
+        with_layout(
+            source,
+            &format!("{} i {{ page-break-before: always; }}", css),
+            |root| assert_eq!(root.page_breaks(100.0), vec![0.0, 60.0, 90.0]),
+        );
+        // Content that doesn't fit on a page is cut.
+        with_layout(
+            "<div></div>",
+            "div { display: block; height: 250px; }",
+            |root| assert_eq!(root.page_breaks(100.0), vec![0.0, 100.0, 200.0]),
+        );
+    }
+
+    #[test]
+    fn test_images_use_intrinsic_size_and_aspect_ratio() {
+        let base = std::env::temp_dir().join(format!("replaced-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("a.ppm"), format!("P3 4 2 1 {}", "0 ".repeat(24))).unwrap();
+
+        with_layout_in(
+            "<div><img src=\"a.ppm\"><img class=\"w\" src=\"a.ppm\">\
+             <img class=\"h\" src=\"a.ppm\" height=\"6\"><img src=\"a.ppm\" width=\"3\" height=\"3\">\
+             <img src=\"missing.ppm\"></div>",
+            "div { display: block; } .w { width: 8px; } .h { height: 10px; }",
+            &base,
+            |root| {
+                let line = &root.children[0].children[0];
+                let boxes: Vec<_> = line
+                    .children
+                    .iter()
+                    .map(|child| {
+                        let content = child.dimensions.content;
+                        (content.width, content.height, content.y)
+                    })
+                    .collect();
+                // Each image is aligned with the bottom of the line. The missing image has no
+                // box in the line.
+                assert_eq!(
+                    boxes,
+                    vec![
+                        (4.0, 2.0, 8.0),
+                        (8.0, 4.0, 6.0),
+                        (20.0, 10.0, 0.0),
+                        (3.0, 3.0, 7.0)
+                    ]
+                );
+                // Without white space between them, nothing separates the images.
+                assert_eq!(line.children[1].dimensions.content.x, 4.0);
+            },
+        );
+
+        with_layout_in(
+            "<div><img src=\"a.ppm\"></div>",
+            "div { display: block; } img { display: block; width: 40px; margin: auto; }",
+            &base,
+            |root| {
+                let image = root.children[0].dimensions;
+                assert_eq!((image.content.x, image.content.y), (380.0, 0.0));
+                assert_eq!(image.content.height, 20.0);
+                assert_eq!(root.dimensions.content.height, 20.0);
+            },
+        );
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_form_controls_are_replaced_boxes() {
+        with_layout(
+            "<form><input size=5><input type=checkbox><input type=hidden>\
+             <select><option>ab</option><option>abcd</option></select>\
+             <input class=\"w\"><textarea rows=3></textarea></form>",
+            "form { display: block; } .w { width: 100px; }",
+            |root| {
+                let boxes: Vec<_> = root.children[0]
+                    .children
+                    .iter()
+                    .flat_map(|line| &line.children)
+                    .map(|child| {
+                        let content = child.dimensions.content;
+                        (content.width, content.height)
+                    })
+                    .collect();
+                // Text is 9.6px a character and 19.2px a line. The hidden input has no box, and
+                // the select is wide enough for its longest option and its arrow. A text field
+                // with a width keeps its own height.
+                assert_eq!(
+                    boxes,
+                    vec![
+                        (48.0, 19.2),
+                        (13.0, 13.0),
+                        (38.4 + 16.0, 19.2),
+                        (100.0, 19.2),
+                        (192.0, 19.2 * 3.0)
+                    ]
+                );
+                let input = root.children[0].children[0].children[0].dimensions;
+                assert_eq!(input.margin_box().width, 48.0 + 2.0 * 2.0 + 2.0 * 2.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_list_markers() {
+        with_layout(
+            "<ol start=\"3\"><li>a</li><li value=\"10\"><p>b</p></li><li class=\"in\">c</li></ol>",
+            "p { display: block; } .in { list-style-position: inside; }",
+            |root| {
+                let rect = |layout_box: &LayoutBox| {
+                    let content = layout_box.dimensions.content;
+                    let round = |value: f32| (value * 10.0).round() / 10.0;
+                    (
+                        round(content.x),
+                        round(content.y),
+                        round(content.width),
+                        round(content.height),
+                    )
+                };
+                let marker = |layout_box: &LayoutBox| match layout_box.box_type {
+                    ReplacedNode(_, Replaced::Marker(ref marker)) => Some(marker.clone()),
+                    _ => None,
+                };
+                // The list is indented by 40px, and outside markers end where the content of
+                // their items starts, beside the first line.
+                let items = &root.children;
+                assert_eq!(
+                    marker(&items[0].children[0]),
+                    Some(Marker::Text("3. ".to_string()))
+                );
+                assert_eq!(rect(&items[0].children[0]), (11.2, 0.0, 28.8, 19.2));
+                assert_eq!(rect(&items[1].children[0]), (1.6, 19.2, 38.4, 19.2));
+                assert_eq!(rect(&items[1]).3, 19.2);
+
+                // An inside marker starts the first line.
+                let line = &items[2].children[0].children[0];
+                assert_eq!(
+                    marker(&line.children[0]),
+                    Some(Marker::Text("11. ".to_string()))
+                );
+                assert_eq!(rect(&line.children[0]), (40.0, 38.4, 38.4, 19.2));
+                assert_eq!(rect(&line.children[1]).0, 40.0 + 38.4);
+            },
+        );
+        with_layout(
+            "<ul><li>a</li></ul>",
+            "li { list-style-type: none; }",
+            |root| assert!(marker_count(root) == 0),
+        );
+
+        fn marker_count(layout_box: &LayoutBox) -> usize {
+            let own = matches!(layout_box.box_type, ReplacedNode(_, Replaced::Marker(_)));
+            own as usize + layout_box.children.iter().map(marker_count).sum::<usize>()
+        }
+    }
+
+    #[test]
+    fn test_before_and_after_generate_boxes() {
+        with_layout(
+            "<div><p>b</p></div>",
+            "div, p { display: block; } p::before { content: \"a\"; } \
+             div::after { content: \"c d\"; display: block; padding: 1px; }",
+            |root| {
+                let p = &root.children[0];
+                let line = &p.children[0].children[0];
+                let texts: Vec<_> = line
+                    .children
+                    .iter()
+                    .map(|run| match run.box_type {
+                        TextRun(_, ref text) => text.as_str(),
+                        _ => "",
+                    })
+                    .collect();
+                assert_eq!(texts, ["a", "b"]);
+
+                let after = &root.children[1];
+                assert!(matches!(after.box_type, BlockNode(style)
+                    if style.pseudo_element == Some(PseudoElement::After)));
+                assert_eq!(after.dimensions.content.y, 19.2 + 1.0);
+                assert_eq!(after.dimensions.content.height, 19.2);
+            },
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Form controls, such as text fields, checkboxes and buttons. Like images, they are replaced
+//! elements: what they show comes from their attributes, and from the text of their options or
+//! label, rather than from laying out their children. Their borders, padding and backgrounds
+//! come from the user agent stylesheet.
+//!
+//! http://html.spec.whatwg.org/multipage/rendering.html#form-controls
+
+use super::inline::{font_size, line_height, text_width, GLYPH_ADVANCE};
+use crate::dom::{ElementData, Node, NodeType};
+use crate::style::StyledNode;
+
+/// The width and height of a checkbox or radio button, in px.
+const CHECKBOX_SIZE: f32 = 13.0;
+
+/// The width of the arrow beside the selected option of a `<select>`, as a multiple of the
+/// font size.
+pub const SELECT_ARROW_WIDTH: f32 = 1.0;
+
+/// The state of a form control, as it is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    /// A text field: a single-line `<input>`, or a `<textarea>`, showing its lines of text.
+    /// The text is the field's placeholder if `placeholder` is set, which is shown when its
+    /// value is empty. The field is sized to show `columns` characters on each of `rows` lines.
+    TextField {
+        lines: Vec<String>,
+        placeholder: bool,
+        columns: usize,
+        rows: usize,
+    },
+    Checkbox {
+        checked: bool,
+    },
+    Radio {
+        checked: bool,
+    },
+    /// A `<button>`, or an `<input>` of type `submit`, `reset` or `button`.
+    Button {
+        label: String,
+    },
+    /// A drop-down `<select>`, showing the label of its `selected` option. It is wide enough
+    /// for the longest of its options.
+    Select {
+        options: Vec<String>,
+        selected: Option<usize>,
+    },
+}
+
+impl Control {
+    /// The control that an element shows, or `None` if it isn't a form control. Hidden inputs
+    /// aren't controls, as they show nothing.
+    pub fn for_node(node: &Node) -> Option<Control> {
+        let NodeType::Element(ref element) = node.node_type else {
+            return None;
+        };
+        match &*element.tag_name.to_ascii_lowercase() {
+            "input" => input_control(element),
+            "textarea" => {
+                let text = text_content(node);
+                Some(text_field(
+                    element,
+                    text.lines().map(str::to_string).collect(),
+                    size_attribute(element, "cols", 20),
+                    size_attribute(element, "rows", 2),
+                ))
+            }
+            "button" => Some(Control::Button {
+                label: collapse_whitespace(&text_content(node)),
+            }),
+            "select" => {
+                let mut options = Vec::new();
+                let mut selected = None;
+                collect_options(node, &mut options, &mut selected);
+                Some(Control::Select {
+                    selected: selected.or((!options.is_empty()).then_some(0)),
+                    options,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The size of the control's content box, when its `width` and `height` aren't given.
+    pub(super) fn intrinsic_size(&self, style: &StyledNode) -> (f32, f32) {
+        let line_height = line_height(style);
+        match *self {
+            Control::TextField { columns, rows, .. } => (
+                columns as f32 * GLYPH_ADVANCE * font_size(style),
+                rows as f32 * line_height,
+            ),
+            Control::Checkbox { .. } | Control::Radio { .. } => (CHECKBOX_SIZE, CHECKBOX_SIZE),
+            Control::Button { ref label } => (text_width(label, style), line_height),
+            Control::Select { ref options, .. } => {
+                let widest = options
+                    .iter()
+                    .map(|option| text_width(option, style))
+                    .fold(0.0, f32::max);
+                (widest + SELECT_ARROW_WIDTH * font_size(style), line_height)
+            }
+        }
+    }
+}
+
+/// The control for an `<input>`, depending on its `type`. Types that aren't supported are
+/// shown as text fields.
+fn input_control(element: &ElementData) -> Option<Control> {
+    let attr = |name: &str| element.attrs.get(name).map(String::as_str);
+    let value = attr("value").unwrap_or("");
+    let kind = attr("type").unwrap_or("text").to_ascii_lowercase();
+    Some(match &*kind {
+        "hidden" => return None,
+        "checkbox" => Control::Checkbox {
+            checked: attr("checked").is_some(),
+        },
+        "radio" => Control::Radio {
+            checked: attr("checked").is_some(),
+        },
+        "submit" => Control::Button {
+            label: attr("value").unwrap_or("Submit").to_string(),
+        },
+        "reset" => Control::Button {
+            label: attr("value").unwrap_or("Reset").to_string(),
+        },
+        "button" => Control::Button {
+            label: value.to_string(),
+        },
+        // The characters of a password are hidden.
+        "password" => text_field(
+            element,
+            vec!["\u{2022}".repeat(value.chars().count())],
+            size_attribute(element, "size", 20),
+            1,
+        ),
+        _ => text_field(
+            element,
+            vec![value.to_string()],
+            size_attribute(element, "size", 20),
+            1,
+        ),
+    })
+}
+
+/// A text field with the given lines of text, or its placeholder if they are empty.
+fn text_field(element: &ElementData, lines: Vec<String>, columns: usize, rows: usize) -> Control {
+    let placeholder = element.attrs.get("placeholder");
+    let empty = lines.iter().all(String::is_empty);
+    let (lines, placeholder) = match placeholder {
+        Some(placeholder) if empty => (vec![placeholder.clone()], true),
+        _ => (lines, false),
+    };
+    Control::TextField {
+        lines,
+        placeholder,
+        columns,
+        rows,
+    }
+}
+
+/// A size in characters or lines given by an attribute such as `size` or `rows`, or `default`
+/// if it isn't a positive integer.
+fn size_attribute(element: &ElementData, name: &str, default: usize) -> usize {
+    element
+        .attrs
+        .get(name)
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|&size| size > 0)
+        .unwrap_or(default)
+}
+
+/// Gather the labels of the `<option>`s in a `<select>`, including those in `<optgroup>`s,
+/// and find the selected one: the last with a `selected` attribute.
+fn collect_options(node: &Node, options: &mut Vec<String>, selected: &mut Option<usize>) {
+    for child in &node.children {
+        let NodeType::Element(ref element) = child.node_type else {
+            continue;
+        };
+        if element.tag_name.eq_ignore_ascii_case("option") {
+            if element.attrs.contains_key("selected") {
+                *selected = Some(options.len());
+            }
+            options.push(match element.attrs.get("label") {
+                Some(label) => label.clone(),
+                None => collapse_whitespace(&text_content(child)),
+            });
+        } else {
+            collect_options(child, options, selected);
+        }
+    }
+}
+
+/// The text of all the text nodes within a node, in order.
+fn text_content(node: &Node) -> String {
+    match node.node_type {
+        NodeType::Text(ref text) => text.clone(),
+        NodeType::Element(_) => node.children.iter().map(text_content).collect(),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html;
+
+    fn control(source: &str) -> Option<Control> {
+        Control::for_node(&html::parse(source.to_string()))
+    }
+
+    #[test]
+    fn test_controls_from_elements() {
+        assert_eq!(
+            control("<input value=\"Ada\" size=10>"),
+            Some(Control::TextField {
+                lines: vec!["Ada".to_string()],
+                placeholder: false,
+                columns: 10,
+                rows: 1,
+            })
+        );
+        assert_eq!(
+            control("<input type=password value=abc>"),
+            Some(Control::TextField {
+                lines: vec!["\u{2022}\u{2022}\u{2022}".to_string()],
+                placeholder: false,
+                columns: 20,
+                rows: 1,
+            })
+        );
+        assert_eq!(
+            control("<textarea placeholder=\"Notes\" rows=4></textarea>"),
+            Some(Control::TextField {
+                lines: vec!["Notes".to_string()],
+                placeholder: true,
+                columns: 20,
+                rows: 4,
+            })
+        );
+        assert_eq!(
+            control("<input type=CHECKBOX checked>"),
+            Some(Control::Checkbox { checked: true })
+        );
+        assert_eq!(
+            control("<input type=radio>"),
+            Some(Control::Radio { checked: false })
+        );
+        assert_eq!(
+            control("<input type=submit>"),
+            Some(Control::Button {
+                label: "Submit".to_string()
+            })
+        );
+        assert_eq!(
+            control("<button>Save <em>all</em></button>"),
+            Some(Control::Button {
+                label: "Save all".to_string()
+            })
+        );
+        assert_eq!(
+            control(
+                "<select><option>One</option><optgroup><option selected>Two</option>\
+                 <option label=\"3\">Three</option></optgroup></select>"
+            ),
+            Some(Control::Select {
+                options: vec!["One".to_string(), "Two".to_string(), "3".to_string()],
+                selected: Some(1),
+            })
+        );
+        assert_eq!(control("<input type=hidden value=1>"), None);
+        assert_eq!(control("<div></div>"), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! Descriptions of the layout tree as indented text or as JSON, for tests and tools that check
+//! the geometry of boxes.
+
+use std::fmt::Write;
+
+use super::{
+    AnonymousBlock, BlockNode, FlexNode, GridNode, InlineNode, LayoutBox, LineBox, Rect, Replaced,
+    ReplacedNode, TableCellNode, TableNode, TableRowNode, TextRun,
+};
+use crate::dom::{Node, NodeType};
+
+impl LayoutBox<'_> {
+    /// Describe this box and its descendants, one box per line, indented by depth. Each line
+    /// gives the type of the box, the element or text it was generated for, and its content,
+    /// padding, border and margin rects as `x,y widthxheight`.
+    ///
+    /// ```text
+    /// block <div class="a"> content=2,2 100x19.2 padding=0,0 104x23.2 border=0,0 104x23.2 ...
+    ///   anonymous content=2,2 100x19.2 padding=2,2 100x19.2 border=2,2 100x19.2 ...
+    ///     line content=2,2 100x19.2 padding=2,2 100x19.2 border=2,2 100x19.2 ...
+    ///       text "Hello" content=2,2 48x19.2 padding=2,2 48x19.2 border=2,2 48x19.2 ...
+    /// ```
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        self.dump_into(&mut out, 0);
+        out
+    }
+
+    fn dump_into(&self, out: &mut String, depth: usize) {
+        write!(
+            out,
+            "{:indent$}{}",
+            "",
+            self.type_name(),
+            indent = depth * 2
+        )
+        .unwrap();
+        match self.box_type {
+            TextRun(_, ref text) => write!(out, " {:?}", text).unwrap(),
+            AnonymousBlock(_) | LineBox => {}
+            _ => {
+                let style = self.get_style_node();
+                write!(out, " {}", describe_node(style.node)).unwrap();
+                if let Some(pseudo_element) = style.pseudo_element {
+                    write!(out, "::{}", pseudo_element.name()).unwrap();
+                }
+            }
+        }
+        let d = self.dimensions;
+        for (name, rect) in [
+            ("content", d.content),
+            ("padding", d.padding_box()),
+            ("border", d.border_box()),
+            ("margin", d.margin_box()),
+        ] {
+            write!(
+                out,
+                " {}={},{} {}x{}",
+                name, rect.x, rect.y, rect.width, rect.height
+            )
+            .unwrap();
+        }
+        out.push('\n');
+        for child in &self.children {
+            child.dump_into(out, depth + 1);
+        }
+    }
+
+    /// Describe this box and its descendants as a JSON object, with the members `type`,
+    /// `element` (the tag name and attributes of its element, or `null` for anonymous boxes
+    /// and text), `text` (for runs of text), `content`, `padding`, `border` and `margin` (each
+    /// an object with `x`, `y`, `width` and `height`), and `children`.
+    pub fn dump_json(&self) -> String {
+        let mut out = String::new();
+        self.dump_json_into(&mut out);
+        out
+    }
+
+    fn dump_json_into(&self, out: &mut String) {
+        write!(out, "{{\"type\":{}", json_string(self.type_name())).unwrap();
+        out.push_str(",\"element\":");
+        match self.box_type {
+            AnonymousBlock(_) | LineBox | TextRun(..) => out.push_str("null"),
+            _ => match self.get_style_node().node.node_type {
+                NodeType::Element(ref element) => {
+                    let mut attributes: Vec<_> = element.attrs.iter().collect();
+                    attributes.sort();
+                    let attributes: Vec<String> = attributes
+                        .iter()
+                        .map(|(name, value)| {
+                            format!("{}:{}", json_string(name), json_string(value))
+                        })
+                        .collect();
+                    write!(
+                        out,
+                        "{{\"tag\":{},\"attributes\":{{{}}}}}",
+                        json_string(&element.tag_name),
+                        attributes.join(",")
+                    )
+                    .unwrap();
+                }
+                NodeType::Text(_) => out.push_str("null"),
+            },
+        }
+        if let TextRun(_, ref text) = self.box_type {
+            write!(out, ",\"text\":{}", json_string(text)).unwrap();
+        }
+        let d = self.dimensions;
+        for (name, rect) in [
+            ("content", d.content),
+            ("padding", d.padding_box()),
+            ("border", d.border_box()),
+            ("margin", d.margin_box()),
+        ] {
+            write!(out, ",\"{}\":{}", name, json_rect(rect)).unwrap();
+        }
+        out.push_str(",\"children\":[");
+        for (index, child) in self.children.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            child.dump_json_into(out);
+        }
+        out.push_str("]}");
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self.box_type {
+            BlockNode(_) => "block",
+            FlexNode(_) => "flex",
+            GridNode(_) => "grid",
+            TableNode(_) => "table",
+            TableRowNode(_) => "table-row",
+            TableCellNode(_) => "table-cell",
+            InlineNode(_) => "inline",
+            AnonymousBlock(_) => "anonymous",
+            LineBox => "line",
+            TextRun(..) => "text",
+            ReplacedNode(_, Replaced::Marker(_)) => "marker",
+            ReplacedNode(..) => "replaced",
+        }
+    }
+}
+
+/// An element's start tag, or a text node's text.
+fn describe_node(node: &Node) -> String {
+    match node.node_type {
+        NodeType::Element(ref element) => element.to_string(),
+        NodeType::Text(ref text) => format!("{:?}", text),
+    }
+}
+
+fn json_rect(rect: Rect) -> String {
+    format!(
+        "{{\"x\":{},\"y\":{},\"width\":{},\"height\":{}}}",
+        rect.x, rect.y, rect.width, rect.height
+    )
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,600 @@
+//! Flexible box layout.
+//!
+//! http://www.w3.org/TR/css-flexbox-1/
+//!
+//! Flex items are laid out along the main axis (horizontal for `row`, vertical for `column`),
+//! sized by resolving their flexible lengths, and then aligned along the cross axis within their
+//! flex line.
+
+use std::ops::Range;
+
+use super::{
+    align, gaps, keyword, margins, padding_and_border, Align, AnonymousBlock, LayoutBox,
+    PercentBasis, Rect,
+};
+use crate::css::Value::{self, Function, Keyword, Length, List, Number};
+use crate::style::StyledNode;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Row,
+    RowReverse,
+    Column,
+    ColumnReverse,
+}
+
+impl Direction {
+    fn is_row(self) -> bool {
+        matches!(self, Direction::Row | Direction::RowReverse)
+    }
+
+    fn is_reverse(self) -> bool {
+        matches!(self, Direction::RowReverse | Direction::ColumnReverse)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlexWrap {
+    NoWrap,
+    Wrap,
+    WrapReverse,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Justify {
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+/// A flex item's sizes along the main axis, as they are resolved.
+struct FlexItem {
+    /// The index of the item's box among the container's children.
+    index: usize,
+    grow: f32,
+    shrink: f32,
+    /// The flex base size of the content box.
+    basis: f32,
+    /// The flex base size clamped by the item's min and max main sizes.
+    hypothetical: f32,
+    min_main: f32,
+    max_main: f32,
+    /// Margins, borders and padding along the main axis.
+    main_edges: f32,
+    /// Whether the margins at the main-start and main-end sides are `auto`.
+    auto_margins: (bool, bool),
+    align: Align,
+    /// The used main size of the content box.
+    target: f32,
+    frozen: bool,
+}
+
+impl FlexItem {
+    fn outer_hypothetical(&self) -> f32 {
+        self.hypothetical + self.main_edges
+    }
+
+    fn outer_target(&self) -> f32 {
+        self.target + self.main_edges
+    }
+
+    fn auto_margin_count(&self) -> usize {
+        usize::from(self.auto_margins.0) + usize::from(self.auto_margins.1)
+    }
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Lay out the children of a flex container as flex items within its content box, and set
+    /// its content height.
+    ///
+    /// http://www.w3.org/TR/css-flexbox-1/#layout-algorithm
+    pub(super) fn layout_flex_children(&mut self) {
+        let style = self.get_style_node();
+        let direction = direction(style);
+        let row = direction.is_row();
+        let wrap = match keyword(style, "flex-wrap").as_deref() {
+            Some("wrap") => FlexWrap::Wrap,
+            Some("wrap-reverse") => FlexWrap::WrapReverse,
+            _ => FlexWrap::NoWrap,
+        };
+        let justify = justify_content(style);
+        let align_items = align(keyword(style, "align-items").as_deref()).unwrap_or(Align::Stretch);
+        let content = self.dimensions.content;
+        let definite_height = self.definite_height();
+        let (column_gap, row_gap) = gaps(
+            style,
+            PercentBasis {
+                width: Some(content.width),
+                height: definite_height,
+            },
+        );
+        let (main_gap, cross_gap) = if row {
+            (column_gap, row_gap)
+        } else {
+            (row_gap, column_gap)
+        };
+
+        let main_available = if row {
+            Some(content.width)
+        } else {
+            definite_height
+        };
+
+        // Determine the flex base size and hypothetical main size of each item.
+        let mut items = Vec::new();
+        for (index, child) in self.children.iter_mut().enumerate() {
+            if child.is_absolutely_positioned() {
+                // The static position of an absolutely positioned child is the content-box
+                // origin of its container.
+                child.dimensions.content.x = content.x;
+                child.dimensions.content.y = content.y;
+                continue;
+            }
+            items.push(child.flex_item(index, row, content.width, main_available, align_items));
+        }
+
+        // Collect the items into flex lines.
+        let mut lines: Vec<Range<usize>> = Vec::new();
+        let mut start = 0;
+        let mut line_size = 0.0;
+        for (i, item) in items.iter().enumerate() {
+            let outer = item.outer_hypothetical();
+            let needed = if i == start {
+                outer
+            } else {
+                line_size + main_gap + outer
+            };
+            match main_available {
+                Some(available) if wrap != FlexWrap::NoWrap && i > start && needed > available => {
+                    lines.push(start..i);
+                    start = i;
+                    line_size = outer;
+                }
+                _ => line_size = needed,
+            }
+        }
+        if start < items.len() {
+            lines.push(start..items.len());
+        }
+
+        // Resolve the main size of each item, then lay out its contents to find its cross size.
+        for line in &lines {
+            resolve_flexible_lengths(&mut items[line.clone()], main_available, main_gap);
+        }
+        for item in &items {
+            let child = &mut self.children[item.index];
+            if row {
+                child.layout_contents(item.target);
+            } else {
+                child.dimensions.content.height = item.target;
+            }
+        }
+
+        // The cross size of each line is that of its largest item, unless a single line fills
+        // the container.
+        let container_cross = if row {
+            definite_height
+        } else {
+            Some(content.width)
+        };
+        let line_cross_sizes: Vec<f32> = lines
+            .iter()
+            .map(|line| match container_cross {
+                Some(cross) if wrap == FlexWrap::NoWrap => cross,
+                _ => items[line.clone()]
+                    .iter()
+                    .map(|item| {
+                        outer_cross_size(self.children[item.index].dimensions.margin_box(), row)
+                    })
+                    .fold(0.0, f32::max),
+            })
+            .collect();
+
+        // Stretch items with an auto cross size to fill their line.
+        for (line, &line_cross) in lines.iter().zip(&line_cross_sizes) {
+            for item in &items[line.clone()] {
+                let child = &mut self.children[item.index];
+                if item.align == Align::Stretch {
+                    child.stretch(line_cross, row, item.target);
+                }
+            }
+        }
+
+        let main_size = match main_available {
+            Some(size) => size,
+            None => {
+                let longest = lines
+                    .iter()
+                    .map(|line| line_main_size(&items[line.clone()], main_gap))
+                    .fold(0.0, f32::max);
+                self.clamp_size(longest, false)
+            }
+        };
+        let cross_size = match container_cross {
+            Some(size) => size,
+            None => {
+                let gaps = cross_gap * lines.len().saturating_sub(1) as f32;
+                self.clamp_size(line_cross_sizes.iter().sum::<f32>() + gaps, false)
+            }
+        };
+
+        // Place the items along both axes, then move them into position.
+        let mut line_start = 0.0;
+        for (line, &line_cross) in lines.iter().zip(&line_cross_sizes) {
+            let items = &items[line.clone()];
+            let free_space = main_size - line_main_size(items, main_gap);
+            let auto_margins: usize = items.iter().map(FlexItem::auto_margin_count).sum();
+
+            // Positive free space goes to auto margins first, then to `justify-content`.
+            let auto_margin = if free_space > 0.0 && auto_margins > 0 {
+                free_space / auto_margins as f32
+            } else {
+                0.0
+            };
+            let (mut main, between) = if auto_margin > 0.0 {
+                (0.0, 0.0)
+            } else {
+                justify_offsets(justify, free_space, items.len())
+            };
+
+            for item in items {
+                let child = &mut self.children[item.index];
+                let margin_box = child.dimensions.margin_box();
+                if item.auto_margins.0 {
+                    main += auto_margin;
+                }
+                let mut main_position = main;
+                main += item.outer_target();
+                if item.auto_margins.1 {
+                    main += auto_margin;
+                }
+                main += main_gap + between;
+
+                let outer_cross = outer_cross_size(margin_box, row);
+                let mut cross_position = line_start
+                    + match item.align {
+                        Align::Start | Align::Stretch => 0.0,
+                        Align::End => line_cross - outer_cross,
+                        Align::Center => (line_cross - outer_cross) / 2.0,
+                    };
+
+                // Reversed directions mirror the positions along the relevant axis.
+                if direction.is_reverse() {
+                    main_position = main_size - main_position - item.outer_target();
+                }
+                if wrap == FlexWrap::WrapReverse {
+                    cross_position = cross_size - cross_position - outer_cross;
+                }
+
+                let (x, y) = if row {
+                    (main_position, cross_position)
+                } else {
+                    (cross_position, main_position)
+                };
+                child.translate(content.x + x - margin_box.x, content.y + y - margin_box.y);
+                child.apply_relative_position();
+            }
+            line_start += line_cross + cross_gap;
+        }
+
+        self.dimensions.content.height = if row { cross_size } else { main_size };
+    }
+
+    /// Set up this box as a flex item, computing its flex base size and hypothetical main size.
+    /// A percentage flex basis is of `main_size`, the container's inner main size, if it is
+    /// definite. Items in a column are laid out here at their preferred width, since their
+    /// content height is needed to size them.
+    fn flex_item(
+        &mut self,
+        index: usize,
+        row: bool,
+        container_width: f32,
+        main_size: Option<f32>,
+        align_items: Align,
+    ) -> FlexItem {
+        self.resolve_edges();
+        let edges = padding_and_border(self.dimensions, row);
+        let (grow, shrink, basis, align, auto_margins) = match self.box_type {
+            AnonymousBlock(_) => (0.0, 1.0, None, align_items, (false, false)),
+            _ => {
+                let style = self.get_style_node();
+                let (grow, shrink, basis) = flex_factors(style, main_size);
+                let align = align(keyword(style, "align-self").as_deref()).unwrap_or(align_items);
+                let (start, end) = if row {
+                    ("left", "right")
+                } else {
+                    ("top", "bottom")
+                };
+                let is_auto = |side| {
+                    let name = format!("margin-{}", side);
+                    matches!(
+                        style.side_value(side, &name, "margin"),
+                        Some(Keyword(ref k)) if k == "auto"
+                    )
+                };
+                let basis = basis.map(|basis| self.content_box_size(basis, edges));
+                (grow, shrink, basis, align, (is_auto(start), is_auto(end)))
+            }
+        };
+
+        if !row {
+            let width = self.shrink_to_fit_width(container_width);
+            self.layout_contents(width);
+        }
+        let main_name = if row { "width" } else { "height" };
+        let basis = basis
+            .or_else(|| self.content_size(main_name, edges))
+            .unwrap_or_else(|| {
+                if row {
+                    self.max_content_width() - edges
+                } else {
+                    self.dimensions.content.height
+                }
+            });
+        let min_main = self.clamp_size(0.0, row);
+        let max_main = self.clamp_size(f32::INFINITY, row);
+        FlexItem {
+            index,
+            grow,
+            shrink,
+            basis,
+            hypothetical: basis.min(max_main).max(min_main),
+            min_main,
+            max_main,
+            main_edges: edges + margins(self.dimensions, row),
+            auto_margins,
+            align,
+            target: 0.0,
+            frozen: false,
+        }
+    }
+
+    /// Stretch a flex item with an auto cross size to fill a line of the given cross size. An
+    /// item stretched horizontally is laid out again at its new width.
+    fn stretch(&mut self, line_cross: f32, row: bool, main_size: f32) {
+        let edges = padding_and_border(self.dimensions, !row);
+        let cross_name = if row { "height" } else { "width" };
+        if self.content_size(cross_name, edges).is_some() {
+            return;
+        }
+        let outer_edges = edges + margins(self.dimensions, !row);
+        let size = self.clamp_size((line_cross - outer_edges).max(0.0), !row);
+        if row {
+            self.dimensions.content.height = size;
+        } else if size != self.dimensions.content.width {
+            self.layout_contents(size);
+            self.dimensions.content.height = main_size;
+        }
+    }
+
+    /// The content width of a flex container if all of its items were laid out at their
+    /// preferred widths on a single line.
+    pub(super) fn flex_max_content_width(&self) -> f32 {
+        let style = self.get_style_node();
+        let items = self
+            .children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .map(|child| {
+                child.max_content_width()
+                    + child.edge("margin-left", "margin")
+                    + child.edge("margin-right", "margin")
+            });
+        if direction(style).is_row() {
+            let (gap, _) = gaps(style, PercentBasis::default());
+            let (count, width) =
+                items.fold((0, 0.0), |(count, width), item| (count + 1, width + item));
+            width + gap * (count as f32 - 1.0).max(0.0)
+        } else {
+            items.fold(0.0, f32::max)
+        }
+    }
+}
+
+/// Resolve the main sizes of the items on one flex line, growing or shrinking them to fill
+/// `available` space. An indefinite available space leaves every item at its hypothetical size.
+///
+/// http://www.w3.org/TR/css-flexbox-1/#resolve-flexible-lengths
+fn resolve_flexible_lengths(items: &mut [FlexItem], available: Option<f32>, gap: f32) {
+    let gaps = gap * items.len().saturating_sub(1) as f32;
+    let Some(available) = available else {
+        for item in items.iter_mut() {
+            item.target = item.hypothetical;
+        }
+        return;
+    };
+
+    let hypothetical = items.iter().map(FlexItem::outer_hypothetical).sum::<f32>() + gaps;
+    let growing = hypothetical < available;
+    let factor = |item: &FlexItem| if growing { item.grow } else { item.shrink };
+
+    // Items that can't flex in the needed direction keep their hypothetical size.
+    for item in items.iter_mut() {
+        item.target = item.hypothetical;
+        item.frozen = factor(item) == 0.0
+            || (growing && item.basis > item.hypothetical)
+            || (!growing && item.basis < item.hypothetical);
+    }
+
+    let free_space = |items: &[FlexItem]| {
+        let used = items
+            .iter()
+            .map(|item| item.main_edges + if item.frozen { item.target } else { item.basis })
+            .sum::<f32>();
+        available - gaps - used
+    };
+    let initial_free_space = free_space(items);
+
+    while items.iter().any(|item| !item.frozen) {
+        let mut remaining = free_space(items);
+        let factors: f32 = items.iter().filter(|item| !item.frozen).map(factor).sum();
+        if factors < 1.0 && (initial_free_space * factors).abs() < remaining.abs() {
+            remaining = initial_free_space * factors;
+        }
+
+        // Shrinking is weighted by the flex base size, so that large items shrink more.
+        let scaled = |item: &FlexItem| {
+            if growing {
+                item.grow
+            } else {
+                item.shrink * item.basis
+            }
+        };
+        let total: f32 = items.iter().filter(|item| !item.frozen).map(scaled).sum();
+
+        // Distribute the free space, then fix any min or max size violations.
+        let mut violations = Vec::with_capacity(items.len());
+        let mut total_violation = 0.0;
+        for item in items.iter_mut() {
+            if item.frozen {
+                violations.push(0.0);
+                continue;
+            }
+            let size = if total > 0.0 {
+                item.basis + remaining * scaled(item) / total
+            } else {
+                item.basis
+            };
+            item.target = size.min(item.max_main).max(item.min_main);
+            violations.push(item.target - size);
+            total_violation += item.target - size;
+        }
+        for (item, violation) in items.iter_mut().zip(violations) {
+            if total_violation == 0.0
+                || (total_violation > 0.0 && violation > 0.0)
+                || (total_violation < 0.0 && violation < 0.0)
+            {
+                item.frozen = true;
+            }
+        }
+    }
+}
+
+/// The initial offset and extra space between items for `justify-content`. Space that can't be
+/// distributed (because there is none, or only one item) falls back to start or center.
+fn justify_offsets(justify: Justify, free_space: f32, count: usize) -> (f32, f32) {
+    let count = count as f32;
+    match justify {
+        Justify::FlexStart => (0.0, 0.0),
+        Justify::FlexEnd => (free_space, 0.0),
+        Justify::Center => (free_space / 2.0, 0.0),
+        Justify::SpaceBetween if free_space > 0.0 && count > 1.0 => {
+            (0.0, free_space / (count - 1.0))
+        }
+        Justify::SpaceBetween => (0.0, 0.0),
+        Justify::SpaceAround if free_space > 0.0 => (free_space / count / 2.0, free_space / count),
+        Justify::SpaceEvenly if free_space > 0.0 => {
+            (free_space / (count + 1.0), free_space / (count + 1.0))
+        }
+        Justify::SpaceAround | Justify::SpaceEvenly => (free_space / 2.0, 0.0),
+    }
+}
+
+/// The total outer main size of a line of items, including the gaps between them.
+fn line_main_size(items: &[FlexItem], gap: f32) -> f32 {
+    items.iter().map(FlexItem::outer_target).sum::<f32>()
+        + gap * items.len().saturating_sub(1) as f32
+}
+
+/// The size of a margin box along the cross axis.
+fn outer_cross_size(margin_box: Rect, row: bool) -> f32 {
+    if row {
+        margin_box.height
+    } else {
+        margin_box.width
+    }
+}
+
+/// The flex grow factor, flex shrink factor and flex basis of an item, from the `flex`
+/// shorthand and its longhands. A basis of `None` means `auto`: the item's main size property,
+/// or else the size of its content. A percentage basis is of `main_size`, and is treated as
+/// `auto` when the container's main size is indefinite.
+///
+/// http://www.w3.org/TR/css-flexbox-1/#flex-property
+fn flex_factors(style: &StyledNode, main_size: Option<f32>) -> (f32, f32, Option<f32>) {
+    let (mut grow, mut shrink, mut basis) = match style.value("flex") {
+        Some(Keyword(ref keyword)) if keyword == "none" => Some((0.0, 0.0, None)),
+        Some(Keyword(ref keyword)) if keyword == "auto" => Some((1.0, 1.0, None)),
+        Some(List(ref values)) => parse_flex(values, main_size),
+        Some(ref value) => parse_flex(std::slice::from_ref(value), main_size),
+        None => None,
+    }
+    .unwrap_or((0.0, 1.0, None));
+    if let Some(Number(factor)) = style.value("flex-grow") {
+        grow = factor;
+    }
+    if let Some(Number(factor)) = style.value("flex-shrink") {
+        shrink = factor;
+    }
+    if let Some(value) = style.value("flex-basis") {
+        basis = flex_basis(&value, main_size).unwrap_or(basis);
+    }
+    (grow.max(0.0), shrink.max(0.0), basis)
+}
+
+/// Parse the `<grow> <shrink>? || <basis>` form of the `flex` shorthand, with the basis before
+/// or after the factors. An omitted grow factor is 1, an omitted shrink factor is 1, and an
+/// omitted basis is 0. Returns `None` if the values don't match.
+fn parse_flex(values: &[Value], main_size: Option<f32>) -> Option<(f32, f32, Option<f32>)> {
+    let number = |value: &Value| match *value {
+        Number(number) => Some(number),
+        _ => None,
+    };
+    // A number is a factor unless it is the third value, which can only be a zero basis.
+    let (factors, basis) = match values {
+        [first, rest @ ..] if number(first).is_none() => (rest, Some(first)),
+        [factors @ .., last] if values.len() == 3 || number(last).is_none() => {
+            (factors, Some(last))
+        }
+        factors => (factors, None),
+    };
+    let factors: Vec<f32> = factors.iter().map(number).collect::<Option<_>>()?;
+    let (grow, shrink) = match *factors.as_slice() {
+        [] if basis.is_some() => (1.0, 1.0),
+        [grow] => (grow, 1.0),
+        [grow, shrink] => (grow, shrink),
+        _ => return None,
+    };
+    let basis = match basis {
+        Some(value) => flex_basis(value, main_size)?,
+        None => Some(0.0),
+    };
+    Some((grow, shrink, basis))
+}
+
+/// Parse a flex basis: `Some(None)` for `auto` or `content`, or `Some(Some(size))` for a
+/// length, percentage or `calc()` expression. Returns `None` for other values.
+fn flex_basis(value: &Value, main_size: Option<f32>) -> Option<Option<f32>> {
+    match *value {
+        Keyword(ref keyword) if keyword == "auto" || keyword == "content" => Some(None),
+        Length(..) | Number(_) | Function(..) => match value.resolve_length(main_size) {
+            // A percentage of an indefinite size is treated as `content`.
+            None if value.resolve_length(Some(0.0)).is_some() => Some(None),
+            size => size.map(Some),
+        },
+        _ => None,
+    }
+}
+
+fn direction(style: &StyledNode) -> Direction {
+    match keyword(style, "flex-direction").as_deref() {
+        Some("row-reverse") => Direction::RowReverse,
+        Some("column") => Direction::Column,
+        Some("column-reverse") => Direction::ColumnReverse,
+        _ => Direction::Row,
+    }
+}
+
+fn justify_content(style: &StyledNode) -> Justify {
+    match keyword(style, "justify-content").as_deref() {
+        Some("flex-end" | "end") => Justify::FlexEnd,
+        Some("center") => Justify::Center,
+        Some("space-between") => Justify::SpaceBetween,
+        Some("space-around") => Justify::SpaceAround,
+        Some("space-evenly") => Justify::SpaceEvenly,
+        _ => Justify::FlexStart,
+    }
+}
@@ -0,0 +1,154 @@
+//! Floats and clearance.
+//!
+//! http://www.w3.org/TR/CSS2/visuren.html#floats
+
+use super::{Dimensions, LayoutBox, Rect};
+use crate::style::{Clear, Float};
+
+/// The floats placed so far in a block formatting context, as margin boxes in document
+/// coordinates.
+#[derive(Default)]
+pub struct FloatContext {
+    floats: Vec<(Float, Rect)>,
+}
+
+impl FloatContext {
+    /// The horizontal space between `left` and `right` that isn't covered by a float overlapping
+    /// the band from `y` to `y + height`.
+    pub fn available_space(&self, y: f32, height: f32, left: f32, right: f32) -> (f32, f32) {
+        let mut space = (left, right);
+        for (side, rect) in &self.floats {
+            let overlaps = rect.y + rect.height > y && (rect.y <= y || rect.y < y + height);
+            if !overlaps {
+                continue;
+            }
+            match side {
+                Float::Left => space.0 = space.0.max(rect.x + rect.width),
+                Float::Right => space.1 = space.1.min(rect.x),
+                Float::None => {}
+            }
+        }
+        space
+    }
+
+    /// Find the highest position at or below `y` where a box of the given size fits beside the
+    /// floats, returning that position and the left and right edges of the space there. If the
+    /// box is too wide to ever fit, it is placed below all the floats.
+    pub fn find_space(
+        &self,
+        mut y: f32,
+        width: f32,
+        height: f32,
+        left: f32,
+        right: f32,
+    ) -> (f32, f32, f32) {
+        loop {
+            let (space_left, space_right) = self.available_space(y, height, left, right);
+            if space_right - space_left >= width {
+                return (y, space_left, space_right);
+            }
+            match self.next_bottom(y) {
+                Some(bottom) => y = bottom,
+                None => return (y, space_left, space_right),
+            }
+        }
+    }
+
+    /// The closest bottom edge of a float below `y`.
+    fn next_bottom(&self, y: f32) -> Option<f32> {
+        self.floats
+            .iter()
+            .map(|(_, rect)| rect.y + rect.height)
+            .filter(|&bottom| bottom > y)
+            .reduce(f32::min)
+    }
+
+    /// Place the margin box of a new float as high as possible at or below `y`, and as far to
+    /// its side as possible. Returns the position of the margin box.
+    fn place(
+        &mut self,
+        side: Float,
+        width: f32,
+        height: f32,
+        y: f32,
+        left: f32,
+        right: f32,
+    ) -> (f32, f32) {
+        // A float's top may not be higher than the top of any earlier float.
+        let y = self.floats.iter().map(|(_, rect)| rect.y).fold(y, f32::max);
+        let (y, space_left, space_right) = self.find_space(y, width, height, left, right);
+        let x = match side {
+            Float::Right => space_right - width,
+            Float::Left | Float::None => space_left,
+        };
+        self.floats.push((
+            side,
+            Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+        ));
+        (x, y)
+    }
+
+    /// The position a box with the given `clear` value must be moved down to, if any.
+    pub fn clearance(&self, clear: Clear) -> Option<f32> {
+        self.floats
+            .iter()
+            .filter(|(side, _)| match clear {
+                Clear::None => false,
+                Clear::Left => *side == Float::Left,
+                Clear::Right => *side == Float::Right,
+                Clear::Both => true,
+            })
+            .map(|(_, rect)| rect.y + rect.height)
+            .reduce(f32::max)
+    }
+
+    /// The lowest bottom edge of all the floats.
+    pub fn bottom(&self) -> Option<f32> {
+        self.clearance(Clear::Both)
+    }
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Lay out a floated box within `containing_block`, and place it beside the floats already
+    /// in `floats`. `y` is the highest position allowed for its top margin edge.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#float-width
+    pub(super) fn layout_float(
+        &mut self,
+        containing_block: Dimensions,
+        y: f32,
+        floats: &mut FloatContext,
+    ) {
+        // Auto margins on floats are treated as zero, and an auto width shrinks to fit the
+        // contents.
+        self.resolve_edges();
+        let width = self.shrink_to_fit_width(containing_block.content.width);
+
+        // Floats establish a new block formatting context. Lay out the contents at the origin,
+        // then move everything into place once the float's size is known.
+        self.layout_contents(width);
+
+        let y = floats
+            .clearance(self.clear())
+            .map_or(y, |clear_y| clear_y.max(y));
+        let margin_box = self.dimensions.margin_box();
+        let left = containing_block.content.x;
+        let right = left + containing_block.content.width;
+        let (x, y) = floats.place(
+            self.float(),
+            margin_box.width,
+            margin_box.height,
+            y,
+            left,
+            right,
+        );
+        self.translate(x - margin_box.x, y - margin_box.y);
+
+        self.apply_relative_position();
+    }
+}
@@ -0,0 +1,835 @@
+//! Grid layout.
+//!
+//! http://www.w3.org/TR/css-grid-1/
+//!
+//! Grid items are first placed into the cells of the grid, then the columns are sized to fit
+//! the items in them, then the rows, with the items laid out at the width of their grid area.
+
+use std::ops::Range;
+
+use super::{
+    align, gaps, keyword, margins, padding_and_border, Align, AnonymousBlock, LayoutBox,
+    PercentBasis,
+};
+use crate::css::Unit::{Fr, Percent, Px};
+use crate::css::Value::{self, Function, Keyword, Length, List, Number};
+use crate::style::StyledNode;
+
+/// One end of a track sizing function.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Breadth {
+    Fixed(f32),
+    Percent(f32),
+    /// A flexible size in `fr` units.
+    Flex(f32),
+    /// Sized to fit the content of the track (`auto`, `min-content` or `max-content`).
+    Auto,
+}
+
+/// The sizing function of a single grid track, as in `minmax(<min>, <max>)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct TrackSize {
+    min: Breadth,
+    max: Breadth,
+}
+
+impl TrackSize {
+    const AUTO: TrackSize = TrackSize {
+        min: Breadth::Auto,
+        max: Breadth::Auto,
+    };
+
+    /// The fixed size of this track, used to count `auto-fill` repetitions.
+    fn definite_size(&self) -> f32 {
+        match (self.min, self.max) {
+            (_, Breadth::Fixed(size)) | (Breadth::Fixed(size), _) => size,
+            _ => 0.0,
+        }
+    }
+
+    fn flex_factor(&self) -> Option<f32> {
+        match self.max {
+            Breadth::Flex(factor) => Some(factor),
+            _ => None,
+        }
+    }
+}
+
+/// The space available when sizing the tracks along one axis.
+#[derive(Clone, Copy)]
+enum AvailableSpace {
+    Definite(f32),
+    MinContent,
+    MaxContent,
+}
+
+/// One end of an item's placement along one axis, from `grid-column-start` and friends.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum GridLine {
+    Auto,
+    /// A line number, counting from 1 at the start of the explicit grid or from -1 at its end.
+    Line(i32),
+    Span(usize),
+}
+
+/// An item's position along one axis: its first track, if it is fixed, and the number of
+/// tracks it spans.
+#[derive(Clone, Copy)]
+struct AxisPlacement {
+    start: Option<usize>,
+    span: usize,
+}
+
+/// The tracks and item areas of a grid container.
+struct Grid {
+    columns: Vec<TrackSize>,
+    rows: Vec<TrackSize>,
+    items: Vec<GridItem>,
+}
+
+/// A grid item and the area of the grid it occupies.
+struct GridItem {
+    /// The index of the item's box among the container's children.
+    index: usize,
+    rows: Range<usize>,
+    columns: Range<usize>,
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Lay out the children of a grid container as grid items within its content box, and set
+    /// its content height.
+    ///
+    /// http://www.w3.org/TR/css-grid-1/#layout-algorithm
+    pub(super) fn layout_grid_children(&mut self) {
+        let style = self.get_style_node();
+        let justify_items =
+            align(keyword(style, "justify-items").as_deref()).unwrap_or(Align::Stretch);
+        let align_items = align(keyword(style, "align-items").as_deref()).unwrap_or(Align::Stretch);
+
+        let content = self.dimensions.content;
+        let definite_height = self.definite_height();
+        let (column_gap, row_gap) = gaps(
+            style,
+            PercentBasis {
+                width: Some(content.width),
+                height: definite_height,
+            },
+        );
+        let grid = self.build_grid(Some(content.width), definite_height);
+
+        for child in &mut self.children {
+            if child.is_absolutely_positioned() {
+                // The static position of an absolutely positioned child is the content-box
+                // origin of its container.
+                child.dimensions.content.x = content.x;
+                child.dimensions.content.y = content.y;
+            }
+        }
+
+        // Size the columns, then lay out each item at the width of its grid area. The grid area
+        // is the item's containing block, but its height isn't known until the rows are sized.
+        let contributions: Vec<_> = grid
+            .items
+            .iter()
+            .map(|item| {
+                let child = &mut self.children[item.index];
+                child.resolve_edges();
+                let margins = margins(child.dimensions, true);
+                (
+                    item.columns.clone(),
+                    child.min_content_width() + margins,
+                    child.max_content_width() + margins,
+                )
+            })
+            .collect();
+        let columns = size_tracks(
+            &grid.columns,
+            &contributions,
+            AvailableSpace::Definite(content.width),
+            column_gap,
+        );
+        for item in &grid.items {
+            let child = &mut self.children[item.index];
+            let area_width = span_size(&columns, &item.columns, column_gap);
+            child.percent_basis = PercentBasis {
+                width: Some(area_width),
+                height: None,
+            };
+            child.resolve_edges();
+            let justify = child.self_alignment("justify-self", justify_items);
+            let edges = padding_and_border(child.dimensions, true);
+            let width = match child.content_size("width", edges) {
+                None if justify == Align::Stretch => {
+                    let outer_edges = edges + margins(child.dimensions, true);
+                    child.clamp_size(area_width - outer_edges, true)
+                }
+                _ => child.shrink_to_fit_width(area_width),
+            };
+            child.layout_contents(width);
+        }
+
+        // Size the rows to fit the laid out items.
+        let contributions: Vec<_> = grid
+            .items
+            .iter()
+            .map(|item| {
+                let height = self.children[item.index].dimensions.margin_box().height;
+                (item.rows.clone(), height, height)
+            })
+            .collect();
+        let available = match definite_height {
+            Some(height) => AvailableSpace::Definite(height),
+            None => AvailableSpace::MaxContent,
+        };
+        let rows = size_tracks(&grid.rows, &contributions, available, row_gap);
+
+        // Align each item within its grid area, and move it into place.
+        for item in &grid.items {
+            let child = &mut self.children[item.index];
+            let x = span_offset(&columns, item.columns.start, column_gap);
+            let y = span_offset(&rows, item.rows.start, row_gap);
+            let area_width = span_size(&columns, &item.columns, column_gap);
+            let area_height = span_size(&rows, &item.rows, row_gap);
+
+            let justify = child.self_alignment("justify-self", justify_items);
+            let align = child.self_alignment("align-self", align_items);
+            let edges = padding_and_border(child.dimensions, false);
+            child.percent_basis.height = Some(area_height);
+            match child.content_size("height", edges) {
+                Some(height) => child.dimensions.content.height = child.clamp_size(height, false),
+                None if align == Align::Stretch => {
+                    let outer_edges = edges + margins(child.dimensions, false);
+                    child.dimensions.content.height =
+                        child.clamp_size(area_height - outer_edges, false);
+                }
+                None => {}
+            }
+
+            let margin_box = child.dimensions.margin_box();
+            let x = x + alignment_offset(justify, area_width - margin_box.width);
+            let y = y + alignment_offset(align, area_height - margin_box.height);
+            child.translate(content.x + x - margin_box.x, content.y + y - margin_box.y);
+            child.apply_relative_position();
+        }
+
+        self.dimensions.content.height = match definite_height {
+            Some(height) => height,
+            None => self.clamp_size(span_size(&rows, &(0..rows.len()), row_gap), false),
+        };
+    }
+
+    /// The min-content or max-content width of the columns of a grid container.
+    pub(super) fn grid_content_width(&self, min_content: bool) -> f32 {
+        let (column_gap, _) = gaps(self.get_style_node(), PercentBasis::default());
+        let grid = self.build_grid(None, None);
+        let contributions: Vec<_> = grid
+            .items
+            .iter()
+            .map(|item| {
+                let child = &self.children[item.index];
+                let margins =
+                    child.edge("margin-left", "margin") + child.edge("margin-right", "margin");
+                (
+                    item.columns.clone(),
+                    child.min_content_width() + margins,
+                    child.max_content_width() + margins,
+                )
+            })
+            .collect();
+        let available = if min_content {
+            AvailableSpace::MinContent
+        } else {
+            AvailableSpace::MaxContent
+        };
+        let columns = size_tracks(&grid.columns, &contributions, available, column_gap);
+        span_size(&columns, &(0..columns.len()), column_gap)
+    }
+
+    /// Read the explicit grid of this container and place its items in it, adding implicit
+    /// tracks as needed. `width` and `height` are the definite size of the content box, if
+    /// known, used to count `auto-fill` repetitions.
+    fn build_grid(&self, width: Option<f32>, height: Option<f32>) -> Grid {
+        let style = self.get_style_node();
+        let (column_gap, row_gap) = gaps(style, PercentBasis { width, height });
+        let mut columns = track_list(style.value("grid-template-columns"), width, column_gap);
+        let mut rows = track_list(style.value("grid-template-rows"), height, row_gap);
+        let column_flow = matches!(
+            style.value("grid-auto-flow"),
+            Some(Keyword(ref flow)) if flow == "column"
+        );
+
+        // Resolve each item's line placement against the explicit grid.
+        let mut indices = Vec::new();
+        let mut placements = Vec::new();
+        for (index, child) in self.children.iter().enumerate() {
+            if child.is_absolutely_positioned() {
+                continue;
+            }
+            let (row, column) = match child.box_type {
+                AnonymousBlock(_) => (
+                    resolve_placement((GridLine::Auto, GridLine::Auto), rows.len()),
+                    resolve_placement((GridLine::Auto, GridLine::Auto), columns.len()),
+                ),
+                _ => {
+                    let style = child.get_style_node();
+                    (
+                        resolve_placement(grid_lines(style, "row"), rows.len()),
+                        resolve_placement(grid_lines(style, "column"), columns.len()),
+                    )
+                }
+            };
+            indices.push(index);
+            placements.push(if column_flow {
+                (column, row)
+            } else {
+                (row, column)
+            });
+        }
+
+        let minor_count = if column_flow {
+            rows.len()
+        } else {
+            columns.len()
+        };
+        let areas = place_items(&placements, minor_count);
+
+        // Tracks beyond the explicit grid are sized by `grid-auto-rows` and `grid-auto-columns`.
+        let items: Vec<GridItem> = indices
+            .into_iter()
+            .zip(areas)
+            .map(|(index, (major, minor))| {
+                let (rows, columns) = if column_flow {
+                    (minor, major)
+                } else {
+                    (major, minor)
+                };
+                GridItem {
+                    index,
+                    rows,
+                    columns,
+                }
+            })
+            .collect();
+        let row_count = items
+            .iter()
+            .map(|item| item.rows.end)
+            .fold(rows.len(), usize::max);
+        let column_count = items
+            .iter()
+            .map(|item| item.columns.end)
+            .fold(columns.len(), usize::max);
+        rows.resize(row_count, auto_track(style.value("grid-auto-rows")));
+        columns.resize(column_count, auto_track(style.value("grid-auto-columns")));
+        Grid {
+            columns,
+            rows,
+            items,
+        }
+    }
+
+    /// The value of `justify-self` or `align-self` for a grid item, falling back to the
+    /// container's `justify-items` or `align-items`.
+    fn self_alignment(&self, name: &str, default: Align) -> Align {
+        match self.box_type {
+            AnonymousBlock(_) => default,
+            _ => align(keyword(self.get_style_node(), name).as_deref()).unwrap_or(default),
+        }
+    }
+}
+
+/// The components of a space-separated value.
+fn components(value: Value) -> Vec<Value> {
+    match value {
+        List(values) => values,
+        value => vec![value],
+    }
+}
+
+/// Parse a `grid-template-columns` or `grid-template-rows` track list, expanding `repeat()`.
+/// `auto-fill` and `auto-fit` repeat the tracks as many times as fit in `available`.
+///
+/// http://www.w3.org/TR/css-grid-1/#track-sizing
+fn track_list(value: Option<Value>, available: Option<f32>, gap: f32) -> Vec<TrackSize> {
+    let mut tracks = Vec::new();
+    let mut auto_repeat = None;
+    for value in value.map(components).unwrap_or_default() {
+        match value {
+            Function(ref name, ref arguments) if name == "repeat" && arguments.len() == 2 => {
+                let repeated: Vec<TrackSize> = components(arguments[1].clone())
+                    .into_iter()
+                    .filter_map(track_size)
+                    .collect();
+                match arguments[0] {
+                    Number(count) if count >= 1.0 => {
+                        for _ in 0..count as usize {
+                            tracks.extend_from_slice(&repeated);
+                        }
+                    }
+                    Keyword(ref count) if count == "auto-fill" || count == "auto-fit" => {
+                        auto_repeat = Some((tracks.len(), repeated));
+                    }
+                    _ => {}
+                }
+            }
+            value => tracks.extend(track_size(value)),
+        }
+    }
+
+    if let Some((position, repeated)) = auto_repeat {
+        let size = |tracks: &[TrackSize]| {
+            tracks.iter().map(TrackSize::definite_size).sum::<f32>() + gap * tracks.len() as f32
+        };
+        let repetition = size(&repeated);
+        let count = match available {
+            Some(available) if repetition > 0.0 => ((available - size(&tracks) + gap) / repetition)
+                .floor()
+                .max(1.0) as usize,
+            _ => 1,
+        };
+        let repeated = repeated
+            .iter()
+            .cycle()
+            .take(repeated.len() * count)
+            .copied();
+        tracks.splice(position..position, repeated);
+    }
+    tracks
+}
+
+/// The size of implicit tracks, from `grid-auto-rows` or `grid-auto-columns`.
+fn auto_track(value: Option<Value>) -> TrackSize {
+    value.and_then(track_size).unwrap_or(TrackSize::AUTO)
+}
+
+fn track_size(value: Value) -> Option<TrackSize> {
+    let (min, max) = match value {
+        Function(ref name, ref arguments) if name == "minmax" && arguments.len() == 2 => {
+            (breadth(&arguments[0])?, breadth(&arguments[1])?)
+        }
+        value => {
+            let breadth = breadth(&value)?;
+            (breadth, breadth)
+        }
+    };
+    // A flexible size can't be a minimum, and behaves as `auto` there.
+    let min = match min {
+        Breadth::Flex(_) => Breadth::Auto,
+        min => min,
+    };
+    Some(TrackSize { min, max })
+}
+
+fn breadth(value: &Value) -> Option<Breadth> {
+    match *value {
+        Length(size, Px) => Some(Breadth::Fixed(size)),
+        Length(percent, Percent) => Some(Breadth::Percent(percent)),
+        Length(factor, Fr) => Some(Breadth::Flex(factor)),
+        Number(0.0) => Some(Breadth::Fixed(0.0)),
+        Keyword(ref keyword) if matches!(&**keyword, "auto" | "min-content" | "max-content") => {
+            Some(Breadth::Auto)
+        }
+        _ => None,
+    }
+}
+
+/// Read an item's start and end lines along one axis (`"row"` or `"column"`), from the
+/// `grid-row`/`grid-column` shorthands and the `-start` and `-end` longhands.
+fn grid_lines(style: &StyledNode, axis: &str) -> (GridLine, GridLine) {
+    let (mut start, mut end) = match style.value(&format!("grid-{}", axis)) {
+        Some(value) => {
+            let components = components(value);
+            let mut parts = components.split(|value| matches!(value, Keyword(k) if k == "/"));
+            (
+                parts.next().map_or(GridLine::Auto, grid_line),
+                parts.next().map_or(GridLine::Auto, grid_line),
+            )
+        }
+        None => (GridLine::Auto, GridLine::Auto),
+    };
+    if let Some(value) = style.value(&format!("grid-{}-start", axis)) {
+        start = grid_line(&components(value));
+    }
+    if let Some(value) = style.value(&format!("grid-{}-end", axis)) {
+        end = grid_line(&components(value));
+    }
+    (start, end)
+}
+
+fn grid_line(components: &[Value]) -> GridLine {
+    match components {
+        [Number(line)] if *line != 0.0 => GridLine::Line(*line as i32),
+        [Keyword(span), Number(count)] | [Number(count), Keyword(span)]
+            if span == "span" && *count >= 1.0 =>
+        {
+            GridLine::Span(*count as usize)
+        }
+        _ => GridLine::Auto,
+    }
+}
+
+/// Resolve a pair of grid lines to a placement along an axis with `explicit` explicit tracks.
+/// Lines before the start of the grid are clamped to its first line.
+///
+/// http://www.w3.org/TR/css-grid-1/#line-placement
+fn resolve_placement(lines: (GridLine, GridLine), explicit: usize) -> AxisPlacement {
+    let line = |number: i32| {
+        if number > 0 {
+            number as usize - 1
+        } else {
+            (explicit as i32 + 1 + number).max(0) as usize
+        }
+    };
+    let (start, span) = match lines {
+        (GridLine::Line(start), GridLine::Line(end)) => {
+            let (start, end) = (line(start), line(end));
+            (Some(start.min(end)), start.abs_diff(end).max(1))
+        }
+        (GridLine::Line(start), GridLine::Span(span)) => (Some(line(start)), span),
+        (GridLine::Line(start), GridLine::Auto) => (Some(line(start)), 1),
+        (GridLine::Span(span), GridLine::Line(end)) => {
+            let end = line(end).max(1);
+            (Some(end.saturating_sub(span)), span.min(end))
+        }
+        (GridLine::Auto, GridLine::Line(end)) => (Some(line(end).saturating_sub(1)), 1),
+        (GridLine::Span(span), _) | (GridLine::Auto, GridLine::Span(span)) => (None, span),
+        (GridLine::Auto, GridLine::Auto) => (None, 1),
+    };
+    AxisPlacement { start, span }
+}
+
+/// The cells of the grid taken by the items placed so far, indexed by major then minor track
+/// (rows then columns, unless the grid flows in columns).
+#[derive(Default)]
+struct Occupancy {
+    cells: Vec<Vec<bool>>,
+}
+
+impl Occupancy {
+    fn fits(&self, major: Range<usize>, minor: Range<usize>) -> bool {
+        major.into_iter().all(|row| {
+            let cells = self.cells.get(row).map_or(&[][..], Vec::as_slice);
+            minor
+                .clone()
+                .all(|column| !cells.get(column).copied().unwrap_or(false))
+        })
+    }
+
+    fn occupy(&mut self, major: Range<usize>, minor: Range<usize>) {
+        if self.cells.len() < major.end {
+            self.cells.resize(major.end, Vec::new());
+        }
+        for row in &mut self.cells[major] {
+            if row.len() < minor.end {
+                row.resize(minor.end, false);
+            }
+            row[minor.clone()].fill(true);
+        }
+    }
+}
+
+/// Place items given their (major, minor) placements, returning the (major, minor) tracks of
+/// each. Items with an automatic position fill the first free cells, moving along the minor
+/// axis and then on to the next major track.
+///
+/// http://www.w3.org/TR/css-grid-1/#auto-placement-algo
+fn place_items(
+    placements: &[(AxisPlacement, AxisPlacement)],
+    explicit_minor: usize,
+) -> Vec<(Range<usize>, Range<usize>)> {
+    let minor_count = placements
+        .iter()
+        .map(|(_, minor)| minor.start.unwrap_or(0) + minor.span)
+        .fold(explicit_minor, usize::max);
+    let mut grid = Occupancy::default();
+    let mut areas = vec![None; placements.len()];
+    let mut place = |grid: &mut Occupancy, i: usize, major: usize, minor: usize| {
+        let (major_placement, minor_placement) = placements[i];
+        let area = (
+            major..major + major_placement.span,
+            minor..minor + minor_placement.span,
+        );
+        grid.occupy(area.0.clone(), area.1.clone());
+        areas[i] = Some(area);
+    };
+
+    // First, items fixed in both axes.
+    for (i, (major, minor)) in placements.iter().enumerate() {
+        if let (Some(major), Some(minor)) = (major.start, minor.start) {
+            place(&mut grid, i, major, minor);
+        }
+    }
+
+    // Then items locked to a major track, at the first minor position where they fit.
+    for (i, (major, minor)) in placements.iter().enumerate() {
+        if let (Some(major_start), None) = (major.start, minor.start) {
+            let major_range = major_start..major_start + major.span;
+            let minor_start = (0..=minor_count.saturating_sub(minor.span))
+                .find(|&start| grid.fits(major_range.clone(), start..start + minor.span))
+                .unwrap_or(0);
+            place(&mut grid, i, major_start, minor_start);
+        }
+    }
+
+    // Finally, the remaining items in order, moving a cursor through the grid.
+    let (mut cursor_major, mut cursor_minor) = (0, 0);
+    for (i, (major, minor)) in placements.iter().enumerate() {
+        if major.start.is_some() {
+            continue;
+        }
+        match minor.start {
+            Some(minor_start) => {
+                if minor_start < cursor_minor {
+                    cursor_major += 1;
+                }
+                cursor_minor = minor_start;
+                let minor_range = minor_start..minor_start + minor.span;
+                while !grid.fits(cursor_major..cursor_major + major.span, minor_range.clone()) {
+                    cursor_major += 1;
+                }
+            }
+            None => loop {
+                if cursor_minor > 0 && cursor_minor + minor.span > minor_count {
+                    cursor_major += 1;
+                    cursor_minor = 0;
+                    continue;
+                }
+                let minor_range = cursor_minor..cursor_minor + minor.span;
+                if grid.fits(cursor_major..cursor_major + major.span, minor_range) {
+                    break;
+                }
+                cursor_minor += 1;
+            },
+        }
+        place(&mut grid, i, cursor_major, cursor_minor);
+        if minor.start.is_none() {
+            cursor_minor += minor.span;
+        }
+    }
+
+    areas.into_iter().map(Option::unwrap).collect()
+}
+
+/// Size the tracks along one axis, given the min-content and max-content contributions of each
+/// item to the tracks it spans. Returns the used size of each track.
+///
+/// http://www.w3.org/TR/css-grid-1/#algo-track-sizing
+fn size_tracks(
+    tracks: &[TrackSize],
+    items: &[(Range<usize>, f32, f32)],
+    available: AvailableSpace,
+    gap: f32,
+) -> Vec<f32> {
+    let definite = match available {
+        AvailableSpace::Definite(size) => Some(size),
+        AvailableSpace::MinContent | AvailableSpace::MaxContent => None,
+    };
+    // Percentages of an indefinite size behave as `auto`.
+    let resolve = |breadth| match (breadth, definite) {
+        (Breadth::Percent(percent), Some(size)) => Breadth::Fixed(size * percent / 100.0),
+        (Breadth::Percent(_), None) => Breadth::Auto,
+        (breadth, _) => breadth,
+    };
+    let tracks: Vec<TrackSize> = tracks
+        .iter()
+        .map(|track| TrackSize {
+            min: resolve(track.min),
+            max: resolve(track.max),
+        })
+        .collect();
+    let gaps = |count: usize| gap * count.saturating_sub(1) as f32;
+    let flexible = |i: usize| tracks[i].flex_factor().is_some();
+
+    // Initialize each track's base size and growth limit.
+    let mut base: Vec<f32> = tracks
+        .iter()
+        .map(|track| match track.min {
+            Breadth::Fixed(size) => size,
+            _ => 0.0,
+        })
+        .collect();
+    let mut limit: Vec<f32> = tracks
+        .iter()
+        .zip(&base)
+        .map(|(track, &base)| match track.max {
+            Breadth::Fixed(size) => size.max(base),
+            _ => base,
+        })
+        .collect();
+
+    // Grow the tracks to fit their items, starting with items that span fewer tracks. Items
+    // spanning more than one track, one of them flexible, are left to the flexible tracks.
+    let mut sorted: Vec<_> = items.iter().collect();
+    sorted.sort_by_key(|(span, ..)| span.len());
+    for (span, min_content, max_content) in sorted {
+        let spans_flexible = span.clone().any(flexible);
+        if spans_flexible && span.len() > 1 {
+            continue;
+        }
+        let needed = |size: f32| size - gaps(span.len());
+        grow_tracks(
+            &mut base,
+            span.clone(),
+            |i| tracks[i].min == Breadth::Auto,
+            needed(*min_content),
+        );
+        if !spans_flexible {
+            for i in span.clone() {
+                limit[i] = limit[i].max(base[i]);
+            }
+            grow_tracks(
+                &mut limit,
+                span.clone(),
+                |i| tracks[i].max == Breadth::Auto,
+                needed(*max_content),
+            );
+        }
+    }
+    for (limit, &base) in limit.iter_mut().zip(&base) {
+        *limit = limit.max(base);
+    }
+
+    // Grow the inflexible tracks up to their limits, sharing out the free space.
+    match available {
+        AvailableSpace::Definite(size) => {
+            let mut free_space = size - base.iter().sum::<f32>() - gaps(tracks.len());
+            while free_space > 0.0 {
+                let growable: Vec<usize> = (0..tracks.len())
+                    .filter(|&i| !flexible(i) && base[i] < limit[i])
+                    .collect();
+                if growable.is_empty() {
+                    break;
+                }
+                let share = free_space / growable.len() as f32;
+                let mut capped = false;
+                for i in growable {
+                    let growth = share.min(limit[i] - base[i]);
+                    capped |= growth < share;
+                    base[i] += growth;
+                    free_space -= growth;
+                }
+                if !capped {
+                    break;
+                }
+            }
+        }
+        AvailableSpace::MaxContent => {
+            for i in (0..tracks.len()).filter(|&i| !flexible(i)) {
+                base[i] = limit[i];
+            }
+        }
+        AvailableSpace::MinContent => {}
+    }
+
+    // Size the flexible tracks by the size of one `fr`.
+    let flex_fraction = match available {
+        AvailableSpace::Definite(size) => find_fr_size(&tracks, &base, size - gaps(tracks.len())),
+        AvailableSpace::MaxContent => {
+            let from_tracks =
+                (0..tracks.len()).filter_map(|i| Some(base[i] / tracks[i].flex_factor()?.max(1.0)));
+            let from_items = items.iter().filter_map(|(span, _, max_content)| {
+                let factors: f32 = span.clone().filter_map(|i| tracks[i].flex_factor()).sum();
+                if factors == 0.0 {
+                    return None;
+                }
+                let fixed: f32 = span
+                    .clone()
+                    .filter(|&i| !flexible(i))
+                    .map(|i| base[i])
+                    .sum();
+                Some((max_content - gaps(span.len()) - fixed) / factors.max(1.0))
+            });
+            from_tracks.chain(from_items).fold(0.0, f32::max)
+        }
+        AvailableSpace::MinContent => 0.0,
+    };
+    for (i, track) in tracks.iter().enumerate() {
+        if let Some(factor) = track.flex_factor() {
+            base[i] = base[i].max(flex_fraction * factor);
+        }
+    }
+
+    // Any remaining space stretches the tracks with an `auto` maximum.
+    if let Some(size) = definite {
+        let free_space = size - base.iter().sum::<f32>() - gaps(tracks.len());
+        let auto: Vec<usize> = (0..tracks.len())
+            .filter(|&i| tracks[i].max == Breadth::Auto)
+            .collect();
+        if free_space > 0.0 && !auto.is_empty() {
+            for &i in &auto {
+                base[i] += free_space / auto.len() as f32;
+            }
+        }
+    }
+    base
+}
+
+/// Grow the sizes of the eligible tracks in `span` equally until they add up to `needed`.
+pub(super) fn grow_tracks(
+    sizes: &mut [f32],
+    span: Range<usize>,
+    eligible: impl Fn(usize) -> bool,
+    needed: f32,
+) {
+    let extra = needed - sizes[span.clone()].iter().sum::<f32>();
+    let eligible: Vec<usize> = span.filter(|&i| eligible(i)).collect();
+    if extra <= 0.0 || eligible.is_empty() {
+        return;
+    }
+    for &i in &eligible {
+        sizes[i] += extra / eligible.len() as f32;
+    }
+}
+
+/// Find the size of one `fr` that fills `space` with the flexible tracks. Flexible tracks whose
+/// base size is larger than their share are treated as inflexible.
+///
+/// http://www.w3.org/TR/css-grid-1/#algo-find-fr-size
+fn find_fr_size(tracks: &[TrackSize], base: &[f32], space: f32) -> f32 {
+    let mut inflexible: Vec<bool> = tracks
+        .iter()
+        .map(|track| track.flex_factor().is_none())
+        .collect();
+    loop {
+        let leftover = space
+            - (0..tracks.len())
+                .filter(|&i| inflexible[i])
+                .map(|i| base[i])
+                .sum::<f32>();
+        let factors: f32 = (0..tracks.len())
+            .filter(|&i| !inflexible[i])
+            .filter_map(|i| tracks[i].flex_factor())
+            .sum();
+        let fraction = (leftover / factors.max(1.0)).max(0.0);
+        let mut changed = false;
+        for (i, track) in tracks.iter().enumerate() {
+            if let (false, Some(factor)) = (inflexible[i], track.flex_factor()) {
+                if factor * fraction < base[i] {
+                    inflexible[i] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return fraction;
+        }
+    }
+}
+
+/// The size of a run of tracks, including the gaps between them.
+fn span_size(sizes: &[f32], span: &Range<usize>, gap: f32) -> f32 {
+    sizes[span.clone()].iter().sum::<f32>() + gap * span.len().saturating_sub(1) as f32
+}
+
+/// The offset of the start of a track from the start of the grid.
+fn span_offset(sizes: &[f32], start: usize, gap: f32) -> f32 {
+    sizes[..start].iter().sum::<f32>() + gap * start as f32
+}
+
+/// The offset of an item within its grid area along one axis, given the area's free space.
+fn alignment_offset(align: Align, free_space: f32) -> f32 {
+    match align {
+        Align::Start | Align::Stretch => 0.0,
+        Align::End => free_space,
+        Align::Center => free_space / 2.0,
+    }
+}
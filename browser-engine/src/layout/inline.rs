@@ -0,0 +1,452 @@
+//! Inline formatting: breaking text and inline-level replaced boxes into line boxes, and
+//! aligning them within their block.
+//!
+//! http://www.w3.org/TR/CSS2/visuren.html#inline-formatting
+//!
+//! Text is measured as if it were set in a monospace font, so the width of a run of text only
+//! depends on its length, its font size and its letter spacing.
+
+use std::collections::VecDeque;
+use std::ptr;
+
+use super::floats::FloatContext;
+use super::{
+    length, AnonymousBlock, BlockNode, Dimensions, FlexNode, GridNode, InlineNode, LayoutBox,
+    LineBox, PercentBasis, ReplacedNode, TableCellNode, TableNode, TableRowNode, TextRun,
+};
+use crate::css::Value::Number;
+use crate::dom::NodeType;
+use crate::style::{StyledNode, TextAlign, WhiteSpace};
+
+/// The advance of every glyph, as a multiple of the font size.
+pub const GLYPH_ADVANCE: f32 = 0.6;
+
+/// The used value of `line-height: normal`, as a multiple of the font size.
+const NORMAL_LINE_HEIGHT: f32 = 1.2;
+
+/// The initial value of `font-size`.
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// How many characters apart tab stops are in preserved white space.
+const TAB_SIZE: usize = 8;
+
+/// A piece of inline content. Lines are only broken between items, where white space or a
+/// replaced box allows it.
+///
+/// http://www.w3.org/TR/css-text-3/#line-breaking
+enum Item<'a> {
+    /// A word of text, with the style of the text node it came from.
+    Word(&'a StyledNode<'a>, String),
+    /// White space between words: a single space where white space collapses, or the spaces it
+    /// was written with where it is preserved, with tabs expanded.
+    Space(&'a StyledNode<'a>, String),
+    /// A preserved newline, or a `<br>`, which ends its line.
+    LineBreak(&'a StyledNode<'a>),
+    /// An inline-level replaced box, with its size and edges already resolved.
+    Replaced(LayoutBox<'a>),
+}
+
+impl Item<'_> {
+    fn width(&self) -> f32 {
+        match self {
+            Item::Word(style, text) | Item::Space(style, text) => text_width(text, style),
+            Item::LineBreak(_) => 0.0,
+            Item::Replaced(replaced) => replaced.dimensions.margin_box().width,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        match self {
+            Item::Word(style, _) | Item::Space(style, _) | Item::LineBreak(style) => {
+                line_height(style)
+            }
+            Item::Replaced(replaced) => replaced.dimensions.margin_box().height,
+        }
+    }
+
+    fn is_line_break(&self) -> bool {
+        matches!(self, Item::LineBreak(_))
+    }
+
+    /// Whether this is a space that collapses: one is removed at the start or end of a line.
+    fn is_collapsible_space(&self) -> bool {
+        matches!(self, Item::Space(style, _) if style.white_space().collapses())
+    }
+
+    /// Whether this is white space that takes no room at the end of a line: a collapsible
+    /// space, which is removed there, or preserved space that may wrap, which hangs past the
+    /// end of the line.
+    fn hangs(&self) -> bool {
+        matches!(self, Item::Space(style, _) if style.white_space() != WhiteSpace::Pre)
+    }
+}
+
+/// Whether a line may be broken between two items: after white space, unless it is kept from
+/// wrapping, or on either side of a replaced box.
+fn break_allowed(before: &Item, after: &Item) -> bool {
+    match (before, after) {
+        (_, Item::Space(..)) => false,
+        (Item::Space(style, _), _) => style.white_space().wraps(),
+        (Item::Replaced(replaced), _) | (_, Item::Replaced(replaced)) => {
+            replaced.get_style_node().white_space().wraps()
+        }
+        _ => false,
+    }
+}
+
+/// The end of the items from `start` that must be kept on one line: up to the next place the
+/// line may be broken, or the next line break. The white space before a break stays with the
+/// items before it.
+fn unbreakable_end(items: &[Item], start: usize) -> usize {
+    let mut end = start + 1;
+    while end < items.len()
+        && !items[end].is_line_break()
+        && !break_allowed(&items[end - 1], &items[end])
+    {
+        end += 1;
+    }
+    end
+}
+
+/// The width that items take up at the end of a line, leaving out white space that hangs.
+fn trimmed_width(items: &[Item]) -> f32 {
+    let end = items
+        .iter()
+        .rposition(|item| !item.hangs())
+        .map_or(0, |i| i + 1);
+    items[..end].iter().map(Item::width).sum()
+}
+
+/// Add the words, white space and line breaks of some text to `items`.
+///
+/// Where white space collapses, each run of it becomes a single space, even across the
+/// boundaries of inline boxes. Where it is preserved, newlines break lines, and tabs advance to
+/// the next multiple of `TAB_SIZE` characters from the start of the text or its last newline.
+///
+/// http://www.w3.org/TR/css-text-3/#white-space-processing
+fn push_text<'a>(items: &mut Vec<Item<'a>>, style: &'a StyledNode<'a>, text: &str) {
+    let white_space = style.white_space();
+    let mut word = String::new();
+    let mut column = 0;
+    for c in text.chars() {
+        if !matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c') {
+            word.push(c);
+            column += 1;
+            continue;
+        }
+        if !word.is_empty() {
+            items.push(Item::Word(style, std::mem::take(&mut word)));
+        }
+        if white_space.collapses() {
+            if !items.last().is_some_and(Item::is_collapsible_space) {
+                items.push(Item::Space(style, " ".to_string()));
+            }
+            continue;
+        }
+        let spaces = match c {
+            '\n' => {
+                items.push(Item::LineBreak(style));
+                column = 0;
+                continue;
+            }
+            '\r' => continue,
+            '\t' => TAB_SIZE - column % TAB_SIZE,
+            _ => 1,
+        };
+        column += spaces;
+        match items.last_mut() {
+            Some(Item::Space(space_style, space)) if ptr::eq(*space_style, style) => {
+                space.push_str(&" ".repeat(spaces))
+            }
+            _ => items.push(Item::Space(style, " ".repeat(spaces))),
+        }
+    }
+    if !word.is_empty() {
+        items.push(Item::Word(style, word));
+    }
+}
+
+pub fn font_size(style: &StyledNode) -> f32 {
+    length(style.value("font-size")).unwrap_or(DEFAULT_FONT_SIZE)
+}
+
+/// The height of a line box holding text in the given style. A percentage is of the font size.
+pub fn line_height(style: &StyledNode) -> f32 {
+    let font_size = font_size(style);
+    match style.value("line-height") {
+        Some(Number(factor)) => factor * font_size,
+        Some(value) => value
+            .resolve_length(Some(font_size))
+            .unwrap_or(NORMAL_LINE_HEIGHT * font_size),
+        None => NORMAL_LINE_HEIGHT * font_size,
+    }
+}
+
+/// The `letter-spacing` in px, which is added after every character.
+fn letter_spacing(style: &StyledNode) -> f32 {
+    length(style.value("letter-spacing")).unwrap_or(0.0)
+}
+
+/// The width of `text` when set in the given style.
+pub fn text_width(text: &str, style: &StyledNode) -> f32 {
+    let advance = GLYPH_ADVANCE * font_size(style) + letter_spacing(style);
+    text.chars().count() as f32 * advance
+}
+
+/// The `text-indent` of the first line of a block whose content box is `width` wide.
+fn text_indent(style: &StyledNode, width: f32) -> f32 {
+    style
+        .value("text-indent")
+        .and_then(|indent| indent.resolve_length(Some(width)))
+        .unwrap_or(0.0)
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Lay out the inline content of an anonymous block as a stack of line boxes, which replace
+    /// its children. Lines are shortened to fit beside any floats they overlap, and aligned
+    /// by the `text-align` of the block the anonymous block is in. `first_line` is set if its
+    /// first line is also the first line of that block, which is indented by `text-indent`.
+    pub(super) fn layout_anonymous_block(
+        &mut self,
+        containing_block: Dimensions,
+        floats: &mut FloatContext,
+        first_line: bool,
+    ) {
+        let AnonymousBlock(block_style) = self.box_type else {
+            panic!("Only anonymous blocks hold lines");
+        };
+        let d = &mut self.dimensions;
+        d.content.x = containing_block.content.x;
+        d.content.width = containing_block.content.width;
+        d.content.y = containing_block.content.y + containing_block.content.height;
+
+        let mut items = Vec::new();
+        self.collect_items(&mut items, self.percent_basis);
+        let mut items = VecDeque::from(items);
+
+        let content = self.dimensions.content;
+        let text_align = block_style.text_align();
+        let mut indent = if first_line {
+            text_indent(block_style, content.width)
+        } else {
+            0.0
+        };
+        let mut lines = Vec::new();
+        let mut y = content.y;
+        loop {
+            while items.front().is_some_and(Item::is_collapsible_space) {
+                items.pop_front();
+            }
+            if items.is_empty() {
+                break;
+            }
+            let remaining = items.make_contiguous();
+
+            // Move the line down past any floats that leave no room for its first items.
+            let first_end = if remaining[0].is_line_break() {
+                0
+            } else {
+                unbreakable_end(remaining, 0)
+            };
+            let first = &remaining[..first_end];
+            let (top, left, right) = floats.find_space(
+                y,
+                indent + trimmed_width(first),
+                first.iter().map(Item::height).fold(0.0, f32::max),
+                content.x,
+                content.x + content.width,
+            );
+            let left = left + indent;
+
+            // Add items to the line until the next ones don't fit, or a line break ends it.
+            let mut end = first_end;
+            let mut width: f32 = first.iter().map(Item::width).sum();
+            while end < remaining.len() && !remaining[end].is_line_break() {
+                let next = unbreakable_end(remaining, end);
+                if left + width + trimmed_width(&remaining[end..next]) > right {
+                    break;
+                }
+                width += remaining[end..next].iter().map(Item::width).sum::<f32>();
+                end = next;
+            }
+            let mut line_items: Vec<Item> = items.drain(..end).collect();
+            let line_break = if items.front().is_some_and(Item::is_line_break) {
+                items.pop_front()
+            } else {
+                None
+            };
+            // The last line, and lines ended by a line break, aren't justified.
+            let last = line_break.is_some() || items.iter().all(Item::is_collapsible_space);
+
+            while line_items.last().is_some_and(Item::is_collapsible_space) {
+                line_items.pop();
+            }
+            let mut line = LayoutBox::new(LineBox);
+            line.dimensions.content.x = left;
+            line.dimensions.content.y = top;
+            line.dimensions.content.width = right - left;
+            line.dimensions.content.height = match line_break {
+                Some(ref line_break) if line_items.is_empty() => line_break.height(),
+                _ => line_items.iter().map(Item::height).fold(0.0, f32::max),
+            };
+            line.place_items(line_items, text_align, last);
+
+            // Items shorter than the line sit at its bottom, as if aligned on a shared baseline.
+            let bottom = top + line.dimensions.content.height;
+            for child in &mut line.children {
+                let margin_box = child.dimensions.margin_box();
+                child.translate(0.0, bottom - margin_box.y - margin_box.height);
+            }
+            y = bottom;
+            indent = 0.0;
+            lines.push(line);
+        }
+
+        self.children = lines;
+        self.dimensions.content.height = y - content.y;
+    }
+
+    /// Place the items of a line within this line box, starting at its left edge unless they
+    /// are aligned otherwise. Consecutive words and spaces in the same style become one text
+    /// run, except on a justified line, whose spaces are stretched.
+    ///
+    /// http://www.w3.org/TR/css-text-3/#text-align-property
+    fn place_items(&mut self, items: Vec<Item<'a>>, text_align: TextAlign, last: bool) {
+        let line = self.dimensions.content;
+        // Content that overflows the line is aligned to its left edge.
+        let free = (line.width - trimmed_width(&items)).max(0.0);
+        let spaces = items
+            .iter()
+            .rposition(|item| !item.hangs())
+            .map_or(0, |end| {
+                items[..end]
+                    .iter()
+                    .filter(|item| matches!(item, Item::Space(..)))
+                    .count()
+            });
+        let (mut x, stretch) = match text_align {
+            TextAlign::Left => (line.x, 0.0),
+            TextAlign::Right => (line.x + free, 0.0),
+            TextAlign::Center => (line.x + free / 2.0, 0.0),
+            TextAlign::Justify if last || spaces == 0 => (line.x, 0.0),
+            TextAlign::Justify => (line.x, free / spaces as f32),
+        };
+        for item in items {
+            let height = item.height();
+            let (style, text, width) = match item {
+                Item::Word(style, text) => (style, text, 0.0),
+                Item::Space(style, text) => (style, text, stretch),
+                Item::LineBreak(_) => continue,
+                Item::Replaced(mut replaced) => {
+                    let d = &mut replaced.dimensions;
+                    d.content.x = x + d.margin.left + d.border.left + d.padding.left;
+                    d.content.y = line.y + d.margin.top + d.border.top + d.padding.top;
+                    x += d.margin_box().width;
+                    self.children.push(replaced);
+                    continue;
+                }
+            };
+            let width = width + text_width(&text, style);
+            match self.children.last_mut() {
+                Some(LayoutBox {
+                    box_type: TextRun(run_style, run_text),
+                    dimensions,
+                    ..
+                }) if ptr::eq(*run_style, style) && stretch == 0.0 => {
+                    run_text.push_str(&text);
+                    dimensions.content.width = x + width - dimensions.content.x;
+                }
+                _ => {
+                    let mut run = LayoutBox::new(TextRun(style, text));
+                    run.dimensions.content.x = x;
+                    run.dimensions.content.y = line.y;
+                    run.dimensions.content.width = width;
+                    run.dimensions.content.height = height;
+                    self.children.push(run);
+                }
+            }
+            x += width;
+        }
+    }
+
+    /// Gather the words and white space of all the text within this box, its line breaks, and
+    /// its inline-level replaced boxes, in order. The sizes of replaced boxes are resolved
+    /// against `percent_basis`, the containing block of the lines.
+    fn collect_items(&self, items: &mut Vec<Item<'a>>, percent_basis: PercentBasis) {
+        match self.box_type {
+            InlineNode(style) => match style.node.node_type {
+                NodeType::Text(ref text) => push_text(items, style, text),
+                NodeType::Element(ref element)
+                    if element.tag_name.eq_ignore_ascii_case("br")
+                        && style.pseudo_element.is_none() =>
+                {
+                    items.push(Item::LineBreak(style))
+                }
+                NodeType::Element(_) => {}
+            },
+            TextRun(style, ref text) => push_text(items, style, text),
+            ReplacedNode(style, ref content) => {
+                let mut replaced = LayoutBox::new(ReplacedNode(style, content.clone()));
+                replaced.percent_basis = percent_basis;
+                replaced.resolve_edges();
+                let (width, height) = replaced.replaced_size();
+                replaced.dimensions.content.width = replaced.clamp_size(width, true);
+                replaced.dimensions.content.height = replaced.clamp_size(height, false);
+                items.push(Item::Replaced(replaced));
+            }
+            BlockNode(_) | FlexNode(_) | GridNode(_) | TableNode(_) | TableRowNode(_)
+            | TableCellNode(_) | AnonymousBlock(_) | LineBox => {}
+        }
+        for child in &self.children {
+            child.collect_items(items, percent_basis);
+        }
+    }
+
+    /// Whether this is an anonymous block holding nothing but white space that collapses away,
+    /// which generates no box.
+    ///
+    /// http://www.w3.org/TR/CSS2/visuren.html#anonymous
+    pub(super) fn is_collapsible_white_space(&self) -> bool {
+        if !matches!(self.box_type, AnonymousBlock(_)) {
+            return false;
+        }
+        let mut items = Vec::new();
+        self.collect_items(&mut items, PercentBasis::default());
+        items.iter().all(Item::is_collapsible_space)
+    }
+
+    /// The width of the widest run of items in this box's inline content that can't be broken
+    /// across lines.
+    pub(super) fn inline_min_content_width(&self) -> f32 {
+        let mut items = Vec::new();
+        self.collect_items(&mut items, PercentBasis::default());
+        let mut widest: f32 = 0.0;
+        let mut start = 0;
+        while start < items.len() {
+            if items[start].is_line_break() || items[start].is_collapsible_space() {
+                start += 1;
+                continue;
+            }
+            let end = unbreakable_end(&items, start);
+            widest = widest.max(trimmed_width(&items[start..end]));
+            start = end;
+        }
+        widest
+    }
+
+    /// The width of this box's inline content if its lines were only broken by line breaks.
+    pub(super) fn inline_max_content_width(&self) -> f32 {
+        let mut items = Vec::new();
+        self.collect_items(&mut items, PercentBasis::default());
+        items
+            .split(Item::is_line_break)
+            .map(|line| {
+                let start = line
+                    .iter()
+                    .position(|item| !item.is_collapsible_space())
+                    .unwrap_or(line.len());
+                trimmed_width(&line[start..])
+            })
+            .fold(0.0, f32::max)
+    }
+}
@@ -0,0 +1,88 @@
+//! The markers of list items. A marker is laid out like an inline replaced element: inside its
+//! list item, before the first line of its content, or outside it, hanging to the left of the
+//! item's content box beside its first line.
+//!
+//! http://www.w3.org/TR/css-lists-3/#markers
+
+use super::inline::{line_height, text_width};
+use super::{LayoutBox, Replaced, ReplacedNode};
+use crate::css::Value;
+use crate::style::{ListStylePosition, ListStyleType, StyledNode};
+
+/// What a list item's marker shows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Marker {
+    /// A bullet, drawn as a shape so that it shows without text rendering.
+    Disc,
+    Circle,
+    Square,
+    /// A number such as `3. `, or a marker's own `content`.
+    Text(String),
+}
+
+impl Marker {
+    /// The marker shown by a `::marker` pseudo-element, or `None` if it generates no text.
+    pub(super) fn for_style(style: &StyledNode) -> Option<Marker> {
+        let text = style.content.clone()?;
+        let own_content = match style.value("content") {
+            Some(Value::Keyword(keyword)) => keyword != "normal" && keyword != "none",
+            content => content.is_some(),
+        };
+        Some(match style.list_style_type() {
+            ListStyleType::Disc if !own_content => Marker::Disc,
+            ListStyleType::Circle if !own_content => Marker::Circle,
+            ListStyleType::Square if !own_content => Marker::Square,
+            _ => Marker::Text(text),
+        })
+    }
+
+    /// The size of the marker's content box: as wide as its text, and one line high. The space
+    /// at the end of the text separates the marker from its list item's content.
+    pub(super) fn intrinsic_size(&self, style: &StyledNode) -> (f32, f32) {
+        let text = style.content.as_deref().unwrap_or("");
+        (text_width(text, style), line_height(style))
+    }
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Add the box of a list item's marker, given the style of its `::marker`. An outside
+    /// marker is a child of the list item itself, and is kept out of the flow of its content.
+    pub(super) fn add_marker(&mut self, style: &'a StyledNode<'a>) {
+        let Some(marker) = Marker::for_style(style) else {
+            return;
+        };
+        let marker = LayoutBox::new(ReplacedNode(style, Replaced::Marker(marker)));
+        match style.list_style_position() {
+            ListStylePosition::Outside => self.children.push(marker),
+            ListStylePosition::Inside => self.get_inline_container().children.push(marker),
+        }
+    }
+
+    /// Whether this is the marker of a list item that is placed outside it.
+    pub(super) fn is_outside_marker(&self) -> bool {
+        matches!(
+            self.box_type,
+            ReplacedNode(style, Replaced::Marker(_))
+                if style.list_style_position() == ListStylePosition::Outside
+        )
+    }
+
+    /// Place the outside markers of this box to the left of its content box, level with the top
+    /// of its content. The box's content position must already be set.
+    pub(super) fn layout_outside_markers(&mut self) {
+        let content = self.dimensions.content;
+        for marker in self
+            .children
+            .iter_mut()
+            .filter(|child| child.is_outside_marker())
+        {
+            marker.resolve_edges();
+            let (width, height) = marker.replaced_size();
+            let d = &mut marker.dimensions;
+            d.content.width = width;
+            d.content.height = height;
+            d.content.x = content.x - d.margin.right - d.border.right - d.padding.right - width;
+            d.content.y = content.y + d.margin.top + d.border.top + d.padding.top;
+        }
+    }
+}
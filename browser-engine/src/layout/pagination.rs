@@ -0,0 +1,112 @@
+//! Cutting a laid-out document into pages, for paged media such as print.
+//!
+//! The document is laid out as one continuous page, and then cut between boxes or lines, where
+//! `page-break-before`, `page-break-after` and `page-break-inside` allow.
+//!
+//! http://www.w3.org/TR/css-break-3/
+
+use super::{AnonymousBlock, BlockNode, LayoutBox, LineBox, TableNode};
+use crate::style::PageBreak;
+
+/// A place between two boxes where the document may continue on a new page.
+struct BreakPoint {
+    y: f32,
+    forced: bool,
+    avoided: bool,
+}
+
+impl LayoutBox<'_> {
+    /// The positions at which the pages of the document start, when it is cut into pages whose
+    /// content areas are `page_height` tall. The first page starts at the top of the document.
+    ///
+    /// Each page ends at the first forced break, or else at the last break that isn't avoided,
+    /// or else at the last break of any kind. A page with no breaks is cut through its content.
+    pub fn page_breaks(&self, page_height: f32) -> Vec<f32> {
+        let mut pages = vec![0.0];
+        if page_height <= 0.0 {
+            return pages;
+        }
+        let mut points = Vec::new();
+        self.collect_break_points(false, &mut points);
+        points.sort_by(|a, b| a.y.total_cmp(&b.y));
+
+        let overflow = self.scrollable_overflow();
+        let end = overflow.y + overflow.height;
+        loop {
+            let start = pages[pages.len() - 1];
+            let limit = start + page_height;
+            let candidates: Vec<&BreakPoint> = points
+                .iter()
+                .filter(|point| point.y > start && point.y <= limit)
+                .collect();
+            let next = match candidates.iter().find(|point| point.forced) {
+                Some(point) => point.y,
+                None if limit >= end => break,
+                None => candidates
+                    .iter()
+                    .rev()
+                    .find(|point| !point.avoided)
+                    .or(candidates.last())
+                    .map_or(limit, |point| point.y),
+            };
+            pages.push(next);
+        }
+        pages
+    }
+
+    /// Add the places where the document may break between this box's children, and inside
+    /// them, to `points`. Breaks are only made between the block-level children of a block
+    /// container, between lines, and between the rows of a table.
+    fn collect_break_points(&self, avoid: bool, points: &mut Vec<BreakPoint>) {
+        if !matches!(
+            self.box_type,
+            BlockNode(_) | AnonymousBlock(_) | TableNode(_)
+        ) {
+            return;
+        }
+        let avoid = avoid || self.page_break("inside") == PageBreak::Avoid;
+        let children: Vec<&LayoutBox> = self
+            .children
+            .iter()
+            .filter(|child| !child.is_out_of_flow())
+            .collect();
+        for pair in children.windows(2) {
+            let (before, after) = (pair[0], pair[1]);
+            points.push(BreakPoint {
+                y: after.dimensions.border_box().y,
+                forced: before.forced_break("after") || after.forced_break("before"),
+                avoided: avoid
+                    || before.page_break("after") == PageBreak::Avoid
+                    || after.page_break("before") == PageBreak::Avoid,
+            });
+        }
+        for child in children {
+            child.collect_break_points(avoid, points);
+        }
+    }
+
+    /// Whether a page break is forced `before` or `after` this box, by its own style or by
+    /// that of its first or last child, whose break it shares.
+    fn forced_break(&self, position: &str) -> bool {
+        if self.page_break(position) == PageBreak::Always {
+            return true;
+        }
+        if !matches!(self.box_type, BlockNode(_) | AnonymousBlock(_)) {
+            return false;
+        }
+        let mut children = self.children.iter().filter(|child| !child.is_out_of_flow());
+        let child = if position == "before" {
+            children.next()
+        } else {
+            children.next_back()
+        };
+        child.is_some_and(|child| child.forced_break(position))
+    }
+
+    fn page_break(&self, position: &str) -> PageBreak {
+        match self.box_type {
+            AnonymousBlock(_) | LineBox => PageBreak::Auto,
+            _ => self.get_style_node().page_break(position),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Relative, absolute and fixed positioning.
+//!
+//! http://www.w3.org/TR/CSS2/visuren.html#positioning-scheme
+
+use super::{Dimensions, FloatContext, LayoutBox, PercentBasis};
+use crate::css::Unit::Px;
+use crate::css::Value::{self, Keyword, Length};
+use crate::style::Position;
+
+impl<'a> LayoutBox<'a> {
+    /// Shift a relatively positioned box (and its descendants) by its `top`/`left` offsets,
+    /// falling back to `bottom`/`right` when those are auto, then lay out the absolutely
+    /// positioned descendants it contains. Percentages are of the size of the containing block.
+    ///
+    /// http://www.w3.org/TR/CSS2/visuren.html#relative-positioning
+    pub(super) fn apply_relative_position(&mut self) {
+        if self.position() != Position::Relative {
+            return;
+        }
+        let style = self.get_style_node();
+        let PercentBasis { width, height } = self.percent_basis;
+        let dx = offset(style.value("left"), width)
+            .or_else(|| offset(style.value("right"), width).map(|right| -right))
+            .unwrap_or(0.0);
+        let dy = offset(style.value("top"), height)
+            .or_else(|| offset(style.value("bottom"), height).map(|bottom| -bottom))
+            .unwrap_or(0.0);
+        self.translate(dx, dy);
+        self.layout_absolute_descendants(self.padding_box_dimensions());
+    }
+
+    /// Lay out the absolutely positioned descendants whose containing block is `containing_block`,
+    /// i.e. those without a closer positioned ancestor.
+    pub(super) fn layout_absolute_descendants(&mut self, containing_block: Dimensions) {
+        for child in &mut self.children {
+            match child.position() {
+                Position::Absolute => child.layout_absolute(containing_block),
+                Position::Static => child.layout_absolute_descendants(containing_block),
+                // Relatively positioned boxes lay out their own absolute descendants, and fixed
+                // boxes are positioned against the viewport.
+                Position::Relative | Position::Fixed => {}
+            }
+        }
+    }
+
+    /// Lay out the descendants whose containing block is the initial containing block: fixed
+    /// boxes, and absolute boxes without a positioned ancestor.
+    pub(super) fn layout_viewport_descendants(
+        &mut self,
+        viewport: Dimensions,
+        positioned_ancestor: bool,
+    ) {
+        for child in &mut self.children {
+            match child.position() {
+                Position::Fixed => child.layout_absolute(viewport),
+                Position::Absolute if !positioned_ancestor => child.layout_absolute(viewport),
+                _ => {}
+            }
+            let positioned = positioned_ancestor || child.is_positioned();
+            child.layout_viewport_descendants(viewport, positioned);
+        }
+    }
+
+    /// Lay out an absolutely positioned box against the padding box of its containing block.
+    /// The box's content position must hold its static position, recorded during normal flow.
+    /// Offsets and sizes are percentages of the padding box's width or height, and margins and
+    /// padding of its width.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#abs-non-replaced-width
+    fn layout_absolute(&mut self, containing_block: Dimensions) {
+        let style = self.get_style_node();
+        let zero = Length(0.0, Px);
+        let cb = containing_block.content;
+        let static_x = self.dimensions.content.x - cb.x;
+        let static_y = self.dimensions.content.y - cb.y;
+        self.percent_basis = PercentBasis {
+            width: Some(cb.width),
+            height: Some(cb.height),
+        };
+        let (width_basis, height_basis) = (Some(cb.width), Some(cb.height));
+
+        self.resolve_edges();
+        let d = &self.dimensions;
+        let horizontal_edges = d.padding.left + d.padding.right + d.border.left + d.border.right;
+        let vertical_edges = d.padding.top + d.padding.bottom + d.border.top + d.border.bottom;
+
+        // Horizontal dimensions.
+        let shrink_to_fit = self.max_content_width() - horizontal_edges;
+        let margin_left = margin(style.lookup("margin-left", "margin", &zero), cb.width);
+        let margin_right = margin(style.lookup("margin-right", "margin", &zero), cb.width);
+        let solve_horizontal = |width| {
+            solve_absolute_axis(
+                AbsoluteAxis {
+                    start: offset(style.value("left"), width_basis),
+                    size: width,
+                    end: offset(style.value("right"), width_basis),
+                    margin_start: margin_left,
+                    margin_end: margin_right,
+                    edges: horizontal_edges,
+                    static_start: static_x,
+                    available: cb.width,
+                },
+                |available| shrink_to_fit.min(available).max(0.0),
+            )
+        };
+        let width = |name| self.content_size_in(name, horizontal_edges, width_basis);
+        let mut used = solve_horizontal(width("width"));
+        if let Some(max_width) = width("max-width") {
+            if used.size > max_width {
+                used = solve_horizontal(Some(max_width));
+            }
+        }
+        if let Some(min_width) = width("min-width") {
+            if used.size < min_width {
+                used = solve_horizontal(Some(min_width));
+            }
+        }
+        let d = &mut self.dimensions;
+        d.margin.left = used.margin_start;
+        d.margin.right = used.margin_end;
+        d.content.width = used.size;
+        d.content.x = cb.x + used.start + d.margin.left + d.border.left + d.padding.left;
+
+        // Absolutely positioned boxes establish a new block formatting context, so their content
+        // height is known before they are placed vertically.
+        d.content.y = 0.0;
+        let mut floats = FloatContext::default();
+        self.layout_children(&mut floats, true);
+        self.contain_floats(&floats);
+        let content_height = self.dimensions.content.height;
+
+        // http://www.w3.org/TR/CSS2/visudet.html#abs-non-replaced-height
+        let margin_top = margin(style.lookup("margin-top", "margin", &zero), cb.width);
+        let margin_bottom = margin(style.lookup("margin-bottom", "margin", &zero), cb.width);
+        let solve_vertical = |height| {
+            solve_absolute_axis(
+                AbsoluteAxis {
+                    start: offset(style.value("top"), height_basis),
+                    size: height,
+                    end: offset(style.value("bottom"), height_basis),
+                    margin_start: margin_top,
+                    margin_end: margin_bottom,
+                    edges: vertical_edges,
+                    static_start: static_y,
+                    available: cb.height,
+                },
+                |_| content_height,
+            )
+        };
+        let height = |name| self.content_size_in(name, vertical_edges, height_basis);
+        let mut used = solve_vertical(height("height"));
+        if let Some(max_height) = height("max-height") {
+            if used.size > max_height {
+                used = solve_vertical(Some(max_height));
+            }
+        }
+        if let Some(min_height) = height("min-height") {
+            if used.size < min_height {
+                used = solve_vertical(Some(min_height));
+            }
+        }
+        let d = &mut self.dimensions;
+        d.margin.top = used.margin_start;
+        d.margin.bottom = used.margin_end;
+        d.content.height = used.size;
+        let y = cb.y + used.start + d.margin.top + d.border.top + d.padding.top;
+
+        // The children were laid out at y = 0; move them into place along with the box.
+        for child in &mut self.children {
+            child.translate(0.0, y);
+        }
+        self.dimensions.content.y = y;
+
+        self.layout_absolute_descendants(self.padding_box_dimensions());
+    }
+}
+
+/// The constraints along one axis of an absolutely positioned box: `left`, `width`, `right`
+/// horizontally or `top`, `height`, `bottom` vertically. `None` means `auto`.
+struct AbsoluteAxis {
+    start: Option<f32>,
+    size: Option<f32>,
+    end: Option<f32>,
+    margin_start: Option<f32>,
+    margin_end: Option<f32>,
+    /// Padding plus border along this axis.
+    edges: f32,
+    /// The offset the box would have had in normal flow.
+    static_start: f32,
+    available: f32,
+}
+
+/// The used offset, content size and margins along one axis.
+struct UsedAxis {
+    start: f32,
+    size: f32,
+    margin_start: f32,
+    margin_end: f32,
+}
+
+/// Solve the constraint `start + margins + edges + size + end = available` for an absolutely
+/// positioned box. `auto_size` computes the size (from the available space) when it is auto.
+fn solve_absolute_axis(axis: AbsoluteAxis, auto_size: impl Fn(f32) -> f32) -> UsedAxis {
+    let AbsoluteAxis {
+        start,
+        size,
+        end,
+        margin_start,
+        margin_end,
+        edges,
+        static_start,
+        available,
+    } = axis;
+
+    if let (Some(start), Some(size), Some(end)) = (start, size, end) {
+        let underflow = available - start - end - size - edges;
+        let (margin_start, margin_end) = match (margin_start, margin_end) {
+            // Auto margins share the remaining space, unless that would make them negative.
+            (None, None) if underflow >= 0.0 => (underflow / 2.0, underflow / 2.0),
+            (None, None) => (0.0, underflow),
+            (None, Some(margin_end)) => (underflow - margin_end, margin_end),
+            (Some(margin_start), None) => (margin_start, underflow - margin_start),
+            // Over-constrained: ignore `end`.
+            (Some(margin_start), Some(margin_end)) => (margin_start, margin_end),
+        };
+        return UsedAxis {
+            start,
+            size,
+            margin_start,
+            margin_end,
+        };
+    }
+
+    // Otherwise auto margins are treated as zero.
+    let margin_start = margin_start.unwrap_or(0.0);
+    let margin_end = margin_end.unwrap_or(0.0);
+    let outside = margin_start + margin_end + edges;
+    let (start, size) = match (start, size, end) {
+        (None, None, None) | (None, Some(_), None) => {
+            let size = size.unwrap_or_else(|| auto_size(available - static_start - outside));
+            (static_start, size)
+        }
+        (None, None, Some(end)) => {
+            let size = auto_size(available - end - outside);
+            (available - end - outside - size, size)
+        }
+        (Some(start), None, None) => (start, auto_size(available - start - outside)),
+        (None, Some(size), Some(end)) => (available - end - outside - size, size),
+        (Some(start), None, Some(end)) => (start, (available - start - end - outside).max(0.0)),
+        (Some(start), Some(size), None) => (start, size),
+        (Some(_), Some(_), Some(_)) => unreachable!(),
+    };
+    UsedAxis {
+        start,
+        size,
+        margin_start,
+        margin_end,
+    }
+}
+
+/// The px size of a margin, or `None` if it is `auto`. A percentage is of `percent_basis`.
+fn margin(value: Value, percent_basis: f32) -> Option<f32> {
+    match value {
+        Keyword(ref keyword) if keyword == "auto" => None,
+        value => Some(value.resolve_length(Some(percent_basis)).unwrap_or(0.0)),
+    }
+}
+
+/// The px size of an offset such as `left`, or `None` if it is `auto`. A percentage is of
+/// `percent_basis`, and is also `auto` when there is no basis.
+fn offset(value: Option<Value>, percent_basis: Option<f32>) -> Option<f32> {
+    value?.resolve_length(percent_basis)
+}
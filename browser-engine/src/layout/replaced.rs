@@ -0,0 +1,73 @@
+//! Sizing of replaced elements, such as images and form controls, whose content isn't laid out
+//! from their children, and of list markers, which are sized in the same way.
+//!
+//! http://www.w3.org/TR/CSS2/visudet.html#inline-replaced-width
+
+use super::{LayoutBox, Replaced, ReplacedNode};
+use crate::dom::NodeType;
+
+impl LayoutBox<'_> {
+    /// The used content width and height of a replaced box. For an image, a missing `width` or
+    /// `height` follows from the other through the image's aspect ratio, and the image's own
+    /// size is used when both are missing. A form control has no aspect ratio, and its own size
+    /// is used for whichever is missing.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#inline-replaced-height
+    pub(super) fn replaced_size(&self) -> (f32, f32) {
+        let ReplacedNode(style, ref content) = self.box_type else {
+            return (0.0, 0.0);
+        };
+        let (intrinsic_width, intrinsic_height) = match *content {
+            Replaced::Image(ref image) => (image.width as f32, image.height as f32),
+            Replaced::Control(ref control) => control.intrinsic_size(style),
+            Replaced::Marker(ref marker) => marker.intrinsic_size(style),
+        };
+        let has_ratio = matches!(content, Replaced::Image(_));
+        let vertical_edges = self.edge("padding-top", "padding")
+            + self.edge("padding-bottom", "padding")
+            + self.border("top")
+            + self.border("bottom");
+        let width = self.specified_replaced_size("width", self.horizontal_edges());
+        let height = self.specified_replaced_size("height", vertical_edges);
+        match (width, height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) if has_ratio && intrinsic_width > 0.0 => {
+                (width, width * intrinsic_height / intrinsic_width)
+            }
+            (None, Some(height)) if has_ratio && intrinsic_height > 0.0 => {
+                (height * intrinsic_width / intrinsic_height, height)
+            }
+            (width, height) => (
+                width.unwrap_or(intrinsic_width),
+                height.unwrap_or(intrinsic_height),
+            ),
+        }
+    }
+
+    /// The content-box size given by the `width` or `height` property, or else by the
+    /// element's attribute of the same name, in px. A percentage is of the containing block's
+    /// size along the same axis.
+    fn specified_replaced_size(&self, name: &str, edges: f32) -> Option<f32> {
+        let style = self.get_style_node();
+        let attribute = || match style.node.node_type {
+            NodeType::Element(ref element) => element
+                .attrs
+                .get(name)?
+                .trim()
+                .trim_end_matches("px")
+                .parse()
+                .ok(),
+            NodeType::Text(_) => None,
+        };
+        let percent_basis = if name == "width" {
+            self.percent_basis.width
+        } else {
+            self.percent_basis.height
+        };
+        let size = style
+            .value(name)
+            .and_then(|value| value.resolve_length(percent_basis))
+            .or_else(attribute)?;
+        Some(self.content_box_size(size, edges))
+    }
+}
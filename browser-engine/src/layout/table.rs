@@ -0,0 +1,394 @@
+//! Table layout.
+//!
+//! http://www.w3.org/TR/CSS2/tables.html
+//!
+//! A table's children are its rows, and their children are its cells. Cells are placed in a
+//! grid of slots, taking `colspan` and `rowspan` into account, then the columns are sized by the
+//! automatic or fixed table layout algorithm and the rows are sized to fit their cells.
+
+use super::grid::grow_tracks;
+use super::{
+    keyword, padding_and_border, Dimensions, EdgeSize, LayoutBox, PercentBasis, Rect, TableRowNode,
+};
+use crate::css::Value::List;
+use crate::dom::NodeType;
+use crate::style::StyledNode;
+
+/// The slots of a table's grid, and the cells that occupy them.
+struct TableGrid {
+    columns: usize,
+    cells: Vec<TableCell>,
+}
+
+/// A cell and the slots it occupies.
+struct TableCell {
+    /// The index of the cell's row among the table's children.
+    row: usize,
+    /// The index of the cell among its row's children.
+    index: usize,
+    column: usize,
+    colspan: usize,
+    rowspan: usize,
+}
+
+/// The range of widths each column may take, for the automatic table layout algorithm.
+struct ColumnBounds {
+    min: Vec<f32>,
+    max: Vec<f32>,
+    /// Whether the column holds a cell with a specified width.
+    constrained: Vec<bool>,
+}
+
+/// The largest `colspan` or `rowspan` honored, as in HTML.
+const MAX_SPAN: usize = 1000;
+
+impl<'a> LayoutBox<'a> {
+    /// The used content width of a table, given the width available to it.
+    ///
+    /// http://www.w3.org/TR/CSS2/tables.html#width-layout
+    pub(super) fn table_width(&self, available: f32) -> f32 {
+        let edges = self.horizontal_edges();
+        let specified = self.content_size("width", edges);
+        let grid = self.table_grid();
+        let spacing = self.total_spacing(&grid);
+        if self.is_fixed_layout() {
+            let columns: f32 = self.fixed_column_widths(&grid).iter().flatten().sum();
+            return specified.unwrap_or(0.0).max(columns + spacing);
+        }
+        let bounds = self.column_bounds(&grid);
+        let min = bounds.min.iter().sum::<f32>() + spacing;
+        let max = bounds.max.iter().sum::<f32>() + spacing;
+        match specified {
+            Some(width) => width.max(min),
+            None => max.min(available).max(min),
+        }
+    }
+
+    /// The min-content or max-content width of a table's columns, including the spacing
+    /// between them.
+    pub(super) fn table_content_width(&self, min_content: bool) -> f32 {
+        let grid = self.table_grid();
+        let bounds = self.column_bounds(&grid);
+        let columns = if min_content { bounds.min } else { bounds.max };
+        columns.iter().sum::<f32>() + self.total_spacing(&grid)
+    }
+
+    /// Lay out the rows and cells of a table within its content box, and set its content
+    /// height.
+    pub(super) fn layout_table_children(&mut self) {
+        let (horizontal_spacing, vertical_spacing) = border_spacing(self.get_style_node());
+        let grid = self.table_grid();
+        let content = self.dimensions.content;
+        // Cells are sized against the table, rather than the rows they are in.
+        let percent_basis = PercentBasis {
+            width: Some(content.width),
+            height: self.definite_height(),
+        };
+        let columns =
+            self.column_widths(&grid, (content.width - self.total_spacing(&grid)).max(0.0));
+
+        // Lay out each cell at the width of the columns it spans. Cells have no margins.
+        for cell in &grid.cells {
+            let width = span_size(
+                &columns,
+                cell.column..cell.column + cell.colspan,
+                horizontal_spacing,
+            );
+            let cell_box = &mut self.children[cell.row].children[cell.index];
+            cell_box.percent_basis = percent_basis;
+            cell_box.resolve_edges();
+            cell_box.dimensions.margin = EdgeSize::default();
+            let edges = padding_and_border(cell_box.dimensions, true);
+            cell_box.layout_contents((width - edges).max(0.0));
+        }
+
+        // Each row is as tall as its tallest cell, and cells spanning several rows grow the
+        // rows they span.
+        let mut heights: Vec<f32> = self
+            .children
+            .iter()
+            .map(|row| match row.box_type {
+                TableRowNode(_) => row.content_size("height", 0.0).unwrap_or(0.0),
+                _ => 0.0,
+            })
+            .collect();
+        let mut cells: Vec<&TableCell> = grid.cells.iter().collect();
+        cells.sort_by_key(|cell| cell.rowspan);
+        for cell in cells {
+            let height = self.children[cell.row].children[cell.index]
+                .dimensions
+                .border_box()
+                .height;
+            grow_tracks(
+                &mut heights,
+                cell.row..cell.row + cell.rowspan,
+                |_| true,
+                height - vertical_spacing * (cell.rowspan - 1) as f32,
+            );
+        }
+
+        // Position the rows, then the cells within the slots they span.
+        let offsets = |sizes: &[f32], spacing: f32| -> Vec<f32> {
+            sizes
+                .iter()
+                .scan(spacing, |offset, size| {
+                    let start = *offset;
+                    *offset += size + spacing;
+                    Some(start)
+                })
+                .collect()
+        };
+        let column_offsets = offsets(&columns, horizontal_spacing);
+        let row_offsets = offsets(&heights, vertical_spacing);
+        for (row, (&y, &height)) in self
+            .children
+            .iter_mut()
+            .zip(row_offsets.iter().zip(&heights))
+        {
+            row.dimensions = Dimensions {
+                content: Rect {
+                    x: content.x + horizontal_spacing,
+                    y: content.y + y,
+                    width: (content.width - 2.0 * horizontal_spacing).max(0.0),
+                    height,
+                },
+                ..Default::default()
+            };
+        }
+        for cell in &grid.cells {
+            let height = span_size(
+                &heights,
+                cell.row..cell.row + cell.rowspan,
+                vertical_spacing,
+            );
+            let cell_box = &mut self.children[cell.row].children[cell.index];
+            cell_box.stretch_cell(height);
+            let border_box = cell_box.dimensions.border_box();
+            cell_box.translate(
+                content.x + column_offsets[cell.column] - border_box.x,
+                content.y + row_offsets[cell.row] - border_box.y,
+            );
+            cell_box.apply_relative_position();
+        }
+
+        self.dimensions.content.height = match heights.len() {
+            0 => 0.0,
+            rows => heights.iter().sum::<f32>() + vertical_spacing * (rows + 1) as f32,
+        };
+    }
+
+    /// Stretch a cell to the height of the rows it spans, moving its contents according to
+    /// its `vertical-align`.
+    fn stretch_cell(&mut self, border_box_height: f32) {
+        let edges = padding_and_border(self.dimensions, false);
+        let height = (border_box_height - edges).max(self.dimensions.content.height);
+        let free_space = height - self.dimensions.content.height;
+        let offset = match keyword(self.get_style_node(), "vertical-align").as_deref() {
+            Some("middle") => free_space / 2.0,
+            Some("bottom") => free_space,
+            _ => 0.0,
+        };
+        for child in &mut self.children {
+            child.translate(0.0, offset);
+        }
+        self.dimensions.content.height = height;
+    }
+
+    /// Place the table's cells in the slots of its grid.
+    ///
+    /// http://www.w3.org/TR/html401/struct/tables.html#h-11.2.6.1
+    fn table_grid(&self) -> TableGrid {
+        let rows = self.children.len();
+        let mut occupied: Vec<Vec<bool>> = vec![Vec::new(); rows];
+        let mut cells = Vec::new();
+        for (row, row_box) in self.children.iter().enumerate() {
+            let mut column = 0;
+            for (index, cell_box) in row_box.children.iter().enumerate() {
+                while occupied[row].get(column).copied().unwrap_or(false) {
+                    column += 1;
+                }
+                let colspan = span_attribute(cell_box.get_style_node(), "colspan");
+                let rowspan = span_attribute(cell_box.get_style_node(), "rowspan").min(rows - row);
+                for slots in &mut occupied[row..row + rowspan] {
+                    if slots.len() < column + colspan {
+                        slots.resize(column + colspan, false);
+                    }
+                    slots[column..column + colspan].fill(true);
+                }
+                cells.push(TableCell {
+                    row,
+                    index,
+                    column,
+                    colspan,
+                    rowspan,
+                });
+                column += colspan;
+            }
+        }
+        TableGrid {
+            columns: occupied.iter().map(Vec::len).max().unwrap_or(0),
+            cells,
+        }
+    }
+
+    /// The total horizontal border spacing: between the columns and at both of their ends.
+    fn total_spacing(&self, grid: &TableGrid) -> f32 {
+        match grid.columns {
+            0 => 0.0,
+            columns => border_spacing(self.get_style_node()).0 * (columns + 1) as f32,
+        }
+    }
+
+    /// Whether the table uses the fixed table layout algorithm, which requires a width.
+    fn is_fixed_layout(&self) -> bool {
+        keyword(self.get_style_node(), "table-layout").as_deref() == Some("fixed")
+            && self
+                .content_size("width", self.horizontal_edges())
+                .is_some()
+    }
+
+    /// The widths of the columns that the cells of the first row give a width, for the fixed
+    /// table layout algorithm.
+    ///
+    /// http://www.w3.org/TR/CSS2/tables.html#fixed-table-layout
+    fn fixed_column_widths(&self, grid: &TableGrid) -> Vec<Option<f32>> {
+        let mut widths = vec![None; grid.columns];
+        for cell in grid.cells.iter().filter(|cell| cell.row == 0) {
+            let cell_box = &self.children[cell.row].children[cell.index];
+            let edges = cell_box.horizontal_edges();
+            if let Some(width) = cell_box.content_size("width", edges) {
+                let share = (width + edges) / cell.colspan as f32;
+                widths[cell.column..cell.column + cell.colspan].fill(Some(share));
+            }
+        }
+        widths
+    }
+
+    /// The minimum and maximum width of each column, from the min-content and max-content
+    /// widths of its cells. Cells spanning several columns widen the columns they span.
+    ///
+    /// http://www.w3.org/TR/CSS2/tables.html#auto-table-layout
+    fn column_bounds(&self, grid: &TableGrid) -> ColumnBounds {
+        let (spacing, _) = border_spacing(self.get_style_node());
+        let mut bounds = ColumnBounds {
+            min: vec![0.0; grid.columns],
+            max: vec![0.0; grid.columns],
+            constrained: vec![false; grid.columns],
+        };
+        let mut cells: Vec<&TableCell> = grid.cells.iter().collect();
+        cells.sort_by_key(|cell| cell.colspan);
+        for cell in cells {
+            let cell_box = &self.children[cell.row].children[cell.index];
+            let columns = cell.column..cell.column + cell.colspan;
+            let spanned_spacing = spacing * (cell.colspan - 1) as f32;
+            grow_tracks(
+                &mut bounds.min,
+                columns.clone(),
+                |_| true,
+                cell_box.min_content_width() - spanned_spacing,
+            );
+            grow_tracks(
+                &mut bounds.max,
+                columns.clone(),
+                |_| true,
+                cell_box.max_content_width() - spanned_spacing,
+            );
+            // A column holding a cell with a specified width doesn't grow past it.
+            if cell.colspan == 1
+                && cell_box
+                    .content_size("width", cell_box.horizontal_edges())
+                    .is_some()
+            {
+                bounds.constrained[cell.column] = true;
+            }
+        }
+        for (max, &min) in bounds.max.iter_mut().zip(&bounds.min) {
+            *max = max.max(min);
+        }
+        bounds
+    }
+
+    /// The used widths of the columns, which add up to `width` unless the columns can't be
+    /// made that narrow.
+    fn column_widths(&self, grid: &TableGrid, width: f32) -> Vec<f32> {
+        if self.is_fixed_layout() {
+            let fixed = self.fixed_column_widths(grid);
+            let used: f32 = fixed.iter().flatten().sum();
+            let auto = fixed.iter().filter(|width| width.is_none()).count();
+            let remaining = (width - used).max(0.0);
+            return fixed
+                .iter()
+                .map(|column| match *column {
+                    Some(column) if auto == 0 && used > 0.0 => column * width.max(used) / used,
+                    Some(column) => column,
+                    None => remaining / auto as f32,
+                })
+                .collect();
+        }
+
+        let ColumnBounds {
+            min,
+            max,
+            constrained,
+        } = self.column_bounds(grid);
+        let min_total: f32 = min.iter().sum();
+        let max_total: f32 = max.iter().sum();
+        if width <= min_total {
+            return min;
+        }
+        if width <= max_total {
+            // Columns grow from their minimum in proportion to how much wider they could be.
+            let scale = (width - min_total) / (max_total - min_total);
+            return min
+                .iter()
+                .zip(&max)
+                .map(|(min, max)| min + (max - min) * scale)
+                .collect();
+        }
+
+        // Any width left over goes to the columns without a specified width, in proportion to
+        // their maximum widths, or else to all of the columns.
+        let mut widths = max.clone();
+        let mut eligible: Vec<usize> = (0..grid.columns).filter(|&i| !constrained[i]).collect();
+        if eligible.is_empty() {
+            eligible = (0..grid.columns).collect();
+        }
+        let extra = width - max_total;
+        let eligible_total: f32 = eligible.iter().map(|&i| max[i]).sum();
+        for &i in &eligible {
+            widths[i] += match eligible_total {
+                total if total > 0.0 => extra * max[i] / total,
+                _ => extra / eligible.len() as f32,
+            };
+        }
+        widths
+    }
+}
+
+/// The horizontal and vertical `border-spacing` of a table.
+fn border_spacing(style: &StyledNode) -> (f32, f32) {
+    match style.value("border-spacing") {
+        Some(List(values)) if values.len() == 2 => (values[0].to_px(), values[1].to_px()),
+        Some(value) => (value.to_px(), value.to_px()),
+        None => (0.0, 0.0),
+    }
+}
+
+/// The value of a cell's `colspan` or `rowspan` attribute, which defaults to 1.
+fn span_attribute(style: &StyledNode, name: &str) -> usize {
+    match style.node.node_type {
+        NodeType::Element(ref element) => element
+            .attrs
+            .get(name)
+            .and_then(|span| span.trim().parse::<usize>().ok())
+            .filter(|&span| span >= 1)
+            .map_or(1, |span| span.min(MAX_SPAN)),
+        NodeType::Text(_) => 1,
+    }
+}
+
+/// The size of a run of columns or rows, including the spacing between them.
+fn span_size(sizes: &[f32], span: std::ops::Range<usize>, spacing: f32) -> f32 {
+    let count = span.len();
+    sizes[span].iter().sum::<f32>() + spacing * count.saturating_sub(1) as f32
+}
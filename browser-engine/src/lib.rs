@@ -0,0 +1,12 @@
+pub mod css;
+pub mod dom;
+pub mod encoding;
+pub mod html;
+pub mod image;
+pub mod layout;
+pub mod painting;
+pub mod pdf;
+pub mod reftest;
+pub mod script;
+pub mod style;
+pub mod svg;
@@ -1,9 +1,306 @@
-pub mod css;
-pub mod dom;
-pub mod html;
-pub mod layout;
-pub mod style;
+use std::env;
+use std::fs;
+use std::process;
+
+use std::path::Path;
+
+use browser_engine::dom::NodeType;
+use browser_engine::encoding::Encoding;
+use browser_engine::image::{encode_png, Image, ImageCache};
+use browser_engine::layout::{layout_tree, Dimensions, Rect};
+use browser_engine::painting::{full_page, paint};
+use browser_engine::pdf::{self, Font, Page};
+use browser_engine::reftest::{self, Tolerance, VIEWPORT_HEIGHT, VIEWPORT_WIDTH};
+use browser_engine::style::{style_tree, StyledNode};
+use browser_engine::{css, html, script, svg};
+
+const USAGE: &str = "usage: browser-engine --inspect <selector> [--css <file>] [--script <file> [--data <name>=<value>]...]
+           <html file>
+       browser-engine --render <png, svg or pdf file> [--scroll <x>,<y> | --full-page]
+           [--font <ttf file>] [--css <file>] [--script <file> [--data <name>=<value>]...]
+           <html file>
+       browser-engine --reftest <dir> [--tolerance <max difference>,<max pixels>] [--output <dir>]";
+
+/// How much of a document `--render` draws, for the image formats.
+enum RenderArea {
+    /// The viewport, scrolled to the given offset.
+    Viewport(f32, f32),
+    /// The whole of the document.
+    FullPage,
+}
+
+impl RenderArea {
+    /// The option that chose this area.
+    fn option(&self) -> &'static str {
+        match self {
+            RenderArea::Viewport(..) => "--scroll",
+            RenderArea::FullPage => "--full-page",
+        }
+    }
+}
 
 fn main() {
-    println!("Hello, world!");
+    let mut args = env::args().skip(1);
+    let mut html_file = None;
+    let mut css_file = None;
+    let mut script_file = None;
+    let mut data = Vec::new();
+    let mut selector = None;
+    let mut render_file = None;
+    let mut area = None;
+    let mut font_file = None;
+    let mut reftest_dir = None;
+    let mut tolerance = Tolerance::default();
+    let mut output = "reftest-output".to_string();
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--css" => css_file = args.next(),
+            "--inspect" => selector = args.next(),
+            "--render" => render_file = args.next(),
+            "--scroll" | "--full-page" if area.is_some() => {
+                let previous = area.as_ref().map_or("", RenderArea::option);
+                if previous == arg {
+                    fail(&format!("{} may only be given once", arg))
+                }
+                fail(&format!("{} and {} can't be combined", previous, arg))
+            }
+            "--scroll" => {
+                let (x, y) = parse_pair(args.next()).unwrap_or_else(|| fail(USAGE));
+                area = Some(RenderArea::Viewport(x, y));
+            }
+            "--full-page" => area = Some(RenderArea::FullPage),
+            "--font" => font_file = args.next(),
+            "--script" => script_file = args.next(),
+            "--data" => {
+                let arg = args.next().unwrap_or_else(|| fail(USAGE));
+                let (name, value) = arg.split_once('=').unwrap_or_else(|| fail(USAGE));
+                data.push((name.to_string(), value.to_string()));
+            }
+            "--reftest" => reftest_dir = args.next(),
+            "--tolerance" => {
+                tolerance = parse_tolerance(args.next()).unwrap_or_else(|| fail(USAGE))
+            }
+            "--output" => output = args.next().unwrap_or_else(|| fail(USAGE)),
+            _ if html_file.is_none() && !arg.starts_with("--") => html_file = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+    if let Some(dir) = reftest_dir {
+        run_reftests(&dir, tolerance, &output);
+        return;
+    }
+    let Some(html_file) = html_file else {
+        fail(USAGE)
+    };
+    if selector.is_some() && render_file.is_some() {
+        fail(USAGE)
+    }
+    check_render_options(render_file.as_deref(), area.as_ref(), font_file.is_some());
+
+    let file = fs::File::open(&html_file).unwrap_or_else(|error| fail_to_read(&html_file, error));
+    let mut root = html::parse_reader(file).unwrap_or_else(|error| fail_to_read(&html_file, error));
+    if let Some(ref path) = script_file {
+        let source = fs::read_to_string(path).unwrap_or_else(|error| fail_to_read(path, error));
+        let mut program =
+            script::parse(&source).unwrap_or_else(|error| fail(&format!("{}:{}", path, error)));
+        program.data.extend(data);
+        if let Err(error) = program.run(&mut root) {
+            fail(&format!("{}:{}", path, error));
+        }
+    }
+    let stylesheet = match css_file {
+        Some(ref path) => {
+            let (stylesheet, errors) = css::parse_with_errors(read_stylesheet(path));
+            for error in errors {
+                eprintln!("warning: {}:{}", path, error);
+            }
+            stylesheet
+        }
+        None => css::parse(String::new()),
+    };
+    let styled = style_tree(&root, &stylesheet);
+    if let Some(path) = render_file {
+        let base = Path::new(&html_file).parent().unwrap_or(Path::new("."));
+        let bytes = render(
+            &path,
+            &styled,
+            &stylesheet,
+            base,
+            area,
+            font_file.as_deref(),
+        );
+        fs::write(&path, bytes).unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+        return;
+    }
+    let Some(selector) = selector else {
+        fail(USAGE)
+    };
+    let Some(selector) = css::parse_selector(selector.clone()) else {
+        fail(&format!("unsupported selector: {}", selector))
+    };
+    let css_name = css_file.as_deref().unwrap_or("<none>");
+    for node in styled.select(&selector) {
+        print_inspection(node, &stylesheet, css_name);
+    }
+}
+
+/// Lay out and paint a document to the file format named by the extension of `path`. Images
+/// show the viewport at a scroll offset, or the whole page; a PDF has as many pages as the
+/// document needs, set in the TrueType font `font_file` or else in Courier.
+fn render(
+    path: &str,
+    styled: &StyledNode,
+    stylesheet: &css::Stylesheet,
+    base: &Path,
+    area: Option<RenderArea>,
+    font_file: Option<&str>,
+) -> Vec<u8> {
+    let mut images = ImageCache::new(base);
+    match extension(path) {
+        Some("pdf") => {
+            let font = match font_file {
+                Some(font_path) => {
+                    let data =
+                        fs::read(font_path).unwrap_or_else(|error| fail_to_read(font_path, error));
+                    Font::truetype(data).unwrap_or_else(|error| fail_to_read(font_path, error))
+                }
+                None => Font::courier(),
+            };
+            let page = Page::from_stylesheet(stylesheet);
+            let layout = layout_tree(styled, page.content_area(), &mut images);
+            pdf::paint(&layout, &page, &mut images, &font)
+        }
+        Some(format @ ("png" | "svg")) => {
+            let mut viewport = Dimensions::default();
+            viewport.content.width = VIEWPORT_WIDTH;
+            viewport.content.height = VIEWPORT_HEIGHT;
+            let layout = layout_tree(styled, viewport, &mut images);
+            let bounds = match area.unwrap_or(RenderArea::Viewport(0.0, 0.0)) {
+                RenderArea::Viewport(x, y) => Rect {
+                    x,
+                    y,
+                    ..viewport.content
+                },
+                RenderArea::FullPage => full_page(&layout, viewport.content),
+            };
+            if format == "svg" {
+                svg::paint(&layout, bounds, &mut images).into_bytes()
+            } else {
+                let canvas = paint(&layout, bounds, &mut images);
+                encode_png(&Image {
+                    width: canvas.width,
+                    height: canvas.height,
+                    pixels: canvas.pixels,
+                })
+            }
+        }
+        _ => fail(&format!(
+            "{}: the output must be a .png, .svg or .pdf file",
+            path
+        )),
+    }
+}
+
+/// Fail if an option is given that doesn't apply to the format of the `--render` output: the
+/// area to draw applies only to images, and the font only to a PDF.
+fn check_render_options(render_file: Option<&str>, area: Option<&RenderArea>, font: bool) {
+    let Some(path) = render_file else {
+        if let Some(option) = area.map(RenderArea::option).or(font.then_some("--font")) {
+            fail(&format!("{} only applies to --render", option))
+        }
+        return;
+    };
+    match (extension(path), area) {
+        (Some("pdf"), Some(area)) => fail(&format!(
+            "{}: {} only applies to .png and .svg output",
+            path,
+            area.option()
+        )),
+        (Some("png" | "svg"), _) if font => {
+            fail(&format!("{}: --font only applies to .pdf output", path))
+        }
+        _ => {}
+    }
+}
+
+/// The extension of a file name, such as `png`.
+fn extension(path: &str) -> Option<&str> {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+}
+
+/// Print the computed values of an element, each with the rule it came from, followed by all
+/// of the rules that match the element.
+fn print_inspection(node: &StyledNode, stylesheet: &css::Stylesheet, css_name: &str) {
+    let NodeType::Element(ref element) = node.node.node_type else {
+        return;
+    };
+    let inspection = node.inspect(stylesheet);
+    let describe = |index: usize| {
+        let matched = &inspection.matched_rules[index];
+        let (a, b, c) = matched.specificity();
+        let source = if matched.user_agent {
+            "user agent stylesheet"
+        } else {
+            css_name
+        };
+        format!(
+            "{} ({},{},{}) {}:{}",
+            matched.selector, a, b, c, source, matched.rule.location
+        )
+    };
+    println!("{}", element);
+    for (name, value, source) in &inspection.properties {
+        let source = source.map_or("inherited".to_string(), describe);
+        println!("  {}: {}; /* {} */", name, value, source);
+    }
+    println!("  matched rules:");
+    for index in 0..inspection.matched_rules.len() {
+        println!("    {}", describe(index));
+    }
+}
+
+/// Run a directory of reftests, printing each failure, and exit with an error if any fail.
+fn run_reftests(dir: &str, tolerance: Tolerance, output: &str) {
+    let failures = reftest::run(Path::new(dir), tolerance, Path::new(output))
+        .unwrap_or_else(|error| fail(&format!("{}: {}", dir, error)));
+    for failure in &failures {
+        println!("FAIL {}", failure);
+    }
+    if !failures.is_empty() {
+        fail(&format!("{} reftests failed", failures.len()));
+    }
+}
+
+/// Parse a scroll offset such as `0,1200`.
+fn parse_pair(value: Option<String>) -> Option<(f32, f32)> {
+    let value = value?;
+    let (x, y) = value.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+/// Parse a tolerance such as `2,100`: the largest ignored difference in a color channel, and
+/// the number of pixels that may differ by more.
+fn parse_tolerance(value: Option<String>) -> Option<Tolerance> {
+    let value = value?;
+    let (max_difference, max_pixels) = value.split_once(',')?;
+    Some(Tolerance {
+        max_difference: max_difference.trim().parse().ok()?,
+        max_pixels: max_pixels.trim().parse().ok()?,
+    })
+}
+
+fn read_stylesheet(path: &str) -> String {
+    let bytes = fs::read(path).unwrap_or_else(|error| fail_to_read(path, error));
+    css::decode(&bytes, Encoding::Utf8)
+}
+
+fn fail_to_read(path: &str, error: std::io::Error) -> ! {
+    fail(&format!("{}: {}", path, error))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
 }
@@ -0,0 +1,2381 @@
+use std::rc::Rc;
+
+use crate::css::{Color, Unit, Value};
+use crate::image::{Image, ImageCache};
+use crate::layout::{
+    font_size, line_height, text_width, AnonymousBlock, BlockNode, Control, EdgeSize, FlexNode,
+    GridNode, InlineNode, LayoutBox, LineBox, Marker, Rect, Replaced, ReplacedNode, TableCellNode,
+    TableNode, TableRowNode, TextRun, SELECT_ARROW_WIDTH,
+};
+use crate::style::StyledNode;
+use crate::style::{BorderStyle, Float, Position};
+
+pub type DisplayList = Vec<DisplayCommand>;
+
+/// The number of samples taken along each axis of a pixel when anti-aliasing.
+const SUBSAMPLES: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayCommand {
+    SolidColor(Color, Rect),
+    /// A rectangle with rounded corners, filled with a color.
+    RoundedRect(Color, Rect, CornerRadii),
+    /// The border of a box.
+    Border(Border),
+    /// An image or gradient, repeated across the area it is clipped to.
+    Background(Background),
+    /// An image scaled to fill a rectangle, such as the content of an `<img>` element.
+    Image(Rc<Image>, Rect),
+    /// A run of text on one line.
+    Text(Text),
+    /// Commands painted onto a transparent layer, which is then composited with an opacity.
+    Layer(f32, DisplayList),
+    /// Commands painted only within a rectangle, for the contents of a box that clips its
+    /// overflow.
+    Clip(Rect, DisplayList),
+    /// Commands for a fixed-position box, which stay in place when the document is scrolled.
+    Fixed(DisplayList),
+}
+
+/// A run of text set in a monospace font, whose glyphs evenly fill `rect`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    pub text: String,
+    pub rect: Rect,
+    pub font_size: f32,
+    pub color: Color,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Background {
+    pub image: BackgroundImage,
+    /// The area painted, with the curve of the border's corners.
+    pub clip: Rect,
+    pub radii: CornerRadii,
+    /// The position and size of one copy of the image.
+    pub tile: Rect,
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackgroundImage {
+    Image(Rc<Image>),
+    /// A gradient along a line through the center of the tile, at an angle in degrees clockwise
+    /// from upward. The stops are positioned along the part of the line that spans the tile.
+    LinearGradient(f32, Vec<ColorStop>),
+    /// A gradient outward from a center relative to the tile, along an ellipse with the given
+    /// radii. The stops are positioned along the ellipse's horizontal radius.
+    RadialGradient(f32, f32, Radius, Vec<ColorStop>),
+}
+
+/// A color at a position along a gradient, from 0 at its start to 1 at its end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorStop {
+    pub color: Color,
+    pub position: f32,
+}
+
+/// The horizontal and vertical radii of an elliptical corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Radius {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The radii of the four corners of a rounded rectangle. Corners with a zero radius are square.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CornerRadii {
+    pub top_left: Radius,
+    pub top_right: Radius,
+    pub bottom_right: Radius,
+    pub bottom_left: Radius,
+}
+
+/// A border drawn between the edge of `rect` and a curve inset by the width of each side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Border {
+    /// The outer edge of the border: the border box.
+    pub rect: Rect,
+    pub radii: CornerRadii,
+    pub top: BorderSide,
+    pub right: BorderSide,
+    pub bottom: BorderSide,
+    pub left: BorderSide,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BorderSide {
+    pub width: f32,
+    pub color: Color,
+    pub style: BorderStyle,
+}
+
+pub struct Canvas {
+    pub pixels: Vec<Color>,
+    pub width: usize,
+    pub height: usize,
+    /// The point of the document painted at the top left pixel.
+    scroll: (f32, f32),
+    /// The pixels that may be painted, from the enclosing `Clip` commands.
+    clip_rect: Rect,
+}
+
+/// Paint a tree of LayoutBoxes to an array of pixels. `bounds` is the area of the document to
+/// paint, so its origin is the scroll offset of the viewport. Images are loaded through
+/// `images`.
+pub fn paint(layout_root: &LayoutBox, bounds: Rect, images: &mut ImageCache) -> Canvas {
+    let display_list = build_display_list(layout_root, images);
+    let mut canvas = Canvas::new(bounds.width as usize, bounds.height as usize);
+    canvas.scroll = (bounds.x, bounds.y);
+    for item in display_list {
+        canvas.paint_item(&item);
+    }
+    canvas
+}
+
+/// The bounds to `paint` for an image of the whole document, rather than only the part of it
+/// in the viewport: the viewport extended to the right and bottom edges of the scrollable
+/// overflow.
+pub fn full_page(layout_root: &LayoutBox, viewport: Rect) -> Rect {
+    let overflow = layout_root.scrollable_overflow();
+    Rect {
+        x: 0.0,
+        y: 0.0,
+        width: viewport.width.max((overflow.x + overflow.width).ceil()),
+        height: viewport.height.max((overflow.y + overflow.height).ceil()),
+    }
+}
+
+pub fn build_display_list(layout_root: &LayoutBox, images: &mut ImageCache) -> DisplayList {
+    let mut list = Vec::new();
+    render_stacking_context(&mut list, images, layout_root);
+    list
+}
+
+/// Paint a box that establishes a stacking context, followed by its descendants in the order
+/// given by the CSS painting algorithm.
+///
+/// http://www.w3.org/TR/CSS2/zindex.html
+fn render_stacking_context(
+    list: &mut DisplayList,
+    images: &mut ImageCache,
+    layout_box: &LayoutBox,
+) {
+    // A translucent stacking context is painted as a whole onto a layer of its own.
+    let opacity = opacity(layout_box);
+    if opacity < 1.0 {
+        let mut layer = Vec::new();
+        render_stacking_context_contents(&mut layer, images, layout_box);
+        list.push(DisplayCommand::Layer(opacity, layer));
+    } else {
+        render_stacking_context_contents(list, images, layout_box);
+    }
+}
+
+fn render_stacking_context_contents(
+    list: &mut DisplayList,
+    images: &mut ImageCache,
+    layout_box: &LayoutBox,
+) {
+    // Positioned descendants are painted in layers, sorted by z-index. The sort is stable, so
+    // boxes with equal z-index stay in tree order.
+    let mut layers = Vec::new();
+    let clip = overflow_clip(layout_box);
+    let absolute_clip = if layout_box.is_positioned() {
+        clip
+    } else {
+        None
+    };
+    collect_layers(layout_box, clip, absolute_clip, &mut layers);
+    layers.sort_by_key(|&(layer, _)| z_index(layer).unwrap_or(0));
+    let (negative, positive): (Vec<_>, Vec<_>) = layers
+        .into_iter()
+        .partition(|&(layer, _)| z_index(layer).unwrap_or(0) < 0);
+
+    render_box(list, images, layout_box);
+    for (layer, clip) in negative {
+        render_layer(list, images, layer, clip);
+    }
+    render_clipped_contents(list, images, layout_box);
+    for (layer, clip) in positive {
+        render_layer(list, images, layer, clip);
+    }
+}
+
+/// Paint a positioned box, clipped by the overflow of the boxes it is nested in. Boxes with
+/// `z-index: auto` don't establish a stacking context; their positioned descendants were
+/// collected into the enclosing one.
+fn render_layer(
+    list: &mut DisplayList,
+    images: &mut ImageCache,
+    layout_box: &LayoutBox,
+    clip: Option<Rect>,
+) {
+    let mut layer = Vec::new();
+    if z_index(layout_box).is_some() {
+        render_stacking_context(&mut layer, images, layout_box);
+    } else {
+        render_box(&mut layer, images, layout_box);
+        render_clipped_contents(&mut layer, images, layout_box);
+    }
+    if let Some(clip) = clip {
+        layer = vec![DisplayCommand::Clip(clip, layer)];
+    }
+    if layout_box.position() == Position::Fixed {
+        layer = vec![DisplayCommand::Fixed(layer)];
+    }
+    list.extend(layer);
+}
+
+/// Find the positioned and translucent descendants painted as part of the stacking context of
+/// `layout_box`, with the area each is clipped to by the boxes between them.
+///
+/// Overflow clips the descendants of a box whose containing block is inside it. `clip` is the
+/// clip of the children of `layout_box`, and `absolute_clip` the clip of absolutely positioned
+/// descendants, from the nearest positioned box. Fixed boxes are never clipped.
+fn collect_layers<'a, 'b>(
+    layout_box: &'b LayoutBox<'a>,
+    clip: Option<Rect>,
+    absolute_clip: Option<Rect>,
+    layers: &mut Vec<(&'b LayoutBox<'a>, Option<Rect>)>,
+) {
+    for child in &layout_box.children {
+        let child_clip = match child.position() {
+            Position::Absolute => absolute_clip,
+            Position::Fixed => None,
+            Position::Static | Position::Relative => clip,
+        };
+        if is_layer(child) {
+            layers.push((child, child_clip));
+        }
+        if z_index(child).is_none() {
+            let descendant_clip = match (child_clip, overflow_clip(child)) {
+                (Some(clip), Some(overflow)) => Some(clip.intersection(overflow)),
+                (clip, overflow) => clip.or(overflow),
+            };
+            let absolute_clip = if child.is_positioned() {
+                descendant_clip
+            } else {
+                absolute_clip
+            };
+            collect_layers(child, descendant_clip, absolute_clip, layers);
+        }
+    }
+}
+
+/// The area that the contents of a box are clipped to: its padding box, along the axes where
+/// `overflow` isn't `visible`.
+fn overflow_clip(layout_box: &LayoutBox) -> Option<Rect> {
+    // Along an axis that isn't clipped, the clip extends far beyond any content.
+    const UNCLIPPED: f32 = 1.0e9;
+    let (clip_x, clip_y) = layout_box.clips_overflow();
+    if !clip_x && !clip_y {
+        return None;
+    }
+    let padding_box = layout_box.dimensions.padding_box();
+    let (x, width) = if clip_x {
+        (padding_box.x, padding_box.width)
+    } else {
+        (-UNCLIPPED, 2.0 * UNCLIPPED)
+    };
+    let (y, height) = if clip_y {
+        (padding_box.y, padding_box.height)
+    } else {
+        (-UNCLIPPED, 2.0 * UNCLIPPED)
+    };
+    Some(Rect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Paint the non-positioned descendants of a box, clipped if the box clips its overflow.
+fn render_clipped_contents(
+    list: &mut DisplayList,
+    images: &mut ImageCache,
+    layout_box: &LayoutBox,
+) {
+    match overflow_clip(layout_box) {
+        Some(clip) => {
+            let mut contents = Vec::new();
+            render_contents(&mut contents, images, layout_box);
+            if !contents.is_empty() {
+                list.push(DisplayCommand::Clip(clip, contents));
+            }
+        }
+        None => render_contents(list, images, layout_box),
+    }
+}
+
+/// Paint the non-positioned descendants of a box: first the in-flow boxes in tree order, then
+/// the floats, each painted as a unit.
+fn render_contents(list: &mut DisplayList, images: &mut ImageCache, layout_box: &LayoutBox) {
+    render_in_flow_descendants(list, images, layout_box);
+    let mut floats = Vec::new();
+    collect_floats(layout_box, &mut floats);
+    for float in floats {
+        render_box(list, images, float);
+        render_clipped_contents(list, images, float);
+    }
+}
+
+/// Paint the in-flow descendants of a box in tree order. The contents of a box that clips its
+/// overflow, including its floats, are painted together within its clip.
+fn render_in_flow_descendants(
+    list: &mut DisplayList,
+    images: &mut ImageCache,
+    layout_box: &LayoutBox,
+) {
+    for child in &layout_box.children {
+        if !is_layer(child) && child.float() == Float::None {
+            render_box(list, images, child);
+            if overflow_clip(child).is_some() {
+                render_clipped_contents(list, images, child);
+            } else {
+                render_in_flow_descendants(list, images, child);
+            }
+        }
+    }
+}
+
+/// Find the floats painted as part of `layout_box`, excluding those nested in other floats or
+/// in boxes that clip their overflow.
+fn collect_floats<'a, 'b>(layout_box: &'b LayoutBox<'a>, floats: &mut Vec<&'b LayoutBox<'a>>) {
+    for child in &layout_box.children {
+        if is_layer(child) {
+            continue;
+        }
+        if child.float() != Float::None {
+            floats.push(child);
+        } else if overflow_clip(child).is_none() {
+            collect_floats(child, floats);
+        }
+    }
+}
+
+/// The z-index of a box that establishes a stacking context, or `None` for boxes painted as
+/// part of their parent's stacking context.
+fn z_index(layout_box: &LayoutBox) -> Option<i32> {
+    let z_index = if layout_box.is_positioned() {
+        style_node(layout_box).and_then(StyledNode::z_index)
+    } else {
+        None
+    };
+    // Translucent boxes establish a stacking context, painted as if positioned with z-index 0.
+    z_index.or(if opacity(layout_box) < 1.0 {
+        Some(0)
+    } else {
+        None
+    })
+}
+
+/// Whether a box is painted in a layer of the enclosing stacking context, rather than with the
+/// normal flow.
+fn is_layer(layout_box: &LayoutBox) -> bool {
+    layout_box.is_positioned() || opacity(layout_box) < 1.0
+}
+
+/// The `opacity` of a box, between 0 and 1.
+fn opacity(layout_box: &LayoutBox) -> f32 {
+    match style_node(layout_box).and_then(|style| style.value("opacity")) {
+        Some(Value::Number(opacity)) => opacity.clamp(0.0, 1.0),
+        Some(Value::Length(percent, Unit::Percent)) => (percent / 100.0).clamp(0.0, 1.0),
+        _ => 1.0,
+    }
+}
+
+/// Paint the background and borders of a single box, and its content if it is a text run or
+/// a replaced box.
+fn render_box(list: &mut DisplayList, images: &mut ImageCache, layout_box: &LayoutBox) {
+    render_background(list, images, layout_box);
+    render_borders(list, layout_box);
+    let content = layout_box.dimensions.content;
+    if content.width <= 0.0 || content.height <= 0.0 {
+        return;
+    }
+    match layout_box.box_type {
+        ReplacedNode(_, Replaced::Image(ref image)) => {
+            list.push(DisplayCommand::Image(image.clone(), content))
+        }
+        ReplacedNode(style, Replaced::Control(ref control)) => {
+            render_control(list, style, control, content)
+        }
+        ReplacedNode(style, Replaced::Marker(ref marker)) => {
+            render_marker(list, style, marker, content)
+        }
+        TextRun(style, ref text) => {
+            list.push(DisplayCommand::Text(Text {
+                text: text.clone(),
+                rect: content,
+                font_size: font_size(style),
+                color: text_color(style),
+            }));
+            render_text_decorations(list, style, content);
+        }
+        _ => {}
+    }
+}
+
+/// Paint the lines that `text-decoration` draws across a run of text: `underline`, `overline`
+/// and `line-through`, in the color given with them, or else the color of the text. The lines
+/// are placed relative to an em box centered on the line, as the text itself is.
+///
+/// http://www.w3.org/TR/css-text-decor-3/#line-decoration
+fn render_text_decorations(list: &mut DisplayList, style: &StyledNode, rect: Rect) {
+    let values = |name: &str| match style.value(name) {
+        Some(Value::List(values)) => values,
+        Some(value) => vec![value],
+        None => Vec::new(),
+    };
+    let shorthand = values("text-decoration");
+    let lines = match values("text-decoration-line") {
+        lines if lines.is_empty() => shorthand.clone(),
+        lines => lines,
+    };
+    let color = style
+        .value("text-decoration-color")
+        .into_iter()
+        .chain(shorthand)
+        .find_map(|value| match value {
+            Value::ColorValue(color) => Some(color),
+            _ => None,
+        })
+        .unwrap_or_else(|| text_color(style));
+
+    let font_size = font_size(style);
+    let thickness = (font_size * DECORATION_THICKNESS).round().max(1.0);
+    let em_top = rect.y + (rect.height - font_size) / 2.0;
+    let baseline = em_top + 0.8 * font_size;
+    for line in lines {
+        let y = match line {
+            Value::Keyword(ref keyword) if keyword == "underline" => baseline + 0.1 * font_size,
+            Value::Keyword(ref keyword) if keyword == "overline" => em_top,
+            Value::Keyword(ref keyword) if keyword == "line-through" => baseline - 0.3 * font_size,
+            _ => continue,
+        };
+        let line = Rect {
+            y: y.round(),
+            height: thickness,
+            ..rect
+        };
+        list.push(DisplayCommand::SolidColor(color.clone(), line));
+    }
+}
+
+fn text_color(style: &StyledNode) -> Color {
+    match style.value("color") {
+        Some(Value::ColorValue(color)) => color,
+        _ => BLACK,
+    }
+}
+
+/// Paint what a form control shows inside its content box: the text of a text field, the mark
+/// of a checked checkbox or radio button, the label of a button, or the selected option of a
+/// `<select>` and its arrow. Text that doesn't fit is clipped.
+fn render_control(list: &mut DisplayList, style: &StyledNode, control: &Control, content: Rect) {
+    let color = text_color(style);
+    let line_height = line_height(style);
+    let text = |text: &str, x: f32, y: f32, color: Color| {
+        DisplayCommand::Text(Text {
+            text: text.to_string(),
+            rect: Rect {
+                x,
+                y,
+                width: text_width(text, style),
+                height: line_height,
+            },
+            font_size: font_size(style),
+            color,
+        })
+    };
+    match *control {
+        Control::TextField {
+            ref lines,
+            placeholder,
+            ..
+        } => {
+            let color = if placeholder { PLACEHOLDER } else { color };
+            let lines = lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| !line.is_empty())
+                .map(|(index, line)| {
+                    let y = content.y + index as f32 * line_height;
+                    text(line, content.x, y, color.clone())
+                })
+                .collect();
+            list.push(DisplayCommand::Clip(content, lines));
+        }
+        Control::Checkbox { checked: true } => list.push(DisplayCommand::SolidColor(
+            color,
+            inset_rect(content, CONTROL_MARK_INSET),
+        )),
+        Control::Radio { checked: true } => {
+            let dot = inset_rect(content, CONTROL_MARK_INSET);
+            list.push(DisplayCommand::RoundedRect(color, dot, ellipse_radii(dot)));
+        }
+        Control::Checkbox { checked: false } | Control::Radio { checked: false } => {}
+        Control::Button { ref label } => {
+            let x = content.x + (content.width - text_width(label, style)) / 2.0;
+            let y = content.y + (content.height - line_height) / 2.0;
+            list.push(DisplayCommand::Clip(
+                content,
+                vec![text(label, x, y, color)],
+            ));
+        }
+        Control::Select {
+            ref options,
+            selected,
+        } => {
+            let arrow_width = SELECT_ARROW_WIDTH * font_size(style);
+            let label_area = Rect {
+                width: (content.width - arrow_width).max(0.0),
+                ..content
+            };
+            if let Some(label) = selected.map(|index| &options[index]) {
+                let label = text(label, content.x, content.y, color.clone());
+                list.push(DisplayCommand::Clip(label_area, vec![label]));
+            }
+            // A triangle pointing down, drawn a row at a time, centered in the space beside
+            // the label.
+            let size = (arrow_width / 2.0).floor();
+            let x = content.x + content.width - (arrow_width + size) / 2.0;
+            let y = content.y + ((content.height - size / 2.0) / 2.0).floor();
+            for row in 0..(size / 2.0) as usize {
+                let row = row as f32;
+                let rect = Rect {
+                    x: x + row,
+                    y: y + row,
+                    width: size - 2.0 * row,
+                    height: 1.0,
+                };
+                list.push(DisplayCommand::SolidColor(color.clone(), rect));
+            }
+        }
+    }
+}
+
+/// Paint a list item's marker. A bullet is drawn as a shape in the place of its first character,
+/// centered on the line.
+fn render_marker(list: &mut DisplayList, style: &StyledNode, marker: &Marker, content: Rect) {
+    let color = text_color(style);
+    let size = (BULLET_SIZE * font_size(style)).round();
+    let bullet = Rect {
+        x: content.x + ((text_width(" ", style) - size) / 2.0).round(),
+        y: content.y + ((line_height(style) - size) / 2.0).round(),
+        width: size,
+        height: size,
+    };
+    match *marker {
+        Marker::Disc => list.push(DisplayCommand::RoundedRect(
+            color,
+            bullet,
+            ellipse_radii(bullet),
+        )),
+        Marker::Circle => {
+            let side = BorderSide {
+                width: 1.0,
+                color,
+                style: BorderStyle::Solid,
+            };
+            list.push(DisplayCommand::Border(Border {
+                rect: bullet,
+                radii: ellipse_radii(bullet),
+                top: side.clone(),
+                right: side.clone(),
+                bottom: side.clone(),
+                left: side,
+            }));
+        }
+        Marker::Square => list.push(DisplayCommand::SolidColor(color, bullet)),
+        Marker::Text(ref text) => list.push(DisplayCommand::Text(Text {
+            text: text.clone(),
+            rect: content,
+            font_size: font_size(style),
+            color,
+        })),
+    }
+}
+
+/// The radii that round a rectangle into an ellipse.
+fn ellipse_radii(rect: Rect) -> CornerRadii {
+    let radius = Radius {
+        x: rect.width / 2.0,
+        y: rect.height / 2.0,
+    };
+    CornerRadii {
+        top_left: radius,
+        top_right: radius,
+        bottom_right: radius,
+        bottom_left: radius,
+    }
+}
+
+fn render_background(list: &mut DisplayList, images: &mut ImageCache, layout_box: &LayoutBox) {
+    let style = match style_node(layout_box) {
+        Some(style) => style,
+        None => return,
+    };
+    let background = BackgroundStyle::new(style);
+    let d = &layout_box.dimensions;
+
+    // The background is clipped to the border box, or to the padding or content box inside
+    // it, following the curve of the border's corners.
+    let clip_inset = box_inset(d, background.clip.as_deref());
+    let clip = inset_rect(d.border_box(), clip_inset);
+    let radii = border_radii(layout_box).inset(clip_inset);
+    if let Some(color) = background.color {
+        if radii.is_square() {
+            list.push(DisplayCommand::SolidColor(color, clip));
+        } else {
+            list.push(DisplayCommand::RoundedRect(color, clip, radii));
+        }
+    }
+
+    // Images are positioned relative to the padding box, unless `background-origin` says
+    // otherwise.
+    let area = inset_rect(
+        d.border_box(),
+        box_inset(d, background.origin.as_deref().or(Some("padding-box"))),
+    );
+    let image = match background.image {
+        Some(Value::Url(url)) => match images.get(&url) {
+            Some(image) => BackgroundImageSource::Image(image),
+            None => return,
+        },
+        Some(Value::Function(name, arguments)) if name.ends_with("-gradient") => {
+            BackgroundImageSource::Gradient(name, arguments)
+        }
+        _ => return,
+    };
+    let intrinsic = match image {
+        BackgroundImageSource::Image(ref image) => Some((image.width as f32, image.height as f32)),
+        BackgroundImageSource::Gradient(..) => None,
+    };
+    let (width, height) = tile_size(&background.size, &area, intrinsic);
+    if width <= 0.0 || height <= 0.0 {
+        return;
+    }
+    let (x, y) = position(&background.position, &area, width, height);
+    let image = match image {
+        BackgroundImageSource::Image(image) => BackgroundImage::Image(image),
+        BackgroundImageSource::Gradient(name, arguments) => {
+            match gradient(&name, &arguments, width, height) {
+                Some(gradient) => gradient,
+                None => return,
+            }
+        }
+    };
+    list.push(DisplayCommand::Background(Background {
+        image,
+        clip,
+        radii,
+        tile: Rect {
+            x: area.x + x,
+            y: area.y + y,
+            width,
+            height,
+        },
+        repeat_x: background.repeat_x,
+        repeat_y: background.repeat_y,
+    }));
+}
+
+/// The properties of a box's background, from the `background` shorthand and its longhands.
+///
+/// http://www.w3.org/TR/css3-background/#backgrounds
+struct BackgroundStyle {
+    color: Option<Color>,
+    image: Option<Value>,
+    repeat_x: bool,
+    repeat_y: bool,
+    position: Vec<Value>,
+    size: Vec<Value>,
+    origin: Option<String>,
+    clip: Option<String>,
+}
+
+/// A background image before it is sized: a loaded image or a gradient function.
+enum BackgroundImageSource {
+    Image(Rc<Image>),
+    Gradient(String, Vec<Value>),
+}
+
+impl BackgroundStyle {
+    fn new(style: &StyledNode) -> BackgroundStyle {
+        let mut background = BackgroundStyle {
+            color: None,
+            image: None,
+            repeat_x: true,
+            repeat_y: true,
+            position: Vec::new(),
+            size: Vec::new(),
+            origin: None,
+            clip: None,
+        };
+
+        // The shorthand's components can be given in any order, except that the size follows
+        // the position after a `/`.
+        let mut repeat = Vec::new();
+        let mut boxes = Vec::new();
+        let mut after_slash = false;
+        for value in list(style.value("background")) {
+            match value {
+                Value::ColorValue(color) => background.color = Some(color),
+                Value::Url(_) | Value::Function(..) => background.image = Some(value),
+                Value::Keyword(ref keyword) => match &**keyword {
+                    "/" => after_slash = true,
+                    "repeat" | "repeat-x" | "repeat-y" | "no-repeat" | "space" | "round" => {
+                        repeat.push(value)
+                    }
+                    "border-box" | "padding-box" | "content-box" => boxes.push(keyword.clone()),
+                    "none" | "transparent" => {}
+                    _ if after_slash => background.size.push(value),
+                    _ => background.position.push(value),
+                },
+                _ if after_slash => background.size.push(value),
+                _ => background.position.push(value),
+            }
+        }
+        // A single box keyword sets both the origin and the clip.
+        background.origin = boxes.first().cloned();
+        background.clip = boxes.last().cloned();
+
+        if let Some(value) = style.value("background-color") {
+            background.color = match value {
+                Value::ColorValue(color) => Some(color),
+                _ => None,
+            };
+        }
+        if let Some(value) = style.value("background-image") {
+            background.image = Some(value);
+        }
+        if let Some(value) = style.value("background-repeat") {
+            repeat = list(Some(value));
+        }
+        if let Some(value) = style.value("background-position") {
+            background.position = list(Some(value));
+        }
+        if let Some(value) = style.value("background-size") {
+            background.size = list(Some(value));
+        }
+        if let Some(Value::Keyword(origin)) = style.value("background-origin") {
+            background.origin = Some(origin);
+        }
+        if let Some(Value::Keyword(clip)) = style.value("background-clip") {
+            background.clip = Some(clip);
+        }
+
+        let keyword = |value: Option<&Value>| match value {
+            Some(Value::Keyword(keyword)) => keyword.clone(),
+            _ => "repeat".to_string(),
+        };
+        (background.repeat_x, background.repeat_y) = match repeat.len() {
+            0 => (true, true),
+            1 => match &*keyword(repeat.first()) {
+                "repeat-x" => (true, false),
+                "repeat-y" => (false, true),
+                "no-repeat" => (false, false),
+                _ => (true, true),
+            },
+            _ => (
+                keyword(repeat.first()) != "no-repeat",
+                keyword(repeat.get(1)) != "no-repeat",
+            ),
+        };
+        background
+    }
+}
+
+/// The components of a space-separated value.
+fn list(value: Option<Value>) -> Vec<Value> {
+    match value {
+        Some(Value::List(values)) => values,
+        Some(value) => vec![value],
+        None => Vec::new(),
+    }
+}
+
+/// The distance from the border box to the edge named by a `background-clip` or
+/// `background-origin` keyword.
+fn box_inset(d: &crate::layout::Dimensions, keyword: Option<&str>) -> EdgeSize {
+    match keyword {
+        Some("padding-box") => d.border,
+        Some("content-box") => EdgeSize {
+            left: d.border.left + d.padding.left,
+            right: d.border.right + d.padding.right,
+            top: d.border.top + d.padding.top,
+            bottom: d.border.bottom + d.padding.bottom,
+        },
+        _ => EdgeSize::default(),
+    }
+}
+
+/// The size of one copy of a background image within `area`, from `background-size`.
+/// Gradients have no intrinsic size, and fill the area by default.
+///
+/// http://www.w3.org/TR/css3-background/#the-background-size
+fn tile_size(size: &[Value], area: &Rect, intrinsic: Option<(f32, f32)>) -> (f32, f32) {
+    let (intrinsic_width, intrinsic_height) = intrinsic.unwrap_or((area.width, area.height));
+    let ratio = if intrinsic_height > 0.0 {
+        intrinsic_width / intrinsic_height
+    } else {
+        1.0
+    };
+    let resolve = |value: Option<&Value>, basis: f32| match value {
+        Some(Value::Keyword(keyword)) if keyword == "auto" => None,
+        Some(value @ Value::Length(..)) => Some(length(value, basis)),
+        _ => None,
+    };
+    match size.first() {
+        Some(Value::Keyword(keyword)) if keyword == "cover" || keyword == "contain" => {
+            let scale_x = area.width / intrinsic_width;
+            let scale_y = area.height / intrinsic_height;
+            let scale = if keyword == "cover" {
+                scale_x.max(scale_y)
+            } else {
+                scale_x.min(scale_y)
+            };
+            (intrinsic_width * scale, intrinsic_height * scale)
+        }
+        first => {
+            let width = resolve(first, area.width);
+            let height = resolve(size.get(1), area.height);
+            match (width, height) {
+                (Some(width), Some(height)) => (width, height),
+                (Some(width), None) if intrinsic.is_some() => (width, width / ratio),
+                (None, Some(height)) if intrinsic.is_some() => (height * ratio, height),
+                (Some(width), None) => (width, area.height),
+                (None, Some(height)) => (area.width, height),
+                (None, None) => (intrinsic_width, intrinsic_height),
+            }
+        }
+    }
+}
+
+/// The offset of an object of the given size within `area`, from a value such as
+/// `background-position: right 10px`. Percentages align the same point of the object and the
+/// area, and a missing component centers the object along its axis. Without a value, the object
+/// is at the top left.
+///
+/// http://www.w3.org/TR/css3-background/#the-background-position
+fn position(values: &[Value], area: &Rect, width: f32, height: f32) -> (f32, f32) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mut x = None;
+    let mut y = None;
+    for (index, value) in values.iter().enumerate() {
+        match value {
+            Value::Keyword(keyword) => match &**keyword {
+                "left" => x = Some(Value::Length(0.0, Unit::Percent)),
+                "right" => x = Some(Value::Length(100.0, Unit::Percent)),
+                "top" => y = Some(Value::Length(0.0, Unit::Percent)),
+                "bottom" => y = Some(Value::Length(100.0, Unit::Percent)),
+                _ => {}
+            },
+            // A length following a keyword offsets the object from that edge.
+            Value::Length(..) if index > 0 && x.is_some() && y.is_none() => y = Some(value.clone()),
+            Value::Length(..) if index == 0 => x = Some(value.clone()),
+            Value::Length(..) => y = Some(value.clone()),
+            _ => {}
+        }
+    }
+    let center = Value::Length(50.0, Unit::Percent);
+    (
+        length(&x.unwrap_or(center.clone()), area.width - width),
+        length(&y.unwrap_or(center), area.height - height),
+    )
+}
+
+/// Resolve a `linear-gradient()` or `radial-gradient()` for a tile of the given size.
+///
+/// http://www.w3.org/TR/css3-images/#gradients
+fn gradient(name: &str, arguments: &[Value], width: f32, height: f32) -> Option<BackgroundImage> {
+    let (first, rest) = arguments.split_first()?;
+    match name {
+        "linear-gradient" => {
+            // The angle defaults to `to bottom`.
+            let (angle, stops) = match gradient_angle(first, width, height) {
+                Some(angle) => (angle, rest),
+                None => (180.0, arguments),
+            };
+            let radians = angle.to_radians();
+            let line_length = (width * radians.sin()).abs() + (height * radians.cos()).abs();
+            Some(BackgroundImage::LinearGradient(
+                angle,
+                color_stops(stops, line_length)?,
+            ))
+        }
+        "radial-gradient" => {
+            let (shape, stops) = match color_stop(first, 1.0) {
+                Some(_) => (Vec::new(), arguments),
+                None => (list(Some(first.clone())), rest),
+            };
+            let (x, y, radius) = radial_shape(&shape, width, height);
+            Some(BackgroundImage::RadialGradient(
+                x,
+                y,
+                radius,
+                color_stops(stops, radius.x)?,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The angle of a linear gradient, from an angle or a direction such as `to top right`.
+fn gradient_angle(value: &Value, width: f32, height: f32) -> Option<f32> {
+    let values = match value {
+        Value::Length(angle, Unit::Deg) => return Some(*angle),
+        Value::List(values) if values.first() == Some(&Value::Keyword("to".to_string())) => {
+            &values[1..]
+        }
+        _ => return None,
+    };
+    // The direction towards the named side or corner, with y pointing down.
+    let (mut dx, mut dy) = (0.0, 0.0);
+    for value in values {
+        match value {
+            Value::Keyword(side) if side == "left" => dx = -1.0,
+            Value::Keyword(side) if side == "right" => dx = 1.0,
+            Value::Keyword(side) if side == "top" => dy = -1.0,
+            Value::Keyword(side) if side == "bottom" => dy = 1.0,
+            _ => return None,
+        }
+    }
+    // Towards a corner, the gradient line is perpendicular to the diagonal between the other
+    // two corners.
+    let (dx, dy) = if dx != 0.0 && dy != 0.0 {
+        (dx * height, dy * width)
+    } else {
+        (dx, dy)
+    };
+    Some(f32::atan2(dx, -dy).to_degrees())
+}
+
+/// The center and radii of a radial gradient, from its shape, size and position, such as
+/// `circle closest-side at 10px 20%`.
+fn radial_shape(values: &[Value], width: f32, height: f32) -> (f32, f32, Radius) {
+    let at = Value::Keyword("at".to_string());
+    let (shape, at_position) = match values.iter().position(|value| *value == at) {
+        Some(index) => (&values[..index], &values[index + 1..]),
+        None => (values, &[][..]),
+    };
+    let area = Rect {
+        width,
+        height,
+        ..Default::default()
+    };
+    let (x, y) = match at_position {
+        [] => (width / 2.0, height / 2.0),
+        at_position => position(at_position, &area, 0.0, 0.0),
+    };
+
+    let keyword = |name: &str| shape.contains(&Value::Keyword(name.to_string()));
+    let lengths: Vec<&Value> = shape
+        .iter()
+        .filter(|value| matches!(value, Value::Length(..)))
+        .collect();
+    let circle = keyword("circle") || (lengths.len() == 1 && !keyword("ellipse"));
+    if let [radius] = lengths[..] {
+        let radius = length(radius, width);
+        return (
+            x,
+            y,
+            Radius {
+                x: radius,
+                y: radius,
+            },
+        );
+    }
+    if let [rx, ry] = lengths[..] {
+        let radius = Radius {
+            x: length(rx, width),
+            y: length(ry, height),
+        };
+        return (x, y, radius);
+    }
+
+    // The distances from the center to the nearest and farthest sides along each axis.
+    let (near_x, far_x) = (x.min(width - x).abs(), x.max(width - x).abs());
+    let (near_y, far_y) = (y.min(height - y).abs(), y.max(height - y).abs());
+    let radius = if keyword("closest-side") {
+        match circle {
+            true => Radius {
+                x: near_x.min(near_y),
+                y: near_x.min(near_y),
+            },
+            false => Radius {
+                x: near_x,
+                y: near_y,
+            },
+        }
+    } else if keyword("farthest-side") {
+        match circle {
+            true => Radius {
+                x: far_x.max(far_y),
+                y: far_x.max(far_y),
+            },
+            false => Radius { x: far_x, y: far_y },
+        }
+    } else {
+        // The shape passes through the closest or farthest corner. An ellipse keeps the aspect
+        // ratio it would have for the closest or farthest side.
+        let (dx, dy) = if keyword("closest-corner") {
+            (near_x, near_y)
+        } else {
+            (far_x, far_y)
+        };
+        match circle {
+            true => Radius {
+                x: dx.hypot(dy),
+                y: dx.hypot(dy),
+            },
+            false => Radius {
+                x: dx * std::f32::consts::SQRT_2,
+                y: dy * std::f32::consts::SQRT_2,
+            },
+        }
+    };
+    (x, y, radius)
+}
+
+/// The color stops of a gradient whose line is `line_length` long. Stops without a position
+/// are spaced evenly between their neighbors, and no stop comes before the one preceding it.
+fn color_stops(values: &[Value], line_length: f32) -> Option<Vec<ColorStop>> {
+    let stops: Vec<(Color, Option<f32>)> = values
+        .iter()
+        .map(|value| color_stop(value, line_length))
+        .collect::<Option<_>>()?;
+    if stops.is_empty() {
+        return None;
+    }
+    let mut positions: Vec<Option<f32>> = stops.iter().map(|(_, position)| *position).collect();
+    let last = positions.len() - 1;
+    positions[0] = positions[0].or(Some(0.0));
+    positions[last] = positions[last].or(Some(1.0));
+    let mut previous = 0;
+    for index in 1..positions.len() {
+        if let Some(position) = positions[index] {
+            let start = positions[previous].unwrap();
+            let position = position.max(start);
+            positions[index] = Some(position);
+            for (step, between) in (previous + 1..index).enumerate() {
+                let fraction = (step + 1) as f32 / (index - previous) as f32;
+                positions[between] = Some(start + (position - start) * fraction);
+            }
+            previous = index;
+        }
+    }
+    Some(
+        stops
+            .into_iter()
+            .zip(positions)
+            .map(|((color, _), position)| ColorStop {
+                color,
+                position: position.unwrap(),
+            })
+            .collect(),
+    )
+}
+
+/// A color stop such as `#ff0000 20%`, with its position as a fraction of `line_length`.
+fn color_stop(value: &Value, line_length: f32) -> Option<(Color, Option<f32>)> {
+    let (color, position) = match value {
+        Value::List(values) if values.len() == 2 => (&values[0], Some(&values[1])),
+        value => (value, None),
+    };
+    let color = match color {
+        Value::ColorValue(color) => color.clone(),
+        Value::Keyword(keyword) if keyword == "transparent" => TRANSPARENT,
+        _ => return None,
+    };
+    let position = match position {
+        Some(Value::Length(percent, Unit::Percent)) => Some(percent / 100.0),
+        Some(length @ Value::Length(..)) if line_length > 0.0 => Some(length.to_px() / line_length),
+        Some(_) => return None,
+        None => None,
+    };
+    Some((color, position))
+}
+
+fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox) {
+    let style = match style_node(layout_box) {
+        Some(style) => style,
+        None => return,
+    };
+    let d = &layout_box.dimensions;
+    if d.border.left + d.border.right + d.border.top + d.border.bottom <= 0.0 {
+        return;
+    }
+
+    // Borders without a color of their own take the color of the box's text.
+    let side = |name: &str, width: f32| {
+        let color = match style.side_value(name, &format!("border-{}-color", name), "border-color")
+        {
+            Some(Value::ColorValue(color)) => color,
+            _ => match style.value("color") {
+                Some(Value::ColorValue(color)) => color,
+                _ => BLACK,
+            },
+        };
+        BorderSide {
+            width,
+            color,
+            style: style.border_style(name),
+        }
+    };
+    list.push(DisplayCommand::Border(Border {
+        rect: d.border_box(),
+        radii: border_radii(layout_box),
+        top: side("top", d.border.top),
+        right: side("right", d.border.right),
+        bottom: side("bottom", d.border.bottom),
+        left: side("left", d.border.left),
+    }));
+}
+
+const BLACK: Color = Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+/// How far the mark of a checked checkbox or radio button is inside its content box.
+const CONTROL_MARK_INSET: EdgeSize = EdgeSize {
+    left: 3.0,
+    right: 3.0,
+    top: 3.0,
+    bottom: 3.0,
+};
+
+/// The width and height of a list item's bullet, as a multiple of the font size.
+const BULLET_SIZE: f32 = 0.35;
+
+/// The thickness of the lines drawn by `text-decoration`, as a multiple of the font size.
+const DECORATION_THICKNESS: f32 = 1.0 / 16.0;
+
+/// The color of the placeholder text of an empty text field.
+const PLACEHOLDER: Color = Color {
+    r: 117,
+    g: 117,
+    b: 117,
+    a: 255,
+};
+
+const TRANSPARENT: Color = Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 0,
+};
+
+/// The used radii of the corners of a box's border box.
+///
+/// http://www.w3.org/TR/css3-background/#border-radius
+fn border_radii(layout_box: &LayoutBox) -> CornerRadii {
+    let style = match style_node(layout_box) {
+        Some(style) => style,
+        None => return CornerRadii::default(),
+    };
+    let rect = layout_box.dimensions.border_box();
+    let radius = |corner: usize, name: &str| {
+        let (x, y) = match style.value(name) {
+            Some(Value::List(values)) if values.len() == 2 => {
+                (values[0].clone(), values[1].clone())
+            }
+            Some(value) => (value.clone(), value),
+            None => corner_radius(style.value("border-radius"), corner),
+        };
+        let (x, y) = (length(&x, rect.width), length(&y, rect.height));
+        // A corner is only rounded if both of its radii are positive.
+        if x > 0.0 && y > 0.0 {
+            Radius { x, y }
+        } else {
+            Radius::default()
+        }
+    };
+    let radii = CornerRadii {
+        top_left: radius(0, "border-top-left-radius"),
+        top_right: radius(1, "border-top-right-radius"),
+        bottom_right: radius(2, "border-bottom-right-radius"),
+        bottom_left: radius(3, "border-bottom-left-radius"),
+    };
+
+    // Curves on the same side that would overlap are scaled down together.
+    let scale = [
+        rect.width / (radii.top_left.x + radii.top_right.x),
+        rect.width / (radii.bottom_left.x + radii.bottom_right.x),
+        rect.height / (radii.top_left.y + radii.bottom_left.y),
+        rect.height / (radii.top_right.y + radii.bottom_right.y),
+    ]
+    .into_iter()
+    .filter(|scale| scale.is_finite())
+    .fold(1.0, f32::min);
+    radii.scale(scale)
+}
+
+/// The horizontal and vertical radius of one corner, numbered clockwise from the top left,
+/// from the `border-radius` shorthand. Like `margin`, each half of the shorthand takes one to
+/// four values; the vertical radii follow a `/`, and default to the horizontal ones.
+fn corner_radius(shorthand: Option<Value>, corner: usize) -> (Value, Value) {
+    let values = match shorthand {
+        Some(Value::List(values)) => values,
+        Some(value) => vec![value],
+        None => return (Value::Length(0.0, Unit::Px), Value::Length(0.0, Unit::Px)),
+    };
+    let slash = Value::Keyword("/".to_string());
+    let (horizontal, vertical) = match values.iter().position(|value| *value == slash) {
+        Some(index) => (&values[..index], &values[index + 1..]),
+        None => (&values[..], &values[..]),
+    };
+    let pick = |values: &[Value]| {
+        let index = match values.len() {
+            0 => return Value::Length(0.0, Unit::Px),
+            1 => 0,
+            2 => corner % 2,
+            3 if corner == 3 => 1,
+            _ => corner,
+        };
+        values[index].clone()
+    };
+    (pick(horizontal), pick(vertical))
+}
+
+/// A length in px, resolving percentages against `basis`.
+fn length(value: &Value, basis: f32) -> f32 {
+    match *value {
+        Value::Length(percent, Unit::Percent) => percent / 100.0 * basis,
+        ref value => value.to_px(),
+    }
+}
+
+fn inset_rect(rect: Rect, inset: EdgeSize) -> Rect {
+    Rect {
+        x: rect.x + inset.left,
+        y: rect.y + inset.top,
+        width: (rect.width - inset.left - inset.right).max(0.0),
+        height: (rect.height - inset.top - inset.bottom).max(0.0),
+    }
+}
+
+impl CornerRadii {
+    /// Whether every corner is square.
+    pub fn is_square(&self) -> bool {
+        *self == CornerRadii::default()
+    }
+
+    /// The radii of the curve `inset` inside this one, such as the padding edge inside a
+    /// border.
+    pub fn inset(&self, inset: EdgeSize) -> CornerRadii {
+        let radius = |r: Radius, x: f32, y: f32| {
+            let (x, y) = ((r.x - x).max(0.0), (r.y - y).max(0.0));
+            if x > 0.0 && y > 0.0 {
+                Radius { x, y }
+            } else {
+                Radius::default()
+            }
+        };
+        CornerRadii {
+            top_left: radius(self.top_left, inset.left, inset.top),
+            top_right: radius(self.top_right, inset.right, inset.top),
+            bottom_right: radius(self.bottom_right, inset.right, inset.bottom),
+            bottom_left: radius(self.bottom_left, inset.left, inset.bottom),
+        }
+    }
+
+    fn scale(&self, factor: f32) -> CornerRadii {
+        let radius = |r: Radius| Radius {
+            x: r.x * factor,
+            y: r.y * factor,
+        };
+        CornerRadii {
+            top_left: radius(self.top_left),
+            top_right: radius(self.top_right),
+            bottom_right: radius(self.bottom_right),
+            bottom_left: radius(self.bottom_left),
+        }
+    }
+
+    /// Whether the point (x, y) is inside `rect` with these corners.
+    fn contains(&self, rect: Rect, x: f32, y: f32) -> bool {
+        if !rect.contains(x, y) {
+            return false;
+        }
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        let corners = [
+            (
+                self.top_left,
+                x0 + self.top_left.x,
+                y0 + self.top_left.y,
+                -1.0,
+                -1.0,
+            ),
+            (
+                self.top_right,
+                x1 - self.top_right.x,
+                y0 + self.top_right.y,
+                1.0,
+                -1.0,
+            ),
+            (
+                self.bottom_right,
+                x1 - self.bottom_right.x,
+                y1 - self.bottom_right.y,
+                1.0,
+                1.0,
+            ),
+            (
+                self.bottom_left,
+                x0 + self.bottom_left.x,
+                y1 - self.bottom_left.y,
+                -1.0,
+                1.0,
+            ),
+        ];
+        corners.iter().all(|&(radius, cx, cy, sx, sy)| {
+            // Only points beyond the center of a corner's ellipse can be outside its curve.
+            let (dx, dy) = (x - cx, y - cy);
+            if radius.x <= 0.0 || dx * sx <= 0.0 || dy * sy <= 0.0 {
+                return true;
+            }
+            (dx / radius.x).powi(2) + (dy / radius.y).powi(2) <= 1.0
+        })
+    }
+}
+
+impl Border {
+    /// The color of the border at the point (x, y), or `None` outside the border.
+    fn color_at(&self, x: f32, y: f32) -> Option<&Color> {
+        let rect = self.rect;
+        let inset = EdgeSize {
+            left: self.left.width,
+            right: self.right.width,
+            top: self.top.width,
+            bottom: self.bottom.width,
+        };
+        if !self.radii.contains(rect, x, y)
+            || self
+                .radii
+                .inset(inset)
+                .contains(inset_rect(rect, inset), x, y)
+        {
+            return None;
+        }
+
+        // The sides meet along the lines where the point is the same fraction of each side's
+        // width from the outer edge. `along` is the distance from the start of the side.
+        let sides = [
+            (&self.top, y - rect.y, x - rect.x),
+            (&self.right, rect.x + rect.width - x, y - rect.y),
+            (&self.bottom, rect.y + rect.height - y, x - rect.x),
+            (&self.left, x - rect.x, y - rect.y),
+        ];
+        let (side, across, along) = sides
+            .into_iter()
+            .filter(|(side, _, _)| side.width > 0.0)
+            .min_by(|a, b| (a.1 / a.0.width).total_cmp(&(b.1 / b.0.width)))?;
+        let width = side.width;
+        let across = across.min(width);
+        let visible = match side.style {
+            BorderStyle::None => false,
+            BorderStyle::Solid => true,
+            // Two lines, each a third of the width, with a gap between them.
+            BorderStyle::Double => {
+                width < 3.0 || across < width / 3.0 || across >= width * 2.0 / 3.0
+            }
+            // Dashes twice as long as the border is wide, separated by gaps half as long.
+            BorderStyle::Dashed => (along / width).rem_euclid(3.0) < 2.0,
+            // Round dots as wide as the border, one width apart.
+            BorderStyle::Dotted => {
+                let period = 2.0 * width;
+                let center = ((along / period).floor() + 0.5) * period;
+                (along - center).powi(2) + (across - width / 2.0).powi(2) <= (width / 2.0).powi(2)
+            }
+        };
+        if visible {
+            Some(&side.color)
+        } else {
+            None
+        }
+    }
+}
+
+fn style_node<'a>(layout_box: &LayoutBox<'a>) -> Option<&'a StyledNode<'a>> {
+    match layout_box.box_type {
+        BlockNode(style)
+        | FlexNode(style)
+        | GridNode(style)
+        | TableNode(style)
+        | TableRowNode(style)
+        | TableCellNode(style)
+        | InlineNode(style)
+        | TextRun(style, _)
+        | ReplacedNode(style, _) => Some(style),
+        AnonymousBlock(_) | LineBox => None,
+    }
+}
+
+impl Background {
+    /// The color of the background at the point (x, y), or `None` outside its tiles or clip.
+    fn color_at(&self, x: f32, y: f32) -> Option<Color> {
+        let tile = self.tile;
+        if !self.radii.contains(self.clip, x, y) || tile.width <= 0.0 || tile.height <= 0.0 {
+            return None;
+        }
+        let mut u = x - tile.x;
+        let mut v = y - tile.y;
+        if self.repeat_x {
+            u = u.rem_euclid(tile.width);
+        }
+        if self.repeat_y {
+            v = v.rem_euclid(tile.height);
+        }
+        if u < 0.0 || v < 0.0 || u >= tile.width || v >= tile.height {
+            return None;
+        }
+        match self.image {
+            BackgroundImage::Image(ref image) => {
+                Some(image.sample(u / tile.width, v / tile.height).clone())
+            }
+            BackgroundImage::LinearGradient(angle, ref stops) => {
+                let radians = angle.to_radians();
+                let (dx, dy) = (radians.sin(), -radians.cos());
+                let length = (tile.width * dx).abs() + (tile.height * dy).abs();
+                let along = (u - tile.width / 2.0) * dx + (v - tile.height / 2.0) * dy;
+                Some(gradient_color(stops, along / length + 0.5))
+            }
+            BackgroundImage::RadialGradient(cx, cy, radius, ref stops) => {
+                let t = if radius.x > 0.0 && radius.y > 0.0 {
+                    ((u - cx) / radius.x).hypot((v - cy) / radius.y)
+                } else {
+                    1.0
+                };
+                Some(gradient_color(stops, t))
+            }
+        }
+    }
+}
+
+/// The color of a gradient at `position`, interpolated between the stops on either side of it.
+/// Colors are interpolated with premultiplied alpha, so transparent stops don't darken.
+pub fn gradient_color(stops: &[ColorStop], position: f32) -> Color {
+    let next = stops
+        .iter()
+        .position(|stop| stop.position > position)
+        .unwrap_or(stops.len());
+    if next == 0 {
+        return stops[0].color.clone();
+    }
+    if next == stops.len() {
+        return stops[next - 1].color.clone();
+    }
+    let (start, end) = (&stops[next - 1], &stops[next]);
+    let t = (position - start.position) / (end.position - start.position);
+    let alpha = |color: &Color| color.a as f32 / 255.0;
+    let a = alpha(&start.color) * (1.0 - t) + alpha(&end.color) * t;
+    if a <= 0.0 {
+        return TRANSPARENT;
+    }
+    let channel = |from: u8, to: u8| {
+        let value =
+            from as f32 * alpha(&start.color) * (1.0 - t) + to as f32 * alpha(&end.color) * t;
+        (value / a).round() as u8
+    };
+    Color {
+        r: channel(start.color.r, end.color.r),
+        g: channel(start.color.g, end.color.g),
+        b: channel(start.color.b, end.color.b),
+        a: (a * 255.0).round() as u8,
+    }
+}
+
+impl Canvas {
+    /// Create a blank canvas
+    fn new(width: usize, height: usize) -> Canvas {
+        let white = Color {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        };
+        Canvas {
+            pixels: vec![white; width * height],
+            width,
+            height,
+            scroll: (0.0, 0.0),
+            clip_rect: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: width as f32,
+                height: height as f32,
+            },
+        }
+    }
+
+    fn paint_item(&mut self, item: &DisplayCommand) {
+        match *item {
+            DisplayCommand::SolidColor(ref color, rect) => {
+                let (x0, y0, x1, y1) = self.clip(rect);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        self.blend(x, y, color, 1.0);
+                    }
+                }
+            }
+            DisplayCommand::RoundedRect(ref color, rect, ref radii) => {
+                self.paint_samples(rect, SUBSAMPLES, |x, y| {
+                    radii.contains(rect, x, y).then(|| color.clone())
+                });
+            }
+            DisplayCommand::Border(ref border) => {
+                self.paint_samples(border.rect, SUBSAMPLES, |x, y| {
+                    border.color_at(x, y).cloned()
+                });
+            }
+            DisplayCommand::Background(ref background) => {
+                // Only rounded corners need anti-aliasing.
+                let samples = if background.radii.is_square() {
+                    1
+                } else {
+                    SUBSAMPLES
+                };
+                self.paint_samples(background.clip, samples, |x, y| background.color_at(x, y));
+            }
+            DisplayCommand::Image(ref image, rect) => {
+                self.paint_samples(rect, 1, |x, y| {
+                    let u = (x - rect.x) / rect.width;
+                    let v = (y - rect.y) / rect.height;
+                    ((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v))
+                        .then(|| image.sample(u, v).clone())
+                });
+            }
+            // There are no fonts to draw glyphs with, so text only appears in vector output.
+            DisplayCommand::Text(_) => {}
+            DisplayCommand::Layer(opacity, ref items) => {
+                let mut layer = Canvas {
+                    pixels: vec![TRANSPARENT; self.width * self.height],
+                    width: self.width,
+                    height: self.height,
+                    scroll: self.scroll,
+                    clip_rect: self.clip_rect,
+                };
+                for item in items {
+                    layer.paint_item(item);
+                }
+                for (index, color) in layer.pixels.iter().enumerate() {
+                    if color.a > 0 {
+                        self.blend(index % self.width, index / self.width, color, opacity);
+                    }
+                }
+            }
+            DisplayCommand::Clip(rect, ref items) => {
+                let clip_rect = self.clip_rect;
+                self.clip_rect = clip_rect.intersection(self.to_pixels(rect));
+                for item in items {
+                    self.paint_item(item);
+                }
+                self.clip_rect = clip_rect;
+            }
+            DisplayCommand::Fixed(ref items) => {
+                // Fixed boxes are laid out relative to the viewport, and aren't clipped by the
+                // boxes they are nested in.
+                let (scroll, clip_rect) = (self.scroll, self.clip_rect);
+                self.scroll = (0.0, 0.0);
+                self.clip_rect = Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: self.width as f32,
+                    height: self.height as f32,
+                };
+                for item in items {
+                    self.paint_item(item);
+                }
+                (self.scroll, self.clip_rect) = (scroll, clip_rect);
+            }
+        }
+    }
+
+    /// The position of a rectangle of the document on the canvas.
+    fn to_pixels(&self, rect: Rect) -> Rect {
+        Rect {
+            x: rect.x - self.scroll.0,
+            y: rect.y - self.scroll.1,
+            ..rect
+        }
+    }
+
+    /// The pixels covered by `rect`, clipped to the canvas boundaries and the current clip.
+    fn clip(&self, rect: Rect) -> (usize, usize, usize, usize) {
+        let rect = self.to_pixels(rect).intersection(self.clip_rect);
+        let x0 = rect.x.clamp(0.0, self.width as f32) as usize;
+        let y0 = rect.y.clamp(0.0, self.height as f32) as usize;
+        let x1 = (rect.x + rect.width).clamp(0.0, self.width as f32) as usize;
+        let y1 = (rect.y + rect.height).clamp(0.0, self.height as f32) as usize;
+        (x0, y0, x1, y1)
+    }
+
+    /// Paint the pixels within `bounds` by sampling the color of a shape at `samples` by
+    /// `samples` points in each pixel, so that curved and diagonal edges are anti-aliased.
+    fn paint_samples(
+        &mut self,
+        bounds: Rect,
+        samples: usize,
+        color_at: impl Fn(f32, f32) -> Option<Color>,
+    ) {
+        let grown = Rect {
+            x: bounds.x.floor(),
+            y: bounds.y.floor(),
+            width: bounds.width + 1.0,
+            height: bounds.height + 1.0,
+        };
+        let (x0, y0, x1, y1) = self.clip(grown);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // The premultiplied color of the samples that hit the shape.
+                let mut sum = [0.0; 4];
+                for i in 0..samples {
+                    for j in 0..samples {
+                        let sample_x = self.scroll.0 + x as f32 + (i as f32 + 0.5) / samples as f32;
+                        let sample_y = self.scroll.1 + y as f32 + (j as f32 + 0.5) / samples as f32;
+                        if let Some(color) = color_at(sample_x, sample_y) {
+                            let alpha = color.a as f32 / 255.0;
+                            sum[0] += color.r as f32 * alpha;
+                            sum[1] += color.g as f32 * alpha;
+                            sum[2] += color.b as f32 * alpha;
+                            sum[3] += alpha;
+                        }
+                    }
+                }
+                if sum[3] > 0.0 {
+                    let color = Color {
+                        r: (sum[0] / sum[3]).round() as u8,
+                        g: (sum[1] / sum[3]).round() as u8,
+                        b: (sum[2] / sum[3]).round() as u8,
+                        a: 255,
+                    };
+                    let coverage = sum[3] / (samples * samples) as f32;
+                    self.blend(x, y, &color, coverage);
+                }
+            }
+        }
+    }
+
+    /// Composite `color`, its opacity scaled by `coverage`, over the pixel at (x, y).
+    fn blend(&mut self, x: usize, y: usize, color: &Color, coverage: f32) {
+        let alpha = color.a as f32 / 255.0 * coverage;
+        let pixel = &mut self.pixels[y * self.width + x];
+        let behind = pixel.a as f32 / 255.0 * (1.0 - alpha);
+        let total = alpha + behind;
+        if total <= 0.0 {
+            return;
+        }
+        let mix = |source: u8, destination: u8| {
+            ((source as f32 * alpha + destination as f32 * behind) / total).round() as u8
+        };
+        *pixel = Color {
+            r: mix(color.r, pixel.r),
+            g: mix(color.g, pixel.g),
+            b: mix(color.b, pixel.b),
+            a: (total * 255.0).round() as u8,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::{css, html, style::style_tree};
+    use std::path::Path;
+
+    fn with_display_list(source: &str, stylesheet: &str, test: impl FnOnce(DisplayList)) {
+        with_images(source, stylesheet, Path::new("."), test)
+    }
+
+    /// Build the display list of a document in the directory `base`.
+    fn with_images(source: &str, stylesheet: &str, base: &Path, test: impl FnOnce(DisplayList)) {
+        let root = html::parse(source.to_string());
+        let stylesheet = css::parse(stylesheet.to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let mut viewport: Dimensions = Default::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        let mut images = ImageCache::new(base);
+        let layout = layout_tree(&styled, viewport, &mut images);
+        test(build_display_list(&layout, &mut images));
+    }
+
+    /// Return the red channel of each background painted, in paint order.
+    fn paint_order(source: &str, stylesheet: &str) -> Vec<u8> {
+        let mut order = Vec::new();
+        with_display_list(source, stylesheet, |list| {
+            for item in list {
+                if let DisplayCommand::SolidColor(color, _) = item {
+                    order.push(color.r);
+                }
+            }
+        });
+        order
+    }
+
+    /// Paint a single `div` onto a canvas the size of its border box.
+    fn paint_div(stylesheet: &str) -> Canvas {
+        paint_div_in(stylesheet, Path::new("."))
+    }
+
+    fn paint_div_in(stylesheet: &str, base: &Path) -> Canvas {
+        let mut canvas = None;
+        fn bounds(list: &[DisplayCommand]) -> Rect {
+            match list.first() {
+                Some(DisplayCommand::SolidColor(_, rect))
+                | Some(DisplayCommand::RoundedRect(_, rect, _)) => *rect,
+                Some(DisplayCommand::Border(border)) => border.rect,
+                Some(DisplayCommand::Background(background)) => background.clip,
+                Some(DisplayCommand::Image(_, rect)) => *rect,
+                Some(DisplayCommand::Text(text)) => text.rect,
+                Some(DisplayCommand::Layer(_, layer)) => bounds(layer),
+                Some(DisplayCommand::Clip(_, list)) | Some(DisplayCommand::Fixed(list)) => {
+                    bounds(list)
+                }
+                None => Rect::default(),
+            }
+        }
+        with_images("<div></div>", stylesheet, base, |list| {
+            let bounds = bounds(&list);
+            let mut painted = Canvas::new(bounds.width as usize, bounds.height as usize);
+            for item in &list {
+                painted.paint_item(item);
+            }
+            canvas = Some(painted);
+        });
+        canvas.unwrap()
+    }
+
+    /// Paint the part of a document given by `bounds`, which is computed from its layout.
+    fn paint_document(
+        source: &str,
+        stylesheet: &str,
+        bounds: impl FnOnce(&LayoutBox) -> Rect,
+    ) -> Canvas {
+        let root = html::parse(source.to_string());
+        let stylesheet = css::parse(stylesheet.to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let mut viewport: Dimensions = Default::default();
+        viewport.content.width = 100.0;
+        viewport.content.height = 100.0;
+        let mut images = ImageCache::new(Path::new("."));
+        let layout = layout_tree(&styled, viewport, &mut images);
+        paint(&layout, bounds(&layout), &mut images)
+    }
+
+    fn pixel(canvas: &Canvas, x: usize, y: usize) -> (u8, u8, u8) {
+        let color = &canvas.pixels[y * canvas.width + x];
+        (color.r, color.g, color.b)
+    }
+
+    #[test]
+    fn test_positioned_boxes_paint_after_normal_flow() {
+        let order = paint_order(
+            "<body><div class=\"a\"></div><div class=\"b\"></div></body>",
+            "body { display: block; background: #000000; } div { display: block; } \
+             .a { position: relative; background: #010000; } .b { background: #020000; }",
+        );
+        assert_eq!(order, vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn test_z_index_orders_layers() {
+        let order = paint_order(
+            "<body><div class=\"a\"></div><div class=\"b\"></div><div class=\"c\"></div></body>",
+            "body { display: block; background: #000000; } div { display: block; } \
+             .a { position: absolute; z-index: 2; background: #010000; } \
+             .b { position: relative; z-index: -1; background: #020000; } \
+             .c { background: #030000; }",
+        );
+        assert_eq!(order, vec![0, 2, 3, 1]);
+    }
+
+    #[test]
+    fn test_stacking_context_contains_descendants() {
+        // `.inner` has a higher z-index than `.b`, but is confined to the stacking context of
+        // `.a`, which is painted below `.b`.
+        let order = paint_order(
+            "<body><div class=\"a\"><p class=\"inner\"></p></div><div class=\"b\"></div></body>",
+            "body { display: block; } div { display: block; } p { display: block; } \
+             .a { position: relative; z-index: 1; background: #010000; } \
+             .inner { position: relative; z-index: 10; background: #020000; } \
+             .b { position: relative; z-index: 2; background: #030000; }",
+        );
+        assert_eq!(order, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_floats_paint_after_blocks() {
+        let order = paint_order(
+            "<body><div class=\"a\"><p></p></div><div class=\"b\"></div></body>",
+            "body { display: block; } div { display: block; } \
+             .a { background: #010000; } .b { background: #030000; } \
+             p { float: left; background: #020000; }",
+        );
+        assert_eq!(order, vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_canvas_clips_to_bounds() {
+        let mut canvas = Canvas::new(4, 4);
+        let black = Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        canvas.paint_item(&DisplayCommand::SolidColor(
+            black.clone(),
+            Rect {
+                x: -2.0,
+                y: 2.0,
+                width: 4.0,
+                height: 10.0,
+            },
+        ));
+        assert_eq!(canvas.pixels[2 * 4 + 1], black);
+        assert_ne!(canvas.pixels[2 * 4 + 2], black);
+        assert_ne!(canvas.pixels[4 + 1], black);
+    }
+
+    #[test]
+    fn test_border_sides_have_their_own_colors() {
+        let canvas = paint_div(
+            "div { display: block; width: 10px; height: 10px; border-width: 2px; \
+             border-color: #0000ff; border-top-color: #ff0000; }",
+        );
+        assert_eq!(pixel(&canvas, 7, 0), (255, 0, 0));
+        assert_eq!(pixel(&canvas, 0, 7), (0, 0, 255));
+        assert_eq!(pixel(&canvas, 7, 13), (0, 0, 255));
+        assert_eq!(pixel(&canvas, 7, 7), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_border_styles() {
+        let canvas = paint_div(
+            "div { display: block; width: 30px; height: 30px; border-width: 6px; \
+             border-color: #000000; border-style: double dashed; }",
+        );
+        // The top border is two lines with a gap between them.
+        assert_eq!(pixel(&canvas, 20, 0), (0, 0, 0));
+        assert_eq!(pixel(&canvas, 20, 3), (255, 255, 255));
+        assert_eq!(pixel(&canvas, 20, 5), (0, 0, 0));
+        // The left border is dashed: 12px dashes and 6px gaps.
+        assert_eq!(pixel(&canvas, 2, 14), (255, 255, 255));
+        assert_eq!(pixel(&canvas, 2, 20), (0, 0, 0));
+
+        let dotted = paint_div(
+            "div { display: block; width: 30px; height: 30px; border-width: 6px; \
+             border-color: #000000; border-style: dotted; }",
+        );
+        assert_eq!(pixel(&dotted, 18, 3), (0, 0, 0));
+        assert_eq!(pixel(&dotted, 12, 3), (255, 255, 255));
+        assert_eq!(pixel(&dotted, 24, 3), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_border_style_none_has_no_width() {
+        with_display_list(
+            "<div></div>",
+            "div { display: block; border-width: 4px; border-style: none; background: #000000; }",
+            |list| {
+                assert_eq!(list.len(), 1);
+                assert!(
+                    matches!(list[0], DisplayCommand::SolidColor(_, rect) if rect.height == 0.0)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_rounded_background_is_anti_aliased() {
+        let canvas = paint_div(
+            "div { display: block; width: 20px; height: 20px; border-radius: 10px; \
+             background: #000000; }",
+        );
+        assert_eq!(pixel(&canvas, 0, 0), (255, 255, 255));
+        assert_eq!(pixel(&canvas, 10, 10), (0, 0, 0));
+        assert_eq!(pixel(&canvas, 10, 0), (0, 0, 0));
+        // A pixel the curve passes through is partly covered.
+        let (edge, _, _) = pixel(&canvas, 1, 4);
+        assert!(edge > 0 && edge < 255, "{}", edge);
+    }
+
+    #[test]
+    fn test_border_radius_shorthand_and_overlap() {
+        with_display_list(
+            "<div></div>",
+            "div { display: block; width: 100px; height: 40px; background: #000000; \
+             border-radius: 10px 20% / 5px; }",
+            |list| {
+                let DisplayCommand::RoundedRect(_, _, radii) = list[0] else {
+                    panic!("expected a rounded rectangle");
+                };
+                assert_eq!(radii.top_left, Radius { x: 10.0, y: 5.0 });
+                assert_eq!(radii.top_right, Radius { x: 20.0, y: 5.0 });
+                assert_eq!(radii.bottom_left, Radius { x: 20.0, y: 5.0 });
+            },
+        );
+        // Radii that add up to more than the box's height are scaled down.
+        with_display_list(
+            "<div></div>",
+            "div { display: block; width: 100px; height: 40px; background: #000000; \
+             border-radius: 40px; }",
+            |list| {
+                let DisplayCommand::RoundedRect(_, _, radii) = list[0] else {
+                    panic!("expected a rounded rectangle");
+                };
+                assert_eq!(radii.bottom_right, Radius { x: 20.0, y: 20.0 });
+            },
+        );
+    }
+
+    #[test]
+    fn test_background_clip_follows_inner_curve() {
+        with_display_list(
+            "<div></div>",
+            "div { display: block; width: 20px; height: 20px; border-width: 4px; \
+             border-radius: 10px; background: #000000; background-clip: padding-box; }",
+            |list| {
+                let DisplayCommand::RoundedRect(_, rect, radii) = list[0] else {
+                    panic!("expected a rounded rectangle");
+                };
+                assert_eq!((rect.x, rect.width), (4.0, 20.0));
+                assert_eq!(radii.top_left, Radius { x: 6.0, y: 6.0 });
+            },
+        );
+    }
+
+    #[test]
+    fn test_background_image_is_loaded_relative_to_document() {
+        let base = std::env::temp_dir().join(format!("background-image-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("images")).unwrap();
+        std::fs::write(base.join("images/tile.ppm"), "P3 2 1 255 255 0 0 0 0 255").unwrap();
+        let canvas = paint_div_in(
+            "div { display: block; width: 6px; height: 3px; \
+             background: url(images/tile.ppm) repeat-x; }",
+            &base,
+        );
+        std::fs::remove_dir_all(&base).unwrap();
+        // The image is repeated across, but not down.
+        assert_eq!(pixel(&canvas, 0, 0), (255, 0, 0));
+        assert_eq!(pixel(&canvas, 3, 0), (0, 0, 255));
+        assert_eq!(pixel(&canvas, 4, 0), (255, 0, 0));
+        assert_eq!(pixel(&canvas, 4, 1), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_background_position_and_size() {
+        let base = std::env::temp_dir().join(format!("background-size-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(
+            base.join("a.ppm"),
+            "P3 4 2 255 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+        )
+        .unwrap();
+        let tile = |stylesheet: &str| {
+            let mut tile = None;
+            with_images("<div></div>", stylesheet, &base, |list| {
+                if let Some(DisplayCommand::Background(background)) = list.last() {
+                    tile = Some(background.tile);
+                }
+            });
+            tile.unwrap()
+        };
+        let style = "div { display: block; width: 100px; height: 50px; padding: 10px; \
+                     background-image: url(a.ppm); background-repeat: no-repeat;";
+        let tile_at = |rest: &str| {
+            let t = tile(&format!("{} {} }}", style, rest));
+            (t.x, t.y, t.width, t.height)
+        };
+        assert_eq!(tile_at(""), (0.0, 0.0, 4.0, 2.0));
+        assert_eq!(
+            tile_at("background-position: right 10px; background-size: 20px;"),
+            (100.0, 10.0, 20.0, 10.0)
+        );
+        assert_eq!(
+            tile_at("background-position: 50% bottom; background-size: contain;"),
+            (0.0, 10.0, 120.0, 60.0)
+        );
+        assert_eq!(
+            tile_at("background-size: cover; background-origin: content-box;"),
+            (10.0, 10.0, 100.0, 50.0)
+        );
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_missing_background_image_is_skipped() {
+        with_display_list(
+            "<div></div>",
+            "div { display: block; background: #ff0000 url(missing.png); }",
+            |list| assert_eq!(list.len(), 1),
+        );
+    }
+
+    #[test]
+    fn test_linear_gradient() {
+        let canvas = paint_div(
+            "div { display: block; width: 100px; height: 10px; \
+             background-image: linear-gradient(to right, #000000, #ffffff); }",
+        );
+        let (start, _, _) = pixel(&canvas, 0, 5);
+        let (middle, _, _) = pixel(&canvas, 50, 5);
+        let (end, _, _) = pixel(&canvas, 99, 5);
+        assert!(start < 3 && end > 252, "{} {}", start, end);
+        assert!(middle.abs_diff(128) <= 2, "{}", middle);
+
+        // Stops with positions, and stops spaced evenly between them.
+        let canvas = paint_div(
+            "div { display: block; width: 10px; height: 100px; \
+             background-image: linear-gradient(#ff0000 20%, #00ff00, #0000ff 60px); }",
+        );
+        assert_eq!(pixel(&canvas, 5, 10), (255, 0, 0));
+        let (red, green, _) = pixel(&canvas, 5, 40);
+        assert!(red == 0 && green > 240, "{} {}", red, green);
+        assert_eq!(pixel(&canvas, 5, 80), (0, 0, 255));
+    }
+
+    #[test]
+    fn test_gradient_angles() {
+        let angle = |direction: &str| gradient_angle(&css_value(direction), 200.0, 100.0);
+        assert_eq!(angle("45deg"), Some(45.0));
+        assert_eq!(angle("to left"), Some(-90.0));
+        assert_eq!(angle("to bottom"), Some(180.0));
+        // Towards the top right corner of a box twice as wide as it is tall.
+        let corner = angle("to top right").unwrap();
+        assert!((corner - 26.565).abs() < 0.01, "{}", corner);
+    }
+
+    fn css_value(value: &str) -> Value {
+        let stylesheet = css::parse(format!("a {{ b: {}; }}", value));
+        stylesheet.rules[0].declarations[0].value.clone()
+    }
+
+    #[test]
+    fn test_radial_gradient() {
+        let canvas = paint_div(
+            "div { display: block; width: 40px; height: 20px; \
+             background-image: radial-gradient(circle closest-side at 10px 50%, #000000, #ffffff); }",
+        );
+        let (center, _, _) = pixel(&canvas, 10, 10);
+        let (edge, _, _) = pixel(&canvas, 19, 10);
+        let (outside, _, _) = pixel(&canvas, 30, 10);
+        assert!(center < 20, "{}", center);
+        assert!(edge > 220, "{}", edge);
+        assert_eq!(outside, 255);
+
+        let shape = |value: &str| radial_shape(&list(Some(css_value(value))), 40.0, 20.0);
+        assert_eq!(
+            shape("ellipse farthest-side").2,
+            Radius { x: 20.0, y: 10.0 }
+        );
+        assert_eq!(
+            shape("10px at left top"),
+            (0.0, 0.0, Radius { x: 10.0, y: 10.0 })
+        );
+    }
+
+    #[test]
+    fn test_opacity_paints_a_layer() {
+        let canvas = paint_div(
+            "div { display: block; width: 4px; height: 4px; opacity: 0.5; \
+             background: #000000; }",
+        );
+        assert_eq!(pixel(&canvas, 1, 1), (128, 128, 128));
+
+        // A translucent box is painted after the normal flow, like a positioned box.
+        with_display_list(
+            "<body><div class=\"a\"><p></p></div><div class=\"b\"></div></body>",
+            "body { display: block; } div { display: block; } p { display: block; } \
+             .a { opacity: 0.25; background: #010000; } .b { background: #020000; } \
+             p { background: #030000; }",
+            |list| {
+                assert!(matches!(list[0], DisplayCommand::SolidColor(ref c, _) if c.r == 2));
+                let DisplayCommand::Layer(opacity, ref layer) = list[1] else {
+                    panic!("expected a layer");
+                };
+                assert_eq!(opacity, 0.25);
+                assert_eq!(layer.len(), 2);
+            },
+        );
+    }
+
+    #[test]
+    fn test_overflow_clips_contents() {
+        with_display_list(
+            "<div><p></p></div>",
+            "div { display: block; width: 20px; height: 20px; padding: 5px; overflow: hidden; } \
+             p { display: block; height: 50px; background: #ff0000; }",
+            |list| {
+                let DisplayCommand::Clip(clip, ref contents) = list[0] else {
+                    panic!("expected a clip");
+                };
+                assert_eq!(
+                    clip,
+                    Rect {
+                        x: 0.0,
+                        y: 0.0,
+                        width: 30.0,
+                        height: 30.0
+                    }
+                );
+                assert_eq!(contents.len(), 1);
+            },
+        );
+
+        let canvas = paint_document(
+            "<div><p></p></div>",
+            "div { display: block; width: 20px; height: 20px; overflow-y: hidden; } \
+             p { display: block; width: 40px; height: 50px; background: #ff0000; }",
+            |_| Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 50.0,
+                height: 50.0,
+            },
+        );
+        assert_eq!(pixel(&canvas, 5, 19), (255, 0, 0));
+        assert_eq!(pixel(&canvas, 5, 20), (255, 255, 255));
+        // `overflow-x` becomes `auto`, so the contents are clipped horizontally too.
+        assert_eq!(pixel(&canvas, 30, 5), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_overflow_clips_positioned_descendants() {
+        // An absolutely positioned box is clipped only by boxes that contain its containing
+        // block.
+        let clipped_colors = |stylesheet: &str| {
+            let mut colors = Vec::new();
+            with_display_list(
+                "<div class=\"outer\"><div class=\"clip\"><p class=\"a\"></p><p class=\"b\"></p>\
+                 </div></div>",
+                stylesheet,
+                |list| {
+                    fn collect(list: &[DisplayCommand], clipped: bool, colors: &mut Vec<u8>) {
+                        for item in list {
+                            match item {
+                                DisplayCommand::SolidColor(color, _) if clipped => {
+                                    colors.push(color.r)
+                                }
+                                DisplayCommand::Clip(_, list) => collect(list, true, colors),
+                                DisplayCommand::Fixed(list) => collect(list, clipped, colors),
+                                _ => {}
+                            }
+                        }
+                    }
+                    collect(&list, false, &mut colors);
+                },
+            );
+            colors
+        };
+        let stylesheet = "div { display: block; } .clip { overflow: hidden; height: 10px; } \
+                          p { display: block; height: 20px; } \
+                          .a { position: absolute; background: #010000; } \
+                          .b { position: relative; background: #020000; }";
+        assert_eq!(
+            clipped_colors(&format!("{} .outer {{ position: relative; }}", stylesheet)),
+            vec![2]
+        );
+        assert_eq!(
+            clipped_colors(&format!("{} .clip {{ position: relative; }}", stylesheet)),
+            vec![1, 2]
+        );
+        assert_eq!(
+            clipped_colors(&format!(
+                "{} .clip {{ position: relative; }} .a {{ position: fixed; }}",
+                stylesheet
+            )),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_paint_at_scroll_offset() {
+        let source = "<div><p class=\"bar\"></p><p class=\"content\"></p></div>";
+        let stylesheet = "div { display: block; height: 300px; } p { display: block; } \
+                          .bar { position: fixed; top: 0px; height: 10px; width: 10px; \
+                          background: #00ff00; } \
+                          .content { margin-top: 150px; height: 20px; background: #ff0000; }";
+        let canvas = paint_document(source, stylesheet, |_| Rect {
+            x: 0.0,
+            y: 140.0,
+            width: 100.0,
+            height: 100.0,
+        });
+        assert_eq!(pixel(&canvas, 20, 9), (255, 255, 255));
+        assert_eq!(pixel(&canvas, 20, 10), (255, 0, 0));
+        assert_eq!(pixel(&canvas, 20, 30), (255, 255, 255));
+        // The fixed box stays at the top of the viewport.
+        assert_eq!(pixel(&canvas, 5, 5), (0, 255, 0));
+
+        let canvas = paint_document(source, stylesheet, |layout| {
+            full_page(
+                layout,
+                Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 100.0,
+                    height: 100.0,
+                },
+            )
+        });
+        assert_eq!((canvas.width, canvas.height), (100, 300));
+        assert_eq!(pixel(&canvas, 20, 160), (255, 0, 0));
+        assert_eq!(pixel(&canvas, 5, 5), (0, 255, 0));
+    }
+
+    #[test]
+    fn test_image_element_is_scaled_to_its_box() {
+        let base = std::env::temp_dir().join(format!("img-element-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("a.ppm"), "P3 2 1 255 255 0 0 0 0 255").unwrap();
+        with_images(
+            "<div><img src=\"a.ppm\" width=\"8\"></div>",
+            "div { display: block; }",
+            &base,
+            |list| {
+                let rect = Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 8.0,
+                    height: 4.0,
+                };
+                assert!(matches!(list[..], [DisplayCommand::Image(_, r)] if r == rect));
+                let mut canvas = Canvas::new(10, 5);
+                canvas.paint_item(&list[0]);
+                assert_eq!(pixel(&canvas, 3, 3), (255, 0, 0));
+                assert_eq!(pixel(&canvas, 4, 0), (0, 0, 255));
+                assert_eq!(pixel(&canvas, 8, 0), (255, 255, 255));
+                assert_eq!(pixel(&canvas, 0, 4), (255, 255, 255));
+            },
+        );
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_form_controls() {
+        with_display_list(
+            "<form><input value=\"Ada\" size=2><input type=checkbox checked>\
+             <input type=checkbox><button>Save</button><select><option>One</option></select>\
+             </form>",
+            "form { display: block; } * { color: #0000ff; }",
+            |list| {
+                let mut texts = Vec::new();
+                let mut marks = Vec::new();
+                fn walk(list: &[DisplayCommand], texts: &mut Vec<String>, marks: &mut Vec<Rect>) {
+                    for item in list {
+                        match item {
+                            DisplayCommand::Text(text) => texts.push(text.text.clone()),
+                            DisplayCommand::SolidColor(color, rect)
+                                if color.r == 0 && color.b == 255 =>
+                            {
+                                marks.push(*rect)
+                            }
+                            DisplayCommand::Clip(_, list) => walk(list, texts, marks),
+                            _ => {}
+                        }
+                    }
+                }
+                walk(&list, &mut texts, &mut marks);
+                assert_eq!(texts, ["Ada", "Save", "One"]);
+                // The checked checkbox has a mark, and the select's arrow is drawn in rows.
+                assert_eq!(marks[0].width, 7.0);
+                assert!(marks[1..].iter().all(|rect| rect.height == 1.0));
+                // Text that doesn't fit in a field is clipped to its content box.
+                let DisplayCommand::Clip(clip, _) = list
+                    .iter()
+                    .find(|item| matches!(item, DisplayCommand::Clip(..)))
+                    .unwrap()
+                else {
+                    unreachable!()
+                };
+                assert_eq!(clip.width, 2.0 * 9.6);
+            },
+        );
+    }
+
+    #[test]
+    fn test_list_markers() {
+        with_display_list(
+            "<div><ul><li>a</li><li class=\"c\">b</li><li class=\"s\">c</li></ul>\
+             <ol><li>d</li></ol></div>",
+            "div { display: block; } * { color: #0000ff; } .c { list-style-type: circle; } \
+             .s { list-style-type: square; }",
+            |list| {
+                let blue = |color: &Color| color.r == 0 && color.b == 255;
+                let markers: Vec<_> = list
+                    .iter()
+                    .filter_map(|item| match item {
+                        DisplayCommand::RoundedRect(color, rect, _) if blue(color) => {
+                            Some(("disc", *rect))
+                        }
+                        DisplayCommand::Border(border) if blue(&border.top.color) => {
+                            Some(("circle", border.rect))
+                        }
+                        DisplayCommand::SolidColor(color, rect) if blue(color) => {
+                            Some(("square", *rect))
+                        }
+                        DisplayCommand::Text(text) if text.text.ends_with(". ") => {
+                            Some(("text", text.rect))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                let names: Vec<_> = markers.iter().map(|&(name, _)| name).collect();
+                assert_eq!(names, ["disc", "circle", "square", "text"]);
+                // A bullet is centered in the space of a character, beside the first line.
+                let disc = markers[0].1;
+                assert_eq!((disc.width, disc.height), (6.0, 6.0));
+                assert!((disc.x - (40.0 - 2.0 * 9.6 + 2.0)).abs() < 0.01);
+                assert_eq!(disc.y, 7.0);
+                assert!((markers[3].1.x - (40.0 - 3.0 * 9.6)).abs() < 0.01);
+            },
+        );
+    }
+
+    #[test]
+    fn test_text_decorations() {
+        with_display_list(
+            "<div><p>ab <u>cd</u></p><p class=\"x\">e</p></div>",
+            "div, p { display: block; } .x { text-decoration: overline line-through #ff0000; }",
+            |list| {
+                let lines: Vec<_> = list
+                    .iter()
+                    .filter_map(|item| match item {
+                        DisplayCommand::SolidColor(color, rect) => Some((color.r, *rect)),
+                        _ => None,
+                    })
+                    .collect();
+                let rect = |x: f32, y: f32, width: f32| Rect {
+                    x,
+                    y,
+                    width,
+                    height: 1.0,
+                };
+                // Lines are drawn in the text's color unless given one, across the text they
+                // decorate, relative to an em box centered on the line.
+                assert_eq!(
+                    lines,
+                    [
+                        (0, rect(3.0 * 9.6, 16.0, 2.0 * 9.6)),
+                        (255, rect(0.0, 21.0, 9.6)),
+                        (255, rect(0.0, 29.0, 9.6)),
+                    ]
+                );
+            },
+        );
+    }
+}
@@ -0,0 +1,1046 @@
+//! A backend that writes the display list as a PDF document, cut into pages for printing.
+//!
+//! The document is laid out once in the content area of a page, as if it were one very tall
+//! page, and each page of the PDF shows the part of it between two page breaks.
+//!
+//! https://opensource.adobe.com/dc-acrobat-sdk-docs/pdfstandards/pdfreference1.4.pdf
+
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::css::{Stylesheet, Unit, Value};
+use crate::image::{Image, ImageCache};
+use crate::layout::{Dimensions, EdgeSize, LayoutBox, Rect};
+use crate::painting::{
+    build_display_list, gradient_color, Background, BackgroundImage, Border, BorderSide, ColorStop,
+    CornerRadii, DisplayCommand, DisplayList, Radius, Text,
+};
+use crate::style::BorderStyle;
+
+pub use self::font::Font;
+
+mod font;
+
+/// The size of a px in PDF points, which are 1/72 of an inch.
+const POINTS_PER_PX: f32 = 0.75;
+
+/// The most copies of a repeated background image drawn on one page.
+const MAX_TILES: usize = 10_000;
+
+/// The distance of the control points of a cubic Bézier curve approximating a quarter of an
+/// ellipse from its ends, as a fraction of the radius.
+const KAPPA: f32 = 0.5523;
+
+/// The size and margins of the pages of a printed document, in px.
+#[derive(Clone, Copy, Debug)]
+pub struct Page {
+    pub width: f32,
+    pub height: f32,
+    pub margin: EdgeSize,
+}
+
+impl Default for Page {
+    /// An A4 page without margins.
+    fn default() -> Page {
+        let (width, height) = page_size("a4").unwrap();
+        Page {
+            width,
+            height,
+            margin: Default::default(),
+        }
+    }
+}
+
+impl Page {
+    /// The page described by the `size` and `margin` declarations of a stylesheet's `@page`
+    /// rules.
+    ///
+    /// http://www.w3.org/TR/css-page-3/#page-size-prop
+    pub fn from_stylesheet(stylesheet: &Stylesheet) -> Page {
+        let mut page = Page::default();
+        for declaration in &stylesheet.page {
+            let values = match declaration.value {
+                Value::List(ref values) => values.clone(),
+                ref value => vec![value.clone()],
+            };
+            let length = |value: &Value| match *value {
+                Value::Length(length, Unit::Px) => length,
+                _ => 0.0,
+            };
+            match &*declaration.name {
+                "size" => page.set_size(&values),
+                "margin" => {
+                    // Like `margin` on a box, one to four values apply to the top, right, bottom
+                    // and left sides.
+                    let side = |index: usize| {
+                        let index = match values.len() {
+                            1 => 0,
+                            2 => index % 2,
+                            3 if index == 3 => 1,
+                            _ => index,
+                        };
+                        values.get(index).map_or(0.0, length)
+                    };
+                    page.margin = EdgeSize {
+                        top: side(0),
+                        right: side(1),
+                        bottom: side(2),
+                        left: side(3),
+                    };
+                }
+                "margin-top" => page.margin.top = length(&declaration.value),
+                "margin-right" => page.margin.right = length(&declaration.value),
+                "margin-bottom" => page.margin.bottom = length(&declaration.value),
+                "margin-left" => page.margin.left = length(&declaration.value),
+                _ => {}
+            }
+        }
+        page
+    }
+
+    /// Set the page size from the value of `size`: one or two lengths, or the name of a paper
+    /// size, optionally followed by `portrait` or `landscape`.
+    fn set_size(&mut self, values: &[Value]) {
+        let mut orientation = None;
+        let mut lengths = Vec::new();
+        for value in values {
+            match *value {
+                Value::Length(length, Unit::Px) => lengths.push(length),
+                Value::Keyword(ref keyword) => match &*keyword.to_ascii_lowercase() {
+                    "portrait" => orientation = Some(false),
+                    "landscape" => orientation = Some(true),
+                    name => {
+                        if let Some((width, height)) = page_size(name) {
+                            (self.width, self.height) = (width, height);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        match lengths[..] {
+            [side] => (self.width, self.height) = (side, side),
+            [width, height] => (self.width, self.height) = (width, height),
+            _ => {}
+        }
+        let (short, long) = (self.width.min(self.height), self.width.max(self.height));
+        match orientation {
+            Some(true) => (self.width, self.height) = (long, short),
+            Some(false) => (self.width, self.height) = (short, long),
+            None => {}
+        }
+    }
+
+    /// The area inside the margins of a page, which is the viewport the document is laid out
+    /// in.
+    pub fn content_area(&self) -> Dimensions {
+        let mut area: Dimensions = Default::default();
+        area.content.width = (self.width - self.margin.left - self.margin.right).max(0.0);
+        area.content.height = (self.height - self.margin.top - self.margin.bottom).max(0.0);
+        area
+    }
+}
+
+/// The size in px of a named paper size, in portrait orientation.
+fn page_size(name: &str) -> Option<(f32, f32)> {
+    let (width, height, px_per_unit) = match name {
+        "a3" => (297.0, 420.0, 96.0 / 25.4),
+        "a4" => (210.0, 297.0, 96.0 / 25.4),
+        "a5" => (148.0, 210.0, 96.0 / 25.4),
+        "b4" => (250.0, 353.0, 96.0 / 25.4),
+        "b5" => (176.0, 250.0, 96.0 / 25.4),
+        "letter" => (8.5, 11.0, 96.0),
+        "legal" => (8.5, 14.0, 96.0),
+        "ledger" => (11.0, 17.0, 96.0),
+        _ => return None,
+    };
+    Some((width * px_per_unit, height * px_per_unit))
+}
+
+/// Paint a tree of LayoutBoxes, laid out in the `content_area` of `page`, to a PDF document
+/// with as many pages as it needs. Text is set in `font`.
+pub fn paint(
+    layout_root: &LayoutBox,
+    page: &Page,
+    images: &mut ImageCache,
+    font: &Font,
+) -> Vec<u8> {
+    let display_list = build_display_list(layout_root, images);
+    let page_starts = layout_root.page_breaks(page.content_area().content.height);
+    to_pdf(&display_list, page, &page_starts, font)
+}
+
+/// Write a display list as a PDF document with a page for each of `page_starts`, the positions
+/// in the document at which its pages begin.
+pub fn to_pdf(
+    display_list: &DisplayList,
+    page: &Page,
+    page_starts: &[f32],
+    font: &Font,
+) -> Vec<u8> {
+    let mut writer = PdfWriter {
+        objects: Vec::new(),
+        resources: 0,
+        x_objects: Vec::new(),
+        images: Vec::new(),
+        shadings: Vec::new(),
+        alphas: Vec::new(),
+        page_top: 0.0,
+    };
+    let catalog = writer.reserve();
+    let pages = writer.reserve();
+    writer.resources = writer.reserve();
+
+    let content_height = page.content_area().content.height;
+    let mut kids = Vec::new();
+    for (index, &start) in page_starts.iter().enumerate() {
+        let end = page_starts
+            .get(index + 1)
+            .map_or(start + content_height, |&next| {
+                next.min(start + content_height)
+            });
+        writer.page_top = start;
+
+        // Draw in px with the y axis pointing down, as in layout, and shift the part of the
+        // document on this page into its content area.
+        let mut content = String::new();
+        writeln!(
+            content,
+            "q {} 0 0 {} 0 {} cm",
+            number(POINTS_PER_PX),
+            number(-POINTS_PER_PX),
+            number(page.height * POINTS_PER_PX)
+        )
+        .unwrap();
+        writeln!(
+            content,
+            "1 0 0 1 {} {} cm",
+            number(page.margin.left),
+            number(page.margin.top - start)
+        )
+        .unwrap();
+        let area = Rect {
+            x: -page.margin.left,
+            y: start,
+            width: page.width,
+            height: end - start,
+        };
+        writeln!(content, "{} re W n", rect_operands(area)).unwrap();
+        writer.write_list(&mut content, display_list, Some((start, end)));
+        content.push_str("Q\n");
+
+        let contents = writer.add(stream("", content.as_bytes()));
+        kids.push(
+            writer.add(
+                format!(
+                    "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R \
+                 /Contents {} 0 R >>",
+                    pages,
+                    number(page.width * POINTS_PER_PX),
+                    number(page.height * POINTS_PER_PX),
+                    writer.resources,
+                    contents
+                )
+                .into_bytes(),
+            ),
+        );
+    }
+
+    let kids: Vec<String> = kids.iter().map(|id| format!("{} 0 R", id)).collect();
+    writer.set(
+        pages,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            kids.len()
+        ),
+    );
+    writer.set(
+        catalog,
+        format!("<< /Type /Catalog /Pages {} 0 R >>", pages),
+    );
+    let font = writer.font(font);
+    writer.write_resources(font);
+    writer.finish(catalog)
+}
+
+struct PdfWriter {
+    /// The bodies of the document's objects. An object's number is its index plus one.
+    objects: Vec<Vec<u8>>,
+    /// The resource dictionary shared by every page and layer.
+    resources: usize,
+    /// The images and layers drawn with `Do`, named `/X` followed by their object number.
+    x_objects: Vec<usize>,
+    images: Vec<(Rc<Image>, usize)>,
+    /// The gradients drawn with `sh`, named `/Sh` followed by their object number.
+    shadings: Vec<usize>,
+    /// The opacities, out of 255, of the graphics states named `/GS` followed by the opacity.
+    alphas: Vec<u8>,
+    /// The position in the document of the top of the current page, where fixed boxes are
+    /// drawn.
+    page_top: f32,
+}
+
+impl PdfWriter {
+    fn add(&mut self, body: Vec<u8>) -> usize {
+        self.objects.push(body);
+        self.objects.len()
+    }
+
+    /// Number an object whose body is given later with `set`, because it refers to objects
+    /// that don't exist yet.
+    fn reserve(&mut self) -> usize {
+        self.add(Vec::new())
+    }
+
+    fn set(&mut self, id: usize, body: String) {
+        self.objects[id - 1] = body.into_bytes();
+    }
+
+    /// Write the commands of a display list to a content stream. Commands outside of `span`,
+    /// the part of the document on the current page, are left out.
+    fn write_list(&mut self, out: &mut String, list: &[DisplayCommand], span: Option<(f32, f32)>) {
+        for item in list {
+            if let (Some((top, bottom)), Some(rect)) = (span, bounds(item)) {
+                if rect.y >= bottom || rect.y + rect.height <= top {
+                    continue;
+                }
+            }
+            self.write_item(out, item, span);
+        }
+    }
+
+    fn write_item(&mut self, out: &mut String, item: &DisplayCommand, span: Option<(f32, f32)>) {
+        match *item {
+            DisplayCommand::SolidColor(ref color, rect) => {
+                out.push_str("q ");
+                self.fill_color(out, color);
+                writeln!(out, "{} re f Q", rect_operands(rect)).unwrap();
+            }
+            DisplayCommand::RoundedRect(ref color, rect, ref radii) => {
+                out.push_str("q ");
+                self.fill_color(out, color);
+                rounded_rect(out, rect, radii);
+                out.push_str("f Q\n");
+            }
+            DisplayCommand::Border(ref border) => self.write_border(out, border),
+            DisplayCommand::Background(ref background) => self.write_background(out, background),
+            DisplayCommand::Image(ref image, rect) => {
+                if let Some(id) = self.image(image) {
+                    // Images are drawn into a unit square, which is flipped back upright.
+                    writeln!(
+                        out,
+                        "q {} 0 0 {} {} {} cm /X{} Do Q",
+                        number(rect.width),
+                        number(-rect.height),
+                        number(rect.x),
+                        number(rect.y + rect.height),
+                        id
+                    )
+                    .unwrap();
+                }
+            }
+            DisplayCommand::Text(ref text) => self.write_text(out, text),
+            DisplayCommand::Layer(opacity, ref items) => {
+                // The layer is drawn as a transparency group, so its opacity applies to it as a
+                // whole rather than to each of its commands.
+                let mut content = String::new();
+                self.write_list(&mut content, items, span);
+                let id = self.add(stream(
+                    &format!(
+                        "/Type /XObject /Subtype /Form /BBox [-1000000 -1000000 1000000 1000000] \
+                         /Group << /S /Transparency >> /Resources {} 0 R",
+                        self.resources
+                    ),
+                    content.as_bytes(),
+                ));
+                self.x_objects.push(id);
+                let alpha = self.alpha(opacity);
+                writeln!(out, "q /GS{} gs /X{} Do Q", alpha, id).unwrap();
+            }
+            DisplayCommand::Clip(rect, ref items) => {
+                writeln!(out, "q {} re W n", rect_operands(rect)).unwrap();
+                self.write_list(out, items, span);
+                out.push_str("Q\n");
+            }
+            DisplayCommand::Fixed(ref items) => {
+                // Fixed boxes are positioned relative to the page, so they appear on every page.
+                writeln!(out, "q 1 0 0 1 0 {} cm", number(self.page_top)).unwrap();
+                self.write_list(out, items, None);
+                out.push_str("Q\n");
+            }
+        }
+    }
+
+    /// Set the color that shapes are filled with, and strokes drawn with.
+    fn fill_color(&mut self, out: &mut String, color: &crate::css::Color) {
+        let (r, g, b) = (color.r as f32, color.g as f32, color.b as f32);
+        let rgb = format!(
+            "{} {} {}",
+            number(r / 255.0),
+            number(g / 255.0),
+            number(b / 255.0)
+        );
+        write!(out, "{} rg {} RG ", rgb, rgb).unwrap();
+        if color.a < 255 {
+            let alpha = self.alpha(color.a as f32 / 255.0);
+            write!(out, "/GS{} gs ", alpha).unwrap();
+        }
+    }
+
+    /// The opacity out of 255 of a graphics state that paints with `opacity`.
+    fn alpha(&mut self, opacity: f32) -> u8 {
+        let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        if !self.alphas.contains(&alpha) {
+            self.alphas.push(alpha);
+        }
+        alpha
+    }
+
+    /// Draw a border as the area between its outer and inner edges. A border whose sides
+    /// differ is drawn one side at a time, each clipped to the trapezoid between the corners of
+    /// its edges.
+    fn write_border(&mut self, out: &mut String, border: &Border) {
+        let widths = EdgeSize {
+            left: border.left.width,
+            right: border.right.width,
+            top: border.top.width,
+            bottom: border.bottom.width,
+        };
+        let sides = [&border.top, &border.right, &border.bottom, &border.left];
+        let uniform = sides
+            .iter()
+            .all(|side| side.color == border.top.color && side.style == BorderStyle::Solid);
+        if uniform {
+            out.push_str("q ");
+            self.fill_color(out, &border.top.color);
+            ring(out, border, widths, 0.0, 1.0);
+            out.push_str("f* Q\n");
+            return;
+        }
+
+        let outer = border.rect;
+        let inner = inset(outer, widths);
+        let (x0, y0) = (outer.x, outer.y);
+        let (x1, y1) = (outer.x + outer.width, outer.y + outer.height);
+        let (ix0, iy0) = (inner.x, inner.y);
+        let (ix1, iy1) = (inner.x + inner.width, inner.y + inner.height);
+        let trapezoids = [
+            [(x0, y0), (x1, y0), (ix1, iy0), (ix0, iy0)],
+            [(x1, y0), (x1, y1), (ix1, iy1), (ix1, iy0)],
+            [(x1, y1), (x0, y1), (ix0, iy1), (ix1, iy1)],
+            [(x0, y1), (x0, y0), (ix0, iy0), (ix0, iy1)],
+        ];
+        // The line along the middle of each side, for dashes and dots.
+        let centers = [
+            ((x0, y0 + widths.top / 2.0), (x1, y0 + widths.top / 2.0)),
+            ((x1 - widths.right / 2.0, y0), (x1 - widths.right / 2.0, y1)),
+            (
+                (x1, y1 - widths.bottom / 2.0),
+                (x0, y1 - widths.bottom / 2.0),
+            ),
+            ((x0 + widths.left / 2.0, y1), (x0 + widths.left / 2.0, y0)),
+        ];
+        for ((side, trapezoid), (start, end)) in sides.iter().zip(trapezoids).zip(centers) {
+            if side.width <= 0.0 || side.style == BorderStyle::None {
+                continue;
+            }
+            out.push_str("q ");
+            for (index, &(x, y)) in trapezoid.iter().enumerate() {
+                let operator = if index == 0 { "m" } else { "l" };
+                write!(out, "{} {} {} ", number(x), number(y), operator).unwrap();
+            }
+            out.push_str("h W n ");
+            self.fill_color(out, &side.color);
+            match side.style {
+                BorderStyle::Dashed | BorderStyle::Dotted => line(out, start, end, side),
+                BorderStyle::Double => {
+                    ring(out, border, widths, 0.0, 1.0 / 3.0);
+                    ring(out, border, widths, 2.0 / 3.0, 1.0);
+                    out.push_str("f* ");
+                }
+                BorderStyle::Solid | BorderStyle::None => {
+                    ring(out, border, widths, 0.0, 1.0);
+                    out.push_str("f* ");
+                }
+            }
+            out.push_str("Q\n");
+        }
+    }
+
+    /// Draw a background by drawing its image or gradient in each tile that reaches its clip
+    /// area.
+    fn write_background(&mut self, out: &mut String, background: &Background) {
+        let tile = background.tile;
+        if tile.width <= 0.0 || tile.height <= 0.0 {
+            return;
+        }
+        // The commands that draw one tile, with its top left corner at the origin.
+        let paint = match background.image {
+            BackgroundImage::Image(ref image) => match self.image(image) {
+                Some(id) => format!(
+                    "{} 0 0 {} 0 {} cm /X{} Do",
+                    number(tile.width),
+                    number(-tile.height),
+                    number(tile.height),
+                    id
+                ),
+                None => return,
+            },
+            BackgroundImage::LinearGradient(angle, ref stops) => {
+                let radians = angle.to_radians();
+                let (dx, dy) = (radians.sin(), -radians.cos());
+                let half = ((tile.width * dx).abs() + (tile.height * dy).abs()) / 2.0;
+                let (cx, cy) = (tile.width / 2.0, tile.height / 2.0);
+                let coords = [
+                    cx - dx * half,
+                    cy - dy * half,
+                    cx + dx * half,
+                    cy + dy * half,
+                ];
+                let id = self.shading(2, &coords, stops);
+                format!("/Sh{} sh", id)
+            }
+            BackgroundImage::RadialGradient(cx, cy, radius, ref stops) => {
+                if radius.x > 0.0 && radius.y > 0.0 {
+                    // The gradient is circular, then stretched vertically into an ellipse.
+                    let id = self.shading(3, &[0.0, 0.0, 0.0, 0.0, 0.0, radius.x], stops);
+                    format!(
+                        "1 0 0 {} {} {} cm /Sh{} sh",
+                        number(radius.y / radius.x),
+                        number(cx),
+                        number(cy),
+                        id
+                    )
+                } else {
+                    let mut paint = String::new();
+                    self.fill_color(&mut paint, &gradient_color(stops, 1.0));
+                    write!(
+                        paint,
+                        "0 0 {} {} re f",
+                        number(tile.width),
+                        number(tile.height)
+                    )
+                    .unwrap();
+                    paint
+                }
+            }
+        };
+
+        out.push_str("q ");
+        rounded_rect(out, background.clip, &background.radii);
+        out.push_str("W n\n");
+        for tile in tiles(background) {
+            writeln!(
+                out,
+                "q {} re W n 1 0 0 1 {} {} cm {} Q",
+                rect_operands(tile),
+                number(tile.x),
+                number(tile.y),
+                paint
+            )
+            .unwrap();
+        }
+        out.push_str("Q\n");
+    }
+
+    /// Add an axial (type 2) or radial (type 3) shading, whose colors are sampled from a
+    /// gradient's stops in the same way as the raster backend.
+    fn shading(&mut self, shading_type: u8, coords: &[f32], stops: &[ColorStop]) -> usize {
+        let samples: Vec<u8> = (0..256)
+            .flat_map(|index| {
+                let color = gradient_color(stops, index as f32 / 255.0);
+                [color.r, color.g, color.b]
+            })
+            .collect();
+        let function = self.add(stream(
+            "/FunctionType 0 /Domain [0 1] /Range [0 1 0 1 0 1] /Size [256] /BitsPerSample 8",
+            &samples,
+        ));
+        let coords: Vec<String> = coords.iter().map(|&c| number(c)).collect();
+        let id = self.add(
+            format!(
+                "<< /ShadingType {} /ColorSpace /DeviceRGB /Coords [{}] /Function {} 0 R \
+                 /Extend [true true] >>",
+                shading_type,
+                coords.join(" "),
+                function
+            )
+            .into_bytes(),
+        );
+        self.shadings.push(id);
+        id
+    }
+
+    /// Set a run of text with its glyphs stretched to fill its box, so that it matches the
+    /// layout's monospace metrics.
+    fn write_text(&mut self, out: &mut String, text: &Text) {
+        let bytes: Vec<u8> = text.text.chars().map(win_ansi).collect();
+        if bytes.is_empty() || text.font_size <= 0.0 {
+            return;
+        }
+        let advance = 0.6 * text.font_size * bytes.len() as f32;
+        let rect = text.rect;
+        // The baseline is placed 0.8em below the top of an em box centered on the line.
+        let baseline = rect.y + rect.height / 2.0 + 0.3 * text.font_size;
+        out.push_str("q ");
+        self.fill_color(out, &text.color);
+        write!(
+            out,
+            "BT /F1 {} Tf {} Tz 1 0 0 -1 {} {} Tm <",
+            number(text.font_size),
+            number(100.0 * rect.width / advance),
+            number(rect.x),
+            number(baseline)
+        )
+        .unwrap();
+        for byte in bytes {
+            write!(out, "{:02x}", byte).unwrap();
+        }
+        out.push_str("> Tj ET Q\n");
+    }
+
+    /// The number of the image object for an image, adding it the first time it is drawn.
+    /// Images without pixels can't be drawn.
+    fn image(&mut self, image: &Rc<Image>) -> Option<usize> {
+        if image.width == 0 || image.height == 0 {
+            return None;
+        }
+        if let Some(&(_, id)) = self
+            .images
+            .iter()
+            .find(|(other, _)| Rc::ptr_eq(other, image))
+        {
+            return Some(id);
+        }
+        let mut dict = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+             /BitsPerComponent 8",
+            image.width, image.height
+        );
+        // Transparency is given by a separate grayscale image.
+        if image.pixels.iter().any(|pixel| pixel.a < 255) {
+            let alpha: Vec<u8> = image.pixels.iter().map(|pixel| pixel.a).collect();
+            let mask = self.add(stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                     /ColorSpace /DeviceGray /BitsPerComponent 8",
+                    image.width, image.height
+                ),
+                &alpha,
+            ));
+            write!(dict, " /SMask {} 0 R", mask).unwrap();
+        }
+        let rgb: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|pixel| [pixel.r, pixel.g, pixel.b])
+            .collect();
+        let id = self.add(stream(&dict, &rgb));
+        self.images.push((image.clone(), id));
+        self.x_objects.push(id);
+        Some(id)
+    }
+
+    /// Add the font that text is set in. Every character is 600 thousandths of an em wide, the
+    /// advance that layout measures text with, which is also the width of Courier's glyphs.
+    fn font(&mut self, font: &Font) -> usize {
+        let Some(truetype) = font.embedded() else {
+            return self.add(
+                b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+                    .to_vec(),
+            );
+        };
+        let file = self.add(stream(
+            &format!("/Length1 {}", truetype.data.len()),
+            &truetype.data,
+        ));
+        let [x_min, y_min, x_max, y_max] = truetype.bbox;
+        // The flags mark the font as fixed-pitch and nonsymbolic.
+        let descriptor = self.add(
+            format!(
+                "<< /Type /FontDescriptor /FontName /{} /Flags 33 /FontBBox [{} {} {} {}] \
+                 /ItalicAngle 0 /Ascent {} /Descent {} /CapHeight {} /StemV 80 \
+                 /FontFile2 {} 0 R >>",
+                truetype.name,
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+                truetype.ascent,
+                truetype.descent,
+                truetype.ascent,
+                file
+            )
+            .into_bytes(),
+        );
+        let widths = vec!["600"; 224].join(" ");
+        self.add(
+            format!(
+                "<< /Type /Font /Subtype /TrueType /BaseFont /{} /FirstChar 32 /LastChar 255 \
+                 /Widths [{}] /Encoding /WinAnsiEncoding /FontDescriptor {} 0 R >>",
+                truetype.name, widths, descriptor
+            )
+            .into_bytes(),
+        )
+    }
+
+    fn write_resources(&mut self, font: usize) {
+        let mut resources = format!("<< /Font << /F1 {} 0 R >>", font);
+        let names = |prefix: &str, ids: &[usize]| -> String {
+            ids.iter()
+                .map(|id| format!(" /{}{} {} 0 R", prefix, id, id))
+                .collect()
+        };
+        write!(resources, " /XObject <<{} >>", names("X", &self.x_objects)).unwrap();
+        write!(resources, " /Shading <<{} >>", names("Sh", &self.shadings)).unwrap();
+        resources.push_str(" /ExtGState <<");
+        for &alpha in &self.alphas {
+            let opacity = number(alpha as f32 / 255.0);
+            write!(
+                resources,
+                " /GS{} << /ca {} /CA {} >>",
+                alpha, opacity, opacity
+            )
+            .unwrap();
+        }
+        resources.push_str(" >> >>");
+        self.set(self.resources, resources);
+    }
+
+    /// Write the objects, followed by the cross-reference table that gives the position of
+    /// each of them in the file.
+    fn finish(self, catalog: usize) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, body) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            out.extend_from_slice(body);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+        for offset in offsets {
+            writeln!(table, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(
+            table,
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1,
+            catalog,
+            xref
+        )
+        .unwrap();
+        out.extend_from_slice(table.as_bytes());
+        out
+    }
+}
+
+/// The area a command draws in, if it can be left out of pages it doesn't reach.
+fn bounds(item: &DisplayCommand) -> Option<Rect> {
+    match *item {
+        DisplayCommand::SolidColor(_, rect)
+        | DisplayCommand::RoundedRect(_, rect, _)
+        | DisplayCommand::Image(_, rect) => Some(rect),
+        DisplayCommand::Border(ref border) => Some(border.rect),
+        DisplayCommand::Background(ref background) => Some(background.clip),
+        DisplayCommand::Text(ref text) => Some(text.rect),
+        DisplayCommand::Layer(..) | DisplayCommand::Clip(..) | DisplayCommand::Fixed(_) => None,
+    }
+}
+
+/// The positions of the tiles of a background that reach its clip area.
+fn tiles(background: &Background) -> Vec<Rect> {
+    let (tile, clip) = (background.tile, background.clip);
+    let positions = |repeat: bool, start: f32, size: f32, clip_start: f32, clip_size: f32| {
+        if !repeat {
+            return vec![start];
+        }
+        let first = start - ((start - clip_start) / size).ceil() * size;
+        let count = ((clip_start + clip_size - first) / size).ceil().max(0.0) as usize;
+        (0..count.min(MAX_TILES))
+            .map(|index| first + index as f32 * size)
+            .collect()
+    };
+    let xs = positions(background.repeat_x, tile.x, tile.width, clip.x, clip.width);
+    let ys = positions(
+        background.repeat_y,
+        tile.y,
+        tile.height,
+        clip.y,
+        clip.height,
+    );
+    ys.iter()
+        .flat_map(|&y| xs.iter().map(move |&x| Rect { x, y, ..tile }))
+        .take(MAX_TILES)
+        .collect()
+}
+
+/// Format a number with at most three decimal places, without trailing zeros.
+fn number(value: f32) -> String {
+    let formatted = format!("{:.3}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" | "" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// The operands of the `re` operator for a rectangle.
+fn rect_operands(rect: Rect) -> String {
+    format!(
+        "{} {} {} {}",
+        number(rect.x),
+        number(rect.y),
+        number(rect.width),
+        number(rect.height)
+    )
+}
+
+/// A stream object, whose dictionary holds `dict` and the length of `data`.
+fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+    let length = format!("/Length {}", data.len());
+    let dict = if dict.is_empty() {
+        length
+    } else {
+        format!("{} {}", dict, length)
+    };
+    let mut body = format!("<< {} >>\nstream\n", dict).into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(b"\nendstream");
+    body
+}
+
+/// The byte for a character in the WinAnsiEncoding, which matches Latin-1 for the printable
+/// characters of both. Other characters are replaced by `?`.
+fn win_ansi(c: char) -> u8 {
+    match c as u32 {
+        0x20..=0x7e | 0xa0..=0xff => c as u8,
+        _ => b'?',
+    }
+}
+
+/// Stroke a dashed or dotted line along the middle of one side of a border. Dashes are twice
+/// as long as the border is wide, with gaps as wide as the border; dots are round, one width
+/// apart.
+fn line(out: &mut String, start: (f32, f32), end: (f32, f32), side: &BorderSide) {
+    let width = number(side.width);
+    if side.style == BorderStyle::Dotted {
+        write!(out, "1 J [0 {}] 0 d ", number(2.0 * side.width)).unwrap();
+    } else {
+        write!(out, "[{} {}] 0 d ", number(2.0 * side.width), width).unwrap();
+    }
+    write!(
+        out,
+        "{} w {} {} m {} {} l S ",
+        width,
+        number(start.0),
+        number(start.1),
+        number(end.0),
+        number(end.1)
+    )
+    .unwrap();
+}
+
+/// Add the path of the part of a border between two fractions of its widths, as two subpaths
+/// to be filled with the even-odd rule.
+fn ring(out: &mut String, border: &Border, widths: EdgeSize, from: f32, to: f32) {
+    let scaled = |fraction: f32| EdgeSize {
+        left: widths.left * fraction,
+        right: widths.right * fraction,
+        top: widths.top * fraction,
+        bottom: widths.bottom * fraction,
+    };
+    for edges in [scaled(from), scaled(to)] {
+        rounded_rect(out, inset(border.rect, edges), &border.radii.inset(edges));
+    }
+}
+
+/// Add the path of a rectangle with elliptical corners, clockwise from the top left.
+fn rounded_rect(out: &mut String, rect: Rect, radii: &CornerRadii) {
+    let (x0, y0, x1, y1) = (rect.x, rect.y, rect.x + rect.width, rect.y + rect.height);
+    let CornerRadii {
+        top_left,
+        top_right,
+        bottom_right,
+        bottom_left,
+    } = *radii;
+    write!(out, "{} {} m ", number(x0 + top_left.x), number(y0)).unwrap();
+    corner(
+        out,
+        top_right,
+        (x1 - top_right.x, y0),
+        (x1, y0),
+        (x1, y0 + top_right.y),
+    );
+    corner(
+        out,
+        bottom_right,
+        (x1, y1 - bottom_right.y),
+        (x1, y1),
+        (x1 - bottom_right.x, y1),
+    );
+    corner(
+        out,
+        bottom_left,
+        (x0 + bottom_left.x, y1),
+        (x0, y1),
+        (x0, y1 - bottom_left.y),
+    );
+    corner(
+        out,
+        top_left,
+        (x0, y0 + top_left.y),
+        (x0, y0),
+        (x0 + top_left.x, y0),
+    );
+    out.push_str("h ");
+}
+
+/// Add a line from the end of one side of a rounded rectangle, followed by the curve around
+/// the corner point `(x, y)` to the start of the next side.
+fn corner(out: &mut String, radius: Radius, from: (f32, f32), (x, y): (f32, f32), to: (f32, f32)) {
+    write!(out, "{} {} l ", number(from.0), number(from.1)).unwrap();
+    if radius.x > 0.0 && radius.y > 0.0 {
+        let control = |(px, py): (f32, f32)| {
+            format!(
+                "{} {}",
+                number(px + (x - px) * KAPPA),
+                number(py + (y - py) * KAPPA)
+            )
+        };
+        write!(
+            out,
+            "{} {} {} {} c ",
+            control(from),
+            control(to),
+            number(to.0),
+            number(to.1)
+        )
+        .unwrap();
+    }
+}
+
+fn inset(rect: Rect, edges: EdgeSize) -> Rect {
+    Rect {
+        x: rect.x + edges.left,
+        y: rect.y + edges.top,
+        width: (rect.width - edges.left - edges.right).max(0.0),
+        height: (rect.height - edges.top - edges.bottom).max(0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::layout_tree;
+    use crate::{css, html, style::style_tree};
+    use std::path::Path;
+
+    fn pdf(source: &str, stylesheet: &str, font: &Font) -> Vec<u8> {
+        let root = html::parse(source.to_string());
+        let stylesheet = css::parse(stylesheet.to_string());
+        let page = Page::from_stylesheet(&stylesheet);
+        let styled = style_tree(&root, &stylesheet);
+        let mut images = ImageCache::new(Path::new("."));
+        let layout = layout_tree(&styled, page.content_area(), &mut images);
+        paint(&layout, &page, &mut images, font)
+    }
+
+    #[test]
+    fn test_page_from_stylesheet() {
+        let page = Page::from_stylesheet(&css::parse(
+            "@page { size: a5 landscape; margin: 10px 20px; margin-left: 1in; }".to_string(),
+        ));
+        assert_eq!(page.width.round(), 794.0);
+        assert_eq!(page.height.round(), 559.0);
+        assert_eq!(
+            (page.margin.top, page.margin.right, page.margin.bottom),
+            (10.0, 20.0, 10.0)
+        );
+        assert_eq!(page.margin.left, 96.0);
+
+        let page = Page::from_stylesheet(&css::parse("@page { size: 300px; }".to_string()));
+        assert_eq!((page.width, page.height), (300.0, 300.0));
+        assert_eq!(page.content_area().content.height, 300.0);
+    }
+
+    #[test]
+    fn test_pages_and_cross_references() {
+        let data = pdf(
+            "<div><p>hello</p><p class=\"next\"></p></div>",
+            "@page { size: 200px 100px; margin: 10px; } \
+             div { display: block; } \
+             p { display: block; height: 30px; background: #ff0000; } \
+             .next { page-break-before: always; }",
+            &Font::courier(),
+        );
+        let pdf = String::from_utf8_lossy(&data);
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.contains("/Type /Pages /Kids [5 0 R 7 0 R] /Count 2"));
+        assert!(pdf.contains("/MediaBox [0 0 150 75]"));
+        assert!(pdf.contains("/BaseFont /Courier"));
+        assert!(pdf.contains("<68656c6c6f> Tj"));
+        // The second page shows the part of the document below the break.
+        assert!(pdf.contains("1 0 0 1 10 -20 cm"));
+
+        // The cross-reference table gives the position of each object in bytes.
+        let trailer = String::from_utf8_lossy(&data[data.len() - 300..]);
+        let startxref = trailer.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = trailer[startxref..]
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(data[xref..].starts_with(b"xref\n0 9\n"));
+        let first = std::str::from_utf8(&data[xref + 29..xref + 39]).unwrap();
+        assert!(data[first.parse::<usize>().unwrap()..].starts_with(b"1 0 obj\n<< /Type /Catalog"));
+    }
+
+    /// A TrueType font with only the tables that are read: `head`, `hhea` and `name`.
+    fn truetype() -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&2048u16.to_be_bytes());
+        for (index, value) in [-2048i16, -512, 2048, 2048].iter().enumerate() {
+            head[36 + index * 2..38 + index * 2].copy_from_slice(&value.to_be_bytes());
+        }
+        let mut hhea = vec![0; 36];
+        hhea[4..6].copy_from_slice(&1638i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-410i16).to_be_bytes());
+        let mut name = Vec::new();
+        for value in [0u16, 1, 18, 1, 0, 0, 6, 9, 0] {
+            name.extend_from_slice(&value.to_be_bytes());
+        }
+        name.extend_from_slice(b"Test Mono");
+
+        let tables: [(&[u8; 4], &[u8]); 3] = [(b"head", &head), (b"hhea", &hhea), (b"name", &name)];
+        let mut data = vec![0, 1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0];
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in tables {
+            data.extend_from_slice(tag);
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            data.extend_from_slice(table);
+        }
+        data
+    }
+
+    #[test]
+    fn test_embedded_font() {
+        let font = Font::truetype(truetype()).unwrap();
+        let data = pdf("<p>a</p>", "p { display: block; }", &font);
+        let pdf = String::from_utf8_lossy(&data);
+        assert!(pdf.contains("/Subtype /TrueType /BaseFont /TestMono"));
+        assert!(pdf.contains("/FontBBox [-1000 -250 1000 1000] /ItalicAngle 0 /Ascent 799"));
+        assert!(pdf.contains("/FontFile2"));
+
+        assert!(Font::truetype(b"OTTO\0\0".to_vec()).is_err());
+        assert!(Font::truetype(truetype()[..40].to_vec()).is_err());
+    }
+}
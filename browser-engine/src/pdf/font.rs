@@ -0,0 +1,148 @@
+//! The font that PDF text is set in.
+//!
+//! Layout measures text as a monospace font whose characters advance by 0.6em, so every glyph
+//! is given that width, whatever the font itself says.
+//!
+//! https://developer.apple.com/fonts/TrueType-Reference-Manual/
+
+use std::io;
+
+/// A font for the text of a PDF document: either Courier, one of the standard fonts every PDF
+/// reader provides, or a TrueType font embedded in the document.
+pub struct Font {
+    embedded: Option<TrueType>,
+}
+
+/// The tables of a TrueType font that are needed to describe it in a PDF.
+pub(super) struct TrueType {
+    pub data: Vec<u8>,
+    /// The PostScript name of the font.
+    pub name: String,
+    /// The bounding box of all glyphs, and the ascent and descent of the font, in thousandths
+    /// of an em.
+    pub bbox: [i32; 4],
+    pub ascent: i32,
+    pub descent: i32,
+}
+
+impl Font {
+    pub fn courier() -> Font {
+        Font { embedded: None }
+    }
+
+    /// Load a TrueType font to embed, from the contents of a `.ttf` file. It should be a
+    /// monospace font, since its glyphs are placed 0.6em apart.
+    pub fn truetype(data: Vec<u8>) -> io::Result<Font> {
+        let version = read_u32(&data, 0)?;
+        if version != 0x0001_0000 && version != u32::from_be_bytes(*b"true") {
+            return Err(invalid_data("not a TrueType font"));
+        }
+        let head = table(&data, b"head")?;
+        let hhea = table(&data, b"hhea")?;
+        let units_per_em = read_u16(head, 18)? as i32;
+        if units_per_em == 0 {
+            return Err(invalid_data("font has no units per em"));
+        }
+        let scale = |value: u16| (value as i16) as i32 * 1000 / units_per_em;
+        let bbox = [
+            scale(read_u16(head, 36)?),
+            scale(read_u16(head, 38)?),
+            scale(read_u16(head, 40)?),
+            scale(read_u16(head, 42)?),
+        ];
+        let ascent = scale(read_u16(hhea, 4)?);
+        let descent = scale(read_u16(hhea, 6)?);
+        let name = table(&data, b"name")
+            .ok()
+            .and_then(postscript_name)
+            .unwrap_or_else(|| "EmbeddedFont".to_string());
+        Ok(Font {
+            embedded: Some(TrueType {
+                data,
+                name,
+                bbox,
+                ascent,
+                descent,
+            }),
+        })
+    }
+
+    pub(super) fn embedded(&self) -> Option<&TrueType> {
+        self.embedded.as_ref()
+    }
+}
+
+impl Default for Font {
+    fn default() -> Font {
+        Font::courier()
+    }
+}
+
+/// Find a table in the font's table directory.
+fn table<'a>(data: &'a [u8], tag: &[u8; 4]) -> io::Result<&'a [u8]> {
+    let count = read_u16(data, 4)? as usize;
+    for index in 0..count {
+        let record = 12 + index * 16;
+        if data.get(record..record + 4) == Some(tag) {
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            return offset
+                .checked_add(length)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| invalid_data("font table is truncated"));
+        }
+    }
+    Err(invalid_data("font is missing a required table"))
+}
+
+/// The PostScript name (name ID 6) from the `name` table, keeping only the characters that
+/// are allowed in a PDF name.
+fn postscript_name(names: &[u8]) -> Option<String> {
+    let count = read_u16(names, 2).ok()? as usize;
+    let strings = read_u16(names, 4).ok()? as usize;
+    for index in 0..count {
+        let record = 6 + index * 12;
+        let platform = read_u16(names, record).ok()?;
+        if read_u16(names, record + 6).ok()? != 6 {
+            continue;
+        }
+        let length = read_u16(names, record + 8).ok()? as usize;
+        let offset = strings + read_u16(names, record + 10).ok()? as usize;
+        let bytes = names.get(offset..offset + length)?;
+        // Windows names are UTF-16; Macintosh names are single bytes.
+        let name: String = if platform == 3 {
+            bytes
+                .chunks(2)
+                .filter_map(|pair| (pair.len() == 2 && pair[0] == 0).then_some(pair[1] as char))
+                .collect()
+        } else {
+            bytes.iter().map(|&b| b as char).collect()
+        };
+        let name: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            .collect();
+        if !name.is_empty() {
+            return Some(name);
+        }
+    }
+    None
+}
+
+fn read_u16(data: &[u8], offset: usize) -> io::Result<u16> {
+    match data.get(offset..offset + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+        None => Err(invalid_data("font is truncated")),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => Err(invalid_data("font is truncated")),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
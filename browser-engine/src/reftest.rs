@@ -0,0 +1,350 @@
+//! Reference tests, which check rendering by comparing the pixels of two documents: a test
+//! that uses the feature being tested, and a reference that should look the same without it.
+//!
+//! A directory of reftests has one subdirectory per test, containing `test.html` and
+//! `reference.html`. Each page is styled by the optional `style.css` shared by both, followed
+//! by its own optional `test.css` or `reference.css`.
+//!
+//! http://web-platform-tests.org/writing-tests/reftests.html
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::css::{self, Color};
+use crate::image::{encode_png, Image, ImageCache};
+use crate::layout::{layout_tree, Dimensions, Rect};
+use crate::painting::{full_page, paint};
+use crate::{html, style};
+
+/// The size of the viewport that reftests are laid out in.
+pub const VIEWPORT_WIDTH: f32 = 800.0;
+pub const VIEWPORT_HEIGHT: f32 = 600.0;
+
+/// How different the test and reference may be and still pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tolerance {
+    /// The largest difference in any color channel of a pixel that is ignored.
+    pub max_difference: u8,
+    /// The number of pixels that may differ by more than `max_difference`.
+    pub max_pixels: usize,
+}
+
+/// The result of comparing two images.
+pub struct Comparison {
+    /// The number of pixels that differ by more than the tolerance allows.
+    pub differing_pixels: usize,
+    /// The largest difference in any color channel of any pixel.
+    pub max_difference: u8,
+    /// An image of the differences: the differing pixels in red, over a faded copy of the test.
+    pub diff: Image,
+}
+
+/// A reftest whose test and reference don't match.
+#[derive(Debug)]
+pub struct Failure {
+    pub name: String,
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+    /// Where the image of the differences was written.
+    pub diff: PathBuf,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} pixels differ, by up to {} (see {})",
+            self.name,
+            self.differing_pixels,
+            self.max_difference,
+            self.diff.display()
+        )
+    }
+}
+
+/// Render every reftest in `dir`, in order of name, and return the ones that fail. For each
+/// failure, the rendered test and reference and an image of their differences are written to
+/// `output` as `<name>-test.png`, `<name>-reference.png` and `<name>-diff.png`.
+pub fn run(dir: &Path, tolerance: Tolerance, output: &Path) -> io::Result<Vec<Failure>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.path().join("test.html").is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+
+    let mut failures = Vec::new();
+    for name in names {
+        let test_dir = dir.join(&name);
+        let (test, reference) = render_pair(&test_dir)?;
+        let comparison = compare(&test, &reference, tolerance);
+        if comparison.differing_pixels > tolerance.max_pixels {
+            fs::create_dir_all(output)?;
+            let diff = output.join(format!("{}-diff.png", name));
+            fs::write(output.join(format!("{}-test.png", name)), encode_png(&test))?;
+            fs::write(
+                output.join(format!("{}-reference.png", name)),
+                encode_png(&reference),
+            )?;
+            fs::write(&diff, encode_png(&comparison.diff))?;
+            failures.push(Failure {
+                name,
+                differing_pixels: comparison.differing_pixels,
+                max_difference: comparison.max_difference,
+                diff,
+            });
+        }
+    }
+    Ok(failures)
+}
+
+/// Render the test and reference in `dir` to images of the same size, large enough for the
+/// whole of both documents.
+pub fn render_pair(dir: &Path) -> io::Result<(Image, Image)> {
+    let shared = read_optional(&dir.join("style.css"))?;
+    let page = |name: &str| -> io::Result<(String, String)> {
+        let source = fs::read_to_string(dir.join(format!("{}.html", name)))?;
+        let own = read_optional(&dir.join(format!("{}.css", name)))?;
+        Ok((source, format!("{}\n{}", shared, own)))
+    };
+    let (test_html, test_css) = page("test")?;
+    let (reference_html, reference_css) = page("reference")?;
+    let bounds = union(
+        page_bounds(&test_html, &test_css, dir),
+        page_bounds(&reference_html, &reference_css, dir),
+    );
+    Ok((
+        render(&test_html, &test_css, dir, bounds),
+        render(&reference_html, &reference_css, dir, bounds),
+    ))
+}
+
+/// Lay out and paint a document, with images loaded relative to `base`.
+pub fn render(source: &str, stylesheet: &str, base: &Path, bounds: Rect) -> Image {
+    let root = html::parse(source.to_string());
+    let stylesheet = css::parse(stylesheet.to_string());
+    let styled = style::style_tree(&root, &stylesheet);
+    let mut images = ImageCache::new(base);
+    let layout = layout_tree(&styled, viewport(), &mut images);
+    let canvas = paint(&layout, bounds, &mut images);
+    Image {
+        width: canvas.width,
+        height: canvas.height,
+        pixels: canvas.pixels,
+    }
+}
+
+/// Compare two images pixel by pixel. Parts of one image outside the other count as differing
+/// as much as possible.
+pub fn compare(test: &Image, reference: &Image, tolerance: Tolerance) -> Comparison {
+    let width = test.width.max(reference.width);
+    let height = test.height.max(reference.height);
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let a = pixel_at(test, x, y);
+            let b = pixel_at(reference, x, y);
+            let difference = match (a, b) {
+                (Some(a), Some(b)) => [
+                    a.r.abs_diff(b.r),
+                    a.g.abs_diff(b.g),
+                    a.b.abs_diff(b.b),
+                    a.a.abs_diff(b.a),
+                ]
+                .into_iter()
+                .max()
+                .unwrap(),
+                _ => u8::MAX,
+            };
+            max_difference = max_difference.max(difference);
+            if difference > tolerance.max_difference {
+                differing_pixels += 1;
+                pixels.push(Color {
+                    r: 255,
+                    g: 0,
+                    b: 0,
+                    a: 255,
+                });
+            } else {
+                pixels.push(faded(a.or(b).unwrap()));
+            }
+        }
+    }
+    Comparison {
+        differing_pixels,
+        max_difference,
+        diff: Image {
+            width,
+            height,
+            pixels,
+        },
+    }
+}
+
+fn pixel_at(image: &Image, x: usize, y: usize) -> Option<&Color> {
+    (x < image.width && y < image.height).then(|| image.pixel(x, y))
+}
+
+/// A pale grey version of a pixel, so that differences stand out against it.
+fn faded(color: &Color) -> Color {
+    let luma = (color.r as u32 * 3 + color.g as u32 * 6 + color.b as u32) / 10;
+    let value = (192 + luma / 4) as u8;
+    Color {
+        r: value,
+        g: value,
+        b: value,
+        a: 255,
+    }
+}
+
+fn viewport() -> Dimensions {
+    let mut viewport: Dimensions = Default::default();
+    viewport.content.width = VIEWPORT_WIDTH;
+    viewport.content.height = VIEWPORT_HEIGHT;
+    viewport
+}
+
+/// The area to paint for the whole of a document.
+fn page_bounds(source: &str, stylesheet: &str, base: &Path) -> Rect {
+    let root = html::parse(source.to_string());
+    let stylesheet = css::parse(stylesheet.to_string());
+    let styled = style::style_tree(&root, &stylesheet);
+    let layout = layout_tree(&styled, viewport(), &mut ImageCache::new(base));
+    full_page(&layout, viewport().content)
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    Rect {
+        x: 0.0,
+        y: 0.0,
+        width: a.width.max(b.width),
+        height: a.height.max(b.height),
+    }
+}
+
+/// The contents of a file, or nothing if it doesn't exist.
+fn read_optional(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(colors: &[(u8, u8, u8)], width: usize) -> Image {
+        Image {
+            width,
+            height: colors.len() / width,
+            pixels: colors
+                .iter()
+                .map(|&(r, g, b)| Color { r, g, b, a: 255 })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_compare_with_tolerance() {
+        let test = image(&[(0, 0, 0), (100, 100, 100), (255, 255, 255)], 3);
+        let reference = image(&[(0, 0, 0), (102, 100, 100), (0, 255, 255)], 3);
+        let exact = compare(&test, &reference, Tolerance::default());
+        assert_eq!(exact.differing_pixels, 2);
+        assert_eq!(exact.max_difference, 255);
+
+        let tolerance = Tolerance {
+            max_difference: 2,
+            max_pixels: 0,
+        };
+        let comparison = compare(&test, &reference, tolerance);
+        assert_eq!(comparison.differing_pixels, 1);
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        assert_ne!(comparison.diff.pixels[1], red);
+        assert_eq!(comparison.diff.pixels[2], red);
+
+        // Pixels outside one of the images always differ.
+        let wider = image(&[(0, 0, 0), (100, 100, 100), (255, 255, 255), (0, 0, 0)], 4);
+        let comparison = compare(&wider, &test, tolerance);
+        assert_eq!((comparison.diff.width, comparison.differing_pixels), (4, 1));
+    }
+
+    #[test]
+    fn test_run_writes_diffs_of_failures() {
+        let dir = std::env::temp_dir().join(format!("reftests-{}", std::process::id()));
+        let output = dir.join("output");
+        let case = |name: &str, test: &str, reference: &str| {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join("test.html"), "<div></div>").unwrap();
+            fs::write(dir.join(name).join("reference.html"), "<div></div>").unwrap();
+            fs::write(dir.join(name).join("test.css"), test).unwrap();
+            fs::write(dir.join(name).join("reference.css"), reference).unwrap();
+        };
+        case(
+            "margin",
+            "div { display: block; margin-left: 10px; width: 5px; height: 5px; \
+             background: #0000ff; }",
+            "div { display: block; padding-left: 10px; width: 5px; height: 5px; \
+             background: #0000ff; background-clip: content-box; }",
+        );
+        case(
+            "size",
+            "div { display: block; width: 5px; height: 5px; background: #0000ff; }",
+            "div { display: block; width: 6px; height: 5px; background: #0000ff; }",
+        );
+        let failures = run(&dir, Tolerance::default(), &output).unwrap();
+        let summary: Vec<String> = failures.iter().map(|f| f.to_string()).collect();
+        let diff_exists = failures.iter().all(|failure| failure.diff.is_file());
+        let loose = run(
+            &dir,
+            Tolerance {
+                max_difference: 0,
+                max_pixels: 5,
+            },
+            &output,
+        )
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(summary.len(), 1, "{:?}", summary);
+        assert_eq!(failures[0].name, "size");
+        assert_eq!(failures[0].differing_pixels, 5);
+        assert!(diff_exists);
+        assert!(loose.is_empty());
+    }
+
+    #[test]
+    fn test_hidden_root_is_a_failure_rather_than_a_crash() {
+        let dir = std::env::temp_dir().join(format!("reftests-hidden-{}", std::process::id()));
+        let output = dir.join("output");
+        fs::create_dir_all(dir.join("hidden")).unwrap();
+        fs::write(dir.join("hidden").join("test.html"), "<div></div>").unwrap();
+        fs::write(dir.join("hidden").join("reference.html"), "<div></div>").unwrap();
+        fs::write(
+            dir.join("hidden").join("style.css"),
+            "div { display: block; width: 5px; height: 5px; background: #0000ff; }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("hidden").join("test.css"),
+            "div { display: none; }",
+        )
+        .unwrap();
+        let failures = run(&dir, Tolerance::default(), &output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].differing_pixels, 25);
+    }
+}
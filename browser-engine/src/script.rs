@@ -0,0 +1,632 @@
+//! Hooks that change a document after it is parsed and before it is styled, so that a page can
+//! be filled in from data without a JavaScript engine.
+//!
+//! A hook is either a Rust function given the root of the document, or a `Program` in a small
+//! language of commands, each applied to every element a selector matches:
+//!
+//! ```text
+//! // Values in strings are filled in from the program's data.
+//! set-text #title "Report for {client}";
+//! set-attr img.logo src "{logo}";
+//! add-class tr.total highlight;
+//! append ul#items "<li>{first}</li><li>{second}</li>";
+//! if draft { remove .signature; }
+//! if status != "final" { append body "<p class='draft'>Draft</p>"; }
+//! ```
+//!
+//! ```
+//! use browser_engine::{html, script};
+//!
+//! let mut document = html::parse("<div><p class=\"a\">Hello</p></div>".to_string());
+//! let mut program = script::parse("toggle-class p a; set-text p \"{greeting}\";").unwrap();
+//! program.data.insert("greeting".to_string(), "Hi".to_string());
+//! program.run(&mut document).unwrap();
+//! assert_eq!(document.to_string(), "<div><p class=\"\">Hi</p></div>");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::css::{self, Selector, SourceLocation};
+use crate::dom::{self, Node, NodeType};
+use crate::html;
+use crate::style;
+
+/// A change made to a document after it is parsed and before it is styled.
+pub trait Hook {
+    fn run(&mut self, document: &mut Node) -> Result<(), ScriptError>;
+}
+
+impl<F: FnMut(&mut Node)> Hook for F {
+    fn run(&mut self, document: &mut Node) -> Result<(), ScriptError> {
+        self(document);
+        Ok(())
+    }
+}
+
+impl Hook for Program {
+    fn run(&mut self, document: &mut Node) -> Result<(), ScriptError> {
+        Program::run(self, document)
+    }
+}
+
+/// Call `f` with each element in a document that `selector` matches, in reverse document
+/// order. The elements are all found before any are changed, and changing an element only
+/// affects elements that come before it, so `f` may add and remove nodes.
+pub fn for_each_match(document: &mut Node, selector: &Selector, mut f: impl FnMut(&mut Node)) {
+    let mut paths = Vec::new();
+    find_matches(document, selector, &mut Vec::new(), &mut paths);
+    for path in paths.iter().rev() {
+        let mut node = &mut *document;
+        for &index in path {
+            node = &mut node.children[index];
+        }
+        f(node);
+    }
+}
+
+/// Record the path of child indices to each element that `selector` matches, in document order.
+fn find_matches(
+    node: &Node,
+    selector: &Selector,
+    path: &mut Vec<usize>,
+    paths: &mut Vec<Vec<usize>>,
+) {
+    if let NodeType::Element(ref element) = node.node_type {
+        if style::matches(element, selector, None) {
+            paths.push(path.clone());
+        }
+    }
+    for (index, child) in node.children.iter().enumerate() {
+        path.push(index);
+        find_matches(child, selector, path, paths);
+        path.pop();
+    }
+}
+
+/// An error in the source of a program, or in running it.
+#[derive(Debug, PartialEq)]
+pub struct ScriptError {
+    pub message: String,
+    /// Where in the program's source the error is, or the statement that failed.
+    pub location: SourceLocation,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+type ScriptResult<T> = Result<T, ScriptError>;
+
+/// A program that changes a document, with the data that its strings are filled in from.
+#[derive(Debug)]
+pub struct Program {
+    statements: Vec<Statement>,
+    pub data: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+enum Statement {
+    Command {
+        command: Command,
+        selector: Selector,
+        location: SourceLocation,
+    },
+    If {
+        condition: Condition,
+        body: Vec<Statement>,
+    },
+}
+
+/// What a command does to each element its selector matches.
+#[derive(Debug)]
+enum Command {
+    SetAttribute(String, Template),
+    RemoveAttribute(String),
+    AddClass(String),
+    RemoveClass(String),
+    ToggleClass(String),
+    /// Replace the element's contents with text.
+    SetText(Template),
+    /// Parse HTML and add it at the end of the element's contents.
+    Append(Template),
+    /// Parse HTML and add it at the start of the element's contents.
+    Prepend(Template),
+    Remove,
+}
+
+/// Whether the statements of an `if` are run: if a value in the data is set and isn't empty,
+/// or if it is or isn't equal to a string. A value that isn't set is equal to `""`.
+#[derive(Debug)]
+enum Condition {
+    Set(String),
+    Equals(String, Template, bool),
+}
+
+/// A string in which `{name}` stands for a value in the data, and `{{` and `}}` for braces.
+#[derive(Debug)]
+struct Template(Vec<Part>);
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Value(String),
+}
+
+impl Template {
+    fn fill(&self, data: &HashMap<String, String>) -> Result<String, String> {
+        let mut text = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(part) => text.push_str(part),
+                Part::Value(name) => match data.get(name) {
+                    Some(value) => text.push_str(value),
+                    None => return Err(format!("no value for {{{}}}", name)),
+                },
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Parse the source of a program. Its data starts out empty.
+pub fn parse(source: &str) -> ScriptResult<Program> {
+    let mut parser = Parser {
+        input: source,
+        pos: 0,
+    };
+    let statements = parser.parse_statements(false)?;
+    Ok(Program {
+        statements,
+        data: HashMap::new(),
+    })
+}
+
+impl Program {
+    /// Run the program's statements in order. The program stops at the first statement that
+    /// fails, which leaves the document with the changes made before it.
+    pub fn run(&self, document: &mut Node) -> ScriptResult<()> {
+        run_statements(&self.statements, document, &self.data)
+    }
+}
+
+fn run_statements(
+    statements: &[Statement],
+    document: &mut Node,
+    data: &HashMap<String, String>,
+) -> ScriptResult<()> {
+    for statement in statements {
+        match statement {
+            Statement::If { condition, body } => {
+                let value = |name: &str| data.get(name).map_or("", String::as_str);
+                let run = match condition {
+                    Condition::Set(name) => !value(name).is_empty(),
+                    // A condition whose string can't be filled in is false.
+                    Condition::Equals(name, string, equal) => string
+                        .fill(data)
+                        .is_ok_and(|string| (value(name) == string) == *equal),
+                };
+                if run {
+                    run_statements(body, document, data)?;
+                }
+            }
+            Statement::Command {
+                command,
+                selector,
+                location,
+            } => {
+                let error = |message| ScriptError {
+                    message,
+                    location: *location,
+                };
+                let fill = |template: &Template| template.fill(data).map_err(error);
+                match command {
+                    Command::SetAttribute(name, value) => {
+                        let value = fill(value)?;
+                        for_each_element(document, selector, |element| {
+                            element.attrs.insert(name.clone(), value.clone());
+                        });
+                    }
+                    Command::RemoveAttribute(name) => {
+                        for_each_element(document, selector, |element| {
+                            element.attrs.remove(name);
+                        })
+                    }
+                    Command::AddClass(class) => {
+                        for_each_element(document, selector, |element| element.add_class(class))
+                    }
+                    Command::RemoveClass(class) => {
+                        for_each_element(document, selector, |element| element.remove_class(class))
+                    }
+                    Command::ToggleClass(class) => {
+                        for_each_element(document, selector, |element| {
+                            element.toggle_class(class);
+                        })
+                    }
+                    Command::SetText(text) => {
+                        let text = fill(text)?;
+                        for_each_match(document, selector, |node| {
+                            node.children = vec![dom::text(text.clone())];
+                        });
+                    }
+                    Command::Append(source) | Command::Prepend(source) => {
+                        let nodes = html::parse_fragment(&fill(source)?);
+                        let prepend = matches!(command, Command::Prepend(_));
+                        for_each_match(document, selector, |node| {
+                            let at = if prepend { 0 } else { node.children.len() };
+                            node.children.splice(at..at, nodes.iter().cloned());
+                        });
+                    }
+                    Command::Remove => remove_matches(document, selector),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn for_each_element(
+    document: &mut Node,
+    selector: &Selector,
+    mut f: impl FnMut(&mut dom::ElementData),
+) {
+    for_each_match(document, selector, |node| {
+        if let NodeType::Element(ref mut element) = node.node_type {
+            f(element);
+        }
+    });
+}
+
+/// Remove the elements that `selector` matches, along with their contents. The root of the
+/// document can't be removed, so it is left in place.
+fn remove_matches(node: &mut Node, selector: &Selector) {
+    node.children.retain(|child| {
+        !matches!(child.node_type, NodeType::Element(ref element)
+            if style::matches(element, selector, None))
+    });
+    for child in &mut node.children {
+        remove_matches(child, selector);
+    }
+}
+
+/// Reads statements from the source of a program. Whitespace and `//` comments separate
+/// tokens.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn consume_char(&mut self) -> Option<char> {
+        let c = self.next_char()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn consume_while(&mut self, test: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        while let Some(c) = self.next_char().filter(|&c| test(c)) {
+            self.pos += c.len_utf8();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn consume_whitespace(&mut self) {
+        loop {
+            self.consume_while(char::is_whitespace);
+            if !self.input[self.pos..].starts_with("//") {
+                break;
+            }
+            self.consume_while(|c| c != '\n');
+        }
+    }
+
+    fn location(&self) -> SourceLocation {
+        let before = &self.input[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        SourceLocation {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn error<T>(&self, message: String) -> ScriptResult<T> {
+        Err(ScriptError {
+            message,
+            location: self.location(),
+        })
+    }
+
+    /// Consume the character `c`, after any whitespace.
+    fn expect_char(&mut self, c: char) -> ScriptResult<()> {
+        self.consume_whitespace();
+        match self.next_char() {
+            Some(next) if next == c => {
+                self.consume_char();
+                Ok(())
+            }
+            Some(next) => self.error(format!("expected {:?} but found {:?}", c, next)),
+            None => self.error(format!("expected {:?} at the end of the program", c)),
+        }
+    }
+
+    /// Parse statements up to the end of the program, or the `}` that ends a block.
+    fn parse_statements(&mut self, in_block: bool) -> ScriptResult<Vec<Statement>> {
+        let mut statements = Vec::new();
+        loop {
+            self.consume_whitespace();
+            match self.next_char() {
+                None if in_block => return self.error("unclosed '{'".to_string()),
+                None => return Ok(statements),
+                Some('}') if in_block => {
+                    self.consume_char();
+                    return Ok(statements);
+                }
+                Some(_) => statements.push(self.parse_statement()?),
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> ScriptResult<Statement> {
+        let location = self.location();
+        let name = self.parse_word("a command")?;
+        if name == "if" {
+            let condition = self.parse_condition()?;
+            self.expect_char('{')?;
+            let body = self.parse_statements(true)?;
+            return Ok(Statement::If { condition, body });
+        }
+        let selector = self.parse_selector()?;
+        let command = match &*name {
+            "set-attr" => Command::SetAttribute(
+                self.parse_word("an attribute name")?,
+                self.parse_template()?,
+            ),
+            "remove-attr" => Command::RemoveAttribute(self.parse_word("an attribute name")?),
+            "add-class" => Command::AddClass(self.parse_word("a class")?),
+            "remove-class" => Command::RemoveClass(self.parse_word("a class")?),
+            "toggle-class" => Command::ToggleClass(self.parse_word("a class")?),
+            "set-text" => Command::SetText(self.parse_template()?),
+            "append" => Command::Append(self.parse_template()?),
+            "prepend" => Command::Prepend(self.parse_template()?),
+            "remove" => Command::Remove,
+            _ => {
+                return Err(ScriptError {
+                    message: format!("unknown command {}", name),
+                    location,
+                })
+            }
+        };
+        self.expect_char(';')?;
+        Ok(Statement::Command {
+            command,
+            selector,
+            location,
+        })
+    }
+
+    /// Parse a name, such as a command, a class or a key in the data: anything up to the next
+    /// whitespace or punctuation. `what` describes it in errors.
+    fn parse_word(&mut self, what: &str) -> ScriptResult<String> {
+        self.consume_whitespace();
+        let word = self.consume_while(|c| {
+            !c.is_whitespace() && !matches!(c, ';' | '{' | '}' | '"' | '\'' | '=' | '!')
+        });
+        if word.is_empty() {
+            return self.error(format!("expected {}", what));
+        }
+        Ok(word.to_string())
+    }
+
+    /// Parse a selector, which ends at whitespace outside of brackets and quotes.
+    fn parse_selector(&mut self) -> ScriptResult<Selector> {
+        self.consume_whitespace();
+        let location = self.location();
+        let start = self.pos;
+        let mut quote = None;
+        let mut in_brackets = false;
+        while let Some(c) = self.next_char() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') if in_brackets => quote = Some(c),
+                (None, '[') => in_brackets = true,
+                (None, ']') => in_brackets = false,
+                (None, c) if !in_brackets && (c.is_whitespace() || c == ';') => break,
+                _ => {}
+            }
+            self.consume_char();
+        }
+        let source = &self.input[start..self.pos];
+        css::parse_selector(source.to_string())
+            .filter(|selector| selector.pseudo_element().is_none())
+            .ok_or_else(|| ScriptError {
+                message: format!("invalid selector {:?}", source),
+                location,
+            })
+    }
+
+    /// Parse a quoted string, in which a backslash escapes the next character and `\n` is a
+    /// newline, into a template.
+    fn parse_template(&mut self) -> ScriptResult<Template> {
+        self.consume_whitespace();
+        let quote = match self.next_char() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return self.error("expected a string".to_string()),
+        };
+        self.consume_char();
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        loop {
+            let c = match self.consume_char() {
+                Some(c) if c == quote => break,
+                Some(c) => c,
+                None => return self.error("unclosed string".to_string()),
+            };
+            match c {
+                '\\' => match self.consume_char() {
+                    Some('n') => text.push('\n'),
+                    Some(c) => text.push(c),
+                    None => return self.error("unclosed string".to_string()),
+                },
+                '{' | '}' if self.next_char() == Some(c) => {
+                    self.consume_char();
+                    text.push(c);
+                }
+                '{' => {
+                    self.pos -= 1;
+                    let location = self.location();
+                    self.pos += 1;
+                    let name = self.consume_while(|c| c != '}' && c != quote).to_string();
+                    if self.consume_char() != Some('}') || name.trim().is_empty() {
+                        return Err(ScriptError {
+                            message: "expected a name and '}' after '{'".to_string(),
+                            location,
+                        });
+                    }
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Value(name.trim().to_string()));
+                }
+                '}' => return self.error("unmatched '}' in string".to_string()),
+                c => text.push(c),
+            }
+        }
+        parts.push(Part::Text(text));
+        Ok(Template(parts))
+    }
+
+    /// Parse the condition of an `if`: a name, optionally followed by `==` or `!=` and a string.
+    fn parse_condition(&mut self) -> ScriptResult<Condition> {
+        let name = self.parse_word("a name")?;
+        self.consume_whitespace();
+        let rest = &self.input[self.pos..];
+        let equal = if rest.starts_with("==") {
+            true
+        } else if rest.starts_with("!=") {
+            false
+        } else {
+            return Ok(Condition::Set(name));
+        };
+        self.pos += 2;
+        Ok(Condition::Equals(name, self.parse_template()?, equal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str, document: &str, data: &[(&str, &str)]) -> ScriptResult<String> {
+        let mut document = html::parse(document.to_string());
+        let mut program = parse(source)?;
+        for &(name, value) in data {
+            program.data.insert(name.to_string(), value.to_string());
+        }
+        program.run(&mut document)?;
+        Ok(document.to_string())
+    }
+
+    #[test]
+    fn test_commands() {
+        let source = "
+            // Fill in a page from data.
+            set-text h1 \"Report for {client}\";
+            set-attr a.home href '/clients/{id}?x={{1}}';
+            remove-attr [data-temp] data-temp;
+            add-class li total;
+            remove-class .old old;
+            toggle-class li odd;
+            append ul \"<li>{client}</li>\";
+            prepend ul '<li>first</li>';
+            remove p.draft;
+        ";
+        let document = "<div><h1>Report</h1><a class=\"home\" data-temp>x</a>\
+                        <ul><li class=\"old odd\">a</li></ul><p class=\"draft\">d<p>n</p></p></div>";
+        assert_eq!(
+            run(source, document, &[("client", "ACME"), ("id", "7")]),
+            Ok(
+                "<div><h1>Report for ACME</h1><a class=\"home\" href=\"/clients/7?x={1}\">x</a>\
+                <ul><li>first</li><li class=\"total\">a</li><li>ACME</li></ul></div>"
+                    .to_string()
+            )
+        );
+
+        // Elements added by a command aren't matched by it again.
+        assert_eq!(
+            run("append div '<div></div>';", "<div></div>", &[]),
+            Ok("<div><div></div></div>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        let source = "if draft { add-class p draft; } if status == 'final' { set-text p 'Done'; }
+                      if status != \"final\" { if draft { append p '!'; } }";
+        let document = "<p>Text</p>";
+        assert_eq!(
+            run(source, document, &[("draft", "yes")]),
+            Ok("<p class=\"draft\">Text!</p>".to_string())
+        );
+        assert_eq!(
+            run(source, document, &[("draft", ""), ("status", "final")]),
+            Ok("<p>Done</p>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| match run(source, "<p></p>", &[]) {
+            Err(error) => error.to_string(),
+            Ok(_) => panic!("{} didn't fail", source),
+        };
+        assert_eq!(error("add-class p"), "1:12: expected a class");
+        assert_eq!(error("frobnicate p x;"), "1:1: unknown command frobnicate");
+        assert_eq!(
+            error("remove p::before;"),
+            "1:8: invalid selector \"p::before\""
+        );
+        assert_eq!(
+            error("set-text p 'a {b';"),
+            "1:15: expected a name and '}' after '{'"
+        );
+        assert_eq!(error("if x { remove p;"), "1:17: unclosed '{'");
+        assert_eq!(
+            error("remove p"),
+            "1:9: expected ';' at the end of the program"
+        );
+        // A value that isn't in the data fails the statement using it.
+        assert_eq!(
+            error("remove-attr p x;\nset-text p \"{missing}\";"),
+            "2:1: no value for {missing}"
+        );
+    }
+
+    #[test]
+    fn test_rust_hooks() {
+        let mut document = html::parse("<ul><li>a</li><li>b</li></ul>".to_string());
+        let mut count = 0;
+        let mut number = |document: &mut Node| {
+            let selector = css::parse_selector("li".to_string()).unwrap();
+            for_each_match(document, &selector, |item| {
+                count += 1;
+                item.children.push(dom::text(format!(" {}", count)));
+            });
+        };
+        let mut program = parse("add-class li item;").unwrap();
+        let hooks: [&mut dyn Hook; 2] = [&mut number, &mut program];
+        for hook in hooks {
+            hook.run(&mut document).unwrap();
+        }
+        // The elements are visited in reverse document order.
+        assert_eq!(
+            document.to_string(),
+            "<ul><li class=\"item\">a 2</li><li class=\"item\">b 1</li></ul>"
+        );
+    }
+}
@@ -1,47 +1,547 @@
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
-use crate::css::{Rule, Selector, SimpleSelector, Specificity, Stylesheet, Value};
+use crate::css::{
+    self, PseudoElement, Rule, Selector, SimpleSelector, Specificity, Stylesheet, Value,
+};
 use crate::dom::{ElementData, Node, NodeType};
 
+pub use self::counters::ListStyleType;
+
+use self::counters::{counter_list, Counters};
+
+mod counters;
+
 type PropertyMap = HashMap<String, Value>;
 
+/// The default styles of HTML elements, which author rules override regardless of specificity.
+const USER_AGENT_STYLESHEET: &str = "
+    table { display: table; border-spacing: 2px; }
+    thead, tbody, tfoot { display: table-row-group; }
+    tr { display: table-row; }
+    td, th { display: table-cell; padding: 1px; }
+    input, textarea, select, button {
+        border-width: 2px; border-color: #767676; padding: 1px 2px; background-color: #ffffff;
+    }
+    button, select, input[type=submit], input[type=reset], input[type=button] {
+        padding: 1px 6px; background-color: #efefef; border-radius: 3px;
+    }
+    input[type=checkbox], input[type=radio] {
+        border-width: 1px; padding: 0; margin: 3px 3px 3px 4px;
+    }
+    input[type=radio] { border-radius: 50%; }
+    input[type=hidden] { display: none; }
+    ul, ol, menu {
+        display: block; padding-left: 40px; counter-reset: list-item; list-style: disc;
+    }
+    ol { list-style: decimal; }
+    li { display: list-item; }
+    pre, listing, xmp { display: block; white-space: pre; }
+    textarea { white-space: pre-wrap; }
+    nobr { white-space: nowrap; }
+    center { display: block; text-align: center; }
+    u, ins, a[href] { text-decoration: underline; }
+    s, strike, del { text-decoration: line-through; }
+";
+
+/// Properties that take their parent's value when an element doesn't specify them.
+const INHERITED_PROPERTIES: &[&str] = &[
+    "color",
+    "font-family",
+    "font-size",
+    "line-height",
+    "list-style",
+    "list-style-position",
+    "list-style-type",
+    "letter-spacing",
+    "text-align",
+    // Decorations are drawn across the text of an element's descendants, which inheriting them
+    // approximates.
+    "text-decoration",
+    "text-indent",
+    "white-space",
+];
+
 pub struct StyledNode<'a> {
-    node: &'a Node,
+    /// The node this was styled from. For a pseudo-element, it is the element the
+    /// pseudo-element belongs to.
+    pub node: &'a Node,
     specified_values: PropertyMap,
+    /// The styles of the node's children, preceded by its `::marker` and `::before`
+    /// pseudo-elements, and followed by its `::after`, if it has them.
     pub children: Vec<StyledNode<'a>>,
+    pub pseudo_element: Option<PseudoElement>,
+    /// The text a pseudo-element generates, from its `content` or its list item's marker.
+    pub content: Option<String>,
+}
+
+/// How the border of one side of a box is drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BorderStyle {
+    None,
+    Solid,
+    Dashed,
+    Dotted,
+    Double,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Display {
     Inline,
     Block,
+    /// A block-level flex container.
+    Flex,
+    /// A block-level grid container.
+    Grid,
+    Table,
+    /// A group of table rows, such as `<tbody>`.
+    TableRowGroup,
+    TableRow,
+    TableCell,
+    /// A block with a marker, such as `<li>`.
+    ListItem,
     None,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Position {
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Float {
+    None,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Clear {
+    None,
+    Left,
+    Right,
+    Both,
+}
+
+/// Whether content that overflows a box along one axis is clipped. `scroll` and `auto` clip in
+/// the same way as `hidden`, since scrollable boxes are always painted at their initial scroll
+/// position.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Overflow {
+    Visible,
+    Hidden,
+    Scroll,
+    Auto,
+}
+
+/// Whether a page break is forced or avoided before, after or inside a box.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PageBreak {
+    Auto,
+    Always,
+    Avoid,
+}
+
+/// Whether a list item's marker is placed outside its box, to the left of its content, or
+/// inside it, before the first line of its content.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ListStylePosition {
+    Outside,
+    Inside,
+}
+
+/// How white space in text is handled: whether runs of it collapse to a single space, and
+/// whether lines may be broken at it to fit their container. Newlines break lines where white
+/// space is preserved.
+///
+/// http://www.w3.org/TR/css-text-3/#white-space-property
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WhiteSpace {
+    Normal,
+    Pre,
+    Nowrap,
+    PreWrap,
+}
+
+impl WhiteSpace {
+    /// Whether runs of white space, including newlines, collapse to a single space.
+    pub fn collapses(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::Nowrap)
+    }
+
+    /// Whether lines may be broken at white space.
+    pub fn wraps(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::PreWrap)
+    }
+}
+
+/// How the lines of a block's inline content are aligned between its left and right edges.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextAlign {
+    Left,
+    Right,
+    Center,
+    /// Spaces are stretched to fill every line but the last, and lines ended by a newline.
+    Justify,
+}
+
+pub enum BoxSizing {
+    ContentBox,
+    BorderBox,
+}
+
+/// An explanation of how a node's style was computed.
+#[derive(Debug)]
+pub struct StyleInspection<'a> {
+    /// The rules that match the node, from the lowest precedence to the highest.
+    pub matched_rules: Vec<MatchedRule<'a>>,
+    /// The node's properties and their values, sorted by name, each with the index in
+    /// `matched_rules` of the rule that set it, or `None` if it was inherited.
+    pub properties: Vec<(String, Value, Option<usize>)>,
+}
+
 impl<'a> StyledNode<'a> {
     pub fn value(&self, name: &str) -> Option<Value> {
         self.specified_values.get(name).cloned()
     }
 
+    /// The values of every property this node has a value for, whether from a rule that
+    /// matches it or inherited from its parent, sorted by name.
+    pub fn computed_values(&self) -> Vec<(&str, &Value)> {
+        let mut values: Vec<(&str, &Value)> = self
+            .specified_values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .collect();
+        values.sort_by_key(|&(name, _)| name);
+        values
+    }
+
+    /// Explain this node's style: the rules that match it, and which of them set each of its
+    /// values. `stylesheet` must be the one the style tree was built with.
+    pub fn inspect<'s>(&self, stylesheet: &'s Stylesheet) -> StyleInspection<'s> {
+        let matched_rules = match self.node.node_type {
+            NodeType::Element(ref elem) => matching_rules(elem, stylesheet, self.pseudo_element),
+            NodeType::Text(_) => Vec::new(),
+        };
+        let properties = self
+            .computed_values()
+            .into_iter()
+            .map(|(name, value)| {
+                let source = matched_rules.iter().rposition(|matched| {
+                    matched
+                        .rule
+                        .declarations
+                        .iter()
+                        .any(|declaration| declaration.name == name)
+                });
+                (name.to_string(), value.clone(), source)
+            })
+            .collect();
+        StyleInspection {
+            matched_rules,
+            properties,
+        }
+    }
+
+    /// This node and its descendants that match `selector`, in document order. A selector with
+    /// a pseudo-element, such as `li::marker`, selects those pseudo-elements.
+    pub fn select(&self, selector: &Selector) -> Vec<&StyledNode<'a>> {
+        let mut found = Vec::new();
+        self.select_into(selector, &mut found);
+        found
+    }
+
+    fn select_into<'b>(&'b self, selector: &Selector, found: &mut Vec<&'b StyledNode<'a>>) {
+        if let NodeType::Element(ref elem) = self.node.node_type {
+            if matches(elem, selector, self.pseudo_element) {
+                found.push(self);
+            }
+        }
+        for child in &self.children {
+            child.select_into(selector, found);
+        }
+    }
+
+    /// The value of `name`, or else of its shorthand `fallback_name`, or else `default`. For a
+    /// per-side property such as `margin-left`, the shorthand may give each side its own value.
     pub fn lookup(&self, name: &str, fallback_name: &str, default: &Value) -> Value {
-        self.value(name)
-            .unwrap_or_else(|| self.value(fallback_name).unwrap_or_else(|| default.clone()))
+        let side = ["top", "right", "bottom", "left"]
+            .into_iter()
+            .find(|side| name.ends_with(&format!("-{}", side)));
+        match side {
+            Some(side) => self.side_value(side, name, fallback_name),
+            None => self.value(name).or_else(|| self.value(fallback_name)),
+        }
+        .unwrap_or_else(|| default.clone())
     }
 
     pub fn display(&self) -> Display {
-        match self.value("display") {
+        let display = match self.value("display") {
             Some(Value::Keyword(s)) => match &*s {
                 "block" => Display::Block,
+                "flex" => Display::Flex,
+                "grid" => Display::Grid,
+                "table" => Display::Table,
+                "table-row-group" | "table-header-group" | "table-footer-group" => {
+                    Display::TableRowGroup
+                }
+                "table-row" => Display::TableRow,
+                "table-cell" => Display::TableCell,
+                "list-item" => Display::ListItem,
                 "none" => Display::None,
                 _ => Display::Inline,
             },
             _ => Display::Inline,
+        };
+
+        // Absolutely positioned and floated boxes are always block-level, and can't be internal
+        // table boxes.
+        let blockify = matches!(self.position(), Position::Absolute | Position::Fixed)
+            || self.float() != Float::None;
+        match display {
+            Display::Inline | Display::TableRowGroup | Display::TableRow | Display::TableCell
+                if blockify =>
+            {
+                Display::Block
+            }
+            display => display,
+        }
+    }
+
+    /// The `float` of this node. Absolutely positioned boxes can't float.
+    pub fn float(&self) -> Float {
+        if matches!(self.position(), Position::Absolute | Position::Fixed) {
+            return Float::None;
+        }
+        match self.value("float") {
+            Some(Value::Keyword(s)) => match &*s {
+                "left" => Float::Left,
+                "right" => Float::Right,
+                _ => Float::None,
+            },
+            _ => Float::None,
+        }
+    }
+
+    pub fn clear(&self) -> Clear {
+        match self.value("clear") {
+            Some(Value::Keyword(s)) => match &*s {
+                "left" => Clear::Left,
+                "right" => Clear::Right,
+                "both" => Clear::Both,
+                _ => Clear::None,
+            },
+            _ => Clear::None,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match self.value("position") {
+            Some(Value::Keyword(s)) => match &*s {
+                "relative" => Position::Relative,
+                "absolute" => Position::Absolute,
+                "fixed" => Position::Fixed,
+                _ => Position::Static,
+            },
+            _ => Position::Static,
+        }
+    }
+
+    /// The integer `z-index`, or `None` for `auto`.
+    pub fn z_index(&self) -> Option<i32> {
+        match self.value("z-index") {
+            Some(Value::Number(n)) => Some(n as i32),
+            _ => None,
+        }
+    }
+
+    /// The value of a per-side property such as `border-top-color`, falling back to its
+    /// shorthand. Like `margin`, a shorthand's one to four values apply to the top, right,
+    /// bottom and left sides.
+    pub fn side_value(&self, side: &str, name: &str, shorthand: &str) -> Option<Value> {
+        self.value(name).or_else(|| match self.value(shorthand)? {
+            Value::List(values) => {
+                let side = ["top", "right", "bottom", "left"]
+                    .iter()
+                    .position(|&s| s == side)?;
+                let index = match values.len() {
+                    1 => 0,
+                    2 => side % 2,
+                    3 if side == 3 => 1,
+                    _ => side,
+                };
+                values.get(index).cloned()
+            }
+            value => Some(value),
+        })
+    }
+
+    /// The `border-style` of one side. Borders are solid unless a style is specified.
+    pub fn border_style(&self, side: &str) -> BorderStyle {
+        let name = format!("border-{}-style", side);
+        match self.side_value(side, &name, "border-style") {
+            Some(Value::Keyword(s)) => match &*s {
+                "none" | "hidden" => BorderStyle::None,
+                "dashed" => BorderStyle::Dashed,
+                "dotted" => BorderStyle::Dotted,
+                "double" => BorderStyle::Double,
+                _ => BorderStyle::Solid,
+            },
+            _ => BorderStyle::Solid,
+        }
+    }
+
+    /// The used `border-width` of one side in px, which is zero if the side has no border.
+    pub fn border_width(&self, side: &str) -> f32 {
+        if self.border_style(side) == BorderStyle::None {
+            return 0.0;
+        }
+        let name = format!("border-{}-width", side);
+        self.side_value(side, &name, "border-width")
+            .map_or(0.0, |width| width.to_px())
+    }
+
+    /// The horizontal `overflow`, from `overflow-x` or the `overflow` shorthand.
+    pub fn overflow_x(&self) -> Overflow {
+        self.overflow("overflow-x", "overflow-y")
+    }
+
+    /// The vertical `overflow`, from `overflow-y` or the `overflow` shorthand.
+    pub fn overflow_y(&self) -> Overflow {
+        self.overflow("overflow-y", "overflow-x")
+    }
+
+    /// The `overflow` along one axis. A box can't clip along only one axis, so `visible`
+    /// becomes `auto` when the other axis isn't `visible`.
+    ///
+    /// http://www.w3.org/TR/css-overflow-3/#overflow-properties
+    fn overflow(&self, name: &str, other_name: &str) -> Overflow {
+        let specified = |name: &str| {
+            let shorthand = match self.value("overflow") {
+                Some(Value::List(values)) => {
+                    let index = if name == "overflow-x" { 0 } else { 1 };
+                    values.get(index).or(values.first()).cloned()
+                }
+                value => value,
+            };
+            match self.value(name).or(shorthand) {
+                Some(Value::Keyword(s)) => match &*s {
+                    "hidden" | "clip" => Overflow::Hidden,
+                    "scroll" => Overflow::Scroll,
+                    "auto" => Overflow::Auto,
+                    _ => Overflow::Visible,
+                },
+                _ => Overflow::Visible,
+            }
+        };
+        match (specified(name), specified(other_name)) {
+            (Overflow::Visible, Overflow::Visible) => Overflow::Visible,
+            (Overflow::Visible, _) => Overflow::Auto,
+            (overflow, _) => overflow,
+        }
+    }
+
+    /// The page break `before`, `after` or `inside` this box, from `page-break-before` and so
+    /// on, or the newer `break-before` properties.
+    ///
+    /// http://www.w3.org/TR/css-break-3/#break-between
+    pub fn page_break(&self, position: &str) -> PageBreak {
+        let value = self
+            .value(&format!("page-break-{}", position))
+            .or_else(|| self.value(&format!("break-{}", position)));
+        match value {
+            Some(Value::Keyword(s)) => match &*s {
+                "always" | "page" | "left" | "right" | "recto" | "verso" => PageBreak::Always,
+                "avoid" | "avoid-page" => PageBreak::Avoid,
+                _ => PageBreak::Auto,
+            },
+            _ => PageBreak::Auto,
+        }
+    }
+
+    /// The `list-style-type` of a list item's marker, from the longhand or the `list-style`
+    /// shorthand.
+    pub fn list_style_type(&self) -> ListStyleType {
+        let value = self.value("list-style-type").or_else(|| {
+            self.list_style_shorthand()
+                .into_iter()
+                .find(|value| ListStyleType::from_value(value).is_some())
+        });
+        value
+            .as_ref()
+            .and_then(ListStyleType::from_value)
+            .unwrap_or(ListStyleType::Disc)
+    }
+
+    pub fn list_style_position(&self) -> ListStylePosition {
+        let value = self.value("list-style-position").or_else(|| {
+            self.list_style_shorthand().into_iter().find(|value| {
+                matches!(value, Value::Keyword(keyword) if keyword == "inside" || keyword == "outside")
+            })
+        });
+        match value {
+            Some(Value::Keyword(s)) if s == "inside" => ListStylePosition::Inside,
+            _ => ListStylePosition::Outside,
+        }
+    }
+
+    fn list_style_shorthand(&self) -> Vec<Value> {
+        match self.value("list-style") {
+            Some(Value::List(values)) => values,
+            Some(value) => vec![value],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn white_space(&self) -> WhiteSpace {
+        match self.value("white-space") {
+            Some(Value::Keyword(s)) => match &*s {
+                "pre" => WhiteSpace::Pre,
+                "nowrap" => WhiteSpace::Nowrap,
+                "pre-wrap" => WhiteSpace::PreWrap,
+                _ => WhiteSpace::Normal,
+            },
+            _ => WhiteSpace::Normal,
+        }
+    }
+
+    /// The `text-align` of a block's lines. Text is left to right, so `start` is the left.
+    pub fn text_align(&self) -> TextAlign {
+        match self.value("text-align") {
+            Some(Value::Keyword(s)) => match &*s {
+                "right" | "end" => TextAlign::Right,
+                "center" => TextAlign::Center,
+                "justify" => TextAlign::Justify,
+                _ => TextAlign::Left,
+            },
+            _ => TextAlign::Left,
+        }
+    }
+
+    /// Which box `width`, `height` and their min/max limits refer to.
+    pub fn box_sizing(&self) -> BoxSizing {
+        match self.value("box-sizing") {
+            Some(Value::Keyword(s)) if s == "border-box" => BoxSizing::BorderBox,
+            _ => BoxSizing::ContentBox,
         }
     }
 }
 
-fn matches(elem: &ElementData, selector: &Selector) -> bool {
+/// Whether `selector` matches an element, or one of its pseudo-elements if `pseudo_element` is
+/// set.
+pub(crate) fn matches(
+    elem: &ElementData,
+    selector: &Selector,
+    pseudo_element: Option<PseudoElement>,
+) -> bool {
     match selector {
-        Selector::Simple(s) => matches_simple_selector(elem, s),
+        Selector::Simple(s) => {
+            s.pseudo_element == pseudo_element && matches_simple_selector(elem, s)
+        }
     }
 }
 
@@ -65,60 +565,396 @@ fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> boo
         return false;
     }
 
-    return true;
+    // check attribute selectors
+    if selector.attributes.iter().any(|attribute| {
+        match (elem.attrs.get(&attribute.name), &attribute.value) {
+            (Some(actual), Some(expected)) => actual != expected,
+            (actual, None) => actual.is_none(),
+            (None, Some(_)) => true,
+        }
+    }) {
+        return false;
+    }
+
+    true
+}
+
+/// A rule whose selectors match an element.
+#[derive(Debug)]
+pub struct MatchedRule<'a> {
+    pub rule: &'a Rule,
+    /// The most specific of the rule's selectors that match, which gives the rule's
+    /// specificity.
+    pub selector: &'a Selector,
+    /// Whether the rule is one of the user agent's default styles, rather than the author's.
+    pub user_agent: bool,
 }
 
-type MatchedRule<'a> = (Specificity, &'a Rule);
+impl MatchedRule<'_> {
+    pub fn specificity(&self) -> Specificity {
+        self.selector.specificity()
+    }
+}
 
-// If `rule` matches `elem`, return a `MatchedRule`. Otherwise return `None`.
-fn match_rule<'a>(elem: &ElementData, rule: &'a Rule) -> Option<MatchedRule<'a>> {
+// If `rule` matches `elem`, or its `pseudo_element`, return a `MatchedRule`. Otherwise return
+// `None`.
+fn match_rule<'a>(
+    elem: &ElementData,
+    rule: &'a Rule,
+    user_agent: bool,
+    pseudo_element: Option<PseudoElement>,
+) -> Option<MatchedRule<'a>> {
     rule.selectors
         .iter()
-        .find(|selector| matches(elem, selector))
-        .map(|selector| (selector.specificity(), rule))
+        .find(|selector| matches(elem, selector, pseudo_element))
+        .map(|selector| MatchedRule {
+            rule,
+            selector,
+            user_agent,
+        })
 }
 
-fn matching_rules<'a>(elem: &ElementData, stylesheet: &'a Stylesheet) -> Vec<MatchedRule<'a>> {
-    stylesheet
-        .rules
-        .iter()
-        .filter_map(|rule| match_rule(elem, rule))
-        .collect()
+/// The rules from the user agent stylesheet and `stylesheet` that match an element, or one of
+/// its pseudo-elements, in the order they are applied: from the lowest precedence to the
+/// highest.
+pub fn matching_rules<'a>(
+    elem: &ElementData,
+    stylesheet: &'a Stylesheet,
+    pseudo_element: Option<PseudoElement>,
+) -> Vec<MatchedRule<'a>> {
+    let mut matched = Vec::new();
+    // Author rules are applied after the user-agent rules, so that they take precedence.
+    for (stylesheet, user_agent) in [(user_agent_stylesheet(), true), (stylesheet, false)] {
+        let mut rules: Vec<MatchedRule> = stylesheet
+            .rules
+            .iter()
+            .filter_map(|rule| match_rule(elem, rule, user_agent, pseudo_element))
+            .collect();
+
+        // Go through the rules from lowest to highest specificity
+        rules.sort_by_key(|matched| matched.specificity());
+        matched.extend(rules);
+    }
+    matched
 }
 
-fn specified_values(elem: &ElementData, stylesheet: &Stylesheet) -> PropertyMap {
+fn specified_values(
+    elem: &ElementData,
+    stylesheet: &Stylesheet,
+    pseudo_element: Option<PseudoElement>,
+) -> PropertyMap {
     let mut values = HashMap::new();
-    let mut rules = matching_rules(elem, stylesheet);
-
-    // Go through the rules from lowest to highest specificity
-    rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
-    for (_, rule) in rules {
-        for declaration in &rule.declarations {
+    for matched in matching_rules(elem, stylesheet, pseudo_element) {
+        for declaration in &matched.rule.declarations {
             values.insert(declaration.name.clone(), declaration.value.clone());
         }
     }
     values
 }
 
+fn user_agent_stylesheet() -> &'static Stylesheet {
+    static STYLESHEET: OnceLock<Stylesheet> = OnceLock::new();
+    STYLESHEET.get_or_init(|| css::parse(USER_AGENT_STYLESHEET.to_string()))
+}
+
+/// Copy the inherited properties that `values` doesn't specify from the parent's values. A
+/// longhand such as `list-style-type` isn't inherited when `values` specifies its shorthand,
+/// which sets it too.
+fn inherit(values: &mut PropertyMap, parent: &PropertyMap) {
+    let shorthand_specified = |name: &str| {
+        INHERITED_PROPERTIES.iter().any(|&shorthand| {
+            values.contains_key(shorthand) && name.starts_with(&format!("{}-", shorthand))
+        })
+    };
+    let inherited: Vec<&str> = INHERITED_PROPERTIES
+        .iter()
+        .copied()
+        .filter(|name| !shorthand_specified(name))
+        .collect();
+    for name in inherited {
+        if let Some(value) = parent.get(name) {
+            values
+                .entry(name.to_string())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Custom properties such as `--accent-color`, which are always inherited, and whose values
+/// are substituted wherever `var(--accent-color)` appears.
+///
+/// http://www.w3.org/TR/css-variables-1/
+fn is_custom_property(name: &str) -> bool {
+    name.starts_with("--")
+}
+
+/// Copy the custom properties that `values` doesn't specify from the parent's values. The
+/// parent's values have already had their `var()` references substituted.
+fn inherit_custom_properties(values: &mut PropertyMap, parent: &PropertyMap) {
+    for (name, value) in parent {
+        if is_custom_property(name) {
+            values.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Replace each `var()` in `values` with the value of the custom property it names, or with its
+/// fallback if that property is missing or invalid. A property that still refers to a missing
+/// variable is removed, as if it had never been specified; so are custom properties that refer
+/// to each other in a cycle.
+fn substitute_variables(values: &mut PropertyMap) {
+    let mut resolver = VariableResolver {
+        specified: values.clone(),
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+    };
+    for (name, value) in resolver.specified.clone() {
+        let substituted = if is_custom_property(&name) {
+            resolver.custom_property(&name)
+        } else {
+            resolver.substitute(&value)
+        };
+        match substituted {
+            Some(substituted) => values.insert(name, substituted),
+            None => values.remove(&name),
+        };
+    }
+}
+
+struct VariableResolver {
+    specified: PropertyMap,
+    /// The substituted values of custom properties, or `None` for invalid ones.
+    resolved: HashMap<String, Option<Value>>,
+    /// The custom properties being substituted, each referred to by the one before it.
+    stack: Vec<String>,
+}
+
+impl VariableResolver {
+    fn custom_property(&mut self, name: &str) -> Option<Value> {
+        if let Some(resolved) = self.resolved.get(name) {
+            return resolved.clone();
+        }
+        if let Some(start) = self.stack.iter().position(|entry| entry == name) {
+            // Every property in the cycle is invalid, even if it has a fallback.
+            for entry in &self.stack[start..] {
+                self.resolved.insert(entry.clone(), None);
+            }
+            return None;
+        }
+        let value = self.specified.get(name)?.clone();
+        self.stack.push(name.to_string());
+        let substituted = self.substitute(&value);
+        self.stack.pop();
+        self.resolved
+            .entry(name.to_string())
+            .or_insert(substituted)
+            .clone()
+    }
+
+    fn substitute(&mut self, value: &Value) -> Option<Value> {
+        match *value {
+            Value::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("var") => {
+                let Some(Value::Keyword(variable)) = arguments.first() else {
+                    return None;
+                };
+                if !is_custom_property(variable) {
+                    return None;
+                }
+                match self.custom_property(variable) {
+                    Some(value) => Some(value),
+                    None => self.substitute(arguments.get(1)?),
+                }
+            }
+            Value::Function(ref name, ref arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.substitute(argument))
+                    .collect::<Option<_>>()?;
+                Some(Value::Function(name.clone(), arguments))
+            }
+            Value::List(ref values) => {
+                // A variable may stand for several space-separated values.
+                let mut substituted = Vec::new();
+                for value in values {
+                    match self.substitute(value)? {
+                        Value::List(values) => substituted.extend(values),
+                        value => substituted.push(value),
+                    }
+                }
+                if substituted.len() == 1 {
+                    substituted.pop()
+                } else {
+                    Some(Value::List(substituted))
+                }
+            }
+            ref value => Some(value.clone()),
+        }
+    }
+}
+
 pub fn style_tree<'a>(root: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
-    StyledNode {
-        node: root,
-        specified_values: match root.node_type {
-            NodeType::Element(ref elem) => specified_values(elem, stylesheet),
-            NodeType::Text(_) => HashMap::new(),
-        },
-        children: root
+    styled_node(root, stylesheet, &HashMap::new(), &mut Counters::default())
+}
+
+/// Style a node and its descendants, and generate their pseudo-elements. `counters` holds the
+/// counters in scope before the node.
+fn styled_node<'a>(
+    node: &'a Node,
+    stylesheet: &'a Stylesheet,
+    parent_values: &PropertyMap,
+    counters: &mut Counters,
+) -> StyledNode<'a> {
+    let values = match node.node_type {
+        NodeType::Element(ref elem) => specified_values(elem, stylesheet, None),
+        NodeType::Text(_) => HashMap::new(),
+    };
+    let mut styled = StyledNode {
+        node,
+        specified_values: computed_values(values, parent_values),
+        children: Vec::new(),
+        pseudo_element: None,
+        content: None,
+    };
+    let NodeType::Element(ref elem) = node.node_type else {
+        return styled;
+    };
+    // Nothing inside an element that isn't rendered is counted.
+    if styled.display() == Display::None {
+        let mut counters = Counters::default();
+        styled.children = node
             .children
             .iter()
-            .map(|child| style_tree(child, stylesheet))
-            .collect(),
+            .map(|child| styled_node(child, stylesheet, &styled.specified_values, &mut counters))
+            .collect();
+        return styled;
+    }
+
+    update_counters(&styled, counters);
+    let entered = counters.enter();
+    let values = &styled.specified_values;
+    let mut children = Vec::new();
+    if styled.display() == Display::ListItem {
+        children.extend(pseudo_element(
+            node,
+            elem,
+            PseudoElement::Marker,
+            stylesheet,
+            values,
+            counters,
+        ));
+    }
+    children.extend(pseudo_element(
+        node,
+        elem,
+        PseudoElement::Before,
+        stylesheet,
+        values,
+        counters,
+    ));
+    for child in &node.children {
+        children.push(styled_node(child, stylesheet, values, counters));
+    }
+    children.extend(pseudo_element(
+        node,
+        elem,
+        PseudoElement::After,
+        stylesheet,
+        values,
+        counters,
+    ));
+    counters.leave(entered);
+    styled.children = children;
+    styled
+}
+
+/// Style one of an element's pseudo-elements, if it generates a box: a `::before` or `::after`
+/// whose `content` isn't `none` or `normal`, or the marker of a list item whose
+/// `list-style-type` isn't `none`.
+///
+/// http://www.w3.org/TR/css-pseudo-4/#generated-content
+fn pseudo_element<'a>(
+    node: &'a Node,
+    elem: &ElementData,
+    pseudo_element: PseudoElement,
+    stylesheet: &Stylesheet,
+    element_values: &PropertyMap,
+    counters: &mut Counters,
+) -> Option<StyledNode<'a>> {
+    let values = specified_values(elem, stylesheet, Some(pseudo_element));
+    let mut styled = StyledNode {
+        node,
+        specified_values: computed_values(values, element_values),
+        children: Vec::new(),
+        pseudo_element: Some(pseudo_element),
+        content: None,
+    };
+    if styled.display() == Display::None {
+        return None;
     }
+    let content = styled.value("content");
+    styled.content = match pseudo_element {
+        PseudoElement::Before | PseudoElement::After => {
+            // Check that the content generates a box before counting it.
+            let content = content?;
+            counters.generated_text(&content, &elem.attrs)?;
+            update_counters(&styled, counters);
+            counters.generated_text(&content, &elem.attrs)
+        }
+        // A marker shows its list item's number, unless it has content of its own.
+        PseudoElement::Marker => {
+            match content.and_then(|c| counters.generated_text(&c, &elem.attrs)) {
+                Some(text) => Some(text),
+                None => styled
+                    .list_style_type()
+                    .marker_text(counters.value("list-item")),
+            }
+        }
+    };
+    styled.content.is_some().then_some(styled)
+}
+
+/// Finish the values of a node from the values its rules specify and its parent's values:
+/// substitute variables, and inherit what it doesn't specify.
+fn computed_values(mut values: PropertyMap, parent_values: &PropertyMap) -> PropertyMap {
+    inherit_custom_properties(&mut values, parent_values);
+    substitute_variables(&mut values);
+    inherit(&mut values, parent_values);
+    values
+}
+
+/// Apply the `counter-reset`, `counter-increment` and `counter-set` of an element or
+/// pseudo-element, along with the counting that lists do: each list item increments the
+/// `list-item` counter unless its `counter-increment` says otherwise, the `value` attribute of
+/// an `<li>` sets it, and the `start` attribute of an `<ol>` gives the number that its first
+/// item gets.
+///
+/// http://www.w3.org/TR/css-lists-3/#list-item-counter
+fn update_counters(styled: &StyledNode, counters: &mut Counters) {
+    let mut reset = counter_list(styled.value("counter-reset"), 0);
+    let mut increment = counter_list(styled.value("counter-increment"), 1);
+    let mut set = counter_list(styled.value("counter-set"), 0);
+    let list_item = styled.display() == Display::ListItem;
+    if list_item && !increment.iter().any(|(name, _)| name == "list-item") {
+        increment.push(("list-item".to_string(), 1));
+    }
+    if let (None, NodeType::Element(ref elem)) = (styled.pseudo_element, &styled.node.node_type) {
+        let integer = |name: &str| elem.attrs.get(name)?.trim().parse::<i32>().ok();
+        if let Some(start) = integer("start").filter(|_| elem.tag_name.eq_ignore_ascii_case("ol")) {
+            for counter in reset.iter_mut().filter(|(name, _)| name == "list-item") {
+                counter.1 = start.saturating_sub(1);
+            }
+        }
+        if let Some(value) = integer("value").filter(|_| list_item) {
+            set.push(("list-item".to_string(), value));
+        }
+    }
+    counters.update(&reset, &increment, &set);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::css::Declaration;
+    use crate::css::{Declaration, Unit};
     use crate::dom::{elem, text};
 
     #[test]
@@ -131,13 +967,18 @@ mod tests {
             tag_name: Some("div".to_string()),
             id: None,
             class: vec![],
+            attributes: vec![],
+            pseudo_element: None,
         };
         assert!(matches_simple_selector(&element, &selector));
     }
 
     #[test]
     fn test_specified_values() {
-        let mut stylesheet = Stylesheet { rules: vec![] };
+        let mut stylesheet = Stylesheet {
+            rules: vec![],
+            page: vec![],
+        };
         let declarations = vec![
             Declaration {
                 name: "color".to_string(),
@@ -153,15 +994,18 @@ mod tests {
                 tag_name: Some("p".to_string()),
                 id: None,
                 class: vec![],
+                attributes: vec![],
+                pseudo_element: None,
             })],
             declarations,
+            location: Default::default(),
         };
         stylesheet.rules.push(rule);
         let element = ElementData {
             tag_name: "p".to_string(),
             attrs: HashMap::new(),
         };
-        let values = specified_values(&element, &stylesheet);
+        let values = specified_values(&element, &stylesheet, None);
         assert_eq!(
             values.get("color"),
             Some(&Value::Keyword("red".to_string()))
@@ -172,9 +1016,169 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_inherited_properties() {
+        let stylesheet = crate::css::parse(
+            "div { color: #ff0000; font-size: 20px; width: 100px; } span { font-size: 10px; }"
+                .to_string(),
+        );
+        let root = crate::html::parse("<div><span>Hi</span></div>".to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let span = &styled.children[0];
+        assert_eq!(span.value("font-size"), Some(Value::Length(10.0, Unit::Px)));
+        assert_eq!(span.value("width"), None);
+        let text = &span.children[0];
+        assert_eq!(text.value("color"), styled.value("color"));
+        assert_eq!(text.value("font-size"), Some(Value::Length(10.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_custom_properties() {
+        let stylesheet = crate::css::parse(
+            "div { --gap: 10px; --accent: #ff0000; --a: var(--b); --b: var(--a, 1px); } \
+             p { --gap: var(--unit) 20px; --unit: 5px; color: var(--accent); \
+                 margin: var(--gap); padding: var(--missing, var(--unit)); \
+                 width: calc(var(--unit) * 2); height: var(--a, 3px); border-width: var(--b); }"
+                .to_string(),
+        );
+        let root = crate::html::parse("<div><p></p></div>".to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let p = &styled.children[0];
+        let px = |size| Value::Length(size, Unit::Px);
+        // Custom properties inherit, and may be overridden by the child.
+        assert_eq!(p.value("--accent"), styled.value("--accent"));
+        assert_eq!(p.value("--gap"), Some(Value::List(vec![px(5.0), px(20.0)])));
+        assert_eq!(p.value("color"), styled.value("--accent"));
+        assert_eq!(
+            p.value("margin"),
+            Some(Value::List(vec![px(5.0), px(20.0)]))
+        );
+        assert_eq!(p.value("padding"), Some(px(5.0)));
+        assert_eq!(p.value("width").map(|width| width.to_px()), Some(10.0));
+        // Properties in a cycle are invalid, even with a fallback, and are never inherited.
+        assert_eq!(styled.value("--a"), None);
+        assert_eq!(styled.value("--b"), None);
+        assert_eq!(p.value("height"), Some(px(3.0)));
+        assert_eq!(p.value("border-width"), None);
+    }
+
+    #[test]
+    fn test_author_rules_override_user_agent_stylesheet() {
+        let stylesheet = crate::css::parse("* { padding: 0px; }".to_string());
+        let root = crate::html::parse("<table><tr><td></td></tr></table>".to_string());
+        let styled = style_tree(&root, &stylesheet);
+        assert_eq!(styled.display(), Display::Table);
+        let cell = &styled.children[0].children[0];
+        assert_eq!(cell.display(), Display::TableCell);
+        assert_eq!(cell.value("padding"), Some(Value::Length(0.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_attribute_selectors_and_form_controls() {
+        let stylesheet = crate::css::parse(
+            "[disabled] { color: #808080; } input[type=\"text\"] { padding: 4px; }".to_string(),
+        );
+        let root = crate::html::parse(
+            "<form><input type=text disabled><input type=checkbox><input type=hidden></form>"
+                .to_string(),
+        );
+        let styled = style_tree(&root, &stylesheet);
+        let [text, checkbox, hidden] = &styled.children[..] else {
+            panic!("expected three inputs");
+        };
+        let px = |value| Value::Length(value, Unit::Px);
+        let zero = px(0.0);
+        assert!(text.value("color").is_some());
+        assert_eq!(checkbox.value("color"), None);
+        // The author's shorthand overrides the user agent's, and applies to every side.
+        assert_eq!(text.lookup("padding-left", "padding", &zero), px(4.0));
+        // A shorthand with several values gives each side its own.
+        assert_eq!(checkbox.lookup("margin-left", "margin", &zero), px(4.0));
+        assert_eq!(checkbox.lookup("margin-right", "margin", &zero), px(3.0));
+        assert_eq!(hidden.display(), Display::None);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let stylesheet = crate::css::parse(
+            "p { color: #0000ff; display: block; }\n\
+             .note { color: #ff0000; }\n\
+             div { font-size: 20px; }"
+                .to_string(),
+        );
+        let root = crate::html::parse("<div><p class=\"note\"></p></div>".to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let selector = crate::css::parse_selector("p.note".to_string()).unwrap();
+        let found = styled.select(&selector);
+        assert_eq!(found.len(), 1);
+
+        let inspection = found[0].inspect(&stylesheet);
+        let rules: Vec<(String, Specificity, String)> = inspection
+            .matched_rules
+            .iter()
+            .map(|matched| {
+                (
+                    matched.selector.to_string(),
+                    matched.specificity(),
+                    matched.rule.location.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("p".to_string(), (0, 0, 1), "1:1".to_string()),
+                (".note".to_string(), (0, 1, 0), "2:1".to_string()),
+            ]
+        );
+        let properties: Vec<(&str, String, Option<usize>)> = inspection
+            .properties
+            .iter()
+            .map(|(name, value, source)| (name.as_str(), value.to_string(), *source))
+            .collect();
+        assert_eq!(
+            properties,
+            vec![
+                ("color", "#ff0000".to_string(), Some(1)),
+                ("display", "block".to_string(), Some(0)),
+                ("font-size", "20px".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overflow_axes() {
+        let stylesheet = crate::css::parse(
+            ".a { overflow: hidden; } .b { overflow: visible scroll; } .c { overflow-x: auto; }"
+                .to_string(),
+        );
+        let root = crate::html::parse(
+            "<div><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p><p></p></div>"
+                .to_string(),
+        );
+        let styled = style_tree(&root, &stylesheet);
+        let axes: Vec<_> = styled
+            .children
+            .iter()
+            .map(|node| (node.overflow_x(), node.overflow_y()))
+            .collect();
+        assert_eq!(
+            axes,
+            vec![
+                (Overflow::Hidden, Overflow::Hidden),
+                (Overflow::Auto, Overflow::Scroll),
+                (Overflow::Auto, Overflow::Auto),
+                (Overflow::Visible, Overflow::Visible),
+            ]
+        );
+    }
+
     #[test]
     fn test_style_tree() {
-        let mut stylesheet = Stylesheet { rules: vec![] };
+        let mut stylesheet = Stylesheet {
+            rules: vec![],
+            page: vec![],
+        };
         let declarations = vec![Declaration {
             name: "display".to_string(),
             value: Value::Keyword("block".to_string()),
@@ -184,8 +1188,11 @@ mod tests {
                 tag_name: Some("div".to_string()),
                 id: None,
                 class: vec![],
+                attributes: vec![],
+                pseudo_element: None,
             })],
             declarations,
+            location: Default::default(),
         };
         stylesheet.rules.push(rule);
         let root = elem(
@@ -199,4 +1206,110 @@ mod tests {
             Some(Value::Keyword("block".to_string()))
         );
     }
+
+    #[test]
+    fn test_counters_and_generated_content() {
+        let stylesheet = crate::css::parse(
+            "body { counter-reset: section; } h2 { counter-increment: section; }\n\
+             h2::before { content: \"\u{a7}\" counter(section, upper-roman) \" \"; color: #ff0000; }\n\
+             h2::after { content: none; } p::after { content: \"\"; display: none; }\n\
+             ul { list-style: square inside; } .plain { list-style-type: none; }\n\
+             .alpha { list-style-type: lower-alpha; } li::marker { content: normal; }"
+                .to_string(),
+        );
+        let root = crate::html::parse(
+            "<body><h2>A</h2><ul><li>x</li><li class=\"plain\">y</li></ul><h2>B</h2>\
+             <ol start=\"3\"><li>p<ol class=\"alpha\"><li>q</li></ol></li><li value=\"9\">r</li>\
+             <li>s</li></ol><p></p></body>"
+                .to_string(),
+        );
+        let styled = style_tree(&root, &stylesheet);
+
+        fn collect(node: &StyledNode, found: &mut Vec<(&'static str, String)>) {
+            if let (Some(pseudo_element), Some(content)) = (node.pseudo_element, &node.content) {
+                found.push((pseudo_element.name(), content.clone()));
+            }
+            for child in &node.children {
+                collect(child, found);
+            }
+        }
+        let mut generated = Vec::new();
+        collect(&styled, &mut generated);
+        let expected = [
+            ("before", "\u{a7}I "),
+            ("marker", "\u{25aa} "),
+            ("before", "\u{a7}II "),
+            ("marker", "3. "),
+            ("marker", "a. "),
+            ("marker", "9. "),
+            ("marker", "10. "),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(name, content)| (name, content.to_string()))
+            .collect();
+        assert_eq!(generated, expected);
+
+        // Pseudo-elements inherit from their element, and can be selected.
+        let selector = crate::css::parse_selector("h2::before".to_string()).unwrap();
+        let before = styled.select(&selector);
+        assert_eq!(before.len(), 2);
+        assert_eq!(
+            before[0].value("color"),
+            Some(Value::ColorValue(crate::css::Color {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 255
+            }))
+        );
+        let marker = crate::css::parse_selector("li::marker".to_string()).unwrap();
+        let markers = styled.select(&marker);
+        assert_eq!(markers[0].list_style_position(), ListStylePosition::Inside);
+        assert_eq!(markers[1].list_style_position(), ListStylePosition::Outside);
+        assert_eq!(markers[1].list_style_type(), ListStyleType::Decimal);
+    }
+
+    #[test]
+    fn test_generated_content_from_attributes() {
+        let stylesheet = crate::css::parse(
+            "a:after { content: \" (\" attr(href) \")\"; }\n\
+             .icon::before { content: \"[\" attr(data-icon) \"] \"; }\n\
+             a::before { content: \"link: \"; }\n\
+             a.external::before { content: \"external: \"; }"
+                .to_string(),
+        );
+        let root = crate::html::parse(
+            "<p><a href=\"/x\" class=\"external\">x</a><a>y</a><span class=\"icon\">z</span></p>"
+                .to_string(),
+        );
+        let styled = style_tree(&root, &stylesheet);
+        let generated: Vec<Vec<_>> = styled
+            .children
+            .iter()
+            .map(|child| {
+                child
+                    .children
+                    .iter()
+                    .filter_map(|node| Some((node.pseudo_element?.name(), node.content.clone()?)))
+                    .collect()
+            })
+            .collect();
+        // The more specific rule wins for the first link, and a missing attribute generates
+        // an empty string.
+        assert_eq!(
+            generated,
+            [
+                vec![
+                    ("before", "external: ".to_string()),
+                    ("after", " (/x)".to_string())
+                ],
+                vec![
+                    ("before", "link: ".to_string()),
+                    ("after", " ()".to_string())
+                ],
+                vec![("before", "[] ".to_string())],
+            ]
+        );
+    }
 }
@@ -0,0 +1,373 @@
+//! CSS counters, which number list items and anything else a stylesheet counts, and the text
+//! that `content` and list markers generate from them.
+//!
+//! http://www.w3.org/TR/css-lists-3/#auto-numbering
+
+use crate::css::Value;
+use crate::dom::AttrMap;
+
+/// How a counter's value is written, as given by `list-style-type` or the second argument of
+/// `counter()`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListStyleType {
+    None,
+    Disc,
+    Circle,
+    Square,
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    /// A string written in place of the value, such as `"-"`.
+    String(String),
+}
+
+impl ListStyleType {
+    /// The style given by a keyword or string, or `None` if `value` isn't one.
+    pub fn from_value(value: &Value) -> Option<ListStyleType> {
+        match *value {
+            Value::Keyword(ref keyword) => Some(match &*keyword.to_ascii_lowercase() {
+                "none" => ListStyleType::None,
+                "disc" => ListStyleType::Disc,
+                "circle" => ListStyleType::Circle,
+                "square" => ListStyleType::Square,
+                "decimal" => ListStyleType::Decimal,
+                "lower-alpha" | "lower-latin" => ListStyleType::LowerAlpha,
+                "upper-alpha" | "upper-latin" => ListStyleType::UpperAlpha,
+                "lower-roman" => ListStyleType::LowerRoman,
+                "upper-roman" => ListStyleType::UpperRoman,
+                _ => return None,
+            }),
+            Value::String(ref string) => Some(ListStyleType::String(string.clone())),
+            _ => None,
+        }
+    }
+
+    /// Whether markers of this style are bullets, which don't depend on the counter's value.
+    pub fn is_bullet(&self) -> bool {
+        matches!(
+            self,
+            ListStyleType::Disc | ListStyleType::Circle | ListStyleType::Square
+        )
+    }
+
+    /// Write a counter's value in this style. Values that an alphabetic or roman style can't
+    /// write, such as zero, are written as decimal numbers.
+    ///
+    /// http://www.w3.org/TR/css-counter-styles-3/#predefined-counters
+    pub fn format(&self, value: i32) -> String {
+        match *self {
+            ListStyleType::None => String::new(),
+            ListStyleType::Disc => "\u{2022}".to_string(),
+            ListStyleType::Circle => "\u{25e6}".to_string(),
+            ListStyleType::Square => "\u{25aa}".to_string(),
+            ListStyleType::LowerAlpha if value > 0 => alphabetic(value, b'a'),
+            ListStyleType::UpperAlpha if value > 0 => alphabetic(value, b'A'),
+            ListStyleType::LowerRoman if (1..4000).contains(&value) => roman(value).to_lowercase(),
+            ListStyleType::UpperRoman if (1..4000).contains(&value) => roman(value),
+            ListStyleType::String(ref string) => string.clone(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// The text of a list item's marker: a bullet or a string as it is, or a number followed by
+    /// a period, and then a space before the item's content.
+    pub fn marker_text(&self, value: i32) -> Option<String> {
+        match *self {
+            ListStyleType::None => None,
+            ListStyleType::String(ref string) => Some(string.clone()),
+            ref style if style.is_bullet() => Some(format!("{} ", style.format(value))),
+            ref style => Some(format!("{}. ", style.format(value))),
+        }
+    }
+}
+
+/// `a` to `z`, then `aa`, `ab` and so on.
+fn alphabetic(value: i32, first: u8) -> String {
+    let mut letters = Vec::new();
+    let mut value = value;
+    while value > 0 {
+        value -= 1;
+        letters.push((first + (value % 26) as u8) as char);
+        value /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn roman(value: i32) -> String {
+    const NUMERALS: [(i32, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut numeral = String::new();
+    let mut value = value;
+    for (size, digits) in NUMERALS {
+        while value >= size {
+            numeral.push_str(digits);
+            value -= size;
+        }
+    }
+    numeral
+}
+
+/// The names and values in a `counter-reset`, `counter-increment` or `counter-set` value, such
+/// as `chapter section 2`. A name without a value gets `default`.
+pub fn counter_list(value: Option<Value>, default: i32) -> Vec<(String, i32)> {
+    let values = match value {
+        Some(Value::List(values)) => values,
+        Some(value) => vec![value],
+        None => return Vec::new(),
+    };
+    let mut counters: Vec<(String, i32)> = Vec::new();
+    for value in values {
+        match value {
+            Value::Keyword(name) if name.eq_ignore_ascii_case("none") => {}
+            Value::Keyword(name) => counters.push((name, default)),
+            Value::Number(number) if number.fract() == 0.0 => {
+                if let Some(counter) = counters.last_mut() {
+                    counter.1 = number as i32;
+                }
+            }
+            _ => return Vec::new(),
+        }
+    }
+    counters
+}
+
+/// The counters in scope at one point in the document, as the style tree is built in document
+/// order.
+///
+/// A counter created by an element is in scope for the element's descendants and its
+/// following siblings, so the counters created by an element's children go out of scope when
+/// the element ends.
+///
+/// http://www.w3.org/TR/css-lists-3/#counters-without-boxes
+#[derive(Default)]
+pub(super) struct Counters {
+    /// Each counter's name, value, and the depth of the element that created it, from the
+    /// outermost to the innermost.
+    counters: Vec<(String, i32, usize)>,
+    depth: usize,
+}
+
+impl Counters {
+    /// Apply an element's `counter-reset`, `counter-increment` and `counter-set`, in that order.
+    pub fn update(
+        &mut self,
+        reset: &[(String, i32)],
+        increment: &[(String, i32)],
+        set: &[(String, i32)],
+    ) {
+        for (name, value) in reset {
+            // A counter created by an earlier sibling is replaced rather than nested.
+            match self.innermost(name) {
+                Some(index) if self.counters[index].2 == self.depth => {
+                    self.counters[index].1 = *value
+                }
+                _ => self.counters.push((name.clone(), *value, self.depth)),
+            }
+        }
+        for (name, value) in increment {
+            let index = self.instantiate(name);
+            let counter = &mut self.counters[index].1;
+            *counter = counter.saturating_add(*value);
+        }
+        for (name, value) in set {
+            let index = self.instantiate(name);
+            self.counters[index].1 = *value;
+        }
+    }
+
+    /// Start on the children of the element that was last updated. Returns what to pass to
+    /// `leave` once they are done.
+    pub fn enter(&mut self) -> usize {
+        self.depth += 1;
+        self.counters.len()
+    }
+
+    /// Finish the children of an element, ending the scope of the counters they created.
+    pub fn leave(&mut self, entered: usize) {
+        self.depth -= 1;
+        self.counters.truncate(entered);
+    }
+
+    /// The value of the innermost counter with a name, or zero if there isn't one.
+    pub fn value(&self, name: &str) -> i32 {
+        self.innermost(name)
+            .map_or(0, |index| self.counters[index].1)
+    }
+
+    /// The values of all the counters with a name, from the outermost to the innermost.
+    fn values(&self, name: &str) -> Vec<i32> {
+        let values: Vec<i32> = self
+            .counters
+            .iter()
+            .filter(|counter| counter.0 == name)
+            .map(|counter| counter.1)
+            .collect();
+        if values.is_empty() {
+            vec![0]
+        } else {
+            values
+        }
+    }
+
+    fn innermost(&self, name: &str) -> Option<usize> {
+        self.counters.iter().rposition(|counter| counter.0 == name)
+    }
+
+    /// The innermost counter with a name, which is created with the value zero if there isn't
+    /// one.
+    fn instantiate(&mut self, name: &str) -> usize {
+        self.innermost(name).unwrap_or_else(|| {
+            self.counters.push((name.to_string(), 0, self.depth));
+            self.counters.len() - 1
+        })
+    }
+
+    /// The text generated by a `content` value, such as `"Chapter " counter(chapter)`, or
+    /// `None` for `none` and `normal`, which generate no box. `attrs` are the attributes of
+    /// the element the content is generated for, which `attr()` reads.
+    ///
+    /// http://www.w3.org/TR/css-content-3/#content-property
+    pub fn generated_text(&self, content: &Value, attrs: &AttrMap) -> Option<String> {
+        let values = match *content {
+            Value::Keyword(ref keyword)
+                if keyword.eq_ignore_ascii_case("none")
+                    || keyword.eq_ignore_ascii_case("normal") =>
+            {
+                return None
+            }
+            Value::List(ref values) => values.as_slice(),
+            ref value => std::slice::from_ref(value),
+        };
+        let mut text = String::new();
+        for value in values {
+            text.push_str(&self.content_item(value, attrs)?);
+        }
+        Some(text)
+    }
+
+    /// The text of one item in a `content` value: a string, `attr(name)`, `counter(name, style)`
+    /// or `counters(name, separator, style)`. Anything else makes the whole value invalid.
+    fn content_item(&self, value: &Value, attrs: &AttrMap) -> Option<String> {
+        let style = |argument: Option<&Value>| match argument {
+            Some(value) => ListStyleType::from_value(value),
+            None => Some(ListStyleType::Decimal),
+        };
+        match *value {
+            Value::String(ref string) => Some(string.clone()),
+            // A missing attribute generates an empty string.
+            Value::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("attr") => {
+                let [Value::Keyword(ref attribute)] = arguments[..] else {
+                    return None;
+                };
+                Some(attrs.get(attribute).cloned().unwrap_or_default())
+            }
+            Value::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("counter") => {
+                let [Value::Keyword(ref counter), ref rest @ ..] = arguments[..] else {
+                    return None;
+                };
+                Some(style(rest.first())?.format(self.value(counter)))
+            }
+            Value::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("counters") => {
+                let [Value::Keyword(ref counter), Value::String(ref separator), ref rest @ ..] =
+                    arguments[..]
+                else {
+                    return None;
+                };
+                let style = style(rest.first())?;
+                let values: Vec<String> = self
+                    .values(counter)
+                    .into_iter()
+                    .map(|value| style.format(value))
+                    .collect();
+                Some(values.join(separator))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        let formats: Vec<String> = [
+            (ListStyleType::Decimal, 7),
+            (ListStyleType::LowerAlpha, 28),
+            (ListStyleType::UpperAlpha, 0),
+            (ListStyleType::UpperRoman, 1994),
+            (ListStyleType::LowerRoman, 4),
+            (ListStyleType::Square, 3),
+        ]
+        .iter()
+        .map(|(style, value)| style.format(*value))
+        .collect();
+        assert_eq!(formats, ["7", "ab", "0", "MCMXCIV", "iv", "\u{25aa}"]);
+        assert_eq!(
+            ListStyleType::Decimal.marker_text(3),
+            Some("3. ".to_string())
+        );
+        assert_eq!(
+            ListStyleType::Disc.marker_text(3),
+            Some("\u{2022} ".to_string())
+        );
+        assert_eq!(ListStyleType::None.marker_text(3), None);
+    }
+
+    #[test]
+    fn test_counter_scopes() {
+        let mut counters = Counters::default();
+        let reset = counter_list(Some(Value::Keyword("item".to_string())), 0);
+        let increment = counter_list(Some(Value::Keyword("item".to_string())), 1);
+        counters.update(&reset, &[], &[]);
+        let outer = counters.enter();
+        counters.update(&[], &increment, &[]);
+        counters.update(&reset, &increment, &[]);
+        let inner = counters.enter();
+        counters.update(&reset, &increment, &[]);
+        counters.update(&[], &increment, &[]);
+        assert_eq!(counters.values("item"), [1, 1, 2]);
+        counters.leave(inner);
+        assert_eq!(counters.values("item"), [1, 1]);
+        // A sibling's reset replaces the counter rather than nesting inside it.
+        counters.update(&reset, &[], &[]);
+        assert_eq!(counters.values("item"), [1, 0]);
+        counters.leave(outer);
+        assert_eq!(counters.values("item"), [1]);
+
+        let content = crate::css::parse(
+            "a { content: \"§\" counters(item, \".\", upper-roman) counter(missing) attr(title) \
+             attr(missing); }"
+                .to_string(),
+        );
+        let content = &content.rules[0].declarations[0].value;
+        let attrs = AttrMap::from([("title".to_string(), " Intro".to_string())]);
+        counters.update(&[], &[], &[("item".to_string(), 3)]);
+        assert_eq!(
+            counters.generated_text(content, &attrs),
+            Some("§III0 Intro".to_string())
+        );
+        assert_eq!(
+            counters.generated_text(&Value::Keyword("none".to_string()), &attrs),
+            None
+        );
+        let invalid = Value::Function("attr".to_string(), Vec::new());
+        assert_eq!(counters.generated_text(&invalid, &attrs), None);
+    }
+}
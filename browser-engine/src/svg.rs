@@ -0,0 +1,651 @@
+//! A backend that writes the display list as an SVG document, for output that is independent of
+//! resolution and can be compared as text.
+//!
+//! http://www.w3.org/TR/SVG11/
+
+use std::fmt::Write;
+
+use crate::css::Color;
+use crate::image::{encode_png, Image, ImageCache};
+use crate::layout::{EdgeSize, LayoutBox, Rect};
+use crate::painting::{
+    build_display_list, Background, BackgroundImage, Border, BorderSide, ColorStop, CornerRadii,
+    DisplayCommand, DisplayList, Radius, Text,
+};
+use crate::style::BorderStyle;
+
+/// Paint a tree of LayoutBoxes to an SVG document showing the area `bounds` of the page, like
+/// `painting::paint`.
+pub fn paint(layout_root: &LayoutBox, bounds: Rect, images: &mut ImageCache) -> String {
+    let display_list = build_display_list(layout_root, images);
+    to_svg(&display_list, bounds)
+}
+
+/// Write a display list as an SVG document showing the area `bounds` of the page, on a white
+/// background.
+pub fn to_svg(display_list: &DisplayList, bounds: Rect) -> String {
+    let mut writer = SvgWriter {
+        out: String::new(),
+        next_id: 0,
+        scroll: (bounds.x, bounds.y),
+    };
+    writeln!(
+        writer.out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        number(bounds.width),
+        number(bounds.height),
+        number(bounds.x),
+        number(bounds.y),
+        number(bounds.width),
+        number(bounds.height)
+    )
+    .unwrap();
+    writeln!(
+        writer.out,
+        r##"<rect {} fill="#ffffff"/>"##,
+        rect_attributes(bounds)
+    )
+    .unwrap();
+    writer.write_list(display_list);
+    writer.out.push_str("</svg>\n");
+    writer.out
+}
+
+struct SvgWriter {
+    out: String,
+    /// The next number used to name the clip paths, gradients and patterns that are referred
+    /// to by ID.
+    next_id: usize,
+    /// The point of the page at the top left of the image, where fixed boxes are drawn.
+    scroll: (f32, f32),
+}
+
+impl SvgWriter {
+    fn id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    fn write_list(&mut self, list: &[DisplayCommand]) {
+        for item in list {
+            self.write_item(item);
+        }
+    }
+
+    fn write_item(&mut self, item: &DisplayCommand) {
+        match *item {
+            DisplayCommand::SolidColor(ref color, rect) => {
+                writeln!(self.out, "<rect {}{}/>", rect_attributes(rect), fill(color)).unwrap();
+            }
+            DisplayCommand::RoundedRect(ref color, rect, ref radii) => {
+                writeln!(
+                    self.out,
+                    r#"<path d="{}"{}/>"#,
+                    rounded_rect_path(rect, radii),
+                    fill(color)
+                )
+                .unwrap();
+            }
+            DisplayCommand::Border(ref border) => self.write_border(border),
+            DisplayCommand::Background(ref background) => self.write_background(background),
+            DisplayCommand::Image(ref image, rect) => {
+                writeln!(
+                    self.out,
+                    r#"<image {} preserveAspectRatio="none" href="{}"/>"#,
+                    rect_attributes(rect),
+                    data_url(image)
+                )
+                .unwrap();
+            }
+            DisplayCommand::Text(ref text) => self.write_text(text),
+            DisplayCommand::Layer(opacity, ref items) => {
+                writeln!(self.out, r#"<g opacity="{}">"#, number(opacity)).unwrap();
+                self.write_list(items);
+                self.out.push_str("</g>\n");
+            }
+            DisplayCommand::Clip(rect, ref items) => {
+                let id = self.id("clip");
+                writeln!(
+                    self.out,
+                    r#"<clipPath id="{}"><rect {}/></clipPath>"#,
+                    id,
+                    rect_attributes(rect)
+                )
+                .unwrap();
+                writeln!(self.out, r#"<g clip-path="url(#{})">"#, id).unwrap();
+                self.write_list(items);
+                self.out.push_str("</g>\n");
+            }
+            DisplayCommand::Fixed(ref items) => {
+                // Fixed boxes are positioned relative to the viewport.
+                writeln!(
+                    self.out,
+                    r#"<g transform="translate({} {})">"#,
+                    number(self.scroll.0),
+                    number(self.scroll.1)
+                )
+                .unwrap();
+                self.write_list(items);
+                self.out.push_str("</g>\n");
+            }
+        }
+    }
+
+    /// Write a border as the area between its outer and inner edges. A border whose sides
+    /// differ is drawn one side at a time, each clipped to the trapezoid between the corners of
+    /// its edges.
+    fn write_border(&mut self, border: &Border) {
+        let widths = EdgeSize {
+            left: border.left.width,
+            right: border.right.width,
+            top: border.top.width,
+            bottom: border.bottom.width,
+        };
+        let sides = [&border.top, &border.right, &border.bottom, &border.left];
+        let uniform = sides
+            .iter()
+            .all(|side| side.color == border.top.color && side.style == BorderStyle::Solid);
+        if uniform {
+            writeln!(
+                self.out,
+                r#"<path d="{}" fill-rule="evenodd"{}/>"#,
+                ring_path(border, widths, 0.0, 1.0),
+                fill(&border.top.color)
+            )
+            .unwrap();
+            return;
+        }
+
+        let outer = border.rect;
+        let inner = inset(outer, widths);
+        let (x0, y0, x1, y1) = (
+            outer.x,
+            outer.y,
+            outer.x + outer.width,
+            outer.y + outer.height,
+        );
+        let (ix0, iy0) = (inner.x, inner.y);
+        let (ix1, iy1) = (inner.x + inner.width, inner.y + inner.height);
+        let trapezoids = [
+            [(x0, y0), (x1, y0), (ix1, iy0), (ix0, iy0)],
+            [(x1, y0), (x1, y1), (ix1, iy1), (ix1, iy0)],
+            [(x1, y1), (x0, y1), (ix0, iy1), (ix1, iy1)],
+            [(x0, y1), (x0, y0), (ix0, iy0), (ix0, iy1)],
+        ];
+        // The line along the middle of each side, for dashes and dots.
+        let centers = [
+            ((x0, y0 + widths.top / 2.0), (x1, y0 + widths.top / 2.0)),
+            ((x1 - widths.right / 2.0, y0), (x1 - widths.right / 2.0, y1)),
+            (
+                (x1, y1 - widths.bottom / 2.0),
+                (x0, y1 - widths.bottom / 2.0),
+            ),
+            ((x0 + widths.left / 2.0, y1), (x0 + widths.left / 2.0, y0)),
+        ];
+        for ((side, trapezoid), (start, end)) in sides.iter().zip(trapezoids).zip(centers) {
+            if side.width <= 0.0 || side.style == BorderStyle::None {
+                continue;
+            }
+            let id = self.id("clip");
+            let points: Vec<String> = trapezoid
+                .iter()
+                .map(|&(x, y)| format!("{},{}", number(x), number(y)))
+                .collect();
+            writeln!(
+                self.out,
+                r#"<clipPath id="{}"><polygon points="{}"/></clipPath>"#,
+                id,
+                points.join(" ")
+            )
+            .unwrap();
+            let shape = match side.style {
+                BorderStyle::Dashed | BorderStyle::Dotted => line(start, end, side),
+                BorderStyle::Double => format!(
+                    r#"<path d="{} {}" fill-rule="evenodd"{}/>"#,
+                    ring_path(border, widths, 0.0, 1.0 / 3.0),
+                    ring_path(border, widths, 2.0 / 3.0, 1.0),
+                    fill(&side.color)
+                ),
+                BorderStyle::Solid | BorderStyle::None => format!(
+                    r#"<path d="{}" fill-rule="evenodd"{}/>"#,
+                    ring_path(border, widths, 0.0, 1.0),
+                    fill(&side.color)
+                ),
+            };
+            writeln!(self.out, r#"<g clip-path="url(#{})">{}</g>"#, id, shape).unwrap();
+        }
+    }
+
+    /// Write a background as a pattern of tiles, filling its clip area.
+    fn write_background(&mut self, background: &Background) {
+        let Background {
+            clip,
+            tile,
+            ref radii,
+            ..
+        } = *background;
+        // An axis that doesn't repeat is given a tile so large that only one copy of the image
+        // reaches the clip area.
+        let spacing = |repeat: bool, tile_start: f32, size: f32, clip_start: f32, clip_size| {
+            if repeat {
+                size
+            } else {
+                size.max(tile_start + size - clip_start)
+                    .max(clip_start + clip_size - tile_start)
+            }
+        };
+        let width = spacing(background.repeat_x, tile.x, tile.width, clip.x, clip.width);
+        let height = spacing(
+            background.repeat_y,
+            tile.y,
+            tile.height,
+            clip.y,
+            clip.height,
+        );
+
+        let pattern = self.id("pattern");
+        writeln!(
+            self.out,
+            r#"<pattern id="{}" patternUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}">"#,
+            pattern,
+            number(tile.x),
+            number(tile.y),
+            number(width),
+            number(height)
+        )
+        .unwrap();
+        let size = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: tile.width,
+            height: tile.height,
+        };
+        match background.image {
+            BackgroundImage::Image(ref image) => {
+                writeln!(
+                    self.out,
+                    r#"<image {} preserveAspectRatio="none" href="{}"/>"#,
+                    rect_attributes(size),
+                    data_url(image)
+                )
+                .unwrap();
+            }
+            BackgroundImage::LinearGradient(angle, ref stops) => {
+                let gradient = self.id("gradient");
+                let radians = angle.to_radians();
+                let (dx, dy) = (radians.sin(), -radians.cos());
+                let half = ((tile.width * dx).abs() + (tile.height * dy).abs()) / 2.0;
+                let (cx, cy) = (tile.width / 2.0, tile.height / 2.0);
+                writeln!(
+                    self.out,
+                    r#"<linearGradient id="{}" gradientUnits="userSpaceOnUse" x1="{}" y1="{}" x2="{}" y2="{}">{}</linearGradient>"#,
+                    gradient,
+                    number(cx - dx * half),
+                    number(cy - dy * half),
+                    number(cx + dx * half),
+                    number(cy + dy * half),
+                    gradient_stops(stops)
+                )
+                .unwrap();
+                writeln!(
+                    self.out,
+                    r#"<rect {} fill="url(#{})"/>"#,
+                    rect_attributes(size),
+                    gradient
+                )
+                .unwrap();
+            }
+            BackgroundImage::RadialGradient(cx, cy, radius, ref stops) => {
+                let gradient = self.id("gradient");
+                // The gradient is circular, then stretched vertically into an ellipse.
+                let scale = if radius.x > 0.0 {
+                    radius.y / radius.x
+                } else {
+                    1.0
+                };
+                writeln!(
+                    self.out,
+                    r#"<radialGradient id="{}" gradientUnits="userSpaceOnUse" cx="{}" cy="{}" r="{}" gradientTransform="translate({} {}) scale(1 {}) translate({} {})">{}</radialGradient>"#,
+                    gradient,
+                    number(cx),
+                    number(cy),
+                    number(radius.x),
+                    number(cx),
+                    number(cy),
+                    number(scale),
+                    number(-cx),
+                    number(-cy),
+                    gradient_stops(stops)
+                )
+                .unwrap();
+                writeln!(
+                    self.out,
+                    r#"<rect {} fill="url(#{})"/>"#,
+                    rect_attributes(size),
+                    gradient
+                )
+                .unwrap();
+            }
+        }
+        self.out.push_str("</pattern>\n");
+
+        let fill = format!(r#" fill="url(#{})""#, pattern);
+        if radii.is_square() {
+            writeln!(self.out, "<rect {}{}/>", rect_attributes(clip), fill).unwrap();
+        } else {
+            writeln!(
+                self.out,
+                r#"<path d="{}"{}/>"#,
+                rounded_rect_path(clip, radii),
+                fill
+            )
+            .unwrap();
+        }
+    }
+
+    /// Write a run of text with its glyphs stretched to fill its box, so that it matches the
+    /// layout's monospace metrics whatever font the viewer substitutes.
+    fn write_text(&mut self, text: &Text) {
+        let rect = text.rect;
+        writeln!(
+            self.out,
+            r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" textLength="{}" lengthAdjust="spacingAndGlyphs" dominant-baseline="central"{}>{}</text>"#,
+            number(rect.x),
+            number(rect.y + rect.height / 2.0),
+            number(text.font_size),
+            number(rect.width),
+            fill(&text.color),
+            escape(&text.text)
+        )
+        .unwrap();
+    }
+}
+
+/// Format a number with at most two decimal places, without trailing zeros.
+fn number(value: f32) -> String {
+    let formatted = format!("{:.2}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" | "" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+fn rect_attributes(rect: Rect) -> String {
+    format!(
+        r#"x="{}" y="{}" width="{}" height="{}""#,
+        number(rect.x),
+        number(rect.y),
+        number(rect.width),
+        number(rect.height)
+    )
+}
+
+/// The `fill` attribute for a color, with its opacity if it isn't opaque.
+fn fill(color: &Color) -> String {
+    let mut fill = format!(
+        r##" fill="#{:02x}{:02x}{:02x}""##,
+        color.r, color.g, color.b
+    );
+    if color.a < 255 {
+        write!(
+            fill,
+            r#" fill-opacity="{}""#,
+            number(color.a as f32 / 255.0)
+        )
+        .unwrap();
+    }
+    fill
+}
+
+fn gradient_stops(stops: &[ColorStop]) -> String {
+    stops
+        .iter()
+        .map(|stop| {
+            let color = &stop.color;
+            format!(
+                r##"<stop offset="{}" stop-color="#{:02x}{:02x}{:02x}" stop-opacity="{}"/>"##,
+                number(stop.position),
+                color.r,
+                color.g,
+                color.b,
+                number(color.a as f32 / 255.0)
+            )
+        })
+        .collect()
+}
+
+/// A dashed or dotted line along the middle of one side of a border. Dashes are twice as long
+/// as the border is wide, with gaps as wide as the border; dots are round, one width apart.
+fn line(start: (f32, f32), end: (f32, f32), side: &BorderSide) -> String {
+    let (dashes, cap) = match side.style {
+        BorderStyle::Dotted => (format!("0 {}", number(2.0 * side.width)), "round"),
+        _ => (
+            format!("{} {}", number(2.0 * side.width), number(side.width)),
+            "butt",
+        ),
+    };
+    format!(
+        r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#{:02x}{:02x}{:02x}" stroke-opacity="{}" stroke-width="{}" stroke-dasharray="{}" stroke-linecap="{}"/>"##,
+        number(start.0),
+        number(start.1),
+        number(end.0),
+        number(end.1),
+        side.color.r,
+        side.color.g,
+        side.color.b,
+        number(side.color.a as f32 / 255.0),
+        number(side.width),
+        dashes,
+        cap
+    )
+}
+
+/// The path of the part of a border between two fractions of its widths, as two subpaths to be
+/// filled with the even-odd rule.
+fn ring_path(border: &Border, widths: EdgeSize, from: f32, to: f32) -> String {
+    let scaled = |fraction: f32| EdgeSize {
+        left: widths.left * fraction,
+        right: widths.right * fraction,
+        top: widths.top * fraction,
+        bottom: widths.bottom * fraction,
+    };
+    let (outer, inner) = (scaled(from), scaled(to));
+    format!(
+        "{} {}",
+        rounded_rect_path(inset(border.rect, outer), &border.radii.inset(outer)),
+        rounded_rect_path(inset(border.rect, inner), &border.radii.inset(inner))
+    )
+}
+
+/// The path of a rectangle with elliptical corners, clockwise from the top left.
+fn rounded_rect_path(rect: Rect, radii: &CornerRadii) -> String {
+    let (x0, y0, x1, y1) = (rect.x, rect.y, rect.x + rect.width, rect.y + rect.height);
+    let n = number;
+    let arc = |radius: Radius, x: f32, y: f32| {
+        if radius.x > 0.0 && radius.y > 0.0 {
+            format!(" A {} {} 0 0 1 {} {}", n(radius.x), n(radius.y), n(x), n(y))
+        } else {
+            format!(" L {} {}", n(x), n(y))
+        }
+    };
+    let CornerRadii {
+        top_left,
+        top_right,
+        bottom_right,
+        bottom_left,
+    } = *radii;
+    let mut path = format!("M {} {}", n(x0 + top_left.x), n(y0));
+    write!(path, " L {} {}", n(x1 - top_right.x), n(y0)).unwrap();
+    path.push_str(&arc(top_right, x1, y0 + top_right.y));
+    write!(path, " L {} {}", n(x1), n(y1 - bottom_right.y)).unwrap();
+    path.push_str(&arc(bottom_right, x1 - bottom_right.x, y1));
+    write!(path, " L {} {}", n(x0 + bottom_left.x), n(y1)).unwrap();
+    path.push_str(&arc(bottom_left, x0, y1 - bottom_left.y));
+    write!(path, " L {} {}", n(x0), n(y0 + top_left.y)).unwrap();
+    path.push_str(&arc(top_left, x0 + top_left.x, y0));
+    path.push_str(" Z");
+    path
+}
+
+fn inset(rect: Rect, edges: EdgeSize) -> Rect {
+    Rect {
+        x: rect.x + edges.left,
+        y: rect.y + edges.top,
+        width: (rect.width - edges.left - edges.right).max(0.0),
+        height: (rect.height - edges.top - edges.bottom).max(0.0),
+    }
+}
+
+/// Escape the characters that have a meaning in XML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// An image embedded in a `data:` URL as a PNG.
+fn data_url(image: &Image) -> String {
+    format!("data:image/png;base64,{}", base64(&encode_png(image)))
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::{css, html, style::style_tree};
+    use std::path::Path;
+
+    fn render(source: &str, stylesheet: &str) -> String {
+        let root = html::parse(source.to_string());
+        let stylesheet = css::parse(stylesheet.to_string());
+        let styled = style_tree(&root, &stylesheet);
+        let mut viewport: Dimensions = Default::default();
+        viewport.content.width = 100.0;
+        viewport.content.height = 50.0;
+        let mut images = ImageCache::new(Path::new("."));
+        let layout = layout_tree(&styled, viewport, &mut images);
+        paint(&layout, viewport.content, &mut images)
+    }
+
+    #[test]
+    fn test_rects_and_text() {
+        let svg = render(
+            "<div><p>a & b</p></div>",
+            "div { display: block; background: #ff0000; padding: 5px; } \
+             p { display: block; color: #0000ff; }",
+        );
+        assert_eq!(
+            svg.lines().collect::<Vec<_>>(),
+            vec![
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 100 50">"#,
+                r##"<rect x="0" y="0" width="100" height="50" fill="#ffffff"/>"##,
+                r##"<rect x="0" y="0" width="100" height="29.2" fill="#ff0000"/>"##,
+                r##"<text x="5" y="14.6" font-family="monospace" font-size="16" textLength="48" lengthAdjust="spacingAndGlyphs" dominant-baseline="central" fill="#0000ff">a &amp; b</text>"##,
+                "</svg>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clip_paths_and_layers() {
+        let svg = render(
+            "<div><p></p></div>",
+            "div { display: block; height: 10px; overflow: hidden; opacity: 0.5; } \
+             p { display: block; height: 20px; background: #00ff00; }",
+        );
+        assert_eq!(
+            svg.lines().skip(2).collect::<Vec<_>>(),
+            vec![
+                r#"<g opacity="0.5">"#,
+                r#"<clipPath id="clip1"><rect x="0" y="0" width="100" height="10"/></clipPath>"#,
+                r##"<g clip-path="url(#clip1)">"##,
+                r##"<rect x="0" y="0" width="100" height="20" fill="#00ff00"/>"##,
+                "</g>",
+                "</g>",
+                "</svg>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_borders_and_rounded_corners() {
+        let svg = render(
+            "<div></div>",
+            "div { display: block; width: 20px; height: 10px; border-width: 2px; \
+             border-style: solid; border-color: #000000; border-radius: 4px; }",
+        );
+        assert!(svg.contains(
+            "<path d=\"M 4 0 L 20 0 A 4 4 0 0 1 24 4 L 24 10 A 4 4 0 0 1 20 14 L 4 14 \
+             A 4 4 0 0 1 0 10 L 0 4 A 4 4 0 0 1 4 0 Z \
+             M 4 2 L 20 2 A 2 2 0 0 1 22 4 L 22 10 A 2 2 0 0 1 20 12 L 4 12 \
+             A 2 2 0 0 1 2 10 L 2 4 A 2 2 0 0 1 4 2 Z\" fill-rule=\"evenodd\" fill=\"#000000\"/>"
+        ));
+
+        let svg = render(
+            "<div></div>",
+            "div { display: block; height: 10px; border-width: 2px; border-style: solid dashed; \
+             border-color: #000000 #ff0000; }",
+        );
+        assert_eq!(svg.matches("<clipPath").count(), 4);
+        assert!(svg.contains(r#"stroke-dasharray="4 2""#));
+    }
+
+    #[test]
+    fn test_gradients_and_images() {
+        let svg = render(
+            "<div></div>",
+            "div { display: block; height: 10px; \
+             background: linear-gradient(to right, #ff0000, #0000ff); }",
+        );
+        assert!(svg.contains(
+            r#"<linearGradient id="gradient2" gradientUnits="userSpaceOnUse" x1="0" y1="5" x2="100" y2="5">"#
+        ));
+        assert!(
+            svg.contains(r##"<rect x="0" y="0" width="100" height="10" fill="url(#pattern1)"/>"##)
+        );
+
+        let image = Image {
+            width: 1,
+            height: 1,
+            pixels: vec![Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255,
+            }],
+        };
+        let url = data_url(&image);
+        assert!(url.starts_with("data:image/png;base64,iVBORw0KGgo"));
+        assert_eq!(base64(b"ab"), "YWI=");
+    }
+}
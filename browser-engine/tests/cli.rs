@@ -0,0 +1,129 @@
+//! Runs the command line tool to render a page in each of its output formats.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use browser_engine::image;
+
+/// The command to render `page.html` in `dir` to `output` with the given options.
+fn render_command(dir: &Path, output: &str, options: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_browser-engine"));
+    command
+        .arg("--render")
+        .arg(dir.join(output))
+        .args(options)
+        .arg("--css")
+        .arg(dir.join("page.css"))
+        .arg(dir.join("page.html"));
+    command
+}
+
+/// Render `page.html` in `dir` to `output` with the given options, and return the file written.
+fn render(dir: &Path, output: &str, options: &[&str]) -> Vec<u8> {
+    let status = render_command(dir, output, options).status().unwrap();
+    assert!(status.success(), "rendering {} failed", output);
+    fs::read(dir.join(output)).unwrap()
+}
+
+/// Write the page to render to a directory of its own for each test.
+fn page_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("page.html"),
+        "<div><p class=\"a\"></p><p class=\"b\"></p></div>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("page.css"),
+        "div { display: block; } p { display: block; height: 500px; background: #ff0000; } \
+         .b { background: #0000ff; }",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn render_images_at_a_scroll_offset_or_of_the_full_page() {
+    let dir = page_dir("images");
+    let color = |image: &image::Image, y: usize| {
+        let pixel = image.pixel(0, y);
+        (pixel.r, pixel.g, pixel.b)
+    };
+
+    let viewport = image::decode(&render(&dir, "viewport.png", &[])).unwrap();
+    assert_eq!((viewport.width, viewport.height), (800, 600));
+    assert_eq!(color(&viewport, 0), (255, 0, 0));
+    assert_eq!(color(&viewport, 599), (0, 0, 255));
+
+    let scrolled = image::decode(&render(&dir, "scrolled.png", &["--scroll", "0,700"])).unwrap();
+    assert_eq!((scrolled.width, scrolled.height), (800, 600));
+    assert_eq!(color(&scrolled, 0), (0, 0, 255));
+    assert_eq!(color(&scrolled, 300), (255, 255, 255));
+
+    let full = image::decode(&render(&dir, "full.png", &["--full-page"])).unwrap();
+    assert_eq!((full.width, full.height), (800, 1000));
+    assert_eq!(color(&full, 999), (0, 0, 255));
+}
+
+#[test]
+fn render_svg_and_pdf() {
+    let dir = page_dir("vector");
+    let svg = String::from_utf8(render(&dir, "full.svg", &["--full-page"])).unwrap();
+    assert!(svg.starts_with("<svg "), "{}", svg);
+    assert!(svg.contains(r#"height="1000""#), "{}", svg);
+
+    let pdf = render(&dir, "page.pdf", &[]);
+    assert!(pdf.starts_with(b"%PDF-"));
+}
+
+#[test]
+fn reject_options_that_do_not_apply_to_the_output() {
+    let dir = page_dir("options");
+    for (output, options, message) in [
+        (
+            "page.pdf",
+            &["--scroll", "0,100"][..],
+            "--scroll only applies to .png and .svg",
+        ),
+        (
+            "page.pdf",
+            &["--full-page"],
+            "--full-page only applies to .png and .svg",
+        ),
+        (
+            "page.png",
+            &["--font", "font.ttf"],
+            "--font only applies to .pdf",
+        ),
+        (
+            "page.svg",
+            &["--font", "font.ttf"],
+            "--font only applies to .pdf",
+        ),
+        (
+            "page.png",
+            &["--full-page", "--full-page"],
+            "--full-page may only be given once",
+        ),
+        (
+            "page.png",
+            &["--scroll", "0,100", "--full-page"],
+            "--scroll and --full-page can't be combined",
+        ),
+    ] {
+        let result = render_command(&dir, output, options).output().unwrap();
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(
+            !result.status.success(),
+            "{} {:?} succeeded",
+            output,
+            options
+        );
+        assert!(stderr.contains(message), "{}", stderr);
+        assert!(!dir.join(output).exists());
+    }
+}
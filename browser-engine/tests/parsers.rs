@@ -0,0 +1,245 @@
+//! Property tests for the HTML and CSS parsers: any input can be parsed without panicking, and
+//! documents and stylesheets written out by the serializers parse back to what was written.
+
+use std::collections::HashMap;
+
+use browser_engine::css::{
+    self, AttributeSelector, Color, Declaration, PseudoElement, Rule, Selector, SimpleSelector,
+    Stylesheet, Unit, Value,
+};
+use browser_engine::dom::{self, Node};
+use browser_engine::html;
+use proptest::prelude::*;
+
+/// Tags that may have children, and void elements, which may not.
+const TAGS: &[&str] = &[
+    "div",
+    "p",
+    "span",
+    "a",
+    "ul",
+    "li",
+    "em",
+    "section",
+    "table",
+    "td",
+    "my-element",
+];
+const VOID_TAGS: &[&str] = &["br", "img", "hr", "input"];
+
+fn identifier() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z][a-z0-9_-]{0,8}",
+        // Identifiers outside ASCII, and ones that must be written with escapes.
+        "[a-zé日_0-9-][a-zA-Z0-9_é日\u{a0}\u{3000} ./#(\"\\\\\t\n-]{0,8}",
+    ]
+}
+
+fn html_text() -> impl Strategy<Value = String> {
+    // Whitespace at the start of text between tags is not significant.
+    "[a-zA-Z0-9.,!?&;=\"'/>-][a-zA-Z0-9 .,!?&;=\"'/>-]{0,16}"
+}
+
+fn attributes() -> impl Strategy<Value = dom::AttrMap> {
+    let value = "[a-zA-Z0-9 ./:=<>'\"-]{0,12}"
+        .prop_filter("values can't be quoted", |value: &String| {
+            !(value.contains('"') && value.contains('\''))
+        });
+    prop::collection::hash_map("[a-zé][a-z0-9_:é-]{0,6}", value, 0..3)
+}
+
+fn element_tree() -> impl Strategy<Value = Node> {
+    let leaf = prop_oneof![
+        (prop::sample::select(TAGS), attributes()).prop_map(|(tag, attrs)| dom::elem(
+            tag.to_string(),
+            attrs,
+            Vec::new()
+        )),
+        (prop::sample::select(VOID_TAGS), attributes()).prop_map(|(tag, attrs)| dom::elem(
+            tag.to_string(),
+            attrs,
+            Vec::new()
+        )),
+    ];
+    leaf.prop_recursive(5, 64, 6, |inner| {
+        let child = prop_oneof![inner, html_text().prop_map(dom::text)];
+        (
+            prop::sample::select(TAGS),
+            attributes(),
+            prop::collection::vec(child, 0..6),
+        )
+            .prop_map(|(tag, attrs, children)| {
+                dom::elem(tag.to_string(), attrs, merge_adjacent_text(children))
+            })
+    })
+}
+
+/// Join adjacent text nodes, which are parsed as one.
+fn merge_adjacent_text(children: Vec<Node>) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::new();
+    for child in children {
+        if let (Some(last), dom::NodeType::Text(ref text)) = (merged.last_mut(), &child.node_type) {
+            if let dom::NodeType::Text(ref mut previous) = last.node_type {
+                previous.push_str(text);
+                continue;
+            }
+        }
+        merged.push(child);
+    }
+    merged
+}
+
+fn component_value() -> impl Strategy<Value = Value> {
+    let number =
+        (-10_000i32..10_000, 0u32..3).prop_map(|(n, scale)| n as f32 / 10f32.powi(scale as i32));
+    let leaf = prop_oneof![
+        identifier().prop_map(Value::Keyword),
+        "--[a-z][a-z0-9-]{0,6}".prop_map(Value::Keyword),
+        Just(Value::Keyword("/".to_string())),
+        (
+            number.clone(),
+            prop_oneof![
+                Just(Unit::Px),
+                Just(Unit::Percent),
+                Just(Unit::Fr),
+                Just(Unit::Deg)
+            ]
+        )
+            .prop_map(|(size, unit)| Value::Length(size, unit)),
+        number.prop_map(Value::Number),
+        any::<(u8, u8, u8)>().prop_map(|(r, g, b)| Value::ColorValue(Color { r, g, b, a: 255 })),
+        "[a-z0-9./:_ ()'\"\\\\\té-]{0,12}".prop_map(Value::Url),
+        "[a-z0-9./:_ ()'\"\\\\\té-]{0,12}".prop_map(Value::String),
+    ];
+    leaf.prop_recursive(3, 16, 4, |inner| {
+        let argument = prop_oneof![inner.clone(), list_of(inner.clone())];
+        (
+            prop::sample::select(&["calc", "var", "repeat", "minmax", "linear-gradient", ""][..]),
+            prop::collection::vec(argument, 1..4),
+        )
+            .prop_map(|(name, arguments)| Value::Function(name.to_string(), arguments))
+    })
+}
+
+/// A space-separated list of two or more values, as a single value parses on its own.
+fn list_of(value: impl Strategy<Value = Value>) -> impl Strategy<Value = Value> {
+    prop::collection::vec(value, 2..5).prop_map(Value::List)
+}
+
+fn declaration() -> impl Strategy<Value = Declaration> {
+    let value = prop_oneof![component_value(), list_of(component_value())];
+    (prop_oneof![identifier(), "--[a-z][a-z0-9-]{0,6}"], value)
+        .prop_map(|(name, value)| Declaration { name, value })
+}
+
+fn selector() -> impl Strategy<Value = Selector> {
+    (
+        prop::option::of(identifier()),
+        prop::option::of(identifier()),
+        prop::collection::vec(identifier(), 0..3),
+        prop::collection::vec((identifier(), prop::option::of(any::<String>())), 0..2),
+        prop::option::of(prop::sample::select(
+            &[
+                PseudoElement::Before,
+                PseudoElement::After,
+                PseudoElement::Marker,
+            ][..],
+        )),
+    )
+        .prop_map(|(tag_name, id, class, attributes, pseudo_element)| {
+            Selector::Simple(SimpleSelector {
+                tag_name,
+                id,
+                class,
+                attributes: attributes
+                    .into_iter()
+                    // The parser reads NUL as U+FFFD.
+                    .map(|(name, value)| AttributeSelector {
+                        name,
+                        value: value.map(|value| value.replace('\0', "\u{fffd}")),
+                    })
+                    .collect(),
+                pseudo_element,
+            })
+        })
+}
+
+fn stylesheet() -> impl Strategy<Value = Stylesheet> {
+    let rule = (
+        prop::collection::vec(selector(), 1..4),
+        prop::collection::vec(declaration(), 0..5),
+    )
+        .prop_map(|(mut selectors, declarations)| {
+            // Selectors are kept in order of specificity.
+            selectors.sort_by_key(|selector| std::cmp::Reverse(selector.specificity()));
+            Rule {
+                selectors,
+                declarations,
+                location: Default::default(),
+            }
+        });
+    (
+        prop::collection::vec(rule, 0..5),
+        prop::collection::vec(declaration(), 0..3),
+    )
+        .prop_map(|(rules, page)| Stylesheet { rules, page })
+}
+
+proptest! {
+    #[test]
+    fn html_parses_any_input(source in any::<String>()) {
+        html::parse(source);
+    }
+
+    #[test]
+    fn html_parses_any_markup(source in "[<>/!='\" a-z-]{0,64}") {
+        html::parse(source);
+    }
+
+    #[test]
+    fn html_parses_the_same_in_chunks(source in "[<>/!='\" a-zé-]{0,64}", size in 1usize..8) {
+        let mut parser = html::Parser::new();
+        for chunk in source.as_bytes().chunks(size) {
+            parser.feed_bytes(chunk);
+        }
+        prop_assert_eq!(parser.finish(), html::parse(source));
+    }
+
+    #[test]
+    fn css_parses_any_input(source in any::<String>()) {
+        css::parse(source);
+    }
+
+    #[test]
+    fn css_parses_any_rules(source in "(url\\(|[a-z0-9#.,:;{}()@%*+/!'\" -]){0,64}") {
+        let (stylesheet, _) = css::parse_with_errors(source);
+        // What was parsed is written out as a stylesheet that parses without errors.
+        let (reparsed, errors) = css::parse_with_errors(stylesheet.to_string());
+        prop_assert!(errors.is_empty(), "{:?}", errors);
+        prop_assert_eq!(reparsed.to_string(), stylesheet.to_string());
+    }
+
+    #[test]
+    fn html_round_trips(root in element_tree()) {
+        let source = root.to_string();
+        let parsed = html::parse(source.clone());
+        prop_assert_eq!(parsed.to_string(), source);
+        prop_assert_eq!(parsed, root);
+    }
+
+    #[test]
+    fn css_round_trips(stylesheet in stylesheet()) {
+        let source = stylesheet.to_string();
+        let (parsed, errors) = css::parse_with_errors(source.clone());
+        prop_assert!(errors.is_empty(), "{:?}", errors);
+        prop_assert_eq!(parsed.to_string(), source);
+    }
+}
+
+#[test]
+fn html_round_trips_attributes_without_values() {
+    let mut attrs = HashMap::new();
+    attrs.insert("checked".to_string(), String::new());
+    let root = dom::elem("input".to_string(), attrs, Vec::new());
+    assert_eq!(html::parse(root.to_string()), root);
+}
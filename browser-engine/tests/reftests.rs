@@ -0,0 +1,19 @@
+//! Runs the reftests in `tests/reftests`. The images of any failures are written to the
+//! target directory.
+
+use std::path::Path;
+
+use browser_engine::reftest::{self, Tolerance};
+
+#[test]
+fn reftests() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reftests");
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("reftests");
+    let failures = reftest::run(&dir, Tolerance::default(), &output).unwrap();
+    let report: Vec<String> = failures.iter().map(|failure| failure.to_string()).collect();
+    assert!(
+        failures.is_empty(),
+        "reftests failed:\n{}",
+        report.join("\n")
+    );
+}